@@ -0,0 +1,196 @@
+//! AES-256-GCM for small secrets at rest, i.e. in KV.
+//!
+//! A fresh random 96 bit nonce is drawn for every seal and carried in front of the ciphertext,
+//! so the sealed form is `nonce || ciphertext || tag`. Random nonces are safe for well under
+//! 2^32 seals per key, rotate keys long before that.
+
+use crate::encoding::{base64_decode, base64_encode, BASE64URL};
+use crate::random::fill_random;
+use crate::Error;
+
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+/// Borrow a key held in a slice, i.e. read from config, as the array `seal` and `open` take
+pub fn key_from_slice(key: &[u8]) -> Result<&[u8; KEY_LEN], Error> {
+    key.try_into().map_err(|_| Error::InvalidLength {
+        expected: KEY_LEN,
+        actual: key.len(),
+    })
+}
+
+/// Encrypt and authenticate the plaintext, the aad is authenticated but not included in the
+/// output so the same aad must be given to `open`
+pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut nonce = [0; NONCE_LEN];
+    fill_random(&mut nonce)?;
+    Ok(seal_with_nonce(key, nonce, plaintext, aad))
+}
+
+/// Authenticate and decrypt the output of `seal`, any change to the nonce, ciphertext, tag,
+/// or aad, or the wrong key, is `Unauthenticated`
+pub fn open(key: &[u8; KEY_LEN], sealed: &[u8], aad: &[u8]) -> Result<Vec<u8>, Error> {
+    let (nonce, ciphertext) = sealed.split_at_checked(NONCE_LEN).ok_or(Error::Unauthenticated)?;
+    if ciphertext.len() < TAG_LEN {
+        return Err(Error::Unauthenticated);
+    }
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| Error::Unauthenticated)?;
+    let mut in_out = ciphertext.to_vec();
+    let plaintext_len = less_safe_key(key)
+        .open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .map_err(|_| Error::Unauthenticated)?
+        .len();
+    in_out.truncate(plaintext_len);
+    Ok(in_out)
+}
+
+// Callers must never repeat a nonce under the same key, only `seal` and the tests use it
+fn seal_with_nonce(key: &[u8; KEY_LEN], nonce: [u8; NONCE_LEN], plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut in_out = Vec::with_capacity(NONCE_LEN + plaintext.len() + TAG_LEN);
+    in_out.extend_from_slice(&nonce);
+    in_out.extend_from_slice(plaintext);
+    let (_, payload) = in_out.split_at_mut(NONCE_LEN);
+    let tag = less_safe_key(key)
+        .seal_in_place_separate_tag(Nonce::assume_unique_for_key(nonce), Aad::from(aad), payload)
+        .expect("plaintext within the AES-GCM size limit");
+    in_out.extend_from_slice(tag.as_ref());
+    in_out
+}
+
+fn less_safe_key(key: &[u8; KEY_LEN]) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).expect("key is the AES-256 length"))
+}
+
+/// Sealed bytes with a text form for stores which only hold strings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedBox(Vec<u8>);
+
+impl SealedBox {
+    pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8], aad: &[u8]) -> Result<Self, Error> {
+        seal(key, plaintext, aad).map(SealedBox)
+    }
+
+    pub fn open(&self, key: &[u8; KEY_LEN], aad: &[u8]) -> Result<Vec<u8>, Error> {
+        open(key, &self.0, aad)
+    }
+
+    /// Wrap bytes produced by `seal`, too short to hold a nonce and tag is `Unauthenticated`
+    pub fn from_bytes(sealed: Vec<u8>) -> Result<Self, Error> {
+        if sealed.len() < NONCE_LEN + TAG_LEN {
+            return Err(Error::Unauthenticated);
+        }
+        Ok(SealedBox(sealed))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Url safe base64 without padding
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.0, BASE64URL, false)
+    }
+
+    pub fn from_base64(text: &str) -> Result<Self, Error> {
+        let sealed = base64_decode(text, BASE64URL).ok_or(Error::InvalidBase64)?;
+        SealedBox::from_bytes(sealed)
+    }
+}
+
+impl std::fmt::Display for SealedBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+impl std::str::FromStr for SealedBox {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SealedBox::from_base64(s)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::encoding::{hex_decode, hex_encode};
+
+    const KEY: &[u8; KEY_LEN] = b"0123456789abcdef0123456789abcdef";
+    const AAD: &[u8] = b"kv:tenant/42/api-key";
+
+    #[test]
+    fn round_trip() {
+        for plaintext in [&b""[..], &b"s3cr3t"[..], &[0xa5; 1000][..]] {
+            let sealed = seal(KEY, plaintext, AAD).unwrap();
+            assert_eq!(NONCE_LEN + plaintext.len() + TAG_LEN, sealed.len());
+            assert_eq!(Ok(plaintext.to_vec()), open(KEY, &sealed, AAD));
+        }
+        assert_ne!(seal(KEY, b"s3cr3t", AAD), seal(KEY, b"s3cr3t", AAD));
+    }
+
+    #[test]
+    fn detect_tampering() {
+        let sealed = seal(KEY, b"s3cr3t", AAD).unwrap();
+        // One byte each in the nonce, ciphertext, and tag
+        for at in [0, NONCE_LEN, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[at] ^= 1;
+            assert_eq!(Err(Error::Unauthenticated), open(KEY, &tampered, AAD), "byte {at}");
+        }
+        assert_eq!(Err(Error::Unauthenticated), open(KEY, &sealed, b"kv:tenant/43/api-key"));
+        assert_eq!(Err(Error::Unauthenticated), open(&[0; KEY_LEN], &sealed, AAD));
+        assert_eq!(Err(Error::Unauthenticated), open(KEY, &sealed[..NONCE_LEN + TAG_LEN - 1], AAD));
+    }
+
+    #[test]
+    fn reject_wrong_key_length() {
+        assert_eq!(Err(Error::InvalidLength { expected: KEY_LEN, actual: 16 }), key_from_slice(&[0; 16]));
+        assert_eq!(Ok(KEY), key_from_slice(&KEY[..]));
+    }
+
+    #[test]
+    fn match_nist_vectors() {
+        // AES-256 test cases 13, 14, and 16 from the GCM specification
+        let vectors = [
+            ("00".repeat(32), "00".repeat(12), "", "", "530f8afbc74536b9a963b4f1c4cb738b"),
+            (
+                "00".repeat(32),
+                "00".repeat(12),
+                "00000000000000000000000000000000",
+                "",
+                "cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919",
+            ),
+            (
+                "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308".to_owned(),
+                "cafebabefacedbaddecaf888".to_owned(),
+                "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a721c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39",
+                "feedfacedeadbeeffeedfacedeadbeefabaddad2",
+                "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f66276fc6ece0f4e1768cddf8853bb2d551b",
+            ),
+        ];
+        for (key, nonce, plaintext, aad, expected) in vectors {
+            let key: [u8; KEY_LEN] = hex_decode(&key).unwrap().try_into().unwrap();
+            let nonce: [u8; NONCE_LEN] = hex_decode(&nonce).unwrap().try_into().unwrap();
+            let (plaintext, aad) = (hex_decode(plaintext).unwrap(), hex_decode(aad).unwrap());
+            let sealed = seal_with_nonce(&key, nonce, &plaintext, &aad);
+            assert_eq!(expected, hex_encode(&sealed[NONCE_LEN..]));
+            assert_eq!(Ok(plaintext), open(&key, &sealed, &aad));
+        }
+    }
+
+    #[test]
+    fn round_trip_sealed_box_as_text() {
+        let sealed = SealedBox::seal(KEY, b"s3cr3t", AAD).unwrap();
+        let text = sealed.to_string();
+        assert!(text.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        let parsed: SealedBox = text.parse().unwrap();
+        assert_eq!(sealed, parsed);
+        assert_eq!(Ok(b"s3cr3t".to_vec()), parsed.open(KEY, AAD));
+        assert_eq!(Err(Error::InvalidBase64), SealedBox::from_base64("not base64!"));
+        assert_eq!(Err(Error::Unauthenticated), SealedBox::from_base64("AAAA"));
+    }
+}
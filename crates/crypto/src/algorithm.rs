@@ -1,3 +1,5 @@
+use crate::{Error, ParseError};
+
 use ring::digest;
 
 static SHA1: &str = "SHA1";
@@ -5,30 +7,124 @@ static SHA256: &str = "SHA256";
 static SHA384: &str = "SHA384";
 static SHA512: &str = "SHA512";
 static SHA512_256: &str = "SHA512_256";
+#[cfg(feature = "blake3")]
+static BLAKE3: &str = "BLAKE3";
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Algorithm {
+    /// Broken for collision resistance, only use it to interoperate with legacy systems
     SHA1,
     SHA256,
     SHA384,
     SHA512,
     SHA512_256,
+    /// Much faster than SHA-2 on large buffers, needs the "blake3" feature
+    #[cfg(feature = "blake3")]
+    BLAKE3,
 }
 
-impl From<&digest::Algorithm> for Algorithm {
-    fn from(src: &digest::Algorithm) -> Self {
-        if *src == digest::SHA256 {
-            Algorithm::SHA256
-        } else if *src == digest::SHA512 {
-            Algorithm::SHA512
-        } else if *src == digest::SHA384 {
-            Algorithm::SHA384
-        } else if *src == digest::SHA512_256 {
-            Algorithm::SHA512_256
-        } else {
-            Algorithm::SHA1
+impl Algorithm {
+    pub const ALL: &'static [Algorithm] = &[
+        Algorithm::SHA1,
+        Algorithm::SHA256,
+        Algorithm::SHA384,
+        Algorithm::SHA512,
+        Algorithm::SHA512_256,
+        #[cfg(feature = "blake3")]
+        Algorithm::BLAKE3,
+    ];
+
+    /// Length in bytes of the digest the algorithm produces
+    pub fn digest_len(self) -> usize {
+        match self {
+            Algorithm::SHA1 => 20,
+            Algorithm::SHA256 => 32,
+            Algorithm::SHA384 => 48,
+            Algorithm::SHA512 => 64,
+            Algorithm::SHA512_256 => 32,
+            #[cfg(feature = "blake3")]
+            Algorithm::BLAKE3 => 32,
+        }
+    }
+
+    /// Lowercase name used as the prefix of the canonical `HashMeta` form, i.e. "sha256"
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Algorithm::SHA1 => "sha1",
+            Algorithm::SHA256 => "sha256",
+            Algorithm::SHA384 => "sha384",
+            Algorithm::SHA512 => "sha512",
+            Algorithm::SHA512_256 => "sha512_256",
+            #[cfg(feature = "blake3")]
+            Algorithm::BLAKE3 => "blake3",
         }
     }
+
+    /// Code in the multicodec table, which prefixes the digest in a multihash
+    pub fn multihash_code(self) -> u64 {
+        match self {
+            Algorithm::SHA1 => 0x11,
+            Algorithm::SHA256 => 0x12,
+            Algorithm::SHA384 => 0x20,
+            Algorithm::SHA512 => 0x13,
+            Algorithm::SHA512_256 => 0x1015,
+            #[cfg(feature = "blake3")]
+            Algorithm::BLAKE3 => 0x1e,
+        }
+    }
+
+    pub fn from_multihash_code(code: u64) -> Option<Algorithm> {
+        Algorithm::ALL.iter().copied().find(|algorithm| algorithm.multihash_code() == code)
+    }
+
+    /// The ring algorithm, for calling into ring directly, `None` for algorithms ring lacks
+    pub fn as_ring(self) -> Option<&'static digest::Algorithm> {
+        match self {
+            Algorithm::SHA1 => Some(&digest::SHA1_FOR_LEGACY_USE_ONLY),
+            Algorithm::SHA256 => Some(&digest::SHA256),
+            Algorithm::SHA384 => Some(&digest::SHA384),
+            Algorithm::SHA512 => Some(&digest::SHA512),
+            Algorithm::SHA512_256 => Some(&digest::SHA512_256),
+            #[cfg(feature = "blake3")]
+            Algorithm::BLAKE3 => None,
+        }
+    }
+}
+
+impl TryFrom<Algorithm> for &'static digest::Algorithm {
+    type Error = Error;
+
+    fn try_from(algorithm: Algorithm) -> Result<Self, Self::Error> {
+        algorithm.as_ring().ok_or(Error::UnsupportedAlgorithm(algorithm))
+    }
+}
+
+/// Only the algorithms listed in `Algorithm` are known, any other is an error rather than a guess
+impl TryFrom<&digest::Algorithm> for Algorithm {
+    type Error = ParseError;
+
+    fn try_from(src: &digest::Algorithm) -> Result<Self, Self::Error> {
+        Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| algorithm.as_ring() == Some(src))
+            .ok_or_else(|| ParseError::UnknownAlgorithm(format!("{src:?}")))
+    }
+}
+
+/// Parses the display name, the prefix, or the dashed name from the standards (i.e. "SHA-256"
+/// or "SHA-512/256"), ignoring case
+impl std::str::FromStr for Algorithm {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_ascii_lowercase().replacen("sha-", "sha", 1).replace(['-', '/'], "_");
+        Algorithm::ALL
+            .iter()
+            .copied()
+            .find(|algorithm| name == algorithm.prefix())
+            .ok_or_else(|| ParseError::UnknownAlgorithm(s.to_owned()))
+    }
 }
 
 impl std::fmt::Display for Algorithm {
@@ -42,7 +138,66 @@ impl std::fmt::Display for Algorithm {
                 Algorithm::SHA384 => SHA384,
                 Algorithm::SHA512 => SHA512,
                 Algorithm::SHA512_256 => SHA512_256,
+                #[cfg(feature = "blake3")]
+                Algorithm::BLAKE3 => BLAKE3,
             }
         )
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Algorithm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.prefix())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Algorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn parse_every_spelling() {
+        for &algorithm in Algorithm::ALL {
+            assert_eq!(Ok(algorithm), algorithm.prefix().parse());
+            assert_eq!(Ok(algorithm), algorithm.to_string().parse());
+        }
+        assert_eq!(Ok(Algorithm::SHA256), "SHA-256".parse());
+        assert_eq!(Ok(Algorithm::SHA1), "sha-1".parse());
+        assert_eq!(Ok(Algorithm::SHA512_256), "SHA-512/256".parse());
+        assert_eq!(Ok(Algorithm::SHA512_256), "sha512-256".parse());
+        assert_eq!(Err(ParseError::UnknownAlgorithm("md5".to_owned())), "md5".parse::<Algorithm>());
+    }
+
+    // ring has no digest outside ALL today so the TryFrom error can't be reached from a test
+    #[test]
+    fn round_trip_ring_algorithms() {
+        for &algorithm in Algorithm::ALL {
+            let Ok(ring) = <&digest::Algorithm>::try_from(algorithm) else {
+                assert_eq!(None, algorithm.as_ring());
+                continue;
+            };
+            assert_eq!(algorithm.digest_len(), ring.output_len());
+            assert_eq!(Ok(algorithm), Algorithm::try_from(ring));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_as_lowercase_name() {
+        for &algorithm in Algorithm::ALL {
+            let json = serde_json::to_string(&algorithm).unwrap();
+            assert_eq!(format!("\"{}\"", algorithm.prefix()), json);
+            assert_eq!(algorithm, serde_json::from_str::<Algorithm>(&json).unwrap());
+        }
+        assert!(serde_json::from_str::<Algorithm>("\"md5\"").is_err());
+    }
+}
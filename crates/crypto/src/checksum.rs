@@ -0,0 +1,232 @@
+//! Fast checksums for cache keys and spotting changed buffers.
+//!
+//! These are NOT cryptographic: collisions can be produced on purpose with little effort, so
+//! never use them where an attacker controls the input and a collision matters (signatures,
+//! integrity against tampering, deduplication of untrusted uploads). Use `hash` for those.
+
+/// CRC-32 with the IEEE 802.3 polynomial (reflected 0xEDB88320), as zlib, PNG, and gzip use
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(data);
+    crc.finalize()
+}
+
+/// XXH64 with the given seed, 0 is the usual choice
+pub fn xxhash64(data: &[u8], seed: u64) -> u64 {
+    let mut hasher = XxHash64::new(seed);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { crc >> 1 ^ CRC32_POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32, the result is the same as `crc32` over the concatenated pieces
+#[derive(Clone, Debug)]
+pub struct Crc32 {
+    crc: u32,
+    bytes_processed: u64,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32 {
+            crc: !0,
+            bytes_processed: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &b in data {
+            self.crc = CRC32_TABLE[usize::from(self.crc as u8 ^ b)] ^ self.crc >> 8;
+        }
+        self.bytes_processed += data.len() as u64;
+    }
+
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed
+    }
+
+    pub fn finalize(self) -> u32 {
+        !self.crc
+    }
+}
+
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+/// Input is consumed in stripes of four 8 byte lanes
+const STRIPE_LEN: usize = 32;
+
+/// Incremental XXH64, the result is the same as `xxhash64` over the concatenated pieces
+#[derive(Clone, Debug)]
+pub struct XxHash64 {
+    seed: u64,
+    lanes: [u64; 4],
+    buffer: [u8; STRIPE_LEN],
+    buffered: usize,
+    bytes_processed: u64,
+}
+
+impl XxHash64 {
+    pub fn new(seed: u64) -> Self {
+        XxHash64 {
+            seed,
+            lanes: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            buffer: [0; STRIPE_LEN],
+            buffered: 0,
+            bytes_processed: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.bytes_processed += data.len() as u64;
+        let mut data = data;
+        if self.buffered > 0 {
+            let take = (STRIPE_LEN - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered < STRIPE_LEN {
+                return;
+            }
+            let stripe = self.buffer;
+            self.consume(&stripe);
+            self.buffered = 0;
+        }
+        let mut stripes = data.chunks_exact(STRIPE_LEN);
+        for stripe in &mut stripes {
+            self.consume(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffered = rest.len();
+    }
+
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed
+    }
+
+    pub fn finalize(self) -> u64 {
+        let mut hash = if self.bytes_processed >= STRIPE_LEN as u64 {
+            let [v1, v2, v3, v4] = self.lanes;
+            let hash = v1
+                .rotate_left(1)
+                .wrapping_add(v2.rotate_left(7))
+                .wrapping_add(v3.rotate_left(12))
+                .wrapping_add(v4.rotate_left(18));
+            self.lanes.iter().fold(hash, |hash, &lane| merge_lane(hash, lane))
+        } else {
+            self.seed.wrapping_add(PRIME64_5)
+        };
+        hash = hash.wrapping_add(self.bytes_processed);
+
+        let mut rest = &self.buffer[..self.buffered];
+        while let Some((word, tail)) = rest.split_first_chunk::<8>() {
+            hash ^= round(0, u64::from_le_bytes(*word));
+            hash = hash.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+            rest = tail;
+        }
+        if let Some((word, tail)) = rest.split_first_chunk::<4>() {
+            hash ^= u64::from(u32::from_le_bytes(*word)).wrapping_mul(PRIME64_1);
+            hash = hash.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+            rest = tail;
+        }
+        for &b in rest {
+            hash ^= u64::from(b).wrapping_mul(PRIME64_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^ hash >> 32
+    }
+
+    fn consume(&mut self, stripe: &[u8]) {
+        for (lane, word) in self.lanes.iter_mut().zip(stripe.chunks_exact(8)) {
+            *lane = round(*lane, u64::from_le_bytes(word.try_into().expect("8 byte word")));
+        }
+    }
+}
+
+fn round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn merge_lane(hash: u64, lane: u64) -> u64 {
+    (hash ^ round(0, lane)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    const FOX: &[u8] = b"The quick brown fox jumps over the lazy dog";
+
+    #[test]
+    fn match_published_crc32_values() {
+        assert_eq!(0, crc32(b""));
+        // The check value from the CRC catalogue
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+        assert_eq!(0x414f_a339, crc32(FOX));
+    }
+
+    #[test]
+    fn match_published_xxhash64_values() {
+        // From the xxHash reference and the python-xxhash documentation
+        assert_eq!(0xef46_db37_51d8_e999, xxhash64(b"", 0));
+        assert_eq!(0xd24e_c4f1_a98c_6e5b, xxhash64(b"a", 0));
+        assert_eq!(0x44bc_2cf5_ad77_0999, xxhash64(b"abc", 0));
+        assert_eq!(0xfbce_a83c_8a37_8bf1, xxhash64(b"Nobody inspects the spammish repetition", 0));
+    }
+
+    #[test]
+    fn stream_in_any_pieces() {
+        let data = crate::test_data::bytes(1000);
+        for piece in [1, 3, 8, 31, 32, 33, 500] {
+            let mut crc = Crc32::new();
+            let mut xxhash = XxHash64::new(7);
+            for chunk in data.chunks(piece) {
+                crc.update(chunk);
+                xxhash.update(chunk);
+            }
+            assert_eq!(data.len() as u64, xxhash.bytes_processed());
+            assert_eq!(crc32(&data), crc.finalize(), "{piece}");
+            assert_eq!(xxhash64(&data, 7), xxhash.finalize(), "{piece}");
+        }
+        assert_ne!(xxhash64(FOX, 0), xxhash64(FOX, 1));
+    }
+}
@@ -0,0 +1,86 @@
+//! Just enough of a DER (X.690) reader and writer to look inside and assemble key structures.
+
+pub(crate) const INTEGER: u8 = 0x02;
+pub(crate) const BIT_STRING: u8 = 0x03;
+pub(crate) const OCTET_STRING: u8 = 0x04;
+pub(crate) const NULL: u8 = 0x05;
+pub(crate) const OID: u8 = 0x06;
+pub(crate) const SEQUENCE: u8 = 0x30;
+
+/// Splits the next element off the input as its tag, contents, and the remaining input
+pub(crate) fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if !(1..=4).contains(&count) {
+            return None;
+        }
+        let (bytes, rest) = rest.split_at_checked(count)?;
+        (bytes.iter().fold(0, |len, &b| len << 8 | usize::from(b)), rest)
+    };
+    let (contents, rest) = rest.split_at_checked(len)?;
+    Some((tag, contents, rest))
+}
+
+/// Reads the next element only if it has the tag, returning its contents and the remaining input
+pub(crate) fn expect_tlv(tag: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    read_tlv(input).and_then(|(found, contents, rest)| (found == tag).then_some((contents, rest)))
+}
+
+/// Encodes an element with the shortest length form
+pub(crate) fn write_tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let len = contents.len();
+    let mut der = vec![tag];
+    if len < 0x80 {
+        der.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|&&b| b == 0).count();
+        der.push(0x80 | (bytes.len() - skip) as u8);
+        der.extend_from_slice(&bytes[skip..]);
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// The bit length of a non-negative INTEGER's contents, ignoring the leading sign byte
+pub(crate) fn integer_bits(contents: &[u8]) -> usize {
+    let skip = contents.iter().take_while(|&&b| b == 0).count();
+    match contents.get(skip) {
+        Some(first) => (contents.len() - skip) * 8 - first.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn round_trip_short_and_long_lengths() {
+        for len in [0, 1, 127, 128, 255, 256, 70_000] {
+            let contents = vec![0xab; len];
+            let der = write_tlv(OCTET_STRING, &contents);
+            assert_eq!(Some((OCTET_STRING, &contents[..], &[][..])), read_tlv(&der), "{len}");
+        }
+        assert_eq!(vec![0x30, 0x81, 0x80], write_tlv(SEQUENCE, &[0; 128])[..3]);
+    }
+
+    #[test]
+    fn reject_truncated_input() {
+        assert_eq!(None, read_tlv(&[0x30]));
+        assert_eq!(None, read_tlv(&[0x30, 0x03, 0x01]));
+        assert_eq!(None, read_tlv(&[0x30, 0x85, 0, 0, 0, 0, 1]));
+        assert_eq!(None, expect_tlv(INTEGER, &[0x30, 0x00]));
+    }
+
+    #[test]
+    fn count_integer_bits() {
+        assert_eq!(2048, integer_bits(&[&[0x00, 0xc1][..], &[0; 255]].concat()));
+        assert_eq!(9, integer_bits(&[0x01, 0x00]));
+        assert_eq!(0, integer_bits(&[0x00]));
+    }
+}
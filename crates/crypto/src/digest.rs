@@ -0,0 +1,102 @@
+//! The hash implementations behind `Algorithm`: ring for the SHA family and, with the "blake3"
+//! feature, the blake3 crate. `hash`, `Hasher`, and the Merkle tree only see the trait.
+
+use crate::Algorithm;
+
+use ring::digest;
+
+/// A running hash of one algorithm
+pub(crate) trait Digest: Send + Sync {
+    fn update(&mut self, data: &[u8]);
+    fn finish(self: Box<Self>) -> Vec<u8>;
+    fn box_clone(&self) -> Box<dyn Digest>;
+}
+
+impl Digest for digest::Context {
+    fn update(&mut self, data: &[u8]) {
+        digest::Context::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        digest::Context::finish(*self).as_ref().to_vec()
+    }
+
+    fn box_clone(&self) -> Box<dyn Digest> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl Digest for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish(self: Box<Self>) -> Vec<u8> {
+        self.finalize().as_bytes().to_vec()
+    }
+
+    fn box_clone(&self) -> Box<dyn Digest> {
+        Box::new(self.clone())
+    }
+}
+
+impl Algorithm {
+    pub(crate) fn start_digest(self) -> Box<dyn Digest> {
+        let ring = match self {
+            Algorithm::SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            Algorithm::SHA256 => &digest::SHA256,
+            Algorithm::SHA384 => &digest::SHA384,
+            Algorithm::SHA512 => &digest::SHA512,
+            Algorithm::SHA512_256 => &digest::SHA512_256,
+            #[cfg(feature = "blake3")]
+            Algorithm::BLAKE3 => return Box::new(blake3::Hasher::new()),
+        };
+        Box::new(digest::Context::new(ring))
+    }
+}
+
+#[cfg(all(test, feature = "blake3"))]
+mod should {
+    use crate::{hash_blake3, Algorithm, HashMeta, Hasher};
+
+    // From the BLAKE3 reference test_vectors.json, the input is n bytes counting 0 to 250
+    const VECTORS: [(usize, &str); 6] = [
+        (0, "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"),
+        (1, "2d3adedff11b61f14c886e35afa036736dcd87a74d27b5c1510225d0f592e213"),
+        (1023, "10108970eeda3eb932baac1428c7a2163b0e924c9a9e25b35bba72b28f70bd11"),
+        (1024, "42214739f095a406f3fc83deb889744ac00df831c10daa55189b5d121c855af7"),
+        (1025, "d00278ae47eb27b34faecf67b4fe263f82d5412916c1ffd97c8cb7fb814b8444"),
+        (31744, "62b6960e1a44bcc1eb1a611a8d6235b6b4b78f32e7abc4fb4c6cdcce94895c47"),
+    ];
+
+    fn input(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn match_official_vectors() {
+        for (len, expected) in VECTORS {
+            assert_eq!(expected, hash_blake3(&input(len)).to_hex(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn stream_like_one_shot() {
+        for (len, _) in VECTORS {
+            let input = input(len);
+            let mut hasher = Hasher::new(Algorithm::BLAKE3);
+            for piece in input.chunks(100) {
+                hasher.update(piece);
+            }
+            assert_eq!(hash_blake3(&input), hasher.finalize(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn read_back_canonical_form() {
+        let meta = hash_blake3(b"abc");
+        assert_eq!(Ok(meta.clone()), HashMeta::parse(&meta.to_string()));
+        assert!(meta.to_string().starts_with("blake3:"));
+    }
+}
@@ -0,0 +1,268 @@
+//! ECDSA over P-256 with SHA-256 (ES256), for partners who verify webhooks with JOSE or
+//! OpenSSL.
+//!
+//! Signing keys travel as PKCS#8 and verifying keys as SPKI, either as DER or PEM. Signatures
+//! come in the fixed 64 byte form JOSE uses or the ASN.1 DER form OpenSSL uses.
+
+use crate::der::{expect_tlv, read_tlv, BIT_STRING, INTEGER, OID, SEQUENCE};
+use crate::encoding::hex_encode;
+use crate::random::rng;
+use crate::{pem, Error};
+
+use ring::signature::{self, EcdsaKeyPair, KeyPair};
+
+/// Uncompressed point: 0x04 then the 32 byte x and y coordinates
+pub const PUBLIC_KEY_LEN: usize = 65;
+pub const FIXED_SIGNATURE_LEN: usize = 64;
+
+const PRIVATE_KEY_LABEL: &str = "PRIVATE KEY";
+const PUBLIC_KEY_LABEL: &str = "PUBLIC KEY";
+/// id-ecPublicKey (1.2.840.10045.2.1)
+const EC_PUBLIC_KEY_OID: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+/// prime256v1 (1.2.840.10045.3.1.7)
+const P256_OID: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// SPKI up to the public key bits, which is the same for every P-256 key
+const SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+    0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// r and s as 32 bytes each, as JWS (RFC 7518) expects
+    Fixed,
+    /// An ASN.1 DER sequence of r and s, as OpenSSL produces
+    Asn1,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub format: SignatureFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// The secret half of a key pair, its Debug only shows the public key
+pub struct SigningKey {
+    fixed: EcdsaKeyPair,
+    asn1: EcdsaKeyPair,
+    pkcs8: Vec<u8>,
+}
+
+impl SigningKey {
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self, Error> {
+        let (body, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        let (_, rest) = expect_tlv(INTEGER, body).ok_or(Error::MalformedKey)?;
+        let (algorithm, _) = expect_tlv(SEQUENCE, rest).ok_or(Error::MalformedKey)?;
+        check_algorithm(algorithm)?;
+        let key_pair = |alg| EcdsaKeyPair::from_pkcs8(alg, der, rng());
+        Ok(SigningKey {
+            fixed: key_pair(&signature::ECDSA_P256_SHA256_FIXED_SIGNING)?,
+            asn1: key_pair(&signature::ECDSA_P256_SHA256_ASN1_SIGNING)?,
+            pkcs8: der.to_vec(),
+        })
+    }
+
+    pub fn from_pkcs8_pem(pem: &str) -> Result<Self, Error> {
+        SigningKey::from_pkcs8_der(&pem::decode(PRIVATE_KEY_LABEL, pem)?)
+    }
+
+    /// The PKCS#8 DER this key was generated or imported as
+    pub fn to_pkcs8_der(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    pub fn to_pkcs8_pem(&self) -> String {
+        pem::encode(PRIVATE_KEY_LABEL, &self.pkcs8)
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let mut point = [0; PUBLIC_KEY_LEN];
+        point.copy_from_slice(self.fixed.public_key().as_ref());
+        VerifyingKey(point)
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SigningKey ( public: {} )", hex_encode(self.fixed.public_key().as_ref()))
+    }
+}
+
+/// The public half of a key pair, held as the uncompressed point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyingKey([u8; PUBLIC_KEY_LEN]);
+
+impl VerifyingKey {
+    /// Import a SubjectPublicKeyInfo, which must be an EC key on P-256
+    pub fn from_spki_der(der: &[u8]) -> Result<Self, Error> {
+        let (spki, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        let (algorithm, rest) = expect_tlv(SEQUENCE, spki).ok_or(Error::MalformedKey)?;
+        check_algorithm(algorithm)?;
+        let (bits, _) = expect_tlv(BIT_STRING, rest).ok_or(Error::MalformedKey)?;
+        // The leading byte counts unused bits, always 0 for a key
+        match bits.split_first() {
+            Some((0, point)) => VerifyingKey::from_bytes(point),
+            _ => Err(Error::MalformedKey),
+        }
+    }
+
+    pub fn from_spki_pem(pem: &str) -> Result<Self, Error> {
+        VerifyingKey::from_spki_der(&pem::decode(PUBLIC_KEY_LABEL, pem)?)
+    }
+
+    /// Import the uncompressed point, the point is only checked to be on the curve when used
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let point: [u8; PUBLIC_KEY_LEN] = bytes.try_into().map_err(|_| Error::InvalidLength {
+            expected: PUBLIC_KEY_LEN,
+            actual: bytes.len(),
+        })?;
+        if point[0] != 0x04 {
+            return Err(Error::MalformedKey);
+        }
+        Ok(VerifyingKey(point))
+    }
+
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LEN] {
+        self.0
+    }
+
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        [&SPKI_PREFIX[..], &self.0[..]].concat()
+    }
+
+    pub fn to_spki_pem(&self) -> String {
+        pem::encode(PUBLIC_KEY_LABEL, &self.to_spki_der())
+    }
+}
+
+// Refuses anything but an EC key on P-256, naming the curve mismatch
+fn check_algorithm(algorithm: &[u8]) -> Result<(), Error> {
+    match read_tlv(algorithm) {
+        Some((OID, EC_PUBLIC_KEY_OID, parameters)) => match read_tlv(parameters) {
+            Some((OID, P256_OID, _)) => Ok(()),
+            Some((OID, ..)) => Err(Error::WrongCurve),
+            _ => Err(Error::MalformedKey),
+        },
+        Some((OID, ..)) => Err(Error::KeyRejected("not an EC key".to_owned())),
+        _ => Err(Error::MalformedKey),
+    }
+}
+
+/// Generate a new key pair from the system random source
+pub fn generate_keypair() -> Result<(SigningKey, VerifyingKey), Error> {
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, rng())
+        .map_err(|_| Error::Random)?;
+    let signing_key = SigningKey::from_pkcs8_der(pkcs8.as_ref())?;
+    let verifying_key = signing_key.verifying_key();
+    Ok((signing_key, verifying_key))
+}
+
+/// ECDSA signatures are randomized so this can fail if the random source does
+pub fn sign(key: &SigningKey, msg: &[u8], format: SignatureFormat) -> Result<Signature, Error> {
+    let key_pair = match format {
+        SignatureFormat::Fixed => &key.fixed,
+        SignatureFormat::Asn1 => &key.asn1,
+    };
+    let signature = key_pair.sign(rng(), msg).map_err(|_| Error::Random)?;
+    Ok(Signature {
+        format,
+        bytes: signature.as_ref().to_vec(),
+    })
+}
+
+pub fn verify(key: &VerifyingKey, msg: &[u8], signature: &Signature) -> bool {
+    let algorithm = match signature.format {
+        SignatureFormat::Fixed => &signature::ECDSA_P256_SHA256_FIXED,
+        SignatureFormat::Asn1 => &signature::ECDSA_P256_SHA256_ASN1,
+    };
+    signature::UnparsedPublicKey::new(algorithm, key.0)
+        .verify(msg, &signature.bytes)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // Generated with `openssl ecparam -name prime256v1 -genkey`, the signature over MESSAGE with
+    // `openssl dgst -sha256 -sign`
+    const OPENSSL_PRIVATE_KEY: &str = include_str!("../fixtures/es256_private.pem");
+    const OPENSSL_PUBLIC_KEY: &str = include_str!("../fixtures/es256_public.pem");
+    const OPENSSL_SIGNATURE: &[u8] = include_bytes!("../fixtures/es256_signature.der");
+    const MESSAGE: &[u8] = br#"webhook payload {"id":42}"#;
+    // The same, on P-384
+    const P384_PRIVATE_KEY: &str = include_str!("../fixtures/p384_private.pem");
+    const P384_PUBLIC_KEY: &str = include_str!("../fixtures/p384_public.pem");
+
+    #[test]
+    fn round_trip_sign_and_verify() {
+        let (signing_key, verifying_key) = generate_keypair().unwrap();
+        for format in [SignatureFormat::Fixed, SignatureFormat::Asn1] {
+            let signature = sign(&signing_key, MESSAGE, format).unwrap();
+            assert!(verify(&verifying_key, MESSAGE, &signature), "{format:?}");
+        }
+        let fixed = sign(&signing_key, MESSAGE, SignatureFormat::Fixed).unwrap();
+        assert_eq!(FIXED_SIGNATURE_LEN, fixed.bytes.len());
+    }
+
+    #[test]
+    fn verify_openssl_signature() {
+        let verifying_key = VerifyingKey::from_spki_pem(OPENSSL_PUBLIC_KEY).unwrap();
+        let signature = Signature {
+            format: SignatureFormat::Asn1,
+            bytes: OPENSSL_SIGNATURE.to_vec(),
+        };
+        assert!(verify(&verifying_key, MESSAGE, &signature));
+    }
+
+    #[test]
+    fn reject_tampered_message() {
+        let verifying_key = VerifyingKey::from_spki_pem(OPENSSL_PUBLIC_KEY).unwrap();
+        let signature = Signature {
+            format: SignatureFormat::Asn1,
+            bytes: OPENSSL_SIGNATURE.to_vec(),
+        };
+        assert!(!verify(&verifying_key, br#"webhook payload {"id":43}"#, &signature));
+        let fixed = Signature { format: SignatureFormat::Fixed, ..signature };
+        assert!(!verify(&verifying_key, MESSAGE, &fixed));
+    }
+
+    #[test]
+    fn interoperate_with_openssl_keys() {
+        let signing_key = SigningKey::from_pkcs8_pem(OPENSSL_PRIVATE_KEY).unwrap();
+        let verifying_key = VerifyingKey::from_spki_pem(OPENSSL_PUBLIC_KEY).unwrap();
+        assert_eq!(verifying_key, signing_key.verifying_key());
+        assert_eq!(OPENSSL_PUBLIC_KEY, verifying_key.to_spki_pem());
+        let reimported = SigningKey::from_pkcs8_pem(&signing_key.to_pkcs8_pem()).unwrap();
+        assert_eq!(signing_key.to_pkcs8_der(), reimported.to_pkcs8_der());
+    }
+
+    #[test]
+    fn reject_keys_on_other_curves() {
+        assert_eq!(Some(Error::WrongCurve), VerifyingKey::from_spki_pem(P384_PUBLIC_KEY).err());
+        assert_eq!(Some(Error::WrongCurve), SigningKey::from_pkcs8_pem(P384_PRIVATE_KEY).err());
+    }
+
+    #[test]
+    fn reject_malformed_keys() {
+        assert_eq!(Some(Error::MalformedKey), VerifyingKey::from_spki_der(&[0x30, 0x05, 0x01]).err());
+        let truncated = &VerifyingKey::from_spki_pem(OPENSSL_PUBLIC_KEY).unwrap().to_spki_der()[..60];
+        assert!(VerifyingKey::from_spki_der(truncated).is_err());
+        assert!(matches!(VerifyingKey::from_spki_pem("no armor"), Err(Error::InvalidPem(_))));
+        let ed25519 = crate::ed25519::generate_keypair().unwrap().0;
+        assert!(matches!(SigningKey::from_pkcs8_der(ed25519.to_pkcs8()), Err(Error::KeyRejected(_))));
+    }
+
+    #[test]
+    fn keep_the_secret_out_of_debug() {
+        let signing_key = SigningKey::from_pkcs8_pem(OPENSSL_PRIVATE_KEY).unwrap();
+        let public = hex_encode(&signing_key.verifying_key().to_bytes());
+        assert_eq!(format!("SigningKey ( public: {public} )"), format!("{signing_key:?}"));
+    }
+}
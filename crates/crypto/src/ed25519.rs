@@ -0,0 +1,206 @@
+//! Ed25519 signatures (RFC 8032), i.e. for short-lived download URLs.
+//!
+//! Signing keys travel as PKCS#8 DER, verifying keys as their raw 32 bytes.
+
+use crate::encoding::hex_encode;
+use crate::random::rng;
+use crate::Error;
+
+use ring::signature::{self, Ed25519KeyPair, KeyPair};
+
+pub const PUBLIC_KEY_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+const SEED_LEN: usize = 32;
+
+/// PKCS#8 v2 (RFC 5958) around the seed and public key, the same form ring generates
+const PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x53, 0x02, 0x01, 0x01, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+const PKCS8_PUBLIC_KEY_TAG: [u8; 5] = [0xa1, 0x23, 0x03, 0x21, 0x00];
+
+/// The secret half of a key pair, its Debug only shows the public key
+pub struct SigningKey {
+    key_pair: Ed25519KeyPair,
+    pkcs8: Vec<u8>,
+}
+
+impl SigningKey {
+    /// Import PKCS#8 DER, either v1 (as OpenSSL writes) or v2 which also carries the public key
+    pub fn from_pkcs8(der: &[u8]) -> Result<Self, Error> {
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der)?;
+        Ok(SigningKey { key_pair, pkcs8: der.to_vec() })
+    }
+
+    /// Build the key from the 32 byte secret seed, as the RFC 8032 test vectors give it
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        if seed.len() != SEED_LEN {
+            return Err(Error::InvalidLength { expected: SEED_LEN, actual: seed.len() });
+        }
+        let key_pair = Ed25519KeyPair::from_seed_unchecked(seed)?;
+        let pkcs8 = [&PKCS8_PREFIX[..], seed, &PKCS8_PUBLIC_KEY_TAG[..], key_pair.public_key().as_ref()].concat();
+        Ok(SigningKey { key_pair, pkcs8 })
+    }
+
+    /// The PKCS#8 DER this key was generated or imported as
+    pub fn to_pkcs8(&self) -> &[u8] {
+        &self.pkcs8
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        let mut bytes = [0; PUBLIC_KEY_LEN];
+        bytes.copy_from_slice(self.key_pair.public_key().as_ref());
+        VerifyingKey(bytes)
+    }
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SigningKey ( public: {} )", hex_encode(self.key_pair.public_key().as_ref()))
+    }
+}
+
+/// The public half of a key pair
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifyingKey([u8; PUBLIC_KEY_LEN]);
+
+impl VerifyingKey {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(VerifyingKey(fixed(bytes)?))
+    }
+
+    pub fn to_bytes(&self) -> [u8; PUBLIC_KEY_LEN] {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signature([u8; SIGNATURE_LEN]);
+
+impl Signature {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Signature(fixed(bytes)?))
+    }
+
+    pub fn to_bytes(&self) -> [u8; SIGNATURE_LEN] {
+        self.0
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], Error> {
+    bytes
+        .try_into()
+        .map_err(|_| Error::InvalidLength { expected: N, actual: bytes.len() })
+}
+
+/// Generate a new key pair from the system random source
+pub fn generate_keypair() -> Result<(SigningKey, VerifyingKey), Error> {
+    let pkcs8 = Ed25519KeyPair::generate_pkcs8(rng()).map_err(|_| Error::Random)?;
+    let signing_key = SigningKey::from_pkcs8(pkcs8.as_ref())?;
+    let verifying_key = signing_key.verifying_key();
+    Ok((signing_key, verifying_key))
+}
+
+pub fn sign(key: &SigningKey, msg: &[u8]) -> Signature {
+    let mut bytes = [0; SIGNATURE_LEN];
+    bytes.copy_from_slice(key.key_pair.sign(msg).as_ref());
+    Signature(bytes)
+}
+
+pub fn verify(key: &VerifyingKey, msg: &[u8], signature: &Signature) -> bool {
+    signature::UnparsedPublicKey::new(&signature::ED25519, key.0)
+        .verify(msg, &signature.0)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::encoding::hex_decode;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        hex_decode(hex).unwrap()
+    }
+
+    // RFC 8032 section 7.1, tests 1-3: secret seed, public key, message, signature
+    const VECTORS: [(&str, &str, &str, &str); 3] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+    ];
+
+    #[test]
+    fn match_rfc_8032_vectors() {
+        for (seed, public, msg, expected) in VECTORS {
+            let signing_key = SigningKey::from_seed(&unhex(seed)).unwrap();
+            let verifying_key = VerifyingKey::from_bytes(&unhex(public)).unwrap();
+            assert_eq!(verifying_key, signing_key.verifying_key());
+            let signature = sign(&signing_key, &unhex(msg));
+            assert_eq!(unhex(expected), signature.as_ref());
+            assert!(verify(&verifying_key, &unhex(msg), &signature));
+        }
+    }
+
+    #[test]
+    fn round_trip_through_pkcs8() {
+        let (signing_key, verifying_key) = generate_keypair().unwrap();
+        let imported = SigningKey::from_pkcs8(signing_key.to_pkcs8()).unwrap();
+        assert_eq!(verifying_key, imported.verifying_key());
+        let signature = sign(&imported, b"download/123?expires=1700000000");
+        assert!(verify(&verifying_key, b"download/123?expires=1700000000", &signature));
+        assert!(!verify(&verifying_key, b"download/124?expires=1700000000", &signature));
+        let raw = VerifyingKey::from_bytes(&verifying_key.to_bytes()).unwrap();
+        assert_eq!(verifying_key, raw);
+    }
+
+    #[test]
+    fn build_the_same_pkcs8_as_ring_from_the_seed() {
+        let (seed, ..) = VECTORS[0];
+        let from_seed = SigningKey::from_seed(&unhex(seed)).unwrap();
+        let imported = SigningKey::from_pkcs8(from_seed.to_pkcs8()).unwrap();
+        assert_eq!(from_seed.verifying_key(), imported.verifying_key());
+        assert_eq!(85, from_seed.to_pkcs8().len());
+    }
+
+    #[test]
+    fn import_pkcs8_v1_from_openssl() {
+        let der = unhex("302e020100300506032b6570042204209d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+        let (_, public, ..) = VECTORS[0];
+        let signing_key = SigningKey::from_pkcs8(&der).unwrap();
+        assert_eq!(unhex(public), signing_key.verifying_key().to_bytes());
+    }
+
+    #[test]
+    fn reject_malformed_keys_and_signatures() {
+        assert!(matches!(SigningKey::from_pkcs8(b"not a key"), Err(Error::KeyRejected(_))));
+        assert_eq!(Err(Error::InvalidLength { expected: 32, actual: 3 }), VerifyingKey::from_bytes(&[1, 2, 3]));
+        assert_eq!(Err(Error::InvalidLength { expected: 64, actual: 0 }), Signature::from_bytes(&[]));
+    }
+
+    #[test]
+    fn keep_the_secret_out_of_debug() {
+        let (seed, public, ..) = VECTORS[0];
+        let debug = format!("{:?}", SigningKey::from_seed(&unhex(seed)).unwrap());
+        assert!(debug.contains(public) && !debug.contains(seed), "{debug}");
+    }
+}
@@ -0,0 +1,219 @@
+//! Hex and base64 (RFC 4648) for digests and tokens, and the varints and base58 multihash
+//! needs, kept in crate rather than pulling in another dependency for a few dozen lines.
+
+pub(crate) const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+pub(crate) const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// Lowercase hex
+pub(crate) fn hex_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        result.push(HEX[usize::from(b >> 4)] as char);
+        result.push(HEX[usize::from(b & 0xf)] as char);
+    }
+    result
+}
+
+/// Accepts either case, `None` for an odd length or any other character
+pub(crate) fn hex_decode(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    let nibble = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    text.as_bytes()
+        .chunks_exact(2)
+        .map(|pair| Some(nibble(pair[0])? << 4 | nibble(pair[1])?))
+        .collect()
+}
+
+pub(crate) fn base64_encode(bytes: &[u8], alphabet: &[u8; 64], pad: bool) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(alphabet[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else if pad {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Padding is optional, `None` for characters outside the alphabet, a length no encoder
+/// produces, or stray bits in the last character
+pub(crate) fn base64_decode(text: &str, alphabet: &[u8; 64]) -> Option<Vec<u8>> {
+    let text = text.strip_suffix("==").or_else(|| text.strip_suffix('=')).unwrap_or(text);
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut result = Vec::with_capacity(text.len() * 3 / 4);
+    let mut accum: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = alphabet.iter().position(|&a| a == c)? as u32;
+        accum = accum << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((accum >> bits & 0xff) as u8);
+        }
+    }
+    if accum & ((1 << bits) - 1) != 0 {
+        return None;
+    }
+    Some(result)
+}
+
+/// Longest unsigned varint multiformats allows, enough for 63 bits
+const MAX_VARINT_LEN: usize = 9;
+
+/// Unsigned LEB128 as multiformats uses for codes and lengths
+pub(crate) fn varint_encode(mut value: u64, dest: &mut Vec<u8>) {
+    while value >= 0x80 {
+        dest.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    dest.push(value as u8);
+}
+
+/// Splits a varint off the front, `None` if it runs off the end or past the longest allowed
+pub(crate) fn varint_decode(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0;
+    for (i, &b) in bytes.iter().enumerate().take(MAX_VARINT_LEN) {
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+#[cfg(feature = "base58")]
+const BASE58: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Bitcoin's base58 alphabet, each leading zero byte becomes a '1'
+#[cfg(feature = "base58")]
+pub(crate) fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Base 58 digits of the number, least significant first
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &b in &bytes[zeros..] {
+        let mut carry = u32::from(b);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let ones = std::iter::repeat_n('1', zeros);
+    ones.chain(digits.iter().rev().map(|&d| BASE58[usize::from(d)] as char)).collect()
+}
+
+#[cfg(feature = "base58")]
+pub(crate) fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    let zeros = text.bytes().take_while(|&c| c == b'1').count();
+    // Bytes of the number, least significant first
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len());
+    for c in text.bytes().skip(zeros) {
+        let mut carry = BASE58.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // RFC 4648 section 10
+    const VECTORS: [(&str, &str, &str); 7] = [
+        ("", "", ""),
+        ("f", "Zg==", "66"),
+        ("fo", "Zm8=", "666f"),
+        ("foo", "Zm9v", "666f6f"),
+        ("foob", "Zm9vYg==", "666f6f62"),
+        ("fooba", "Zm9vYmE=", "666f6f6261"),
+        ("foobar", "Zm9vYmFy", "666f6f626172"),
+    ];
+
+    #[test]
+    fn match_rfc_4648_vectors() {
+        for (plain, base64, hex) in VECTORS {
+            assert_eq!(base64, base64_encode(plain.as_bytes(), BASE64, true));
+            assert_eq!(base64.trim_end_matches('='), base64_encode(plain.as_bytes(), BASE64, false));
+            assert_eq!(Some(plain.as_bytes().to_vec()), base64_decode(base64, BASE64));
+            assert_eq!(Some(plain.as_bytes().to_vec()), base64_decode(base64.trim_end_matches('='), BASE64));
+            assert_eq!(hex, hex_encode(plain.as_bytes()));
+            assert_eq!(Some(plain.as_bytes().to_vec()), hex_decode(hex));
+        }
+    }
+
+    #[test]
+    fn use_url_safe_alphabet() {
+        assert_eq!("-_8", base64_encode(&[0xfb, 0xff], BASE64URL, false));
+        assert_eq!("+/8=", base64_encode(&[0xfb, 0xff], BASE64, true));
+        assert_eq!(Some(vec![0xfb, 0xff]), base64_decode("-_8", BASE64URL));
+        assert_eq!(None, base64_decode("+/8", BASE64URL));
+    }
+
+    #[test]
+    fn reject_malformed_input() {
+        assert_eq!(None, hex_decode("abc"));
+        assert_eq!(None, hex_decode("zz"));
+        assert_eq!(Some(vec![0xab]), hex_decode("AB"));
+        assert_eq!(None, base64_decode("Zm9vY", BASE64));
+        assert_eq!(None, base64_decode("Zh==", BASE64));
+        assert_eq!(None, base64_decode("Zm9v!", BASE64));
+    }
+
+    #[test]
+    fn round_trip_varints() {
+        let vectors = [(0, &[0x00][..]), (0x7f, &[0x7f]), (0x80, &[0x80, 0x01]), (0x1015, &[0x95, 0x20])];
+        for (value, encoded) in vectors {
+            let mut bytes = Vec::new();
+            varint_encode(value, &mut bytes);
+            assert_eq!(encoded, bytes);
+            assert_eq!(Some((value, &[][..])), varint_decode(&bytes));
+        }
+        assert_eq!(None, varint_decode(&[0x80]));
+        assert_eq!(None, varint_decode(&[0xff; 10]));
+    }
+
+    #[cfg(feature = "base58")]
+    #[test]
+    fn round_trip_base58() {
+        // From the Bitcoin Core base58 test data
+        let vectors = [
+            ("", ""),
+            ("61", "2g"),
+            ("626262", "a3gV"),
+            ("00000000000000000000", "1111111111"),
+            ("00eb15231dfceb60925886b67d065299925915aeb172c06647", "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L"),
+        ];
+        for (hex, text) in vectors {
+            let bytes = hex_decode(hex).unwrap();
+            assert_eq!(text, base58_encode(&bytes));
+            assert_eq!(Some(bytes), base58_decode(text));
+        }
+        assert_eq!(None, base58_decode("0OIl"));
+    }
+}
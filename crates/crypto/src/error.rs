@@ -0,0 +1,101 @@
+use crate::{Algorithm, SignatureScheme};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The algorithm can't be used for the requested operation, i.e. HMAC with SHA1 unless the
+    /// legacy variant was asked for
+    UnsupportedAlgorithm(Algorithm),
+    /// The system random number generator failed
+    Random,
+    /// A key couldn't be parsed or doesn't suit the operation, with ring's reason
+    KeyRejected(String),
+    /// A raw key or signature has the wrong number of bytes
+    InvalidLength { expected: usize, actual: usize },
+    /// The DER structure of a key couldn't be read
+    MalformedKey,
+    /// An EC key is on a different curve than the operation needs
+    WrongCurve,
+    /// The PEM armor is missing, has another label, or wraps invalid base64
+    InvalidPem(String),
+    /// The private key is password protected, decrypt it first i.e. with `openssl pkey`
+    EncryptedKey,
+    /// The key's modulus is shorter than the minimum considered safe
+    KeyTooSmall { bits: usize, min: usize },
+    /// Sealed data failed authentication: it was changed, truncated, or opened with the wrong
+    /// key or aad
+    Unauthenticated,
+    /// Text which should be base64 isn't
+    InvalidBase64,
+}
+
+impl From<ring::error::KeyRejected> for Error {
+    fn from(err: ring::error::KeyRejected) -> Self {
+        Error::KeyRejected(err.to_string())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = match self {
+            Error::UnsupportedAlgorithm(algorithm) => format!("unsupported algorithm: {algorithm}"),
+            Error::Random => "random number generator failed".to_owned(),
+            Error::KeyRejected(reason) => format!("key rejected: {reason}"),
+            Error::InvalidLength { expected, actual } => format!("invalid length: expected {expected} bytes, got {actual}"),
+            Error::MalformedKey => "malformed key".to_owned(),
+            Error::WrongCurve => "key is on the wrong curve".to_owned(),
+            Error::InvalidPem(reason) => format!("invalid pem: {reason}"),
+            Error::EncryptedKey => "key is encrypted".to_owned(),
+            Error::KeyTooSmall { bits, min } => format!("key too small: {bits} bits, at least {min} required"),
+            Error::Unauthenticated => "authentication failed".to_owned(),
+            Error::InvalidBase64 => "invalid base64".to_owned(),
+        };
+        write!(f, "Error ( {message} )")
+    }
+}
+
+/// Why a canonical `HashMeta` string ("sha256:<hex>") couldn't be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// There's no "algorithm:" in front of the digest
+    MissingPrefix,
+    UnknownAlgorithm(String),
+    /// The digest isn't valid hex
+    InvalidDigest,
+    /// The digest has the wrong number of bytes for the algorithm
+    WrongLength { expected: usize, actual: usize },
+    /// The multihash code isn't one of the supported algorithms
+    UnknownCode(u64),
+    /// The input ends before the multihash does
+    Truncated,
+    /// The text isn't valid base58
+    InvalidBase58,
+    /// The signature isn't valid base64url
+    InvalidSignature,
+    /// The signature is a length the scheme never produces
+    SignatureLength { scheme: SignatureScheme, actual: usize },
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let message = match self {
+            ParseError::MissingPrefix => "missing algorithm prefix".to_owned(),
+            ParseError::UnknownAlgorithm(algorithm) => format!("unknown algorithm: {algorithm}"),
+            ParseError::InvalidDigest => "digest is not valid hex".to_owned(),
+            ParseError::WrongLength { expected, actual } => {
+                format!("wrong digest length: expected {expected} bytes, got {actual}")
+            }
+            ParseError::UnknownCode(code) => format!("unknown multihash code: {code:#x}"),
+            ParseError::Truncated => "multihash is truncated".to_owned(),
+            ParseError::InvalidBase58 => "not valid base58".to_owned(),
+            ParseError::InvalidSignature => "signature is not valid base64url".to_owned(),
+            ParseError::SignatureLength { scheme, actual } => {
+                format!("wrong signature length for {scheme}: {actual} bytes")
+            }
+        };
+        write!(f, "ParseError ( {message} )")
+    }
+}
@@ -1,6 +1,11 @@
-use crate::Algorithm;
+#[cfg(feature = "base58")]
+use crate::encoding::{base58_decode, base58_encode};
+use crate::encoding::{base64_encode, hex_decode, hex_encode, varint_decode, varint_encode, BASE64, BASE64URL};
+use crate::{Algorithm, ParseError};
 
-#[derive(Clone, Debug)]
+/// A digest along with the algorithm which produced it. Displays in the canonical
+/// "sha256:<hex>" form which `parse` reads back.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HashMeta {
     pub algorithm: Algorithm,
     pub hash: Vec<u8>,
@@ -10,15 +15,186 @@ impl HashMeta {
     pub fn new(algorithm: Algorithm, hash: Vec<u8>) -> Self {
         HashMeta { algorithm, hash }
     }
+
+    /// Hash other data with the same algorithm, i.e. to compare against this one
+    pub fn recompute(&self, data: &[u8]) -> HashMeta {
+        crate::hash(self.algorithm, data)
+    }
+
+    /// Lowercase hex of the digest
+    pub fn to_hex(&self) -> String {
+        hex_encode(&self.hash)
+    }
+
+    /// Standard base64 of the digest, with padding
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.hash, BASE64, true)
+    }
+
+    /// URL and filename safe base64 of the digest, without padding
+    pub fn to_base64url(&self) -> String {
+        base64_encode(&self.hash, BASE64URL, false)
+    }
+
+    /// Reads the canonical "algorithm:hex" form, the algorithm and hex are case insensitive and
+    /// the digest must be the length the algorithm produces
+    pub fn parse(s: &str) -> Result<HashMeta, ParseError> {
+        let (prefix, digest) = s.split_once(':').ok_or(ParseError::MissingPrefix)?;
+        let algorithm: Algorithm = prefix.parse()?;
+        let hash = hex_decode(digest).ok_or(ParseError::InvalidDigest)?;
+        if hash.len() != algorithm.digest_len() {
+            return Err(ParseError::WrongLength {
+                expected: algorithm.digest_len(),
+                actual: hash.len(),
+            });
+        }
+        Ok(HashMeta { algorithm, hash })
+    }
+
+    /// The self describing multihash form: the algorithm's code and the digest length as
+    /// varints, then the digest
+    pub fn to_multihash(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.hash.len() + 4);
+        varint_encode(self.algorithm.multihash_code(), &mut bytes);
+        varint_encode(self.hash.len() as u64, &mut bytes);
+        bytes.extend_from_slice(&self.hash);
+        bytes
+    }
+
+    /// Reads a multihash, the code must be a supported algorithm and the length its digest length
+    pub fn from_multihash(bytes: &[u8]) -> Result<HashMeta, ParseError> {
+        let (code, rest) = varint_decode(bytes).ok_or(ParseError::Truncated)?;
+        let algorithm = Algorithm::from_multihash_code(code).ok_or(ParseError::UnknownCode(code))?;
+        let (len, digest) = varint_decode(rest).ok_or(ParseError::Truncated)?;
+        let expected = algorithm.digest_len();
+        if len != expected as u64 {
+            return Err(ParseError::WrongLength {
+                expected,
+                actual: usize::try_from(len).unwrap_or(usize::MAX),
+            });
+        }
+        match digest.len() {
+            actual if actual < expected => Err(ParseError::Truncated),
+            actual if actual > expected => Err(ParseError::WrongLength { expected, actual }),
+            _ => Ok(HashMeta::new(algorithm, digest.to_vec())),
+        }
+    }
+
+    /// The multihash in base58btc, as IPFS v0 CIDs ("Qm...") are written
+    #[cfg(feature = "base58")]
+    pub fn to_base58(&self) -> String {
+        base58_encode(&self.to_multihash())
+    }
+
+    #[cfg(feature = "base58")]
+    pub fn from_base58(text: &str) -> Result<HashMeta, ParseError> {
+        HashMeta::from_multihash(&base58_decode(text).ok_or(ParseError::InvalidBase58)?)
+    }
+}
+
+impl std::str::FromStr for HashMeta {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HashMeta::parse(s)
+    }
 }
 
 impl std::fmt::Display for HashMeta {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "HashMeta ( alg: {}, hashlen: {}b )",
-            self.algorithm,
-            self.hash.len()
-        )
+        write!(f, "{}:{}", self.algorithm.prefix(), self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::hash;
+
+    #[test]
+    fn round_trip_canonical_form() {
+        for &algorithm in Algorithm::ALL {
+            let meta = hash(algorithm, b"abc");
+            let canonical = meta.to_string();
+            assert!(canonical.starts_with(&format!("{}:", algorithm.prefix())));
+            assert_eq!(algorithm.prefix().len() + 1 + algorithm.digest_len() * 2, canonical.len());
+            assert_eq!(Ok(meta.clone()), HashMeta::parse(&canonical));
+            assert_eq!(Ok(meta), canonical.to_uppercase().parse());
+        }
+    }
+
+    #[test]
+    fn encode_digest() {
+        let meta = hash(Algorithm::SHA256, b"abc");
+        assert_eq!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", meta.to_hex());
+        assert_eq!("ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=", meta.to_base64());
+        assert_eq!("ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0", meta.to_base64url());
+    }
+
+    #[test]
+    fn reject_wrong_length_digest() {
+        let short = format!("sha256:{}", hash(Algorithm::SHA1, b"abc").to_hex());
+        assert_eq!(Err(ParseError::WrongLength { expected: 32, actual: 20 }), HashMeta::parse(&short));
+        assert_eq!(Err(ParseError::WrongLength { expected: 20, actual: 0 }), HashMeta::parse("sha1:"));
+    }
+
+    #[test]
+    fn reject_malformed_input() {
+        assert_eq!(Err(ParseError::UnknownAlgorithm("md5".to_owned())), HashMeta::parse("md5:00"));
+        assert_eq!(Err(ParseError::MissingPrefix), HashMeta::parse("deadbeef"));
+        assert_eq!(Err(ParseError::InvalidDigest), HashMeta::parse("sha1:xyz"));
+    }
+
+    #[test]
+    fn round_trip_multihash() {
+        for &algorithm in Algorithm::ALL {
+            let meta = hash(algorithm, b"multihash");
+            assert_eq!(Ok(meta.clone()), HashMeta::from_multihash(&meta.to_multihash()));
+        }
+        let mut sha512_256 = hash(Algorithm::SHA512_256, b"").to_multihash();
+        assert_eq!([0x95, 0x20, 32], sha512_256[..3]);
+        sha512_256.push(0);
+        assert_eq!(
+            Err(ParseError::WrongLength { expected: 32, actual: 33 }),
+            HashMeta::from_multihash(&sha512_256)
+        );
+    }
+
+    #[test]
+    fn match_multihash_reference_vectors() {
+        // Shaped like the multiformats reference test cases, the base58 forms from its README
+        let vectors = include_str!("../fixtures/multihash_vectors.csv");
+        for line in vectors.lines().skip(1) {
+            let [name, _bits, input, multihash, _base58] = line.split(',').collect::<Vec<_>>()[..] else {
+                panic!("malformed vector {line}");
+            };
+            let algorithm = match name {
+                "sha1" => Algorithm::SHA1,
+                "sha2-256" => Algorithm::SHA256,
+                "sha2-512" => Algorithm::SHA512,
+                _ => panic!("unexpected algorithm {name}"),
+            };
+            let meta = hash(algorithm, input.as_bytes());
+            assert_eq!(multihash, hex_encode(&meta.to_multihash()), "{name}");
+            #[cfg(feature = "base58")]
+            {
+                assert_eq!(_base58, meta.to_base58(), "{name}");
+                assert_eq!(Ok(meta), HashMeta::from_base58(_base58));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_unknown_or_truncated_multihash() {
+        let multihash = hash(Algorithm::SHA256, b"multihash").to_multihash();
+        assert_eq!(Err(ParseError::Truncated), HashMeta::from_multihash(&multihash[..multihash.len() - 1]));
+        assert_eq!(Err(ParseError::Truncated), HashMeta::from_multihash(&multihash[..1]));
+        assert_eq!(Err(ParseError::Truncated), HashMeta::from_multihash(&[]));
+        // md5 is in the table but not supported here
+        assert_eq!(Err(ParseError::UnknownCode(0xd5)), HashMeta::from_multihash(&[0xd5, 0x01, 16]));
+        assert_eq!(
+            Err(ParseError::WrongLength { expected: 32, actual: 20 }),
+            HashMeta::from_multihash(&[&[0x12, 20][..], &[0; 20]].concat())
+        );
     }
 }
@@ -0,0 +1,147 @@
+use crate::digest::Digest;
+use crate::{Algorithm, HashMeta};
+
+use std::io::Read;
+use std::path::Path;
+
+/// Read size for `hash_file`, large enough that syscalls don't dominate
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Incremental hash over data which arrives in pieces (i.e. an upload body), the result is the
+/// same as hashing the concatenated pieces in one go
+pub struct Hasher {
+    algorithm: Algorithm,
+    digest: Box<dyn Digest>,
+    bytes_processed: u64,
+}
+
+impl Clone for Hasher {
+    fn clone(&self) -> Self {
+        Hasher {
+            algorithm: self.algorithm,
+            digest: self.digest.box_clone(),
+            bytes_processed: self.bytes_processed,
+        }
+    }
+}
+
+impl Hasher {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Hasher {
+            algorithm,
+            digest: algorithm.start_digest(),
+            bytes_processed: 0,
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.digest.update(data);
+        self.bytes_processed += data.len() as u64;
+    }
+
+    /// Total length of the data hashed so far, for progress reporting
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed
+    }
+
+    pub fn finalize(self) -> HashMeta {
+        HashMeta::new(self.algorithm, self.digest.finish())
+    }
+}
+
+impl std::fmt::Debug for Hasher {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Hasher ( alg: {}, processed: {}b )", self.algorithm, self.bytes_processed)
+    }
+}
+
+/// The hash of a stream along with how long it was, callers checking a download usually want
+/// both
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hashed {
+    pub meta: HashMeta,
+    pub len: u64,
+}
+
+/// Hash everything the reader yields, reading buf_size bytes at a time
+pub fn hash_reader(algorithm: Algorithm, mut reader: impl Read, buf_size: usize) -> std::io::Result<Hashed> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buffer = vec![0; buf_size.max(1)];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => {
+                let len = hasher.bytes_processed();
+                return Ok(Hashed {
+                    meta: hasher.finalize(),
+                    len,
+                });
+            }
+            Ok(len) => hasher.update(&buffer[..len]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Hash a file without reading it all into memory, i.e. `sha256sum`
+pub fn hash_file(algorithm: Algorithm, path: impl AsRef<Path>) -> std::io::Result<Hashed> {
+    hash_reader(algorithm, std::fs::File::open(path)?, DEFAULT_BUFFER_SIZE)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::hash;
+    use crate::test_data::{bytes, lcg};
+
+    #[test]
+    fn match_one_shot_hash_at_random_boundaries() {
+        let data = bytes(10_000);
+        let mut seed = 42;
+        for &algorithm in Algorithm::ALL {
+            for _ in 0..10 {
+                let mut hasher = Hasher::new(algorithm);
+                let mut rest = data.as_slice();
+                while !rest.is_empty() {
+                    let len = (lcg(&mut seed) as usize % 700).min(rest.len());
+                    let (piece, remaining) = rest.split_at(len);
+                    hasher.update(piece);
+                    rest = remaining;
+                }
+                assert_eq!(data.len() as u64, hasher.bytes_processed());
+                assert_eq!(hash(algorithm, &data), hasher.finalize());
+            }
+        }
+    }
+
+    #[test]
+    fn hash_reader_in_small_reads() {
+        let data = bytes(10_000);
+        for buf_size in [0, 1, 7, 4096, 20_000] {
+            let hashed = hash_reader(Algorithm::SHA256, data.as_slice(), buf_size).unwrap();
+            assert_eq!(hash(Algorithm::SHA256, &data), hashed.meta);
+            assert_eq!(data.len() as u64, hashed.len);
+        }
+    }
+
+    #[test]
+    fn hash_file_like_its_contents() {
+        let path = std::env::temp_dir().join(format!("crypto-hash-file-{}", std::process::id()));
+        for contents in [bytes(10_000), Vec::new()] {
+            std::fs::write(&path, &contents).unwrap();
+            let hashed = hash_file(Algorithm::SHA256, &path).unwrap();
+            assert_eq!(hash(Algorithm::SHA256, &contents), hashed.meta);
+            assert_eq!(contents.len() as u64, hashed.len);
+        }
+        std::fs::remove_file(&path).unwrap();
+        let err = hash_file(Algorithm::SHA256, &path).unwrap_err();
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn hash_nothing_like_empty_input() {
+        let hasher = Hasher::new(Algorithm::SHA512);
+        assert_eq!(0, hasher.bytes_processed());
+        assert_eq!(hash(Algorithm::SHA512, b""), hasher.finalize());
+    }
+}
@@ -0,0 +1,486 @@
+//! Compact JWTs (RFC 7519) signed with HS256 or RS256, enough for Workers to mint and check
+//! short lived tokens without a full JOSE implementation.
+//!
+//! The algorithm is pinned by the key: a token whose header names another algorithm than the
+//! key's is refused before its signature is looked at, so "none" and HS256 signed with an RSA
+//! public key are never accepted.
+
+use crate::encoding::{base64_decode, base64_encode, BASE64URL};
+use crate::{hmac_sign, hmac_verify, Algorithm, Error, RsaPrivateKey, RsaPublicKey};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Clock skew tolerated on exp and nbf unless the validation says otherwise
+pub const DEFAULT_LEEWAY_SECS: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+}
+
+impl JwtAlgorithm {
+    /// The `alg` header value
+    pub fn name(self) -> &'static str {
+        match self {
+            JwtAlgorithm::HS256 => "HS256",
+            JwtAlgorithm::RS256 => "RS256",
+        }
+    }
+}
+
+impl std::fmt::Display for JwtAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a token is signed or verified with, RSA public keys can only verify
+pub enum JwtKey {
+    Hmac(Vec<u8>),
+    RsaPrivate(RsaPrivateKey),
+    RsaPublic(RsaPublicKey),
+}
+
+impl JwtKey {
+    pub fn algorithm(&self) -> JwtAlgorithm {
+        match self {
+            JwtKey::Hmac(_) => JwtAlgorithm::HS256,
+            JwtKey::RsaPrivate(_) | JwtKey::RsaPublic(_) => JwtAlgorithm::RS256,
+        }
+    }
+}
+
+impl std::fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JwtKey::Hmac(_) => write!(f, "JwtKey ( HS256 )"),
+            JwtKey::RsaPrivate(key) => write!(f, "JwtKey ( RS256 private {} bits )", key.modulus_bits()),
+            JwtKey::RsaPublic(key) => write!(f, "JwtKey ( RS256 public {} bits )", key.modulus_bits()),
+        }
+    }
+}
+
+/// The checks `verify` applies beyond the signature, exp is required unless turned off
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Validation {
+    pub algorithms: Vec<JwtAlgorithm>,
+    pub leeway_secs: u64,
+    pub require_exp: bool,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    /// Seconds since the epoch to validate at, the system clock when `None`. Required on
+    /// wasm32-unknown-unknown where there is no system clock, i.e. from `Date.now()`, and
+    /// `verify` fails with `JwtError::NoClock` without it
+    pub now: Option<u64>,
+}
+
+impl Validation {
+    pub fn new(algorithm: JwtAlgorithm) -> Self {
+        Validation {
+            algorithms: vec![algorithm],
+            leeway_secs: DEFAULT_LEEWAY_SECS,
+            require_exp: true,
+            issuer: None,
+            audience: None,
+            now: None,
+        }
+    }
+
+    pub fn with_algorithms(mut self, algorithms: &[JwtAlgorithm]) -> Self {
+        self.algorithms = algorithms.to_vec();
+        self
+    }
+
+    pub fn with_leeway(mut self, leeway_secs: u64) -> Self {
+        self.leeway_secs = leeway_secs;
+        self
+    }
+
+    pub fn with_require_exp(mut self, require_exp: bool) -> Self {
+        self.require_exp = require_exp;
+        self
+    }
+
+    pub fn with_issuer(mut self, issuer: impl Into<String>) -> Self {
+        self.issuer = Some(issuer.into());
+        self
+    }
+
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = Some(audience.into());
+        self
+    }
+
+    pub fn at(mut self, now: u64) -> Self {
+        self.now = Some(now);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JwtError {
+    /// The token isn't three base64url parts holding JSON objects
+    Malformed(String),
+    /// The header names an algorithm this module doesn't implement, including "none"
+    UnsupportedAlgorithm(String),
+    /// The header's algorithm isn't in the validation's allow list
+    AlgorithmNotAllowed(JwtAlgorithm),
+    /// The header's algorithm isn't the key's, i.e. HS256 presented to an RSA key
+    KeyMismatch { token: JwtAlgorithm, key: JwtAlgorithm },
+    /// Encoding needs a secret or private key
+    CannotSign,
+    InvalidSignature,
+    Expired { exp: u64, now: u64 },
+    NotYetValid { nbf: u64, now: u64 },
+    /// The validation has no `now` and the target has no system clock to fall back on
+    NoClock,
+    /// A claim the validation needs is absent or not the expected type
+    MissingClaim(&'static str),
+    /// A time claim isn't a NumericDate, a non-negative number of seconds
+    InvalidClaim(&'static str),
+    InvalidIssuer(String),
+    InvalidAudience,
+    /// The claims verified but didn't deserialize to the requested type
+    Claims(String),
+    Key(Error),
+}
+
+impl std::error::Error for JwtError {}
+
+impl std::fmt::Display for JwtError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JwtError::Malformed(reason) => write!(f, "malformed token: {reason}"),
+            JwtError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm: {alg}"),
+            JwtError::AlgorithmNotAllowed(alg) => write!(f, "algorithm not allowed: {alg}"),
+            JwtError::KeyMismatch { token, key } => write!(f, "token is {token} but the key is {key}"),
+            JwtError::CannotSign => write!(f, "key can only verify"),
+            JwtError::InvalidSignature => write!(f, "invalid signature"),
+            JwtError::Expired { exp, now } => write!(f, "expired at {exp}, now {now}"),
+            JwtError::NotYetValid { nbf, now } => write!(f, "not valid before {nbf}, now {now}"),
+            JwtError::NoClock => write!(f, "no clock, set the validation's now"),
+            JwtError::MissingClaim(claim) => write!(f, "missing claim: {claim}"),
+            JwtError::InvalidClaim(claim) => write!(f, "invalid claim: {claim}"),
+            JwtError::InvalidIssuer(iss) => write!(f, "invalid issuer: {iss}"),
+            JwtError::InvalidAudience => write!(f, "invalid audience"),
+            JwtError::Claims(reason) => write!(f, "invalid claims: {reason}"),
+            JwtError::Key(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<Error> for JwtError {
+    fn from(err: Error) -> Self {
+        JwtError::Key(err)
+    }
+}
+
+/// Sign the claims, which must serialize to a JSON object
+pub fn encode(claims: &impl Serialize, key: &JwtKey) -> Result<String, JwtError> {
+    let claims = serde_json::to_value(claims).map_err(|err| JwtError::Claims(err.to_string()))?;
+    if !claims.is_object() {
+        return Err(JwtError::Claims("not a JSON object".to_owned()));
+    }
+    let header = json!({ "alg": key.algorithm().name(), "typ": "JWT" });
+    let signing_input = format!("{}.{}", encode_part(&header), encode_part(&claims));
+    let signature = match key {
+        JwtKey::Hmac(secret) => hmac_sign(Algorithm::SHA256, secret, signing_input.as_bytes())?.tag,
+        JwtKey::RsaPrivate(private_key) => private_key.sign_pkcs1_sha256(signing_input.as_bytes())?,
+        JwtKey::RsaPublic(_) => return Err(JwtError::CannotSign),
+    };
+    Ok(format!("{signing_input}.{}", base64_encode(&signature, BASE64URL, false)))
+}
+
+/// Check the signature and claims, then deserialize the claims, i.e. to `serde_json::Value` or
+/// an application struct
+pub fn verify<T: DeserializeOwned>(token: &str, key: &JwtKey, validation: &Validation) -> Result<T, JwtError> {
+    let mut parts = token.split('.');
+    let (Some(header), Some(claims), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(JwtError::Malformed("expected three parts".to_owned()));
+    };
+    let signing_input = &token[..header.len() + 1 + claims.len()];
+    let header = decode_part(header, "header")?;
+    let algorithm = match header.get("alg").and_then(Value::as_str) {
+        Some("HS256") => JwtAlgorithm::HS256,
+        Some("RS256") => JwtAlgorithm::RS256,
+        Some(alg) => return Err(JwtError::UnsupportedAlgorithm(alg.to_owned())),
+        None => return Err(JwtError::Malformed("header has no alg".to_owned())),
+    };
+    if !validation.algorithms.contains(&algorithm) {
+        return Err(JwtError::AlgorithmNotAllowed(algorithm));
+    }
+    if algorithm != key.algorithm() {
+        return Err(JwtError::KeyMismatch {
+            token: algorithm,
+            key: key.algorithm(),
+        });
+    }
+    let signature = base64_decode(signature, BASE64URL).ok_or(JwtError::InvalidSignature)?;
+    let valid = match key {
+        JwtKey::Hmac(secret) => hmac_verify(Algorithm::SHA256, secret, signing_input.as_bytes(), &signature),
+        JwtKey::RsaPrivate(private_key) => private_key
+            .public_key()
+            .verify_pkcs1_sha256(signing_input.as_bytes(), &signature),
+        JwtKey::RsaPublic(public_key) => public_key.verify_pkcs1_sha256(signing_input.as_bytes(), &signature),
+    };
+    if !valid {
+        return Err(JwtError::InvalidSignature);
+    }
+    let claims = decode_part(claims, "claims")?;
+    validate(&claims, validation)?;
+    serde_json::from_value(Value::Object(claims)).map_err(|err| JwtError::Claims(err.to_string()))
+}
+
+fn validate(claims: &Map<String, Value>, validation: &Validation) -> Result<(), JwtError> {
+    let now = validation.now.or_else(unix_now).ok_or(JwtError::NoClock)?;
+    // NumericDate may have a fraction (RFC 7519 section 2), rounded towards the stricter side
+    let time = |claim: &'static str, round: fn(f64) -> f64| match claims.get(claim) {
+        None => Ok(None),
+        Some(value) => match (value.as_u64(), value.as_f64()) {
+            (Some(secs), _) => Ok(Some(secs)),
+            (None, Some(secs)) if secs >= 0.0 && secs.is_finite() => Ok(Some(round(secs) as u64)),
+            _ => Err(JwtError::InvalidClaim(claim)),
+        },
+    };
+    match time("exp", f64::floor)? {
+        Some(exp) if now > exp.saturating_add(validation.leeway_secs) => return Err(JwtError::Expired { exp, now }),
+        None if validation.require_exp => return Err(JwtError::MissingClaim("exp")),
+        _ => {}
+    }
+    if let Some(nbf) = time("nbf", f64::ceil)? {
+        if now.saturating_add(validation.leeway_secs) < nbf {
+            return Err(JwtError::NotYetValid { nbf, now });
+        }
+    }
+    if let Some(issuer) = &validation.issuer {
+        let iss = claims.get("iss").and_then(Value::as_str).ok_or(JwtError::MissingClaim("iss"))?;
+        if iss != issuer {
+            return Err(JwtError::InvalidIssuer(iss.to_owned()));
+        }
+    }
+    if let Some(audience) = &validation.audience {
+        // aud is either one string or an array of them (RFC 7519 section 4.1.3)
+        let matches = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => return Err(JwtError::MissingClaim("aud")),
+        };
+        if !matches {
+            return Err(JwtError::InvalidAudience);
+        }
+    }
+    Ok(())
+}
+
+fn encode_part(value: &Value) -> String {
+    base64_encode(value.to_string().as_bytes(), BASE64URL, false)
+}
+
+fn decode_part(part: &str, name: &str) -> Result<Map<String, Value>, JwtError> {
+    let bytes = base64_decode(part, BASE64URL).ok_or_else(|| JwtError::Malformed(format!("{name} is not base64url")))?;
+    match serde_json::from_slice(&bytes) {
+        Ok(Value::Object(object)) => Ok(object),
+        _ => Err(JwtError::Malformed(format!("{name} is not a JSON object"))),
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn unix_now() -> Option<u64> {
+    let elapsed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+    Some(elapsed.map_or(0, |elapsed| elapsed.as_secs()))
+}
+
+// SystemTime::now panics on wasm32-unknown-unknown (Workers), the caller has to pass the time
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn unix_now() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // Minted outside this crate with Python's hmac and cryptography modules, the RS256 token with
+    // the rsa2048 fixture key. Tokens from the jsonwebtoken crate are minted as the tests run
+    // instead, see interoperate_with_jsonwebtoken.
+    const HS256_TOKEN: &str = include_str!("../fixtures/hs256_token.txt");
+    const HS256_SECRET: &[u8] = b"fixture-secret-not-for-production";
+    const RS256_TOKEN: &str = include_str!("../fixtures/rs256_token.txt");
+    const RSA_PRIVATE_KEY: &str = include_str!("../fixtures/rsa2048_private.pem");
+    const RSA_PUBLIC_KEY: &str = include_str!("../fixtures/rsa2048_public.pem");
+    // Both fixtures were issued at this time and expire 300 seconds later
+    const ISSUED_AT: u64 = 1_700_000_000;
+
+    fn hmac_key() -> JwtKey {
+        JwtKey::Hmac(HS256_SECRET.to_vec())
+    }
+
+    fn rsa_public_key() -> JwtKey {
+        JwtKey::RsaPublic(RsaPublicKey::parse(RSA_PUBLIC_KEY.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn round_trip() {
+        let claims = json!({ "sub": "worker", "exp": ISSUED_AT + 300 });
+        let rsa_private_key = JwtKey::RsaPrivate(RsaPrivateKey::parse(RSA_PRIVATE_KEY.as_bytes()).unwrap());
+        for (signing_key, verifying_key) in [(hmac_key(), hmac_key()), (rsa_private_key, rsa_public_key())] {
+            let algorithm = signing_key.algorithm();
+            let token = encode(&claims, &signing_key).unwrap();
+            assert!(!token.contains('='));
+            let validation = Validation::new(algorithm).at(ISSUED_AT);
+            assert_eq!(Ok(claims.clone()), verify::<Value>(&token, &verifying_key, &validation), "{algorithm}");
+        }
+    }
+
+    #[test]
+    fn verify_tokens_minted_elsewhere() {
+        let validation = Validation::new(JwtAlgorithm::HS256).at(ISSUED_AT + 10);
+        let claims: Value = verify(HS256_TOKEN, &hmac_key(), &validation).unwrap();
+        assert_eq!("worker", claims["sub"]);
+
+        let validation = Validation::new(JwtAlgorithm::RS256)
+            .with_issuer("auth.example.com")
+            .with_audience("api.example.com")
+            .at(ISSUED_AT + 10);
+        let claims: Value = verify(RS256_TOKEN, &rsa_public_key(), &validation).unwrap();
+        assert_eq!("worker", claims["sub"]);
+    }
+
+    #[test]
+    fn reject_expired_and_early_tokens() {
+        let validation = Validation::new(JwtAlgorithm::RS256).with_leeway(0);
+        let expired = verify::<Value>(RS256_TOKEN, &rsa_public_key(), &validation.clone().at(ISSUED_AT + 301));
+        assert_eq!(Err(JwtError::Expired { exp: ISSUED_AT + 300, now: ISSUED_AT + 301 }), expired);
+        let early = verify::<Value>(RS256_TOKEN, &rsa_public_key(), &validation.at(ISSUED_AT - 1));
+        assert_eq!(Err(JwtError::NotYetValid { nbf: ISSUED_AT, now: ISSUED_AT - 1 }), early);
+        // Within the default leeway
+        let validation = Validation::new(JwtAlgorithm::RS256).at(ISSUED_AT + 330);
+        assert!(verify::<Value>(RS256_TOKEN, &rsa_public_key(), &validation).is_ok());
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    #[test]
+    fn fall_back_on_the_system_clock() {
+        let validation = Validation::new(JwtAlgorithm::HS256).with_leeway(0);
+        match verify::<Value>(HS256_TOKEN, &hmac_key(), &validation) {
+            Err(JwtError::Expired { exp, now }) => assert!(exp == ISSUED_AT + 300 && now > exp),
+            other => panic!("expected the fixture to have expired, got {other:?}"),
+        }
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    #[test]
+    fn require_now_without_a_system_clock() {
+        let validation = Validation::new(JwtAlgorithm::HS256);
+        assert_eq!(Err(JwtError::NoClock), verify::<Value>(HS256_TOKEN, &hmac_key(), &validation));
+    }
+
+    #[test]
+    fn reject_tampered_payload() {
+        let (signing_input, signature) = HS256_TOKEN.rsplit_once('.').unwrap();
+        let (header, _) = signing_input.split_once('.').unwrap();
+        let forged = encode_part(&json!({ "sub": "admin", "exp": ISSUED_AT + 300 }));
+        let token = format!("{header}.{forged}.{signature}");
+        let validation = Validation::new(JwtAlgorithm::HS256).at(ISSUED_AT);
+        assert_eq!(Err(JwtError::InvalidSignature), verify::<Value>(&token, &hmac_key(), &validation));
+    }
+
+    #[test]
+    fn reject_none_and_algorithm_confusion() {
+        let claims = encode_part(&json!({ "sub": "admin", "exp": ISSUED_AT + 300 }));
+        let none = format!("{}.{claims}.", encode_part(&json!({ "alg": "none" })));
+        let validation = Validation::new(JwtAlgorithm::RS256).at(ISSUED_AT);
+        assert_eq!(
+            Err(JwtError::UnsupportedAlgorithm("none".to_owned())),
+            verify::<Value>(&none, &rsa_public_key(), &validation)
+        );
+        // HS256 keyed with the public key's bytes, the classic confusion attack
+        let public_key = JwtKey::Hmac(RSA_PUBLIC_KEY.as_bytes().to_vec());
+        let confused = encode(&json!({ "sub": "admin", "exp": ISSUED_AT + 300 }), &public_key).unwrap();
+        let both = validation.clone().with_algorithms(&[JwtAlgorithm::HS256, JwtAlgorithm::RS256]);
+        assert_eq!(
+            Err(JwtError::KeyMismatch { token: JwtAlgorithm::HS256, key: JwtAlgorithm::RS256 }),
+            verify::<Value>(&confused, &rsa_public_key(), &both)
+        );
+        assert_eq!(
+            Err(JwtError::AlgorithmNotAllowed(JwtAlgorithm::HS256)),
+            verify::<Value>(&confused, &rsa_public_key(), &validation)
+        );
+    }
+
+    #[test]
+    fn say_which_claim_failed() {
+        let validation = Validation::new(JwtAlgorithm::RS256).at(ISSUED_AT);
+        let check = |validation: Validation| verify::<Value>(RS256_TOKEN, &rsa_public_key(), &validation);
+        assert_eq!(
+            Err(JwtError::InvalidIssuer("auth.example.com".to_owned())),
+            check(validation.clone().with_issuer("other.example.com"))
+        );
+        assert_eq!(Err(JwtError::InvalidAudience), check(validation.clone().with_audience("other.example.com")));
+        let token = encode(&json!({ "sub": "worker" }), &hmac_key()).unwrap();
+        let validation = Validation::new(JwtAlgorithm::HS256).at(ISSUED_AT);
+        assert_eq!(Err(JwtError::MissingClaim("exp")), verify::<Value>(&token, &hmac_key(), &validation));
+        let validation = validation.with_require_exp(false).with_audience("api.example.com");
+        assert_eq!(Err(JwtError::MissingClaim("aud")), verify::<Value>(&token, &hmac_key(), &validation));
+        assert!(matches!(verify::<Value>("a.b", &hmac_key(), &validation), Err(JwtError::Malformed(_))));
+        let token = encode(&json!({ "exp": "tomorrow" }), &hmac_key()).unwrap();
+        assert_eq!(Err(JwtError::InvalidClaim("exp")), verify::<Value>(&token, &hmac_key(), &validation));
+    }
+
+    #[test]
+    fn accept_fractional_numeric_dates() {
+        let claims = json!({ "nbf": ISSUED_AT as f64 + 0.5, "exp": ISSUED_AT as f64 + 300.5 });
+        let token = encode(&claims, &hmac_key()).unwrap();
+        let validation = Validation::new(JwtAlgorithm::HS256).with_leeway(0);
+        let check = |now| verify::<Value>(&token, &hmac_key(), &validation.clone().at(now));
+        assert_eq!(Err(JwtError::NotYetValid { nbf: ISSUED_AT + 1, now: ISSUED_AT }), check(ISSUED_AT));
+        assert!(check(ISSUED_AT + 1).is_ok());
+        assert!(check(ISSUED_AT + 300).is_ok());
+        assert_eq!(Err(JwtError::Expired { exp: ISSUED_AT + 300, now: ISSUED_AT + 301 }), check(ISSUED_AT + 301));
+        let negative = encode(&json!({ "exp": -1.5 }), &hmac_key()).unwrap();
+        let validation = Validation::new(JwtAlgorithm::HS256).at(ISSUED_AT);
+        assert_eq!(Err(JwtError::InvalidClaim("exp")), verify::<Value>(&negative, &hmac_key(), &validation));
+    }
+
+    #[test]
+    fn interoperate_with_jsonwebtoken() {
+        use jsonwebtoken::{DecodingKey, EncodingKey, Header};
+
+        let claims = json!({ "sub": "worker", "iss": "auth.example.com", "exp": ISSUED_AT + 300 });
+        let rsa_private_key = JwtKey::RsaPrivate(RsaPrivateKey::parse(RSA_PRIVATE_KEY.as_bytes()).unwrap());
+        let keys = [
+            (
+                jsonwebtoken::Algorithm::HS256,
+                EncodingKey::from_secret(HS256_SECRET),
+                DecodingKey::from_secret(HS256_SECRET),
+                hmac_key(),
+                hmac_key(),
+            ),
+            (
+                jsonwebtoken::Algorithm::RS256,
+                EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap(),
+                DecodingKey::from_rsa_pem(RSA_PUBLIC_KEY.as_bytes()).unwrap(),
+                rsa_private_key,
+                rsa_public_key(),
+            ),
+        ];
+        for (algorithm, encoding_key, decoding_key, signing_key, verifying_key) in keys {
+            // Theirs verified here
+            let token = jsonwebtoken::encode(&Header::new(algorithm), &claims, &encoding_key).unwrap();
+            let validation = Validation::new(verifying_key.algorithm()).with_issuer("auth.example.com").at(ISSUED_AT);
+            assert_eq!(Ok(claims.clone()), verify::<Value>(&token, &verifying_key, &validation), "{algorithm:?}");
+
+            // Ours verified there, the fixed time is long past so their clock check is off
+            let token = encode(&claims, &signing_key).unwrap();
+            let mut validation = jsonwebtoken::Validation::new(algorithm);
+            validation.validate_exp = false;
+            validation.set_issuer(&["auth.example.com"]);
+            let decoded = jsonwebtoken::decode::<Value>(&token, &decoding_key, &validation).unwrap();
+            assert_eq!(claims, decoded.claims, "{algorithm:?}");
+        }
+    }
+}
@@ -1,13 +1,142 @@
+pub mod aead;
 mod algorithm;
+mod checksum;
+mod der;
+mod digest;
+pub mod ecdsa;
+pub mod ed25519;
+mod encoding;
+mod error;
 mod hash_meta;
+mod hasher;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+mod mac;
+mod mac_meta;
+pub mod merkle;
+mod pem;
+mod random;
+mod rsa;
+mod sign_meta;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(test)]
+mod test_data;
+mod verify;
 
 pub use algorithm::*;
+pub use checksum::*;
+pub use error::*;
 pub use hash_meta::*;
+pub use hasher::*;
+pub use mac::*;
+pub use mac_meta::*;
+pub use random::*;
+pub use rsa::*;
+pub use sign_meta::*;
+#[cfg(feature = "stream")]
+pub use stream::*;
+pub use verify::*;
 
-use ring::digest;
+/// Perform a hash on provided data with any of the supported algorithms
+pub fn hash(algorithm: Algorithm, data: &[u8]) -> HashMeta {
+    let mut digest = algorithm.start_digest();
+    digest.update(data);
+    HashMeta::new(algorithm, digest.finish())
+}
+
+/// Perform sha-1 hash on provided data
+///
+/// SHA-1 is broken for collision resistance, only use it to interoperate with legacy systems
+pub fn hash_sha1(data: &[u8]) -> HashMeta {
+    hash(Algorithm::SHA1, data)
+}
 
 /// Perform sha-256 hash on provided data
 pub fn hash_sha256(data: &[u8]) -> HashMeta {
-    let result = digest::digest(&digest::SHA256, data);
-    HashMeta::new(algorithm::Algorithm::SHA256, result.as_ref().to_vec())
+    hash(Algorithm::SHA256, data)
+}
+
+/// Perform sha-384 hash on provided data
+pub fn hash_sha384(data: &[u8]) -> HashMeta {
+    hash(Algorithm::SHA384, data)
+}
+
+/// Perform sha-512 hash on provided data
+pub fn hash_sha512(data: &[u8]) -> HashMeta {
+    hash(Algorithm::SHA512, data)
+}
+
+/// Perform sha-512/256 hash on provided data
+pub fn hash_sha512_256(data: &[u8]) -> HashMeta {
+    hash(Algorithm::SHA512_256, data)
+}
+
+/// Perform blake3 hash on provided data
+#[cfg(feature = "blake3")]
+pub fn hash_blake3(data: &[u8]) -> HashMeta {
+    hash(Algorithm::BLAKE3, data)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // NIST known answers for the empty input and "abc"
+    const VECTORS: [(Algorithm, &str, &str); 5] = [
+        (
+            Algorithm::SHA1,
+            "da39a3ee5e6b4b0d3255bfef95601890afd80709",
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+        ),
+        (
+            Algorithm::SHA256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            Algorithm::SHA384,
+            "38b060a751ac96384cd9327eb1b1e36a21fdb71114be07434c0cc7bf63f6e1da274edebfe76f65fbd51ad2f14898b95b",
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+        ),
+        (
+            Algorithm::SHA512,
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        ),
+        (
+            Algorithm::SHA512_256,
+            "c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a",
+            "53048e2681941ef99b2e29b76b4c7dabe4c2d0c634fc6d46e0e2f13107e7af23",
+        ),
+    ];
+
+    #[test]
+    fn match_nist_vectors() {
+        for (algorithm, empty, abc) in VECTORS {
+            assert_eq!(empty, hash(algorithm, b"").to_hex(), "{algorithm}");
+            assert_eq!(abc, hash(algorithm, b"abc").to_hex(), "{algorithm}");
+        }
+    }
+
+    #[test]
+    fn dispatch_named_wrappers() {
+        let wrappers: [(Algorithm, fn(&[u8]) -> HashMeta); 5] = [
+            (Algorithm::SHA1, hash_sha1),
+            (Algorithm::SHA256, hash_sha256),
+            (Algorithm::SHA384, hash_sha384),
+            (Algorithm::SHA512, hash_sha512),
+            (Algorithm::SHA512_256, hash_sha512_256),
+        ];
+        for (algorithm, wrapper) in wrappers {
+            assert_eq!(hash(algorithm, b"abc"), wrapper(b"abc"));
+        }
+    }
+
+    #[test]
+    fn recompute_with_the_same_algorithm() {
+        let meta = hash_sha384(b"abc");
+        assert_eq!(meta, meta.recompute(b"abc"));
+        assert_ne!(meta, meta.recompute(b"abd"));
+    }
 }
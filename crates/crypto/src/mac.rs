@@ -0,0 +1,195 @@
+use crate::{Algorithm, Error, MacMeta};
+
+use ring::hmac;
+
+impl Algorithm {
+    // HMAC is only offered over SHA-2, SHA1 needs the legacy opt in
+    fn hmac_algorithm(self, allow_legacy: bool) -> Result<hmac::Algorithm, Error> {
+        match self {
+            Algorithm::SHA1 if allow_legacy => Ok(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY),
+            Algorithm::SHA256 => Ok(hmac::HMAC_SHA256),
+            Algorithm::SHA384 => Ok(hmac::HMAC_SHA384),
+            Algorithm::SHA512 => Ok(hmac::HMAC_SHA512),
+            algorithm => Err(Error::UnsupportedAlgorithm(algorithm)),
+        }
+    }
+}
+
+/// Sign the data with an HMAC over SHA256, SHA384, or SHA512, any other algorithm is an
+/// `UnsupportedAlgorithm` error
+pub fn hmac_sign(algorithm: Algorithm, key: &[u8], data: &[u8]) -> Result<MacMeta, Error> {
+    let key = hmac::Key::new(algorithm.hmac_algorithm(false)?, key);
+    Ok(MacMeta::new(algorithm, hmac::sign(&key, data).as_ref().to_vec()))
+}
+
+/// Sign as `hmac_sign` but also allowing SHA1, only to interoperate with legacy systems
+pub fn hmac_sign_legacy(algorithm: Algorithm, key: &[u8], data: &[u8]) -> Result<MacMeta, Error> {
+    let key = hmac::Key::new(algorithm.hmac_algorithm(true)?, key);
+    Ok(MacMeta::new(algorithm, hmac::sign(&key, data).as_ref().to_vec()))
+}
+
+/// Check the expected tag in constant time, an unsupported algorithm never verifies
+pub fn hmac_verify(algorithm: Algorithm, key: &[u8], data: &[u8], expected: &[u8]) -> bool {
+    verify_with_legacy(algorithm, key, data, expected, false)
+}
+
+/// Verify as `hmac_verify` but also allowing SHA1, for tags from `hmac_sign_legacy`
+pub fn hmac_verify_legacy(algorithm: Algorithm, key: &[u8], data: &[u8], expected: &[u8]) -> bool {
+    verify_with_legacy(algorithm, key, data, expected, true)
+}
+
+fn verify_with_legacy(algorithm: Algorithm, key: &[u8], data: &[u8], expected: &[u8], allow_legacy: bool) -> bool {
+    match algorithm.hmac_algorithm(allow_legacy) {
+        Ok(hmac_algorithm) => hmac::verify(&hmac::Key::new(hmac_algorithm, key), data, expected).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Incremental HMAC mirroring `Hasher`, for signing a body as it arrives
+#[derive(Clone)]
+pub struct HmacSigner {
+    algorithm: Algorithm,
+    context: hmac::Context,
+    bytes_processed: u64,
+}
+
+impl HmacSigner {
+    pub fn new(algorithm: Algorithm, key: &[u8]) -> Result<Self, Error> {
+        HmacSigner::with_legacy(algorithm, key, false)
+    }
+
+    /// As `new` but also allowing SHA1, only to interoperate with legacy systems
+    pub fn new_legacy(algorithm: Algorithm, key: &[u8]) -> Result<Self, Error> {
+        HmacSigner::with_legacy(algorithm, key, true)
+    }
+
+    fn with_legacy(algorithm: Algorithm, key: &[u8], allow_legacy: bool) -> Result<Self, Error> {
+        let key = hmac::Key::new(algorithm.hmac_algorithm(allow_legacy)?, key);
+        Ok(HmacSigner {
+            algorithm,
+            context: hmac::Context::with_key(&key),
+            bytes_processed: 0,
+        })
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.context.update(data);
+        self.bytes_processed += data.len() as u64;
+    }
+
+    /// Total length of the data signed so far, for progress reporting
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed
+    }
+
+    pub fn finalize(self) -> MacMeta {
+        MacMeta::new(self.algorithm, self.context.sign().as_ref().to_vec())
+    }
+
+    /// Finish and check the expected tag in constant time
+    pub fn verify(self, expected: &[u8]) -> bool {
+        crate::verify::slices_equal(self.context.sign().as_ref(), expected)
+    }
+}
+
+impl std::fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "HmacSigner ( alg: HMAC-{}, processed: {}b )", self.algorithm, self.bytes_processed)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::encoding::hex_decode;
+
+    // RFC 4231 test cases 1, 2, and 6 (a key longer than the block size)
+    fn vectors() -> Vec<(Vec<u8>, &'static [u8], [(Algorithm, &'static str); 3])> {
+        vec![
+            (
+                vec![0x0b; 20],
+                &b"Hi There"[..],
+                [
+                    (Algorithm::SHA256, "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+                    (Algorithm::SHA384, "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6"),
+                    (Algorithm::SHA512, "87aa7cdea5ef619d4ff0b4241a1d6cb02379f4e2ce4ec2787ad0b30545e17cdedaa833b7d6b8a702038b274eaea3f4e4be9d914eeb61f1702e696c203a126854"),
+                ],
+            ),
+            (
+                b"Jefe".to_vec(),
+                &b"what do ya want for nothing?"[..],
+                [
+                    (Algorithm::SHA256, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"),
+                    (Algorithm::SHA384, "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649"),
+                    (Algorithm::SHA512, "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"),
+                ],
+            ),
+            (
+                vec![0xaa; 131],
+                &b"Test Using Larger Than Block-Size Key - Hash Key First"[..],
+                [
+                    (Algorithm::SHA256, "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"),
+                    (Algorithm::SHA384, "4ece084485813e9088d2c63a041bc5b44f9ef1012a2b588f3cd11f05033ac4c60c2ef6ab4030fe8296248df163f44952"),
+                    (Algorithm::SHA512, "80b24263c7c1a3ebb71493c1dd7be8b49b46d1f41b4aeec1121b013783f8f3526b56d037e05f2598bd0fd2215d6a1e5295e64f73f63f0aec8b915a985d786598"),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn match_rfc_4231_vectors() {
+        for (key, data, tags) in vectors() {
+            for (algorithm, tag) in tags {
+                let mac = hmac_sign(algorithm, &key, data).unwrap();
+                assert_eq!(MacMeta::new(algorithm, hex_decode(tag).unwrap()), mac);
+                assert!(hmac_verify(algorithm, &key, data, &mac.tag));
+            }
+        }
+    }
+
+    #[test]
+    fn reject_wrong_key_data_or_tag() {
+        let mac = hmac_sign(Algorithm::SHA256, b"key", b"data").unwrap();
+        assert!(!hmac_verify(Algorithm::SHA256, b"other", b"data", &mac.tag));
+        assert!(!hmac_verify(Algorithm::SHA256, b"key", b"other", &mac.tag));
+        assert!(!hmac_verify(Algorithm::SHA256, b"key", b"data", &mac.tag[..31]));
+        assert!(!hmac_verify(Algorithm::SHA384, b"key", b"data", &mac.tag));
+    }
+
+    #[test]
+    fn refuse_sha1_unless_legacy() {
+        assert_eq!(Err(Error::UnsupportedAlgorithm(Algorithm::SHA1)), hmac_sign(Algorithm::SHA1, b"key", b"data"));
+        assert_eq!(
+            Err(Error::UnsupportedAlgorithm(Algorithm::SHA512_256)),
+            hmac_sign_legacy(Algorithm::SHA512_256, b"key", b"data")
+        );
+        assert!(HmacSigner::new(Algorithm::SHA1, b"key").is_err());
+    }
+
+    #[test]
+    fn verify_sha1_only_through_legacy() {
+        // RFC 2202 test case 1
+        let mac = hmac_sign_legacy(Algorithm::SHA1, &[0x0b; 20], b"Hi There").unwrap();
+        assert_eq!(hex_decode("b617318655057264e28bc0b6fb378c8ef146be00"), Some(mac.tag.clone()));
+        assert!(hmac_verify_legacy(Algorithm::SHA1, &[0x0b; 20], b"Hi There", &mac.tag));
+        assert!(!hmac_verify_legacy(Algorithm::SHA1, &[0x0b; 20], b"Hi there", &mac.tag));
+        assert!(!hmac_verify(Algorithm::SHA1, &[0x0b; 20], b"Hi There", &mac.tag));
+
+        let mac = hmac_sign(Algorithm::SHA256, b"key", b"data").unwrap();
+        assert!(hmac_verify_legacy(Algorithm::SHA256, b"key", b"data", &mac.tag));
+    }
+
+    #[test]
+    fn stream_the_same_tag_as_one_shot() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i % 253) as u8).collect();
+        let mut signer = HmacSigner::new(Algorithm::SHA512, b"key").unwrap();
+        for piece in data.chunks(333) {
+            signer.update(piece);
+        }
+        assert_eq!(5000, signer.bytes_processed());
+        let expected = hmac_sign(Algorithm::SHA512, b"key", &data).unwrap();
+        assert!(signer.clone().verify(&expected.tag));
+        assert_eq!(expected, signer.finalize());
+        assert_eq!(format!("hmac-sha512:{}", crate::encoding::hex_encode(&expected.tag)), expected.to_string());
+    }
+}
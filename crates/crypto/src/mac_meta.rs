@@ -0,0 +1,22 @@
+use crate::encoding::hex_encode;
+use crate::Algorithm;
+
+/// An HMAC tag along with the hash algorithm it was keyed over. Displays as "hmac-sha256:<hex>"
+/// in line with `HashMeta`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacMeta {
+    pub algorithm: Algorithm,
+    pub tag: Vec<u8>,
+}
+
+impl MacMeta {
+    pub fn new(algorithm: Algorithm, tag: Vec<u8>) -> Self {
+        MacMeta { algorithm, tag }
+    }
+}
+
+impl std::fmt::Display for MacMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "hmac-{}:{}", self.algorithm.prefix(), hex_encode(&self.tag))
+    }
+}
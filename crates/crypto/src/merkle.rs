@@ -0,0 +1,202 @@
+//! Merkle trees as RFC 6962 (Certificate Transparency) defines them, for inclusion proofs over
+//! published batches such as the daily audit log digest.
+//!
+//! Leaves are hashed as `H(0x00 || leaf)` and interior nodes as `H(0x01 || left || right)` so a
+//! leaf can never pass for a node. An odd node is not duplicated, the tree splits at the largest
+//! power of two below the leaf count instead.
+
+use crate::verify::slices_equal;
+use crate::{hash, Algorithm, HashMeta};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// One step of an inclusion proof, the sibling's hash and which side of the path it sits on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProofNode {
+    pub side: Side,
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    algorithm: Algorithm,
+    // Every level of hashes from the leaves up to the root, kept so proofs don't rehash the tree
+    levels: Vec<Vec<Vec<u8>>>,
+    root: Vec<u8>,
+}
+
+/// Hash the leaves and build the tree over them, an empty tree's root is the hash of nothing
+pub fn build(leaves: &[&[u8]], algorithm: Algorithm) -> MerkleTree {
+    let mut levels = vec![leaves.iter().map(|leaf| hash_leaf(algorithm, leaf)).collect::<Vec<_>>()];
+    // Carrying an odd last node up unpaired builds the same tree as splitting at powers of two
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_node(algorithm, left, right),
+                _ => pair[0].clone(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    let root = match levels.last().and_then(|level| level.first()) {
+        Some(root) => root.clone(),
+        None => hash(algorithm, b"").hash,
+    };
+    MerkleTree { algorithm, levels, root }
+}
+
+impl MerkleTree {
+    pub fn root(&self) -> HashMeta {
+        HashMeta::new(self.algorithm, self.root.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// The audit path for the leaf at index (RFC 6962 section 2.1.1), ordered from the leaf up,
+    /// `None` past the last leaf
+    pub fn proof(&self, index: usize) -> Option<Vec<ProofNode>> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut index = index;
+        // A node carried up unpaired has no sibling on that level, so adds no step
+        let proof = self.levels[..self.levels.len() - 1]
+            .iter()
+            .filter_map(|level| {
+                let side = if index % 2 == 0 { Side::Right } else { Side::Left };
+                let sibling = level.get(index ^ 1);
+                index /= 2;
+                sibling.map(|hash| ProofNode { side, hash: hash.clone() })
+            })
+            .collect();
+        Some(proof)
+    }
+}
+
+/// True if the leaf with the proof hashes up to the root, using the root's algorithm
+pub fn verify_proof(root: &HashMeta, leaf: &[u8], proof: &[ProofNode]) -> bool {
+    let computed = proof.iter().fold(hash_leaf(root.algorithm, leaf), |hash, node| match node.side {
+        Side::Left => hash_node(root.algorithm, &node.hash, &hash),
+        Side::Right => hash_node(root.algorithm, &hash, &node.hash),
+    });
+    slices_equal(&computed, &root.hash)
+}
+
+fn hash_leaf(algorithm: Algorithm, leaf: &[u8]) -> Vec<u8> {
+    let mut digest = algorithm.start_digest();
+    digest.update(&[LEAF_PREFIX]);
+    digest.update(leaf);
+    digest.finish()
+}
+
+fn hash_node(algorithm: Algorithm, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut digest = algorithm.start_digest();
+    digest.update(&[NODE_PREFIX]);
+    digest.update(left);
+    digest.update(right);
+    digest.finish()
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn entries(count: usize) -> Vec<Vec<u8>> {
+        (0..count).map(|i| format!("entry-{i}").into_bytes()).collect()
+    }
+
+    // RFC 6962's recursive definition, splitting at the largest power of two below the count
+    fn subtree_root(leaves: &[Vec<u8>]) -> Vec<u8> {
+        match leaves {
+            [leaf] => leaf.clone(),
+            _ => {
+                let (left, right) = leaves.split_at(split_point(leaves.len()));
+                hash_node(Algorithm::SHA256, &subtree_root(left), &subtree_root(right))
+            }
+        }
+    }
+
+    // The largest power of two smaller than len, which must be at least 2
+    fn split_point(len: usize) -> usize {
+        1 << (len - 1).ilog2()
+    }
+
+    fn tree(entries: &[Vec<u8>]) -> MerkleTree {
+        let leaves: Vec<&[u8]> = entries.iter().map(Vec::as_slice).collect();
+        build(&leaves, Algorithm::SHA256)
+    }
+
+    #[test]
+    fn match_independent_roots() {
+        // Computed with a separate RFC 6962 implementation over Python's hashlib
+        let roots = [
+            (1, "40766b2033429026f53d54502679a839706b4741f8dcaf3a8bba5f41b5ffe075"),
+            (2, "2f27a5082c1d42afa488ac350a9fc4390c084f54f71ecdff859e98db8429b479"),
+            (3, "a64bf26e09128f6fe2fe6f8b2d8c801e166b57c047a7cd9b2b809e7a96a2f1cb"),
+            (7, "9139601cc1ca8ab2a7a0c2c134c04845f2b1ba549a83d6c845cfcda439cc585d"),
+        ];
+        for (count, root) in roots {
+            assert_eq!(root, tree(&entries(count)).root().to_hex(), "{count} leaves");
+        }
+        assert_eq!(crate::hash_sha256(b""), tree(&[]).root());
+    }
+
+    #[test]
+    fn cache_the_recursive_levels() {
+        for count in 1..=40 {
+            let tree = tree(&entries(count));
+            assert_eq!(subtree_root(&tree.levels[0]), tree.root, "{count} leaves");
+            assert_eq!(count.next_power_of_two().ilog2() as usize + 1, tree.levels.len());
+        }
+    }
+
+    #[test]
+    fn verify_every_proof() {
+        for count in 1..=17 {
+            let entries = entries(count);
+            let tree = tree(&entries);
+            for (index, entry) in entries.iter().enumerate() {
+                let proof = tree.proof(index).unwrap();
+                assert!(verify_proof(&tree.root(), entry, &proof), "leaf {index} of {count}");
+                assert!(!verify_proof(&tree.root(), b"entry-x", &proof));
+            }
+            assert_eq!(None, tree.proof(count));
+        }
+    }
+
+    #[test]
+    fn reject_altered_sibling() {
+        let entries = entries(7);
+        let tree = tree(&entries);
+        let proof = tree.proof(4).unwrap();
+        assert_eq!(3, proof.len());
+        for at in 0..proof.len() {
+            let mut altered = proof.clone();
+            altered[at].hash[0] ^= 1;
+            assert!(!verify_proof(&tree.root(), &entries[4], &altered), "sibling {at}");
+        }
+        let mut flipped = proof;
+        flipped[0].side = Side::Left;
+        assert!(!verify_proof(&tree.root(), &entries[4], &flipped));
+    }
+
+    #[test]
+    fn split_at_largest_power_of_two_below() {
+        let splits: Vec<usize> = (2..=9).map(split_point).collect();
+        assert_eq!(vec![1, 2, 2, 4, 4, 4, 4, 8], splits);
+    }
+}
@@ -0,0 +1,61 @@
+//! PEM armor (RFC 7468) around DER keys.
+
+use crate::encoding::{base64_decode, base64_encode, BASE64};
+use crate::Error;
+
+const LINE_LEN: usize = 64;
+
+/// Armor the DER with the label, i.e. "PUBLIC KEY", wrapping the base64 at 64 columns
+pub(crate) fn encode(label: &str, der: &[u8]) -> String {
+    let body = base64_encode(der, BASE64, true);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for start in (0..body.len()).step_by(LINE_LEN) {
+        pem.push_str(&body[start..(start + LINE_LEN).min(body.len())]);
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+/// Find the block with the label and decode it, text around the block is ignored
+pub(crate) fn decode(label: &str, pem: &str) -> Result<Vec<u8>, Error> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+    let (_, rest) = pem
+        .split_once(&begin)
+        .ok_or_else(|| Error::InvalidPem(format!("missing {begin}")))?;
+    let (body, _) = rest
+        .split_once(&end)
+        .ok_or_else(|| Error::InvalidPem(format!("missing {end}")))?;
+    let body: String = body.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    base64_decode(&body, BASE64).ok_or_else(|| Error::InvalidPem("invalid base64".to_owned()))
+}
+
+/// The label of the first block, i.e. "RSA PRIVATE KEY"
+pub(crate) fn label(pem: &str) -> Option<&str> {
+    let (_, rest) = pem.split_once("-----BEGIN ")?;
+    rest.split_once("-----").map(|(label, _)| label)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_wrap_lines() {
+        let der: Vec<u8> = (0..=200).collect();
+        let pem = encode("PUBLIC KEY", &der);
+        assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\n") && pem.ends_with("-----END PUBLIC KEY-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= LINE_LEN || line.starts_with("-----")));
+        assert_eq!(Ok(der), decode("PUBLIC KEY", &format!("leading text\r\n{}", pem.replace('\n', "\r\n"))));
+    }
+
+    #[test]
+    fn reject_other_labels_and_bad_base64() {
+        let pem = encode("PUBLIC KEY", b"key");
+        assert!(matches!(decode("PRIVATE KEY", &pem), Err(Error::InvalidPem(_))));
+        assert_eq!(Some("PUBLIC KEY"), label(&pem));
+        let broken = "-----BEGIN PUBLIC KEY-----\n!!!!\n-----END PUBLIC KEY-----\n";
+        assert!(matches!(decode("PUBLIC KEY", broken), Err(Error::InvalidPem(_))));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::encoding::{base64_encode, hex_encode, BASE64URL};
+use crate::Error;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::OnceLock;
+
+static RNG: OnceLock<SystemRandom> = OnceLock::new();
+
+pub(crate) fn rng() -> &'static SystemRandom {
+    RNG.get_or_init(SystemRandom::new)
+}
+
+/// Fill the buffer from the operating system's secure random source
+pub fn fill_random(dest: &mut [u8]) -> Result<(), Error> {
+    rng().fill(dest).map_err(|_| Error::Random)
+}
+
+/// Secure random bytes, i.e. for nonces and salts
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![0; len];
+    fill_random(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Random bytes as unpadded base64url, safe to use in URLs, headers, and file names
+pub fn random_token(len_bytes: usize) -> Result<String, Error> {
+    Ok(base64_encode(&random_bytes(len_bytes)?, BASE64URL, false))
+}
+
+/// Random bytes as lowercase hex, twice as many characters as bytes
+pub fn random_hex(len_bytes: usize) -> Result<String, Error> {
+    Ok(hex_encode(&random_bytes(len_bytes)?))
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn honor_requested_lengths() {
+        for len in [0, 1, 16, 33] {
+            assert_eq!(len, random_bytes(len).unwrap().len());
+            assert_eq!(len * 2, random_hex(len).unwrap().len());
+            assert_eq!((len * 4).div_ceil(3), random_token(len).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn use_only_url_safe_characters() {
+        let token = random_token(300).unwrap();
+        assert!(token.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'), "{token}");
+        assert!(random_hex(64).unwrap().bytes().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn differ_between_calls() {
+        assert_ne!(random_bytes(32).unwrap(), random_bytes(32).unwrap());
+        assert_ne!(random_token(32).unwrap(), random_token(32).unwrap());
+    }
+}
@@ -0,0 +1,292 @@
+//! RSASSA-PKCS1-v1_5 with SHA-256, as DKIM (rsa-sha256) and JWT (RS256) sign with.
+//!
+//! Keys are accepted as DER or PEM, private keys as PKCS#8 or PKCS#1 and public keys as SPKI or
+//! PKCS#1.
+
+use crate::der::{expect_tlv, integer_bits, write_tlv, BIT_STRING, INTEGER, NULL, OCTET_STRING, OID, SEQUENCE};
+use crate::encoding::{base64_encode, BASE64};
+use crate::random::rng;
+use crate::{pem, Error};
+
+use ring::signature::{self, KeyPair, RsaKeyPair};
+
+/// Keys with a shorter modulus are refused, as ring and DKIM (RFC 8301) require
+pub const MIN_RSA_BITS: usize = 2048;
+
+/// rsaEncryption (1.2.840.113549.1.1.1)
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+const PKCS8_LABEL: &str = "PRIVATE KEY";
+const PKCS1_LABEL: &str = "RSA PRIVATE KEY";
+const ENCRYPTED_PKCS8_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+const SPKI_LABEL: &str = "PUBLIC KEY";
+const PKCS1_PUBLIC_LABEL: &str = "RSA PUBLIC KEY";
+
+/// The secret half of a key pair, its Debug only shows the modulus size
+pub struct RsaPrivateKey {
+    key_pair: RsaKeyPair,
+    bits: usize,
+}
+
+impl RsaPrivateKey {
+    /// Import a PEM or DER key in either PKCS#8 or PKCS#1 form
+    pub fn parse(key: &[u8]) -> Result<Self, Error> {
+        let Some(pem) = as_pem(key)? else {
+            return RsaPrivateKey::from_der(key);
+        };
+        match pem::label(pem) {
+            Some(PKCS8_LABEL) => RsaPrivateKey::from_pkcs8_der(&pem::decode(PKCS8_LABEL, pem)?),
+            // OpenSSL marks encrypted PKCS#1 with a Proc-Type header inside the block
+            Some(PKCS1_LABEL) if pem.contains("ENCRYPTED") => Err(Error::EncryptedKey),
+            Some(PKCS1_LABEL) => RsaPrivateKey::from_pkcs1_der(&pem::decode(PKCS1_LABEL, pem)?),
+            Some(ENCRYPTED_PKCS8_LABEL) => Err(Error::EncryptedKey),
+            label => Err(Error::InvalidPem(format!("expected a private key, found {label:?}"))),
+        }
+    }
+
+    pub fn from_pkcs8_der(der: &[u8]) -> Result<Self, Error> {
+        let (body, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        // EncryptedPrivateKeyInfo starts with the algorithm where PrivateKeyInfo has a version
+        if expect_tlv(SEQUENCE, body).is_some() {
+            return Err(Error::EncryptedKey);
+        }
+        let (_, rest) = expect_tlv(INTEGER, body).ok_or(Error::MalformedKey)?;
+        let (algorithm, rest) = expect_tlv(SEQUENCE, rest).ok_or(Error::MalformedKey)?;
+        check_algorithm(algorithm)?;
+        let (pkcs1, _) = expect_tlv(OCTET_STRING, rest).ok_or(Error::MalformedKey)?;
+        let bits = check_size(pkcs1_private_modulus(pkcs1)?)?;
+        Ok(RsaPrivateKey {
+            key_pair: RsaKeyPair::from_pkcs8(der)?,
+            bits,
+        })
+    }
+
+    pub fn from_pkcs1_der(der: &[u8]) -> Result<Self, Error> {
+        let bits = check_size(pkcs1_private_modulus(der)?)?;
+        Ok(RsaPrivateKey {
+            key_pair: RsaKeyPair::from_der(der)?,
+            bits,
+        })
+    }
+
+    fn from_der(der: &[u8]) -> Result<Self, Error> {
+        let (body, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        // PKCS#1 continues with the modulus where PKCS#8 continues with the algorithm
+        let after_version = expect_tlv(INTEGER, body).map(|(_, rest)| rest);
+        match after_version.and_then(|rest| expect_tlv(INTEGER, rest)) {
+            Some(_) => RsaPrivateKey::from_pkcs1_der(der),
+            None => RsaPrivateKey::from_pkcs8_der(der),
+        }
+    }
+
+    /// The public half, i.e. to publish as a DKIM DNS record or a JWKS entry
+    pub fn public_key(&self) -> RsaPublicKey {
+        RsaPublicKey {
+            pkcs1: self.key_pair.public_key().as_ref().to_vec(),
+            bits: self.bits,
+        }
+    }
+
+    pub fn modulus_bits(&self) -> usize {
+        self.bits
+    }
+
+    /// PKCS#1 v1.5 signatures are deterministic, the same key and message always give the same
+    /// signature, which is as long as the modulus
+    pub fn sign_pkcs1_sha256(&self, msg: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut signature = vec![0; self.key_pair.public().modulus_len()];
+        // ring only fails here if its blinding can't get random bytes
+        self.key_pair
+            .sign(&signature::RSA_PKCS1_SHA256, rng(), msg, &mut signature)
+            .map_err(|_| Error::Random)?;
+        Ok(signature)
+    }
+}
+
+impl std::fmt::Debug for RsaPrivateKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "RsaPrivateKey ( {} bits )", self.bits)
+    }
+}
+
+/// The public half of a key pair, held as the PKCS#1 RSAPublicKey DER
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RsaPublicKey {
+    pkcs1: Vec<u8>,
+    bits: usize,
+}
+
+impl RsaPublicKey {
+    /// Import a PEM or DER key in either SPKI or PKCS#1 form
+    pub fn parse(key: &[u8]) -> Result<Self, Error> {
+        let Some(pem) = as_pem(key)? else {
+            let (body, _) = expect_tlv(SEQUENCE, key).ok_or(Error::MalformedKey)?;
+            // SPKI starts with the algorithm where PKCS#1 starts with the modulus
+            return match expect_tlv(SEQUENCE, body) {
+                Some(_) => RsaPublicKey::from_spki_der(key),
+                None => RsaPublicKey::from_pkcs1_der(key),
+            };
+        };
+        match pem::label(pem) {
+            Some(SPKI_LABEL) => RsaPublicKey::from_spki_der(&pem::decode(SPKI_LABEL, pem)?),
+            Some(PKCS1_PUBLIC_LABEL) => RsaPublicKey::from_pkcs1_der(&pem::decode(PKCS1_PUBLIC_LABEL, pem)?),
+            label => Err(Error::InvalidPem(format!("expected a public key, found {label:?}"))),
+        }
+    }
+
+    pub fn from_spki_der(der: &[u8]) -> Result<Self, Error> {
+        let (spki, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        let (algorithm, rest) = expect_tlv(SEQUENCE, spki).ok_or(Error::MalformedKey)?;
+        check_algorithm(algorithm)?;
+        let (bits, _) = expect_tlv(BIT_STRING, rest).ok_or(Error::MalformedKey)?;
+        // The leading byte counts unused bits, always 0 for a key
+        match bits.split_first() {
+            Some((0, pkcs1)) => RsaPublicKey::from_pkcs1_der(pkcs1),
+            _ => Err(Error::MalformedKey),
+        }
+    }
+
+    pub fn from_pkcs1_der(der: &[u8]) -> Result<Self, Error> {
+        let (body, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+        let (modulus, rest) = expect_tlv(INTEGER, body).ok_or(Error::MalformedKey)?;
+        expect_tlv(INTEGER, rest).ok_or(Error::MalformedKey)?;
+        Ok(RsaPublicKey {
+            pkcs1: der.to_vec(),
+            bits: check_size(integer_bits(modulus))?,
+        })
+    }
+
+    pub fn to_pkcs1_der(&self) -> &[u8] {
+        &self.pkcs1
+    }
+
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        let algorithm = [write_tlv(OID, RSA_ENCRYPTION_OID), write_tlv(NULL, &[])].concat();
+        let bits = [&[0][..], &self.pkcs1].concat();
+        write_tlv(SEQUENCE, &[write_tlv(SEQUENCE, &algorithm), write_tlv(BIT_STRING, &bits)].concat())
+    }
+
+    pub fn to_spki_pem(&self) -> String {
+        pem::encode(SPKI_LABEL, &self.to_spki_der())
+    }
+
+    /// The TXT record to publish at `<selector>._domainkey.<domain>` (RFC 6376 section 3.6.1)
+    pub fn dkim_record(&self) -> String {
+        format!("v=DKIM1; k=rsa; p={}", base64_encode(&self.to_spki_der(), BASE64, true))
+    }
+
+    pub fn modulus_bits(&self) -> usize {
+        self.bits
+    }
+
+    pub fn verify_pkcs1_sha256(&self, msg: &[u8], signature: &[u8]) -> bool {
+        signature::UnparsedPublicKey::new(&signature::RSA_PKCS1_2048_8192_SHA256, &self.pkcs1)
+            .verify(msg, signature)
+            .is_ok()
+    }
+}
+
+/// Sign with a DER or PEM private key, see [RsaPrivateKey::parse]. Parse the key once with
+/// [RsaPrivateKey] when signing many messages.
+pub fn rsa_sign_pkcs1_sha256(private_key: &[u8], msg: &[u8]) -> Result<Vec<u8>, Error> {
+    RsaPrivateKey::parse(private_key)?.sign_pkcs1_sha256(msg)
+}
+
+/// Verify with a DER or PEM public key, see [RsaPublicKey::parse], only an unusable key is an
+/// error
+pub fn rsa_verify_pkcs1_sha256(public_key: &[u8], msg: &[u8], signature: &[u8]) -> Result<bool, Error> {
+    Ok(RsaPublicKey::parse(public_key)?.verify_pkcs1_sha256(msg, signature))
+}
+
+// Treats the key as PEM text when it starts with the armor
+fn as_pem(key: &[u8]) -> Result<Option<&str>, Error> {
+    if !key.trim_ascii_start().starts_with(b"-----BEGIN ") {
+        return Ok(None);
+    }
+    std::str::from_utf8(key)
+        .map(Some)
+        .map_err(|_| Error::InvalidPem("not utf-8".to_owned()))
+}
+
+fn check_algorithm(algorithm: &[u8]) -> Result<(), Error> {
+    match expect_tlv(OID, algorithm) {
+        Some((RSA_ENCRYPTION_OID, _)) => Ok(()),
+        Some(_) => Err(Error::KeyRejected("not an RSA key".to_owned())),
+        None => Err(Error::MalformedKey),
+    }
+}
+
+fn check_size(bits: usize) -> Result<usize, Error> {
+    if bits < MIN_RSA_BITS {
+        return Err(Error::KeyTooSmall { bits, min: MIN_RSA_BITS });
+    }
+    Ok(bits)
+}
+
+// The modulus follows the version in RSAPrivateKey
+fn pkcs1_private_modulus(der: &[u8]) -> Result<usize, Error> {
+    let (body, _) = expect_tlv(SEQUENCE, der).ok_or(Error::MalformedKey)?;
+    let (_, rest) = expect_tlv(INTEGER, body).ok_or(Error::MalformedKey)?;
+    let (modulus, _) = expect_tlv(INTEGER, rest).ok_or(Error::MalformedKey)?;
+    Ok(integer_bits(modulus))
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // Generated with `openssl genpkey -algorithm RSA`, the PKCS#1 copy with `openssl rsa
+    // -traditional` and the signature over MESSAGE with `openssl dgst -sha256 -sign`
+    const OPENSSL_PRIVATE_KEY: &str = include_str!("../fixtures/rsa2048_private.pem");
+    const OPENSSL_PKCS1_PRIVATE_KEY: &str = include_str!("../fixtures/rsa2048_private_pkcs1.pem");
+    const OPENSSL_PUBLIC_KEY: &str = include_str!("../fixtures/rsa2048_public.pem");
+    const OPENSSL_SIGNATURE: &[u8] = include_bytes!("../fixtures/rsa2048_signature.bin");
+    const MESSAGE: &[u8] = b"dkim-signature body hash";
+    // The same key encrypted with `openssl pkey -aes256`, and a key too short to use
+    const ENCRYPTED_PRIVATE_KEY: &str = include_str!("../fixtures/rsa2048_encrypted.pem");
+    const SMALL_PRIVATE_KEY: &str = include_str!("../fixtures/rsa1024_private.pem");
+
+    #[test]
+    fn round_trip_sign_and_verify() {
+        let signature = rsa_sign_pkcs1_sha256(OPENSSL_PRIVATE_KEY.as_bytes(), MESSAGE).unwrap();
+        assert_eq!(256, signature.len());
+        assert_eq!(Ok(true), rsa_verify_pkcs1_sha256(OPENSSL_PUBLIC_KEY.as_bytes(), MESSAGE, &signature));
+        assert_eq!(Ok(false), rsa_verify_pkcs1_sha256(OPENSSL_PUBLIC_KEY.as_bytes(), b"tampered", &signature));
+    }
+
+    #[test]
+    fn match_openssl_signature() {
+        for key in [OPENSSL_PRIVATE_KEY, OPENSSL_PKCS1_PRIVATE_KEY] {
+            assert_eq!(OPENSSL_SIGNATURE, rsa_sign_pkcs1_sha256(key.as_bytes(), MESSAGE).unwrap());
+        }
+        let der = pem::decode(PKCS1_LABEL, OPENSSL_PKCS1_PRIVATE_KEY).unwrap();
+        assert_eq!(OPENSSL_SIGNATURE, rsa_sign_pkcs1_sha256(&der, MESSAGE).unwrap());
+    }
+
+    #[test]
+    fn extract_public_key() {
+        let private_key = RsaPrivateKey::parse(OPENSSL_PRIVATE_KEY.as_bytes()).unwrap();
+        let public_key = private_key.public_key();
+        assert_eq!(2048, public_key.modulus_bits());
+        assert_eq!(OPENSSL_PUBLIC_KEY, public_key.to_spki_pem());
+        assert_eq!(Ok(public_key.clone()), RsaPublicKey::parse(public_key.to_pkcs1_der()));
+        assert_eq!(Ok(public_key.clone()), RsaPublicKey::parse(&public_key.to_spki_der()));
+        assert!(public_key.dkim_record().starts_with("v=DKIM1; k=rsa; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEA"));
+        assert_eq!("RsaPrivateKey ( 2048 bits )", format!("{private_key:?}"));
+    }
+
+    #[test]
+    fn reject_unusable_keys() {
+        assert_eq!(Some(Error::EncryptedKey), RsaPrivateKey::parse(ENCRYPTED_PRIVATE_KEY.as_bytes()).err());
+        let encrypted_der = pem::decode(ENCRYPTED_PKCS8_LABEL, ENCRYPTED_PRIVATE_KEY).unwrap();
+        assert_eq!(Some(Error::EncryptedKey), RsaPrivateKey::parse(&encrypted_der).err());
+        assert_eq!(
+            Some(Error::KeyTooSmall { bits: 1024, min: MIN_RSA_BITS }),
+            RsaPrivateKey::parse(SMALL_PRIVATE_KEY.as_bytes()).err()
+        );
+        assert_eq!(Some(Error::MalformedKey), RsaPrivateKey::parse(&[0x30, 0x03, 0x02]).err());
+        let ec_key = include_str!("../fixtures/es256_private.pem");
+        assert!(matches!(RsaPrivateKey::parse(ec_key.as_bytes()), Err(Error::KeyRejected(_))));
+        assert!(matches!(RsaPublicKey::parse(OPENSSL_PRIVATE_KEY.as_bytes()), Err(Error::InvalidPem(_))));
+    }
+}
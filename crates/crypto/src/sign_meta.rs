@@ -0,0 +1,327 @@
+use crate::encoding::{base64_decode, base64_encode, hex_encode, BASE64, BASE64URL};
+use crate::{ecdsa, ed25519, hmac_verify, Algorithm, ParseError, RsaPublicKey};
+
+use std::ops::RangeInclusive;
+
+/// The signing schemes the crate produces, each with the signature lengths it allows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SignatureScheme {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+    Ed25519,
+    /// ECDSA P-256 with SHA-256, r and s as 32 bytes each (JWS ES256)
+    EcdsaP256,
+    /// ECDSA P-256 with SHA-256 as an ASN.1 DER sequence, as OpenSSL writes it
+    EcdsaP256Asn1,
+    /// RSASSA-PKCS1-v1_5 with SHA-256, as long as the key's modulus
+    RsaPkcs1Sha256,
+}
+
+impl SignatureScheme {
+    pub const ALL: [SignatureScheme; 7] = [
+        SignatureScheme::HmacSha256,
+        SignatureScheme::HmacSha384,
+        SignatureScheme::HmacSha512,
+        SignatureScheme::Ed25519,
+        SignatureScheme::EcdsaP256,
+        SignatureScheme::EcdsaP256Asn1,
+        SignatureScheme::RsaPkcs1Sha256,
+    ];
+
+    /// Lowercase name used as the prefix of the canonical `SignMeta` form, i.e. "ed25519"
+    pub fn prefix(self) -> &'static str {
+        match self {
+            SignatureScheme::HmacSha256 => "hmac-sha256",
+            SignatureScheme::HmacSha384 => "hmac-sha384",
+            SignatureScheme::HmacSha512 => "hmac-sha512",
+            SignatureScheme::Ed25519 => "ed25519",
+            SignatureScheme::EcdsaP256 => "ecdsa-p256",
+            SignatureScheme::EcdsaP256Asn1 => "ecdsa-p256-asn1",
+            SignatureScheme::RsaPkcs1Sha256 => "rsa-pkcs1-sha256",
+        }
+    }
+
+    /// Lengths in bytes a signature of the scheme can have
+    pub fn signature_len(self) -> RangeInclusive<usize> {
+        match self {
+            SignatureScheme::HmacSha256 => 32..=32,
+            SignatureScheme::HmacSha384 => 48..=48,
+            SignatureScheme::HmacSha512 => 64..=64,
+            SignatureScheme::Ed25519 => ed25519::SIGNATURE_LEN..=ed25519::SIGNATURE_LEN,
+            SignatureScheme::EcdsaP256 => ecdsa::FIXED_SIGNATURE_LEN..=ecdsa::FIXED_SIGNATURE_LEN,
+            // A sequence of two integers of up to 33 bytes each
+            SignatureScheme::EcdsaP256Asn1 => 8..=72,
+            // 2048 to 8192 bit moduli, the range ring verifies
+            SignatureScheme::RsaPkcs1Sha256 => 256..=1024,
+        }
+    }
+}
+
+impl std::str::FromStr for SignatureScheme {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SignatureScheme::ALL
+            .into_iter()
+            .find(|scheme| s.eq_ignore_ascii_case(scheme.prefix()))
+            .ok_or_else(|| ParseError::UnknownAlgorithm(s.to_owned()))
+    }
+}
+
+impl std::fmt::Display for SignatureScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.prefix())
+    }
+}
+
+/// A signature along with its scheme and, optionally, the id of the key which made it. Displays
+/// as "ed25519:<base64url>", or "ed25519:<key id>:<base64url>" with a key id, which `parse`
+/// reads back. The fields are private so every value holds a signature of a length its scheme
+/// produces.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignMeta {
+    scheme: SignatureScheme,
+    signature: Vec<u8>,
+    key_id: Option<String>,
+}
+
+/// The key to check a `SignMeta` with, the kind must suit the signature's scheme
+#[derive(Clone, Copy, Debug)]
+pub enum VerifyKey<'a> {
+    /// The shared secret for the HMAC schemes
+    Hmac(&'a [u8]),
+    Ed25519(&'a ed25519::VerifyingKey),
+    Ecdsa(&'a ecdsa::VerifyingKey),
+    Rsa(&'a RsaPublicKey),
+}
+
+impl SignMeta {
+    /// Bundle a signature, its length must be one the scheme produces
+    pub fn new(scheme: SignatureScheme, signature: Vec<u8>) -> Result<Self, ParseError> {
+        if !scheme.signature_len().contains(&signature.len()) {
+            return Err(ParseError::SignatureLength {
+                scheme,
+                actual: signature.len(),
+            });
+        }
+        Ok(SignMeta {
+            scheme,
+            signature,
+            key_id: None,
+        })
+    }
+
+    pub fn with_key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        self.scheme
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// Lowercase hex of the signature
+    pub fn to_hex(&self) -> String {
+        hex_encode(&self.signature)
+    }
+
+    /// Standard base64 of the signature, with padding
+    pub fn to_base64(&self) -> String {
+        base64_encode(&self.signature, BASE64, true)
+    }
+
+    /// URL and filename safe base64 of the signature, without padding
+    pub fn to_base64url(&self) -> String {
+        base64_encode(&self.signature, BASE64URL, false)
+    }
+
+    /// Reads the canonical "scheme:[key id:]base64url" form, the scheme is case insensitive and
+    /// the signature must be a length the scheme produces
+    pub fn parse(s: &str) -> Result<SignMeta, ParseError> {
+        let (prefix, rest) = s.split_once(':').ok_or(ParseError::MissingPrefix)?;
+        let scheme: SignatureScheme = prefix.parse()?;
+        // base64url has no ':' so anything before the last one is the key id
+        let (key_id, signature) = match rest.rsplit_once(':') {
+            Some((key_id, signature)) => (Some(key_id.to_owned()), signature),
+            None => (None, rest),
+        };
+        let signature = base64_decode(signature, BASE64URL).ok_or(ParseError::InvalidSignature)?;
+        Ok(SignMeta {
+            key_id,
+            ..SignMeta::new(scheme, signature)?
+        })
+    }
+
+    /// Check the signature over the message, a key which doesn't suit the scheme never verifies
+    pub fn verify(&self, key: VerifyKey, msg: &[u8]) -> bool {
+        match (self.scheme, key) {
+            (SignatureScheme::HmacSha256, VerifyKey::Hmac(secret)) => {
+                hmac_verify(Algorithm::SHA256, secret, msg, &self.signature)
+            }
+            (SignatureScheme::HmacSha384, VerifyKey::Hmac(secret)) => {
+                hmac_verify(Algorithm::SHA384, secret, msg, &self.signature)
+            }
+            (SignatureScheme::HmacSha512, VerifyKey::Hmac(secret)) => {
+                hmac_verify(Algorithm::SHA512, secret, msg, &self.signature)
+            }
+            (SignatureScheme::Ed25519, VerifyKey::Ed25519(key)) => ed25519::Signature::from_bytes(&self.signature)
+                .is_ok_and(|signature| ed25519::verify(key, msg, &signature)),
+            (SignatureScheme::EcdsaP256, VerifyKey::Ecdsa(key)) => {
+                ecdsa::verify(key, msg, &self.ecdsa_signature(ecdsa::SignatureFormat::Fixed))
+            }
+            (SignatureScheme::EcdsaP256Asn1, VerifyKey::Ecdsa(key)) => {
+                ecdsa::verify(key, msg, &self.ecdsa_signature(ecdsa::SignatureFormat::Asn1))
+            }
+            (SignatureScheme::RsaPkcs1Sha256, VerifyKey::Rsa(key)) => key.verify_pkcs1_sha256(msg, &self.signature),
+            _ => false,
+        }
+    }
+
+    fn ecdsa_signature(&self, format: ecdsa::SignatureFormat) -> ecdsa::Signature {
+        ecdsa::Signature {
+            format,
+            bytes: self.signature.clone(),
+        }
+    }
+}
+
+impl From<ed25519::Signature> for SignMeta {
+    fn from(signature: ed25519::Signature) -> Self {
+        SignMeta {
+            scheme: SignatureScheme::Ed25519,
+            signature: signature.to_bytes().to_vec(),
+            key_id: None,
+        }
+    }
+}
+
+/// Checks the length since `ecdsa::Signature`'s bytes can be anything
+impl TryFrom<ecdsa::Signature> for SignMeta {
+    type Error = ParseError;
+
+    fn try_from(signature: ecdsa::Signature) -> Result<Self, Self::Error> {
+        let scheme = match signature.format {
+            ecdsa::SignatureFormat::Fixed => SignatureScheme::EcdsaP256,
+            ecdsa::SignatureFormat::Asn1 => SignatureScheme::EcdsaP256Asn1,
+        };
+        SignMeta::new(scheme, signature.bytes)
+    }
+}
+
+impl std::str::FromStr for SignMeta {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SignMeta::parse(s)
+    }
+}
+
+impl std::fmt::Display for SignMeta {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.key_id {
+            Some(key_id) => write!(f, "{}:{key_id}:{}", self.scheme, self.to_base64url()),
+            None => write!(f, "{}:{}", self.scheme, self.to_base64url()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SignMeta {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SignMeta {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{hmac_sign, RsaPrivateKey};
+
+    fn signature_for(scheme: SignatureScheme) -> Vec<u8> {
+        vec![0xa5; *scheme.signature_len().end()]
+    }
+
+    #[test]
+    fn round_trip_canonical_form() {
+        for scheme in SignatureScheme::ALL {
+            let meta = SignMeta::new(scheme, signature_for(scheme)).unwrap();
+            assert_eq!(Ok(meta.clone()), SignMeta::parse(&meta.to_string()));
+            let meta = meta.with_key_id("keys/2024:rotation-1");
+            assert!(meta.to_string().starts_with(&format!("{scheme}:keys/2024:rotation-1:")));
+            assert_eq!(Ok(meta.clone()), SignMeta::parse(&meta.to_string()));
+        }
+    }
+
+    #[test]
+    fn validate_signature_length() {
+        assert_eq!(
+            Err(ParseError::SignatureLength { scheme: SignatureScheme::Ed25519, actual: 63 }),
+            SignMeta::new(SignatureScheme::Ed25519, vec![0; 63])
+        );
+        assert!(SignMeta::new(SignatureScheme::RsaPkcs1Sha256, vec![0; 384]).is_ok());
+        assert!(SignMeta::new(SignatureScheme::RsaPkcs1Sha256, vec![0; 128]).is_err());
+        let short = format!("hmac-sha256:{}", base64_encode(&[0; 31], BASE64URL, false));
+        assert!(matches!(SignMeta::parse(&short), Err(ParseError::SignatureLength { .. })));
+        assert_eq!(Err(ParseError::MissingPrefix), SignMeta::parse("AAAA"));
+        assert!(matches!(SignMeta::parse("dsa:AAAA"), Err(ParseError::UnknownAlgorithm(_))));
+        assert_eq!(Err(ParseError::InvalidSignature), SignMeta::parse("ed25519:not base64!"));
+        let truncated = ecdsa::Signature { format: ecdsa::SignatureFormat::Fixed, bytes: vec![0; 63] };
+        assert_eq!(
+            Err(ParseError::SignatureLength { scheme: SignatureScheme::EcdsaP256, actual: 63 }),
+            SignMeta::try_from(truncated)
+        );
+    }
+
+    #[test]
+    fn verify_with_matching_key_only() {
+        let secret = b"webhook secret";
+        let tag = hmac_sign(Algorithm::SHA256, secret, b"payload").unwrap().tag;
+        let hmac = SignMeta::new(SignatureScheme::HmacSha256, tag).unwrap();
+        assert!(hmac.verify(VerifyKey::Hmac(secret), b"payload"));
+        assert!(!hmac.verify(VerifyKey::Hmac(secret), b"tampered"));
+
+        let (signing_key, verifying_key) = ed25519::generate_keypair().unwrap();
+        let ed = SignMeta::from(ed25519::sign(&signing_key, b"payload"));
+        assert!(ed.verify(VerifyKey::Ed25519(&verifying_key), b"payload"));
+
+        let (signing_key, ecdsa_key) = ecdsa::generate_keypair().unwrap();
+        let signature = ecdsa::sign(&signing_key, b"payload", ecdsa::SignatureFormat::Asn1).unwrap();
+        let es = SignMeta::try_from(signature).unwrap();
+        assert_eq!(SignatureScheme::EcdsaP256Asn1, es.scheme());
+        assert!(es.verify(VerifyKey::Ecdsa(&ecdsa_key), b"payload"));
+
+        let rsa_key = RsaPrivateKey::parse(include_bytes!("../fixtures/rsa2048_private.pem")).unwrap();
+        let rs = SignMeta::new(SignatureScheme::RsaPkcs1Sha256, rsa_key.sign_pkcs1_sha256(b"payload").unwrap());
+        assert!(rs.unwrap().verify(VerifyKey::Rsa(&rsa_key.public_key()), b"payload"));
+
+        // The right bytes but the wrong kind of key
+        assert!(!ed.verify(VerifyKey::Hmac(secret), b"payload"));
+        assert!(!hmac.verify(VerifyKey::Ed25519(&verifying_key), b"payload"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn keep_key_id_through_serde() {
+        let meta = SignMeta::new(SignatureScheme::Ed25519, vec![7; 64]).unwrap().with_key_id("k1");
+        let json = serde_json::to_string(&meta).unwrap();
+        assert!(json.starts_with("\"ed25519:k1:"));
+        assert_eq!(meta, serde_json::from_str::<SignMeta>(&json).unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{Algorithm, HashMeta, Hasher};
+
+use bytes::Bytes;
+use futures_core::Stream;
+
+use std::future::poll_fn;
+use std::pin::pin;
+
+/// Why `hash_stream` stopped before the end of the stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HashStreamError<E> {
+    /// The stream yielded an error, the chunks before it were hashed and dropped
+    Stream(E),
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for HashStreamError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HashStreamError::Stream(err) => Some(err),
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for HashStreamError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            HashStreamError::Stream(err) => write!(f, "HashStreamError ( stream failed: {err} )"),
+        }
+    }
+}
+
+/// Hash an async body (i.e. a Workers request) chunk by chunk as it arrives, returning the
+/// digest and total length. Works on any executor, it only polls the stream.
+pub async fn hash_stream<S, E>(algorithm: Algorithm, stream: S) -> Result<(HashMeta, u64), HashStreamError<E>>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    let mut stream = pin!(stream);
+    let mut hasher = Hasher::new(algorithm);
+    while let Some(chunk) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+        hasher.update(&chunk.map_err(HashStreamError::Stream)?);
+    }
+    let len = hasher.bytes_processed();
+    Ok((hasher.finalize(), len))
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::hash;
+    use crate::test_data::{bytes, lcg};
+
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    /// Yields its items in turn, returning Pending before each one as a network body would
+    struct Chunks<E> {
+        items: VecDeque<Result<Bytes, E>>,
+        ready: bool,
+    }
+
+    impl<E: Unpin> Stream for Chunks<E> {
+        type Item = Result<Bytes, E>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            self.ready = !self.ready;
+            if self.ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(self.items.pop_front())
+        }
+    }
+
+    fn chunks<E>(items: impl IntoIterator<Item = Result<Bytes, E>>) -> Chunks<E> {
+        Chunks {
+            items: items.into_iter().collect(),
+            ready: false,
+        }
+    }
+
+    // The test streams wake themselves, so spinning is enough of an executor
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn match_one_shot_hash_over_random_chunks() {
+        let data = bytes(10_000);
+        let mut seed = 7;
+        for _ in 0..10 {
+            let mut pieces = Vec::new();
+            let mut rest = data.as_slice();
+            while !rest.is_empty() {
+                let (piece, remaining) = rest.split_at((lcg(&mut seed) as usize % 900).min(rest.len()));
+                pieces.push(Ok::<_, ()>(Bytes::copy_from_slice(piece)));
+                rest = remaining;
+            }
+            let (meta, len) = block_on(hash_stream(Algorithm::SHA256, chunks(pieces))).unwrap();
+            assert_eq!(hash(Algorithm::SHA256, &data), meta);
+            assert_eq!(data.len() as u64, len);
+        }
+    }
+
+    #[test]
+    fn hash_empty_stream_like_empty_input() {
+        let (meta, len) = block_on(hash_stream(Algorithm::SHA512, chunks::<()>([]))).unwrap();
+        assert_eq!(hash(Algorithm::SHA512, b""), meta);
+        assert_eq!(0, len);
+    }
+
+    #[test]
+    fn propagate_stream_error() {
+        let items = [
+            Ok(Bytes::from_static(b"first")),
+            Err(std::io::ErrorKind::ConnectionReset),
+            Ok(Bytes::from_static(b"never hashed")),
+        ];
+        let result = block_on(hash_stream(Algorithm::SHA256, chunks(items)));
+        assert_eq!(Err(HashStreamError::Stream(std::io::ErrorKind::ConnectionReset)), result);
+    }
+}
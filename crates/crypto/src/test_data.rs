@@ -0,0 +1,12 @@
+//! Fixtures shared by the unit tests
+
+/// Linear congruential generator, seeded by the test so failures can be reproduced
+pub(crate) fn lcg(seed: &mut u64) -> u64 {
+    *seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+    *seed >> 33
+}
+
+/// `len` bytes which don't repeat on any power of two boundary
+pub(crate) fn bytes(len: u32) -> Vec<u8> {
+    (0..len).map(|i| (i * 31 % 251) as u8).collect()
+}
@@ -0,0 +1,74 @@
+use crate::encoding::{base64_decode, hex_decode, BASE64, BASE64URL};
+use crate::{hash, Algorithm, HashMeta};
+
+use ring::constant_time;
+
+/// Compare in constant time, a length mismatch is simply unequal
+pub(crate) fn slices_equal(a: &[u8], b: &[u8]) -> bool {
+    constant_time::verify_slices_are_equal(a, b).is_ok()
+}
+
+/// Hash the data and compare against the expected digest in constant time
+pub fn verify_hash(algorithm: Algorithm, data: &[u8], expected: &[u8]) -> bool {
+    slices_equal(&hash(algorithm, data).hash, expected)
+}
+
+/// As `verify_hash` with the expected digest in hex, which doesn't match if it can't be decoded
+pub fn verify_hex(algorithm: Algorithm, data: &[u8], expected: &str) -> bool {
+    hex_decode(expected).is_some_and(|expected| verify_hash(algorithm, data, &expected))
+}
+
+/// As `verify_hash` with the expected digest in standard or URL safe base64, padded or not,
+/// which doesn't match if it can't be decoded
+pub fn verify_base64(algorithm: Algorithm, data: &[u8], expected: &str) -> bool {
+    base64_decode(expected, BASE64)
+        .or_else(|| base64_decode(expected, BASE64URL))
+        .is_some_and(|expected| verify_hash(algorithm, data, &expected))
+}
+
+impl HashMeta {
+    /// Recompute over the data and compare against this digest in constant time
+    pub fn verify_data(&self, data: &[u8]) -> bool {
+        verify_hash(self.algorithm, data, &self.hash)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn match_the_same_data() {
+        let meta = hash(Algorithm::SHA256, b"abc");
+        assert!(meta.verify_data(b"abc"));
+        assert!(verify_hash(Algorithm::SHA256, b"abc", &meta.hash));
+        assert!(verify_hex(Algorithm::SHA256, b"abc", &meta.to_hex()));
+        assert!(verify_hex(Algorithm::SHA256, b"abc", &meta.to_hex().to_uppercase()));
+        assert!(verify_base64(Algorithm::SHA256, b"abc", &meta.to_base64()));
+        assert!(verify_base64(Algorithm::SHA256, b"abc", &meta.to_base64url()));
+    }
+
+    #[test]
+    fn reject_other_data() {
+        let meta = hash(Algorithm::SHA512, b"abc");
+        assert!(!meta.verify_data(b"abd"));
+        assert!(!verify_hex(Algorithm::SHA512, b"abd", &meta.to_hex()));
+        assert!(!verify_hash(Algorithm::SHA384, b"abc", &meta.hash));
+    }
+
+    #[test]
+    fn reject_wrong_length() {
+        let meta = hash(Algorithm::SHA256, b"abc");
+        assert!(!verify_hash(Algorithm::SHA256, b"abc", &meta.hash[..31]));
+        assert!(!verify_hash(Algorithm::SHA256, b"abc", &[]));
+        assert!(!verify_hex(Algorithm::SHA256, b"abc", &meta.to_hex()[..62]));
+    }
+
+    #[test]
+    fn reject_malformed_encoding() {
+        assert!(!verify_hex(Algorithm::SHA256, b"abc", "not hex"));
+        assert!(!verify_hex(Algorithm::SHA256, b"abc", "abc"));
+        assert!(!verify_base64(Algorithm::SHA256, b"abc", "!!!!"));
+        assert!(!verify_base64(Algorithm::SHA256, b"abc", "Zh=="));
+    }
+}
@@ -1,9 +1,11 @@
 use core::fmt::Formatter;
-use reqwest::{ Client, Response };
+use reqwest::{ Client, Response, StatusCode };
 use reqwest::header::{self, HeaderValue, CONTENT_TYPE, USER_AGENT};
 use serde::{Serialize, Deserialize};
 use std::fmt::Debug;
 
+pub mod rfc2047;
+
 const APPLICTION_JSON: &str = "application/json";
 const LIB_USER_AGENT: &str = concat!["CF-MAILCHANNELS", "/", env!("CARGO_PKG_VERSION")];
 const MAILCHANNELS_SEND_API: &str = "https://api.mailchannels.net/tx/v1/send";
@@ -119,6 +121,26 @@ impl Dkim {
 }
 
 impl EmailMessage {
+    /// Returns a copy of the message with the display names of every participant and the
+    /// subject encoded as RFC 2047 encoded-words where they contain non-ASCII characters
+    pub fn with_encoded_headers(&self) -> Self {
+        let encode_participant = |p: &Participant| Participant {
+            email: p.email.to_owned(),
+            name: rfc2047::encode(&p.name),
+        };
+        EmailMessage {
+            personalizations: self.personalizations.iter()
+                .map(|p| Personalization {
+                    to: p.to.iter().map(encode_participant).collect(),
+                    ..p.clone()
+                })
+                .collect(),
+            from: encode_participant(&self.from),
+            subject: rfc2047::encode(&self.subject),
+            ..self.clone()
+        }
+    }
+
     pub fn new(from: impl Into<Participant>, to: impl Into<Participants>, subject: impl Into<String>, content: impl Into<Content>) -> Self {
         Self::new_with_dkim(None, from, to, subject, content)
     }
@@ -144,9 +166,110 @@ impl EmailMessage {
     }
 }
 
-#[derive(Debug)]
+/// Whether a failed send is worth retrying
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// Connection problems, timeouts, rate limiting, and server side failures
+    Transient,
+    /// The request itself was rejected (validation, auth, payload too large) and will fail again
+    Permanent,
+    /// Not enough information to decide
+    Unknown,
+}
+
+impl ErrorClass {
+    pub fn from_status(status: StatusCode) -> Self {
+        if status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status.is_server_error()
+        {
+            ErrorClass::Transient
+        } else if status.is_client_error() {
+            ErrorClass::Permanent
+        } else {
+            ErrorClass::Unknown
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let class = match self {
+            ErrorClass::Transient => "transient",
+            ErrorClass::Permanent => "permanent",
+            ErrorClass::Unknown => "unknown",
+        };
+        write!(f, "{class}")
+    }
+}
+
 pub enum Error {
     Reqwest(reqwest::Error),
+    /// MailChannels responded with a non-success status, the body carries its explanation
+    Status(StatusCode, String),
+}
+
+impl Error {
+    pub fn classify(&self) -> ErrorClass {
+        match self {
+            Error::Reqwest(err) => {
+                // Connect errors are only reported by the native client
+                #[cfg(not(target_arch = "wasm32"))]
+                let is_connect = err.is_connect();
+                #[cfg(target_arch = "wasm32")]
+                let is_connect = false;
+                if err.is_timeout() || is_connect {
+                    ErrorClass::Transient
+                } else if let Some(status) = err.status() {
+                    ErrorClass::from_status(status)
+                } else if err.is_builder() || err.is_redirect() {
+                    ErrorClass::Permanent
+                } else {
+                    ErrorClass::Unknown
+                }
+            }
+            Error::Status(status, _) => ErrorClass::from_status(*status),
+        }
+    }
+
+    /// Shorthand for checking if the send should be requeued
+    pub fn is_transient(&self) -> bool {
+        self.classify() == ErrorClass::Transient
+    }
+}
+
+impl Debug for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Reqwest(err) => f.debug_struct("Reqwest")
+                .field("class", &self.classify())
+                .field("source", err)
+                .finish(),
+            Error::Status(status, body) => f.debug_struct("Status")
+                .field("class", &self.classify())
+                .field("status", status)
+                .field("body", body)
+                .finish(),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            Error::Reqwest(err) => write!(f, "{} error: {err}", self.classify()),
+            Error::Status(status, body) => write!(f, "{} error: status {status}: {body}", self.classify()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Reqwest(err) => Some(err),
+            Error::Status(..) => None,
+        }
+    }
 }
 
 impl From<reqwest::Error> for Error {
@@ -157,6 +280,7 @@ impl From<reqwest::Error> for Error {
 
 pub struct MailChannelsClient {
     client: Client,
+    encode_headers: bool,
 }
 
 impl Default for MailChannelsClient {
@@ -168,19 +292,53 @@ impl Default for MailChannelsClient {
             .default_headers(headers)
             .build()
             .expect("Reqwest client builder should not fail");
-        MailChannelsClient { client }
+        MailChannelsClient { client, encode_headers: true }
     }
 }
 
 impl MailChannelsClient {
+    /// Enables or disables RFC 2047 encoding of non-ASCII display names and subjects (on by default)
+    pub fn with_encoded_headers(mut self, enabled: bool) -> Self {
+        self.encode_headers = enabled;
+        self
+    }
+
+    /// Returns the message exactly as it will be serialized into the send request
+    pub fn payload(&self, email: EmailMessage) -> EmailMessage {
+        if self.encode_headers {
+            email.with_encoded_headers()
+        } else {
+            email
+        }
+    }
+
     pub async fn send(&self, email: EmailMessage) -> Result<Response, Error> {
         let response = self.client
             .post(MAILCHANNELS_SEND_API)
-            .json(&email)
+            .json(&self.payload(email))
             .send()
             .await?;
         Ok(response)
     }
+
+    /// Sends each message in turn, never stopping at a failure. Every message is handed back
+    /// alongside its result so transient failures can be requeued, i.e. those where
+    /// `Error::is_transient` is true. Non-success statuses are reported as `Error::Status`.
+    pub async fn send_batch(&self, emails: Vec<EmailMessage>) -> Vec<(EmailMessage, Result<Response, Error>)> {
+        let mut results = Vec::with_capacity(emails.len());
+        for email in emails {
+            let result = match self.send(email.clone()).await {
+                Ok(response) if !response.status().is_success() => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    Err(Error::Status(status, body))
+                }
+                result => result,
+            };
+            results.push((email, result));
+        }
+        results
+    }
 }
 
 #[cfg(test)]
@@ -222,6 +380,54 @@ mod should {
         let deserialized = serde_json::from_str(&json).unwrap();
         assert_eq!(email, deserialized);
     }
+
+    #[test]
+    fn classify_status_errors() {
+        let cases = [
+            (StatusCode::BAD_REQUEST, ErrorClass::Permanent),
+            (StatusCode::UNAUTHORIZED, ErrorClass::Permanent),
+            (StatusCode::FORBIDDEN, ErrorClass::Permanent),
+            (StatusCode::PAYLOAD_TOO_LARGE, ErrorClass::Permanent),
+            (StatusCode::UNPROCESSABLE_ENTITY, ErrorClass::Permanent),
+            (StatusCode::REQUEST_TIMEOUT, ErrorClass::Transient),
+            (StatusCode::TOO_MANY_REQUESTS, ErrorClass::Transient),
+            (StatusCode::INTERNAL_SERVER_ERROR, ErrorClass::Transient),
+            (StatusCode::BAD_GATEWAY, ErrorClass::Transient),
+            (StatusCode::SERVICE_UNAVAILABLE, ErrorClass::Transient),
+            (StatusCode::MOVED_PERMANENTLY, ErrorClass::Unknown),
+        ];
+        for (status, expected) in cases {
+            let err = Error::Status(status, String::new());
+            assert_eq!(expected, err.classify(), "{status}");
+            assert!(format!("{err}").starts_with(&expected.to_string()));
+            assert!(format!("{err:?}").contains(&format!("{expected:?}")));
+        }
+    }
+
+    #[test]
+    fn classify_request_builder_errors_as_permanent() {
+        let err: Error = Client::new().get("not a url").build().unwrap_err().into();
+        assert_eq!(ErrorClass::Permanent, err.classify());
+        assert!(!err.is_transient());
+    }
+
+    #[test]
+    fn encode_non_ascii_headers_in_payload_by_default() {
+        let from = Participant { email: "asa@acme.com".to_owned(), name: "Åsa Öberg".to_owned() };
+        let email = EmailMessage::new(from, "to@acme.com", "Hej 🎉", "content");
+        let json = serde_json::to_string(&MailChannelsClient::default().payload(email)).unwrap();
+        assert!(json.contains(&rfc2047::encode("Åsa Öberg")));
+        assert!(json.contains(&rfc2047::encode("Hej 🎉")));
+        assert!(!json.contains("Åsa"));
+    }
+
+    #[test]
+    fn send_raw_headers_when_encoding_is_disabled() {
+        let from = Participant { email: "asa@acme.com".to_owned(), name: "Åsa Öberg".to_owned() };
+        let email = EmailMessage::new(from, "to@acme.com", "Hej 🎉", "content");
+        let client = MailChannelsClient::default().with_encoded_headers(false);
+        assert_eq!(email, client.payload(email.clone()));
+    }
 }
 
 // pub async fn send_email(email_message: EmailMessage) -> Result<Response, Error> {
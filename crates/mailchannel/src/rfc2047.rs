@@ -0,0 +1,226 @@
+//! RFC 2047 encoded-words for header values (display names and subjects) which contain
+//! non-ASCII characters.
+//!
+//! Only the UTF-8 charset with B (base64) encoding is produced. Decoding also accepts the Q
+//! encoding so values produced by other mailers can be round-tripped in tests.
+
+const PREFIX: &str = "=?UTF-8?B?";
+const SUFFIX: &str = "?=";
+/// RFC 2047 section 2: an encoded-word may not be more than 75 characters long
+const MAX_WORD_LEN: usize = 75;
+/// Separator placed between encoded-words when a value spans more than one
+const FOLD: &str = "\r\n ";
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DecodeError {
+    /// The encoded-word is missing one of its `=?charset?encoding?text?=` parts
+    Malformed(String),
+    /// Only UTF-8 and US-ASCII are supported
+    UnsupportedCharset(String),
+    /// Only the B and Q encodings are defined by the RFC
+    UnsupportedEncoding(String),
+    /// The encoded text was not valid base64 / quoted-printable
+    InvalidEncodedText(String),
+    /// The decoded bytes were not valid for the declared charset
+    InvalidUtf8,
+}
+
+impl std::error::Error for DecodeError {}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::Malformed(word) => write!(f, "malformed encoded-word: {word}"),
+            DecodeError::UnsupportedCharset(charset) => write!(f, "unsupported charset: {charset}"),
+            DecodeError::UnsupportedEncoding(encoding) => write!(f, "unsupported encoding: {encoding}"),
+            DecodeError::InvalidEncodedText(text) => write!(f, "invalid encoded text: {text}"),
+            DecodeError::InvalidUtf8 => write!(f, "decoded text is not valid utf-8"),
+        }
+    }
+}
+
+/// Encodes the value as one or more RFC 2047 encoded-words if it contains non-ASCII characters,
+/// otherwise the value is returned unchanged.
+///
+/// Each encoded-word is at most 75 characters long and never splits a multi-byte character.
+/// When more than one word is required they are folded onto continuation lines (CRLF + space).
+pub fn encode(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_owned();
+    }
+    // Every 3 input bytes become 4 base64 characters so only whole groups are budgeted
+    let max_bytes = (MAX_WORD_LEN - PREFIX.len() - SUFFIX.len()) / 4 * 3;
+    let mut words = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk_len = 0;
+    for c in value.chars() {
+        let len = c.len_utf8();
+        if chunk_len + len > max_bytes {
+            words.push(encode_word(&value.as_bytes()[chunk_start..chunk_start + chunk_len]));
+            chunk_start += chunk_len;
+            chunk_len = 0;
+        }
+        chunk_len += len;
+    }
+    words.push(encode_word(&value.as_bytes()[chunk_start..chunk_start + chunk_len]));
+    words.join(FOLD)
+}
+
+/// Decodes any RFC 2047 encoded-words contained in the value.
+///
+/// Whitespace (including folding) between two adjacent encoded-words is dropped as required by
+/// the RFC, any other text is passed through untouched.
+pub fn decode(value: &str) -> Result<String, DecodeError> {
+    let mut result = String::with_capacity(value.len());
+    let mut pending_whitespace = String::new();
+    let mut previous_was_word = false;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let token_start = rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len());
+        let (whitespace, remaining) = rest.split_at(token_start);
+        pending_whitespace.push_str(whitespace);
+        if remaining.is_empty() {
+            break;
+        }
+        let token_end = remaining.find(char::is_whitespace).unwrap_or(remaining.len());
+        let (token, remaining) = remaining.split_at(token_end);
+        let is_word = token.starts_with("=?") && token.ends_with(SUFFIX) && token.len() > 4;
+        if !(is_word && previous_was_word) {
+            result.push_str(&pending_whitespace);
+        }
+        pending_whitespace.clear();
+        if is_word {
+            result.push_str(&decode_word(token)?);
+        } else {
+            result.push_str(token);
+        }
+        previous_was_word = is_word;
+        rest = remaining;
+    }
+    result.push_str(&pending_whitespace);
+    Ok(result)
+}
+
+fn encode_word(bytes: &[u8]) -> String {
+    format!("{PREFIX}{}{SUFFIX}", base64_encode(bytes))
+}
+
+fn decode_word(word: &str) -> Result<String, DecodeError> {
+    let malformed = || DecodeError::Malformed(word.to_owned());
+    let inner = &word[2..word.len() - 2];
+    let mut parts = inner.splitn(3, '?');
+    let charset = parts.next().ok_or_else(malformed)?;
+    let encoding = parts.next().ok_or_else(malformed)?;
+    let text = parts.next().ok_or_else(malformed)?;
+    // RFC 2231 allows a language suffix on the charset, i.e. UTF-8*en
+    let charset_name = charset.split('*').next().unwrap_or_default();
+    if !charset_name.eq_ignore_ascii_case("utf-8") && !charset_name.eq_ignore_ascii_case("us-ascii") {
+        return Err(DecodeError::UnsupportedCharset(charset.to_owned()));
+    }
+    let bytes = match encoding {
+        "B" | "b" => base64_decode(text),
+        "Q" | "q" => q_decode(text),
+        _ => return Err(DecodeError::UnsupportedEncoding(encoding.to_owned())),
+    }
+    .ok_or_else(|| DecodeError::InvalidEncodedText(text.to_owned()))?;
+    String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut result = Vec::with_capacity(text.len() * 3 / 4);
+    let mut accum: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+        accum = accum << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((accum >> bits & 0xff) as u8);
+        }
+    }
+    Some(result)
+}
+
+fn q_decode(text: &str) -> Option<Vec<u8>> {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'_' => result.push(b' '),
+            b'=' => {
+                let hex = text.get(i + 1..i + 3)?;
+                result.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 2;
+            }
+            b => result.push(b),
+        }
+        i += 1;
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn pass_through_pure_ascii() {
+        assert_eq!("John Doe", encode("John Doe"));
+        assert_eq!("John Doe", decode("John Doe").unwrap());
+    }
+
+    #[test]
+    fn encode_short_non_ascii_name_as_single_word() {
+        let encoded = encode("Åsa Öberg");
+        assert_eq!("=?UTF-8?B?w4VzYSDDlmJlcmc=?=", encoded);
+        assert_eq!("Åsa Öberg", decode(&encoded).unwrap());
+    }
+
+    #[test]
+    fn fold_long_subject_into_words_of_at_most_75_chars() {
+        let subject = "Ünïcödé 🎉 subject which is long enough that it has to be folded over several encoded words";
+        let encoded = encode(subject);
+        let words: Vec<&str> = encoded.split(FOLD).collect();
+        assert!(words.len() > 1);
+        for word in &words {
+            assert!(word.len() <= MAX_WORD_LEN, "{word} is {} chars", word.len());
+            assert!(word.starts_with(PREFIX) && word.ends_with(SUFFIX));
+        }
+        assert_eq!(subject, decode(&encoded).unwrap());
+    }
+
+    #[test]
+    fn keep_whitespace_between_text_and_words() {
+        assert_eq!("Re: Åsa", decode("Re: =?UTF-8?B?w4VzYQ==?=").unwrap());
+        assert_eq!("Åsa Öberg", decode("=?utf-8?Q?=C3=85sa_=C3=96berg?=").unwrap());
+    }
+
+    #[test]
+    fn reject_unsupported_charset() {
+        assert_eq!(
+            Err(DecodeError::UnsupportedCharset("KOI8-R".to_owned())),
+            decode("=?KOI8-R?B?AAAA?=")
+        );
+    }
+}
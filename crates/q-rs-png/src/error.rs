@@ -0,0 +1,39 @@
+/// The reasons the requested QR image can't be produced from otherwise valid encoder input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorPayload {
+    /// The module grid (including the border) of `size` pixels doesn't fit in the `max` pixel target,
+    /// or is 0 pixels because the scale is 0
+    InvalidDimensions { size: u32, max: u32 },
+    /// The data is `size` bytes which is more than the `max` payload allowed
+    PayloadTooLarge { size: usize, max: usize },
+    /// Masks only go from 0 to 7
+    InvalidMask(u8),
+    /// Versions must be within 1 to 40 and min can't be greater than max
+    InvalidVersion { min: u8, max: u8 },
+    /// JPEG quality only goes from 1 to 100
+    InvalidQuality(u8),
+    /// The output format has no alpha channel but the colors aren't fully opaque
+    TransparencyUnsupported,
+}
+
+impl std::error::Error for ErrorPayload {}
+
+impl std::fmt::Display for ErrorPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::InvalidDimensions { size: 0, .. } => write!(f, "Scale of 0 leaves the image without pixels"),
+            Self::InvalidDimensions { size, max } => {
+                write!(f, "Image needs at least {} pixels but the max is {}", size, max)
+            },
+            Self::PayloadTooLarge { size, max } => {
+                write!(f, "Payload of {} bytes is over the max of {}", size, max)
+            },
+            Self::InvalidMask(mask) => write!(f, "Mask {} is out of range", mask),
+            Self::InvalidVersion { min, max } => {
+                write!(f, "Version range {} to {} is invalid", min, max)
+            },
+            Self::InvalidQuality(quality) => write!(f, "Quality {} is out of range", quality),
+            Self::TransparencyUnsupported => write!(f, "Output format does not support transparency"),
+        }
+    }
+}
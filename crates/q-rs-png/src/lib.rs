@@ -1,14 +1,24 @@
 use std::io::Cursor;
 use serde::{Serialize, Deserialize};
 
-use image::{ ImageBuffer, DynamicImage, ImageFormat, ImageError };//, ImageOutputFormat };
+use image::{ ImageBuffer, DynamicImage, ImageFormat, ImageError, Pixel };//, ImageOutputFormat };
 use image::imageops::resize;
 use q_rs::*;
 
+mod error;
+pub use error::*;
+
+/// Largest width or height in pixels an image will be rendered at
+pub const MAX_DIMENSION: u32 = 16384;
+
+/// Default `max_payload`, the most bytes a version 40 code can hold at low error correction
+pub const DEFAULT_MAX_PAYLOAD: usize = 2953;
+
 #[derive(Debug)]
 pub enum QrPngError {
     ImageError(ImageError),
     QrError(q_rs::QrError),
+    InvalidData(ErrorPayload),
 }
 
 impl std::error::Error for QrPngError {}
@@ -25,6 +35,12 @@ impl From<QrError> for QrPngError {
     }
 }
 
+impl From<ErrorPayload> for QrPngError {
+    fn from(value: ErrorPayload) -> Self {
+        QrPngError::InvalidData(value)
+    }
+}
+
 impl std::fmt::Display for QrPngError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -34,11 +50,14 @@ impl std::fmt::Display for QrPngError {
             Self::QrError(err) => {
                 write!(f, "{:?}", err)
             },
+            Self::InvalidData(err) => {
+                write!(f, "{}", err)
+            },
         }
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ColorTemplate {
     BlackOnWhite,
     BlackOnTransparant,
@@ -48,6 +67,13 @@ pub enum ColorTemplate {
         gray: u8,
         alpha: u8,
     },
+    // RGBA bytes for the on and off modules, (de)serialized as "#RRGGBB" or "#RRGGBBAA"
+    CustomRgba {
+        #[serde(with = "hex_color")]
+        on: [u8; 4],
+        #[serde(with = "hex_color")]
+        off: [u8; 4],
+    },
 }
 
 impl ColorTemplate {
@@ -57,7 +83,43 @@ impl ColorTemplate {
             ColorTemplate::BlackOnTransparant => (image::LumaA([0u8, 255u8]), image::LumaA([0u8, 0u8])),
             ColorTemplate::WhiteOnTransparant => (image::LumaA([255u8, 255u8]), image::LumaA([0u8, 0u8])),
             ColorTemplate::CustomGrayOnTransparant { gray, alpha } => (image::LumaA([*gray, *alpha]), image::LumaA([0u8, 0u8])),
+            ColorTemplate::CustomRgba { on, off } => (image::Rgba(*on).to_luma_alpha(), image::Rgba(*off).to_luma_alpha()),
+        }
+    }
+
+    // Returns the RGBA colors for templates that can't be represented as LumaA
+    pub fn into_rgba_colors(&self) -> Option<(image::Rgba<u8>, image::Rgba<u8>)> {
+        match self {
+            ColorTemplate::CustomRgba { on, off } => Some((image::Rgba(*on), image::Rgba(*off))),
+            _ => None,
+        }
+    }
+}
+
+mod hex_color {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::de::Error;
+
+    pub fn serialize<S: Serializer>(color: &[u8; 4], serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a] = *color;
+        serializer.serialize_str(&format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 4], D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse(&text).ok_or_else(|| D::Error::custom(format!("Invalid color \"{}\", expected #RRGGBB or #RRGGBBAA", text)))
+    }
+
+    pub fn parse(text: &str) -> Option<[u8; 4]> {
+        let hex = text.strip_prefix('#')?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+            return None;
+        }
+        let mut color = [0u8, 0u8, 0u8, 255u8];
+        for (i, channel) in color.iter_mut().take(hex.len() / 2).enumerate() {
+            *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
         }
+        Some(color)
     }
 }
 
@@ -67,7 +129,7 @@ impl Default for ColorTemplate {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DensityVersion(u8);
 
 impl DensityVersion {
@@ -89,7 +151,7 @@ impl From<DensityVersion> for Version {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ErrorCorrection {
     /// The QR Code can tolerate about  7% erroneous codewords.
     Low,
@@ -118,6 +180,23 @@ impl From<ErrorCorrection> for CodeEcc {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum OutputFormat {
+    Png,
+    /// Always encoded losslessly so module edges stay crisp
+    WebP,
+    /// Quality from 1 to 100, only valid with fully opaque color templates since JPEG has no alpha
+    Jpeg {
+        quality: u8,
+    },
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        OutputFormat::Png
+    }
+}
+
 /// Returns a QR Code representing the given segments with the given encoding parameters.
 ///
 /// The smallest possible QR Code version within the given range is automatically
@@ -132,7 +211,7 @@ impl From<ErrorCorrection> for CodeEcc {
 ///
 /// Returns a wrapped `QrCode` if successful, or `Err` if the data is too
 /// long to fit in any version in the given range at the given ECC level.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QROptions {
     // Sets the colors used for the foreground and background
     pub color_template: Option<ColorTemplate>,
@@ -144,6 +223,14 @@ pub struct QROptions {
     pub error_correction: Option<ErrorCorrection>,
     // Defines the default size of each block in the QR
     pub scale: Option<u8>,
+    // Final width and height in pixels, picks the largest scale that fits and pads the rest (overrides scale)
+    pub target_size: Option<u32>,
+    // Number of light blocks (quiet zone) around the QR, 4 by default as required by the spec
+    pub border: Option<u8>,
+    // Image encoding of the output, PNG by default
+    pub output_format: Option<OutputFormat>,
+    // Largest input in bytes that will be encoded, 2953 by default
+    pub max_payload: Option<usize>,
     // Specify the mask if desired
     pub mask: Option<u8>,
     // True automatically optimizes the error correction within version bounds if possible
@@ -158,6 +245,10 @@ impl Default for QROptions {
             max_version: None,
             error_correction: None,
             scale: None,
+            target_size: None,
+            border: None,
+            output_format: None,
+            max_payload: None,
             mask: None,
             boost_ecl: true,
         }
@@ -168,42 +259,724 @@ pub async fn generate_qr_image(
     data: &str,
     options: Option<QROptions>,
 ) -> Result<Vec<u8>, QrPngError> {
-    let segments = Segment::make_segments(data);
+    generate_qr_image_sync(data, options)
+}
+
+/// Same as `generate_qr_image` for callers without an async runtime.
+pub fn generate_qr_image_sync(
+    data: &str,
+    options: Option<QROptions>,
+) -> Result<Vec<u8>, QrPngError> {
+    let options = options.unwrap_or_default();
+    let qr = build_qr(data, &options)?;
+    render_qr(&qr, &options)
+}
+
+/// Encodes raw bytes in a single byte mode segment, avoiding the size cost of
+/// a text encoding like base64.
+pub fn generate_qr_image_binary(
+    data: &[u8],
+    options: Option<QROptions>,
+) -> Result<Vec<u8>, QrPngError> {
     let options = options.unwrap_or_default();
-    let color_template = options.color_template.unwrap_or_default();
-    let min_version = options.min_version.unwrap_or(DensityVersion(1));
-    let max_version = options.max_version.unwrap_or(DensityVersion(10));
-    let error_correction = options.error_correction.unwrap_or_default();
-    let scale = options.scale.unwrap_or(8) as i32;
+    let qr = build_qr_binary(data, &options)?;
+    render_qr(&qr, &options)
+}
+
+/// Encodes a pre-built sequence of segments, e.g. to switch modes or add an ECI designator.
+pub fn generate_qr_image_segments(
+    segments: &[Segment],
+    options: Option<QROptions>,
+) -> Result<Vec<u8>, QrPngError> {
+    let options = options.unwrap_or_default();
+    let qr = build_qr_segments(segments, &options)?;
+    render_qr(&qr, &options)
+}
+
+fn render_qr(qr: &QrCode, options: &QROptions) -> Result<Vec<u8>, QrPngError> {
+    encode_image(&rasterize(qr, options)?, &options.output_format.clone().unwrap_or_default())
+}
+
+/// Encodes the data into a QR Code using the version, error correction and mask options.
+///
+/// Returns `ErrorPayload::PayloadTooLarge`, `InvalidMask` or `InvalidVersion` before
+/// encoding anything if the data or options are out of bounds.
+pub fn build_qr(data: &str, options: &QROptions) -> Result<QrCode, QrPngError> {
+    options.validate(data.len())?;
+    encode_segments(&Segment::make_segments(data), options)
+}
+
+/// Same as `build_qr` for raw bytes.
+pub fn build_qr_binary(data: &[u8], options: &QROptions) -> Result<QrCode, QrPngError> {
+    options.validate(data.len())?;
+    encode_segments(&[Segment::make_bytes(data)], options)
+}
+
+/// Same as `build_qr` for pre-built segments, the payload size is the segment data bits
+/// rounded up to whole bytes.
+pub fn build_qr_segments(segments: &[Segment], options: &QROptions) -> Result<QrCode, QrPngError> {
+    let bits: usize = segments.iter().map(|seg| seg.data().len()).sum();
+    options.validate(bits.div_ceil(8))?;
+    encode_segments(segments, options)
+}
+
+fn encode_segments(segments: &[Segment], options: &QROptions) -> Result<QrCode, QrPngError> {
+    let min_version = options.min_version();
+    let max_version = options.max_version();
+    let error_correction = options.error_correction.clone().unwrap_or_default();
     let mask = options.mask.map(|v| Mask::new(v));
     let boost_ecl = options.boost_ecl;
 
-    // let qr = QrCode::encode_segments_advanced(&segments, CodeEcc::Medium,
-    //     Version::new(5), Version::new(5), Some(Mask::new(2)), false).unwrap();
-    // let qr = QrCode::encode_segments_advanced(&segments, error_correction.into(), min_version.into(), max_version.into(), mask, boost_ecl).unwrap();
-    let qr = QrCode::encode_segments_advanced(&segments, error_correction.into(), min_version.into(), max_version.into(), mask, boost_ecl)?;
-    // let png: ImageBuffer<Luma<u8>, Vec<u8>> = qr.render::<Luma<u8>>().build();
-    let size = qr.size;
-    
-    let (on, off) = color_template.into_colors();
-    let png = ImageBuffer::from_fn(size as u32, size as u32, |x, y| {
-        if qr.get_module(x as i32, y as i32) {
-            // image::LumaA([0u8, 0u8])
-            // image::Luma([0u8])
-            on
-        } else {
-            off
-            // image::LumaA([255u8, 255u8])
-            // image::Luma([255u8])
-        }
-    });
-    // let scale = 8;
-    let resized = resize(&png, (size * scale) as u32, (size * scale) as u32, image::imageops::FilterType::Nearest);
+    Ok(QrCode::encode_segments_advanced(segments, error_correction.into(), min_version.into(), max_version.into(), mask, boost_ecl)?)
+}
+
+/// Draws the QR Code with the color, scale and border options, keeping the LumaA
+/// pixel format for the grayscale templates and only paying for RGBA when a color is requested.
+///
+/// Returns `ErrorPayload::InvalidDimensions` if `target_size` can't fit one pixel per module.
+pub fn rasterize(qr: &QrCode, options: &QROptions) -> Result<DynamicImage, QrPngError> {
+    let border = options.border();
+    let layout = options.layout(qr.bordered_size(border))?;
+    let color_template = options.color_template();
+    Ok(match color_template.into_rgba_colors() {
+        Some((on, off)) => DynamicImage::ImageRgba8(render_image(qr, border, layout, on, off)),
+        None => {
+            let (on, off) = color_template.into_colors();
+            DynamicImage::ImageLumaA8(render_image(qr, border, layout, on, off))
+        },
+    })
+}
+
+/// Writes the image out as PNG bytes.
+pub fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, QrPngError> {
+    encode_image(image, &OutputFormat::Png)
+}
+
+/// Writes the image out in the given format.
+///
+/// Returns `ErrorPayload::InvalidQuality` for a JPEG quality outside 1 to 100 and
+/// `ErrorPayload::TransparencyUnsupported` rather than flattening a JPEG with transparent pixels.
+pub fn encode_image(image: &DynamicImage, format: &OutputFormat) -> Result<Vec<u8>, QrPngError> {
     let mut w = Cursor::new(Vec::new());
-    // DynamicImage::ImageLuma8(resized)
-    DynamicImage::ImageLumaA8(resized)
-        // .write_to(&mut w, ImageOutputFormat::Png)
-        .write_to(&mut w, ImageFormat::Png)?;
+    match *format {
+        OutputFormat::Png => image.write_to(&mut w, ImageFormat::Png)?,
+        OutputFormat::WebP => image.write_to(&mut w, ImageFormat::WebP)?,
+        OutputFormat::Jpeg { quality } => {
+            if !(1..=100).contains(&quality) {
+                return Err(ErrorPayload::InvalidQuality(quality).into());
+            }
+            if image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p.0[3] < 255) {
+                return Err(ErrorPayload::TransparencyUnsupported.into());
+            }
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut w, quality);
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+        },
+    }
     let vec: Vec<_> = w.into_inner();
     Ok(vec)
-}
\ No newline at end of file
+}
+
+/// Returns a path based SVG document where each module is one unit of the view box
+/// and `scale` sets the rendered width and height of a module. Drawn by q-rs so it matches
+/// `QrCode::to_svg_with_colors`, with transparent colors as `#RRGGBBAA`.
+///
+/// A `target_size` sets the rendered width and height instead, as for the raster formats,
+/// but the modules stretch to fill it rather than leaving padding since SVG isn't bound to
+/// whole pixels.
+pub fn generate_qr_svg(
+    data: &str,
+    options: Option<QROptions>,
+) -> Result<String, QrPngError> {
+    let options = options.unwrap_or_default();
+    let qr = build_qr(data, &options)?;
+    let border = options.border();
+    let layout = options.layout(qr.bordered_size(border))?;
+    let (on, off) = rgba_colors(&options.color_template());
+    let (dark, light) = (svg_color(on), svg_color(off));
+    Ok(qr.to_svg_with_width(border as u32, SvgColors { light: &light, dark: &dark }, layout.width))
+}
+
+/// Returns a single page PDF sized to the code with each dark module drawn as a filled
+/// rectangle. PDF has no alpha without extra graphics state so a transparent off color
+/// just leaves the page unpainted.
+#[cfg(feature = "pdf")]
+pub fn generate_qr_pdf(
+    data: &str,
+    options: Option<QROptions>,
+) -> Result<Vec<u8>, QrPngError> {
+    let options = options.unwrap_or_default();
+    let qr = build_qr(data, &options)?;
+    let (scale, border) = (options.scale()?, options.border());
+    let size = qr.bordered_size(border);
+    let width = size * scale;
+    let (on, off) = rgba_colors(&options.color_template());
+    let rgb = |color: image::Rgba<u8>| {
+        let [r, g, b, _] = color.0;
+        format!("{:.3} {:.3} {:.3} rg", r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    };
+
+    let mut content = String::new();
+    if off.0[3] > 0 {
+        content += &format!("{}\n0 0 {} {} re f\n", rgb(off), width, width);
+    }
+    // Flip the y axis so module rows run top to bottom like the other outputs
+    content += &format!("{} 0 0 -{} 0 {} cm\n{}\n", scale, scale, width, rgb(on));
+    render_modules(&qr, border, |x, y, dark| {
+        if dark {
+            content += &format!("{} {} 1 1 re\n", x, y);
+        }
+    });
+    content += "f";
+
+    let objects = [
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+        format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R >>", width, width),
+        format!("<< /Length {} >>\nstream\n{}\nendstream", content.len(), content),
+    ];
+    let mut pdf = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf += &format!("{} 0 obj\n{}\nendobj\n", i + 1, object);
+    }
+    let xref = pdf.len();
+    pdf += &format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        pdf += &format!("{:010} 00000 n \n", offset);
+    }
+    pdf += &format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref);
+    Ok(pdf.into_bytes())
+}
+
+impl QROptions {
+    fn color_template(&self) -> ColorTemplate {
+        self.color_template.clone().unwrap_or_default()
+    }
+
+    fn min_version(&self) -> DensityVersion {
+        self.min_version.clone().unwrap_or(DensityVersion(1))
+    }
+
+    fn max_version(&self) -> DensityVersion {
+        self.max_version.clone().unwrap_or(DensityVersion(10))
+    }
+
+    // Checks the limits that would otherwise panic or fail deep inside the encoder
+    fn validate(&self, payload: usize) -> Result<(), ErrorPayload> {
+        let max = self.max_payload.unwrap_or(DEFAULT_MAX_PAYLOAD);
+        if payload > max {
+            return Err(ErrorPayload::PayloadTooLarge { size: payload, max });
+        }
+        if let Some(mask) = self.mask.filter(|&mask| mask > 7) {
+            return Err(ErrorPayload::InvalidMask(mask));
+        }
+        let (DensityVersion(min), DensityVersion(max)) = (self.min_version(), self.max_version());
+        if min < Version::MIN.value() || max > Version::MAX.value() || min > max {
+            return Err(ErrorPayload::InvalidVersion { min, max });
+        }
+        // target_size overrides the scale so it only has to be valid without one
+        if self.target_size.is_none() {
+            self.scale()?;
+        }
+        Ok(())
+    }
+
+    // A scale of 0 would leave every module, and so the whole image, without pixels
+    fn scale(&self) -> Result<i32, ErrorPayload> {
+        match self.scale.unwrap_or(8) {
+            0 => Err(ErrorPayload::InvalidDimensions { size: 0, max: MAX_DIMENSION }),
+            scale => Ok(scale as i32),
+        }
+    }
+
+    fn border(&self) -> i32 {
+        i32::from(self.border.unwrap_or(4))
+    }
+
+    // Resolves the scale and final image size for a grid of `size` modules (including the border)
+    fn layout(&self, size: i32) -> Result<Layout, ErrorPayload> {
+        let size = size as u32;
+        let layout = match self.target_size {
+            Some(target) if target < size => return Err(ErrorPayload::InvalidDimensions { size, max: target }),
+            Some(target) => Layout { scale: target / size, width: target },
+            None => {
+                let scale = self.scale()? as u32;
+                Layout { scale, width: size * scale }
+            },
+        };
+        if layout.width > MAX_DIMENSION {
+            return Err(ErrorPayload::InvalidDimensions { size: layout.width, max: MAX_DIMENSION });
+        }
+        Ok(layout)
+    }
+}
+
+// Pixels per module and the final square image width, anything past the scaled grid is padding
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    scale: u32,
+    width: u32,
+}
+
+// Walks every module of the code including the border, row by row, reporting whether it's dark.
+// The raster and PDF outputs share it, SVG is left to q-rs' `to_svg_with_width` so there's one
+// SVG writer across both crates.
+fn render_modules(qr: &QrCode, border: i32, mut visit: impl FnMut(i32, i32, bool)) {
+    let size = qr.bordered_size(border);
+    for y in 0 .. size {
+        for x in 0 .. size {
+            visit(x, y, qr.get_module_bordered(x, y, border));
+        }
+    }
+}
+
+// Draws one pixel per module (including the border) then scales it up without smoothing the edges,
+// centering it on a canvas of the off color when the target width leaves some padding
+fn render_image<P: Pixel + 'static>(qr: &QrCode, border: i32, layout: Layout, on: P, off: P) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let size = qr.bordered_size(border);
+    let mut png = ImageBuffer::new(size as u32, size as u32);
+    render_modules(qr, border, |x, y, dark| {
+        png.put_pixel(x as u32, y as u32, if dark { on } else { off });
+    });
+    let scaled = size as u32 * layout.scale;
+    let resized = resize(&png, scaled, scaled, image::imageops::FilterType::Nearest);
+    if scaled == layout.width {
+        return resized;
+    }
+    let mut canvas = ImageBuffer::from_pixel(layout.width, layout.width, off);
+    let offset = i64::from((layout.width - scaled) / 2);
+    image::imageops::replace(&mut canvas, &resized, offset, offset);
+    canvas
+}
+
+fn rgba_colors(color_template: &ColorTemplate) -> (image::Rgba<u8>, image::Rgba<u8>) {
+    color_template.into_rgba_colors().unwrap_or_else(|| {
+        let (on, off) = color_template.into_colors();
+        (on.to_rgba(), off.to_rgba())
+    })
+}
+
+fn svg_color(color: image::Rgba<u8>) -> String {
+    match color.0 {
+        [r, g, b, 255] => format!("#{:02X}{:02X}{:02X}", r, g, b),
+        [r, g, b, a] => format!("#{:02X}{:02X}{:02X}{:02X}", r, g, b, a),
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    // The generator never awaits so a single poll completes it
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+                return value;
+            }
+        }
+    }
+
+    fn render(options: QROptions) -> image::DynamicImage {
+        let png = block_on(generate_qr_image("HELLO WORLD", Some(options))).unwrap();
+        image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn render_custom_rgba_with_exact_module_colors() {
+        let on = [0x1A, 0x73, 0xE8, 0xFF];
+        let off = [0xFF, 0xFF, 0xFF, 0x00];
+        let img = render(QROptions {
+            color_template: Some(ColorTemplate::CustomRgba { on, off }),
+            scale: Some(4),
+            border: Some(0),
+            ..Default::default()
+        });
+        assert!(matches!(img, DynamicImage::ImageRgba8(_)));
+        let img = img.to_rgba8();
+        // The top left finder pattern is dark on its outer ring and light just inside it
+        assert_eq!(image::Rgba(on), *img.get_pixel(1, 1));
+        assert_eq!(image::Rgba(off), *img.get_pixel(4 + 1, 4 + 1));
+    }
+
+    #[test]
+    fn keep_luma_alpha_for_gray_templates() {
+        let img = render(QROptions {
+            color_template: Some(ColorTemplate::BlackOnTransparant),
+            scale: Some(1),
+            border: Some(0),
+            ..Default::default()
+        });
+        let DynamicImage::ImageLumaA8(img) = img else { panic!("Expected a LumaA image") };
+        assert_eq!(image::LumaA([0, 255]), *img.get_pixel(0, 0));
+        assert_eq!(image::LumaA([0, 0]), *img.get_pixel(1, 1));
+    }
+
+    #[test]
+    fn parse_hex_colors() {
+        assert_eq!(Some([0x1A, 0x73, 0xE8, 0xFF]), hex_color::parse("#1A73E8"));
+        assert_eq!(Some([0x1A, 0x73, 0xE8, 0x80]), hex_color::parse("#1a73e880"));
+        assert_eq!(None, hex_color::parse("1A73E8"));
+        assert_eq!(None, hex_color::parse("#1A73E"));
+        assert_eq!(None, hex_color::parse("#1A73EG"));
+        assert_eq!(None, hex_color::parse("#1A73É"));
+    }
+
+    #[test]
+    fn round_trip_custom_rgba_as_hex_strings() {
+        let template: ColorTemplate = serde_json::from_str(r##"{"CustomRgba":{"on":"#1A73E8","off":"#FFFFFF00"}}"##).unwrap();
+        let ColorTemplate::CustomRgba { on, off } = template else { panic!("Expected CustomRgba") };
+        assert_eq!([0x1A, 0x73, 0xE8, 0xFF], on);
+        assert_eq!([0xFF, 0xFF, 0xFF, 0x00], off);
+        assert_eq!(
+            r##"{"CustomRgba":{"on":"#1A73E8FF","off":"#FFFFFF00"}}"##,
+            serde_json::to_string(&template).unwrap()
+        );
+        assert!(serde_json::from_str::<ColorTemplate>(r#"{"CustomRgba":{"on":"blue","off":"#FFFFFF"}}"#).is_err());
+    }
+
+    #[test]
+    fn add_four_module_quiet_zone_by_default() {
+        // HELLO WORLD fits in a version 1 code of 21 modules
+        let img = render(QROptions { scale: Some(3), ..Default::default() }).to_luma_alpha8();
+        let side = (21 + 2 * 4) * 3;
+        assert_eq!((side, side), img.dimensions());
+        for (x, y) in [(0, 0), (side - 1, 0), (0, side - 1), (side - 1, side - 1)] {
+            assert_eq!(image::LumaA([255, 255]), *img.get_pixel(x, y));
+        }
+        // The finder pattern starts right after the scaled border
+        assert_eq!(image::LumaA([255, 255]), *img.get_pixel(4 * 3 - 1, 4 * 3 - 1));
+        assert_eq!(image::LumaA([0, 255]), *img.get_pixel(4 * 3, 4 * 3));
+    }
+
+    #[test]
+    fn fill_custom_border_with_off_color() {
+        let off = [0x10, 0x20, 0x30, 0x00];
+        let img = render(QROptions {
+            color_template: Some(ColorTemplate::CustomRgba { on: [0, 0, 0, 255], off }),
+            scale: Some(2),
+            border: Some(1),
+            ..Default::default()
+        })
+        .to_rgba8();
+        let side = (21 + 2) * 2;
+        assert_eq!((side, side), img.dimensions());
+        for (x, y) in [(0, 0), (side - 1, 0), (0, side - 1), (side - 1, side - 1)] {
+            assert_eq!(image::Rgba(off), *img.get_pixel(x, y));
+        }
+        assert_eq!(image::Rgba([0, 0, 0, 255]), *img.get_pixel(2, 2));
+    }
+
+    #[test]
+    fn produce_identical_bytes_from_sync_and_async() {
+        let options = QROptions {
+            color_template: Some(ColorTemplate::CustomRgba { on: [0x1A, 0x73, 0xE8, 0xFF], off: [0xFF; 4] }),
+            scale: Some(3),
+            ..Default::default()
+        };
+        let sync = generate_qr_image_sync("HELLO WORLD", Some(options.clone())).unwrap();
+        let not_sync = block_on(generate_qr_image("HELLO WORLD", Some(options))).unwrap();
+        assert_eq!(sync, not_sync);
+        assert_eq!(generate_qr_image_sync("HELLO WORLD", None).unwrap(), block_on(generate_qr_image("HELLO WORLD", None)).unwrap());
+    }
+
+    #[test]
+    fn compose_build_rasterize_and_encode() {
+        let options = QROptions { scale: Some(2), border: Some(1), ..Default::default() };
+        let qr = build_qr("HELLO WORLD", &options).unwrap();
+        assert_eq!(21, qr.size());
+
+        let image = rasterize(&qr, &options).unwrap();
+        assert_eq!((46, 46), (image.width(), image.height()));
+
+        let png = encode_png(&image).unwrap();
+        assert_eq!(generate_qr_image_sync("HELLO WORLD", Some(options)).unwrap(), png);
+    }
+
+    fn render_target(target_size: u32) -> Result<image::GrayAlphaImage, QrPngError> {
+        let png = generate_qr_image_sync("HELLO WORLD", Some(QROptions { target_size: Some(target_size), ..Default::default() }))?;
+        Ok(image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap().to_luma_alpha8())
+    }
+
+    #[test]
+    fn scale_to_exact_target_size() {
+        // 21 modules plus a border of 4 on each side is 29 which fits twice in 58
+        let img = render_target(58).unwrap();
+        assert_eq!((58, 58), img.dimensions());
+        assert_eq!(image::LumaA([255, 255]), *img.get_pixel(7, 7));
+        assert_eq!(image::LumaA([0, 255]), *img.get_pixel(8, 8));
+        assert_eq!((29, 29), render_target(29).unwrap().dimensions());
+    }
+
+    #[test]
+    fn pad_target_size_with_off_color() {
+        // A scale of 2 leaves 6 pixels which get split evenly on both sides
+        let img = render_target(64).unwrap();
+        assert_eq!((64, 64), img.dimensions());
+        for (x, y) in [(0, 0), (63, 0), (0, 63), (63, 63), (10, 10)] {
+            assert_eq!(image::LumaA([255, 255]), *img.get_pixel(x, y));
+        }
+        assert_eq!(image::LumaA([0, 255]), *img.get_pixel(11, 11));
+        assert_eq!(image::LumaA([0, 255]), *img.get_pixel(12, 12));
+    }
+
+    #[test]
+    fn reject_target_size_below_module_grid() {
+        assert!(matches!(
+            render_target(28),
+            Err(QrPngError::InvalidData(ErrorPayload::InvalidDimensions { size: 29, max: 28 }))
+        ));
+    }
+
+    fn invalid_data(data: &str, options: QROptions) -> ErrorPayload {
+        match generate_qr_image_sync(data, Some(options)) {
+            Err(QrPngError::InvalidData(payload)) => payload,
+            other => panic!("Expected invalid data but got {:?}", other.map(|png| png.len())),
+        }
+    }
+
+    #[test]
+    fn reject_payload_over_max() {
+        assert_eq!(
+            ErrorPayload::PayloadTooLarge { size: 11, max: 10 },
+            invalid_data("HELLO WORLD", QROptions { max_payload: Some(10), ..Default::default() })
+        );
+        assert_eq!(
+            ErrorPayload::PayloadTooLarge { size: 2954, max: DEFAULT_MAX_PAYLOAD },
+            invalid_data(&"a".repeat(2954), QROptions::default())
+        );
+    }
+
+    #[test]
+    fn reject_mask_out_of_range() {
+        assert_eq!(ErrorPayload::InvalidMask(8), invalid_data("HELLO WORLD", QROptions { mask: Some(8), ..Default::default() }));
+    }
+
+    #[test]
+    fn reject_inconsistent_versions() {
+        assert_eq!(
+            ErrorPayload::InvalidVersion { min: 5, max: 3 },
+            invalid_data("HELLO WORLD", QROptions {
+                min_version: Some(DensityVersion::new(5)),
+                max_version: Some(DensityVersion::new(3)),
+                ..Default::default()
+            })
+        );
+        // Only raising the min past the default max of 10 is just as inconsistent
+        assert_eq!(
+            ErrorPayload::InvalidVersion { min: 12, max: 10 },
+            invalid_data("HELLO WORLD", QROptions { min_version: Some(DensityVersion::new(12)), ..Default::default() })
+        );
+        assert_eq!(
+            ErrorPayload::InvalidVersion { min: 0, max: 10 },
+            invalid_data("HELLO WORLD", QROptions { min_version: Some(DensityVersion::new(0)), ..Default::default() })
+        );
+        assert_eq!(
+            ErrorPayload::InvalidVersion { min: 1, max: 41 },
+            invalid_data("HELLO WORLD", QROptions { max_version: Some(DensityVersion::new(41)), ..Default::default() })
+        );
+    }
+
+    #[test]
+    fn reject_output_over_max_dimension() {
+        // Version 40 is 177 modules, plus the border is 185 which at a scale of 255 is 47175 pixels
+        assert_eq!(
+            ErrorPayload::InvalidDimensions { size: 47175, max: MAX_DIMENSION },
+            invalid_data("HELLO WORLD", QROptions {
+                min_version: Some(DensityVersion::new(40)),
+                max_version: Some(DensityVersion::new(40)),
+                scale: Some(255),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            ErrorPayload::InvalidDimensions { size: MAX_DIMENSION + 1, max: MAX_DIMENSION },
+            invalid_data("HELLO WORLD", QROptions { target_size: Some(MAX_DIMENSION + 1), ..Default::default() })
+        );
+    }
+
+    #[test]
+    fn reject_zero_scale() {
+        let options = QROptions { scale: Some(0), ..Default::default() };
+        let zero = ErrorPayload::InvalidDimensions { size: 0, max: MAX_DIMENSION };
+        assert_eq!(zero, invalid_data("HELLO WORLD", options.clone()));
+        let qr = build_qr("HELLO WORLD", &QROptions::default()).unwrap();
+        assert!(matches!(rasterize(&qr, &options), Err(QrPngError::InvalidData(payload)) if payload == zero));
+        let svg = generate_qr_svg("HELLO WORLD", Some(options.clone()));
+        assert!(matches!(svg, Err(QrPngError::InvalidData(payload)) if payload == zero));
+
+        let targeted = QROptions { target_size: Some(58), ..options };
+        let png = generate_qr_image_sync("HELLO WORLD", Some(targeted.clone())).unwrap();
+        let img = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+        assert_eq!((58, 58), (img.width(), img.height()));
+        assert!(generate_qr_svg("HELLO WORLD", Some(targeted)).is_ok());
+    }
+
+    fn render_format(output_format: OutputFormat, color_template: ColorTemplate) -> Result<Vec<u8>, QrPngError> {
+        generate_qr_image_sync("HELLO WORLD", Some(QROptions {
+            color_template: Some(color_template),
+            output_format: Some(output_format),
+            scale: Some(2),
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn encode_each_output_format() {
+        for (output_format, expected) in [
+            (OutputFormat::Png, ImageFormat::Png),
+            (OutputFormat::WebP, ImageFormat::WebP),
+            (OutputFormat::Jpeg { quality: 90 }, ImageFormat::Jpeg),
+        ] {
+            let bytes = render_format(output_format, ColorTemplate::BlackOnWhite).unwrap();
+            assert_eq!(expected, image::guess_format(&bytes).unwrap());
+            let img = image::load_from_memory_with_format(&bytes, expected).unwrap();
+            assert_eq!((58, 58), (img.width(), img.height()));
+        }
+        assert!(render_format(OutputFormat::Png, ColorTemplate::BlackOnWhite).unwrap().starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert!(render_format(OutputFormat::Jpeg { quality: 1 }, ColorTemplate::BlackOnWhite).unwrap().starts_with(&[0xFF, 0xD8, 0xFF]));
+        let webp = render_format(OutputFormat::WebP, ColorTemplate::BlackOnWhite).unwrap();
+        assert_eq!((b"RIFF".as_slice(), b"WEBP".as_slice()), (&webp[0..4], &webp[8..12]));
+    }
+
+    #[test]
+    fn keep_webp_lossless() {
+        let color_template = ColorTemplate::CustomRgba { on: [0x1A, 0x73, 0xE8, 0xFF], off: [0xFF, 0xFF, 0xFF, 0x00] };
+        let webp = render_format(OutputFormat::WebP, color_template).unwrap();
+        let img = image::load_from_memory_with_format(&webp, ImageFormat::WebP).unwrap().to_rgba8();
+        assert_eq!(image::Rgba([0xFF, 0xFF, 0xFF, 0x00]), *img.get_pixel(0, 0));
+        assert_eq!(image::Rgba([0x1A, 0x73, 0xE8, 0xFF]), *img.get_pixel(8, 8));
+    }
+
+    #[test]
+    fn reject_jpeg_quality_out_of_range() {
+        for quality in [0, 101] {
+            assert!(matches!(
+                render_format(OutputFormat::Jpeg { quality }, ColorTemplate::BlackOnWhite),
+                Err(QrPngError::InvalidData(ErrorPayload::InvalidQuality(q))) if q == quality
+            ));
+        }
+    }
+
+    #[test]
+    fn reject_jpeg_with_transparent_colors() {
+        for color_template in [
+            ColorTemplate::BlackOnTransparant,
+            ColorTemplate::CustomRgba { on: [0, 0, 0, 128], off: [0xFF; 4] },
+        ] {
+            assert!(matches!(
+                render_format(OutputFormat::Jpeg { quality: 90 }, color_template),
+                Err(QrPngError::InvalidData(ErrorPayload::TransparencyUnsupported))
+            ));
+        }
+    }
+
+    #[test]
+    fn encode_binary_payload_smaller_than_base64() {
+        let data = [0xA5; 300];
+        let options = QROptions { max_version: Some(DensityVersion::new(40)), ..Default::default() };
+
+        let png = generate_qr_image_binary(&data, Some(options.clone())).unwrap();
+        let img = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+        let qr = build_qr_binary(&data, &options).unwrap();
+        let side = (qr.size() as u32 + 2 * 4) * 8;
+        assert_eq!((side, side), (img.width(), img.height()));
+
+        // The data as base64, mixed case so it also needs byte mode
+        let text = "paWl".repeat(100);
+        assert_eq!(400, text.len());
+        let text_qr = build_qr(&text, &options).unwrap();
+        assert!(qr.version().value() < text_qr.version().value());
+    }
+
+    #[test]
+    fn encode_prebuilt_segments() {
+        let segments = [Segment::make_alphanumeric("HELLO "), Segment::make_numeric("0123456789")];
+        let options = QROptions { mask: Some(3), ..Default::default() };
+        let png = generate_qr_image_segments(&segments, Some(options.clone())).unwrap();
+        assert_eq!(ImageFormat::Png, image::guess_format(&png).unwrap());
+
+        let qr = build_qr_segments(&segments, &options).unwrap();
+        assert_eq!(1, qr.version().value());
+        assert_eq!(
+            ErrorPayload::PayloadTooLarge { size: 9, max: 8 },
+            match build_qr_segments(&segments, &QROptions { max_payload: Some(8), ..Default::default() }) {
+                Err(QrPngError::InvalidData(payload)) => payload,
+                other => panic!("Expected invalid data but got {:?}", other.map(|qr| qr.size())),
+            }
+        );
+    }
+
+    fn dark_modules(data: &str) -> usize {
+        let qr = QrCode::encode_segments_advanced(&Segment::make_segments(data), CodeEcc::Medium, Version::new(1), Version::new(10), None, true).unwrap();
+        qr.iter_modules().filter(|&(_, _, dark)| dark).count()
+    }
+
+    #[test]
+    fn render_svg_path_per_dark_module() {
+        let svg = generate_qr_svg("HELLO WORLD", Some(QROptions { scale: Some(5), ..Default::default() })).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let root = doc.root_element();
+        assert_eq!("svg", root.tag_name().name());
+        assert_eq!(Some("145"), root.attribute("width"));
+        assert_eq!(Some("0 0 29 29"), root.attribute("viewBox"));
+
+        let rects: Vec<_> = root.children().filter(|n| n.has_tag_name("rect")).collect();
+        assert_eq!(1, rects.len());
+        assert_eq!(Some("#FFFFFF"), rects[0].attribute("fill"));
+
+        let path = root.children().find(|n| n.has_tag_name("path")).unwrap();
+        assert_eq!(Some("#000000"), path.attribute("fill"));
+        let d = path.attribute("d").unwrap();
+        assert_eq!(dark_modules("HELLO WORLD"), d.matches('M').count());
+        // The first dark module is the corner of the finder pattern just inside the border
+        assert!(d.starts_with("M4,4h1v1h-1z"));
+
+        let qr = build_qr("HELLO WORLD", &QROptions::default()).unwrap();
+        assert_eq!(qr.to_svg_with_width(4, SvgColors::default(), 145), svg);
+    }
+
+    #[test]
+    fn stretch_svg_to_target_size() {
+        let svg = generate_qr_svg("HELLO WORLD", Some(QROptions { target_size: Some(200), ..Default::default() })).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        assert_eq!(Some("200"), doc.root_element().attribute("width"));
+        assert_eq!(Some("0 0 29 29"), doc.root_element().attribute("viewBox"));
+        assert!(matches!(
+            generate_qr_svg("HELLO WORLD", Some(QROptions { target_size: Some(28), ..Default::default() })),
+            Err(QrPngError::InvalidData(ErrorPayload::InvalidDimensions { size: 29, max: 28 }))
+        ));
+    }
+
+    #[test]
+    fn render_svg_opacity_for_transparent_colors() {
+        let svg = generate_qr_svg("HELLO WORLD", Some(QROptions {
+            color_template: Some(ColorTemplate::WhiteOnTransparant),
+            ..Default::default()
+        })).unwrap();
+        let doc = roxmltree::Document::parse(&svg).unwrap();
+        let rect = doc.root_element().children().find(|n| n.has_tag_name("rect")).unwrap();
+        assert_eq!(Some("#00000000"), rect.attribute("fill"));
+        let path = doc.root_element().children().find(|n| n.has_tag_name("path")).unwrap();
+        assert_eq!(Some("#FFFFFF"), path.attribute("fill"));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn render_pdf_rect_per_dark_module() {
+        let pdf = String::from_utf8(generate_qr_pdf("HELLO WORLD", Some(QROptions { scale: Some(2), ..Default::default() })).unwrap()).unwrap();
+        assert!(pdf.starts_with("%PDF-1.4\n"));
+        assert!(pdf.ends_with("%%EOF\n"));
+        assert!(pdf.contains("/MediaBox [0 0 58 58]"));
+        // Background plus one rectangle per dark module
+        assert_eq!(dark_modules("HELLO WORLD") + 1, pdf.matches(" re").count());
+
+        let xref: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[xref..].starts_with("xref\n0 5\n"));
+    }
+}
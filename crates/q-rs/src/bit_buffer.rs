@@ -17,4 +17,101 @@ impl BitBuffer {
         self.0
             .extend((0..i32::from(len)).rev().map(|i| get_bit(val, i))); // Append bit by bit
     }
+
+    /// Appends all 8 bits of each of the given bytes to this buffer, most significant bit first.
+    pub fn append_bytes(&mut self, data: &[u8]) {
+        self.0.reserve(data.len() * 8);
+        for &b in data {
+            self.append_bits(u32::from(b), 8);
+        }
+    }
+
+    /// Returns the number of bits in this buffer.
+    pub fn len_bits(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns the bits of this buffer packed into bytes in big endian, the final partial byte
+    /// (if any) padded with 0s.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![0u8; self.0.len().div_ceil(8)];
+        for (i, &bit) in self.0.iter().enumerate() {
+            result[i >> 3] |= u8::from(bit) << (7 - (i & 7));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::segment::Segment;
+    use crate::version::Version;
+
+    fn bits(text: &str) -> Vec<bool> {
+        text.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn pack_empty_buffer() {
+        let bb = BitBuffer(Vec::new());
+        assert_eq!(0, bb.len_bits());
+        assert_eq!(Vec::<u8>::new(), bb.to_bytes());
+    }
+
+    #[test]
+    fn pad_final_partial_byte_with_zeros() {
+        assert_eq!(vec![0b1010_0000], BitBuffer(bits("101")).to_bytes());
+        assert_eq!(vec![0xFF, 0x80], BitBuffer(bits("111111111")).to_bytes());
+        assert_eq!(
+            vec![0x0F, 0xF0],
+            BitBuffer(bits("0000111111110")).to_bytes()
+        );
+    }
+
+    #[test]
+    fn append_bytes_after_unaligned_bits() {
+        let mut bb = BitBuffer(Vec::new());
+        bb.append_bits(0b101, 3);
+        bb.append_bytes(&[0xC3, 0x00, 0xFF]);
+        assert_eq!(27, bb.len_bits());
+        assert_eq!(
+            vec![0b1011_1000, 0b0110_0000, 0b0001_1111, 0b1110_0000],
+            bb.to_bytes()
+        );
+    }
+
+    #[test]
+    fn pack_segments_like_bit_by_bit() {
+        let corpus = [
+            "",
+            "0",
+            "HELLO WORLD",
+            "314159265358979323846264338327950288419716939937510",
+            "Hello, world! 123",
+            "こんにちは",
+            "https://example.com/path?query=1&other=TWO",
+        ];
+        for text in corpus {
+            let mut bb = BitBuffer(Vec::new());
+            for seg in Segment::make_segments(text) {
+                bb.append_bits(seg.mode().mode_bits(), 4);
+                bb.append_bits(
+                    u32::try_from(seg.num_chars()).unwrap(),
+                    seg.mode().num_char_count_bits(Version::new(10)),
+                );
+                bb.0.extend_from_slice(seg.data());
+            }
+            let mut expected = vec![0u8; bb.len_bits().div_ceil(8)];
+            for (i, &bit) in bb.0.iter().enumerate() {
+                if bit {
+                    expected[i / 8] |= 0x80 >> (i % 8);
+                }
+            }
+            assert_eq!(expected, bb.to_bytes(), "{:?}", text);
+        }
+
+        let bytes = Segment::make_bytes(b"\x00\x7F\x80\xFF");
+        assert_eq!(bits("00000000011111111000000011111111"), *bytes.data());
+    }
 }
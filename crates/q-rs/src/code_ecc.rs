@@ -1,3 +1,7 @@
+use std::str::FromStr;
+
+use crate::error::QrError;
+
 /// The error correction level in a QR Code symbol.
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub enum CodeEcc {
@@ -23,6 +27,19 @@ impl CodeEcc {
         }
     }
 
+    /// Returns the level with the given ordinal (0 for `Low` to 3 for `High`), the inverse
+    /// of `ordinal()`.
+    pub fn try_from_ordinal(ordinal: u8) -> Result<Self, QrError> {
+        use CodeEcc::*;
+        match ordinal {
+            0 => Ok(Low),
+            1 => Ok(Medium),
+            2 => Ok(Quartile),
+            3 => Ok(High),
+            _ => Err(QrError::InvalidEcc(ordinal)),
+        }
+    }
+
     // Returns an unsigned 2-bit integer (in the range 0 to 3).
     pub fn format_bits(self) -> u8 {
         use CodeEcc::*;
@@ -34,3 +51,104 @@ impl CodeEcc {
         }
     }
 }
+
+impl TryFrom<u8> for CodeEcc {
+    type Error = QrError;
+
+    fn try_from(ordinal: u8) -> Result<Self, QrError> {
+        CodeEcc::try_from_ordinal(ordinal)
+    }
+}
+
+impl FromStr for CodeEcc {
+    type Err = QrError;
+
+    /// Parses "low", "medium", "quartile" or "high", or their initials, in any case.
+    fn from_str(name: &str) -> Result<Self, QrError> {
+        use CodeEcc::*;
+        match name.to_ascii_lowercase().as_str() {
+            "l" | "low" => Ok(Low),
+            "m" | "medium" => Ok(Medium),
+            "q" | "quartile" => Ok(Quartile),
+            "h" | "high" => Ok(High),
+            _ => Err(QrError::InvalidEccName(name.to_owned())),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for CodeEcc {
+    /// As its lowercase name, i.e. "quartile"
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use CodeEcc::*;
+        serializer.serialize_str(match self {
+            Low => "low",
+            Medium => "medium",
+            Quartile => "quartile",
+            High => "high",
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for CodeEcc {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<str> as serde::Deserialize>::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    const LEVELS: [CodeEcc; 4] = [
+        CodeEcc::Low,
+        CodeEcc::Medium,
+        CodeEcc::Quartile,
+        CodeEcc::High,
+    ];
+
+    #[test]
+    fn round_trip_ordinals() {
+        for ecl in LEVELS {
+            let ordinal = u8::try_from(ecl.ordinal()).unwrap();
+            assert_eq!(ecl, CodeEcc::try_from_ordinal(ordinal).unwrap());
+            assert_eq!(ecl, CodeEcc::try_from(ordinal).unwrap());
+        }
+        for ordinal in [4, 7, 255] {
+            assert!(
+                matches!(CodeEcc::try_from(ordinal), Err(QrError::InvalidEcc(o)) if o == ordinal)
+            );
+        }
+    }
+
+    #[test]
+    fn parse_names_and_initials_in_any_case() {
+        let aliases = [
+            ["low", "LOW", "l", "L"],
+            ["medium", "Medium", "m", "M"],
+            ["quartile", "QuArTiLe", "q", "Q"],
+            ["high", "HIGH", "h", "H"],
+        ];
+        for (ecl, names) in LEVELS.into_iter().zip(aliases) {
+            for name in names {
+                assert_eq!(ecl, name.parse::<CodeEcc>().unwrap(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn reject_unknown_names() {
+        for name in ["", "lo", "highest", " low", "x", "3"] {
+            match name.parse::<CodeEcc>() {
+                Err(QrError::InvalidEccName(n)) => assert_eq!(name, n),
+                other => panic!("{:?} parsed as {:?}", name, other),
+            }
+        }
+        assert_eq!(
+            "Invalid error correction level \"lo\"",
+            "lo".parse::<CodeEcc>().unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use crate::code_ecc::CodeEcc;
+use crate::mask::Mask;
+use crate::version::Version;
+
+/// What the encoder chose for a QR Code, returned by `QrCode::encode_segments_with_report()`.
+///
+/// The slack (`data_bits_available - data_bits_used`) shows how much more data would fit
+/// before the next version is needed, which helps to tune the minversion and maxversion bounds.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EncodeReport {
+    /// The smallest version in the given range that fits the data.
+    pub version: Version,
+    /// The final error correction level, higher than the requested one iff `ecl_boosted`.
+    pub ecl: CodeEcc,
+    /// The mask applied to the symbol.
+    pub mask: Mask,
+    /// The number of bits taken by the segments, not counting the terminator and padding.
+    pub data_bits_used: usize,
+    /// The number of data bits in this version at the final error correction level.
+    pub data_bits_available: usize,
+    /// Whether boostecl raised the error correction level above the requested one.
+    pub ecl_boosted: bool,
+}
@@ -1,3 +1,5 @@
+use crate::version::Version;
+
 /// The error type when the supplied data does not fit any QR Code version.
 ///
 /// Ways to handle this exception include:
@@ -14,6 +16,14 @@
 pub enum QrError {
     SegmentTooLong,
     DataOverCapacity(usize, usize),
+    NonNumericCharacter(char),
+    NonAlphanumericCharacter(char),
+    NonKanjiCharacter(char),
+    InvalidVersionRange(Version, Version),
+    InvalidEci(u32),
+    InvalidEcc(u8),
+    InvalidEccName(String),
+    InvalidFraction(f32),
 }
 
 impl std::error::Error for QrError {}
@@ -27,6 +37,33 @@ impl std::fmt::Display for QrError {
                 "Data length = {} bits, Max capacity = {} bits",
                 datalen, maxcapacity
             ),
+            Self::NonNumericCharacter(c) => {
+                write!(f, "Character {:?} cannot be encoded in numeric mode", c)
+            }
+            Self::NonAlphanumericCharacter(c) => {
+                write!(
+                    f,
+                    "Character {:?} cannot be encoded in alphanumeric mode",
+                    c
+                )
+            }
+            Self::NonKanjiCharacter(c) => {
+                write!(f, "Character {:?} cannot be encoded in kanji mode", c)
+            }
+            Self::InvalidVersionRange(min, max) => write!(
+                f,
+                "Min version {} is greater than max version {}",
+                min.value(),
+                max.value()
+            ),
+            Self::InvalidEci(assignval) => write!(f, "Invalid ECI assignment value {}", assignval),
+            Self::InvalidEcc(ordinal) => write!(f, "Invalid error correction level {}", ordinal),
+            Self::InvalidEccName(ref name) => {
+                write!(f, "Invalid error correction level {:?}", name)
+            }
+            Self::InvalidFraction(fraction) => {
+                write!(f, "Fraction {} is outside [0, 1]", fraction)
+            }
         }
     }
 }
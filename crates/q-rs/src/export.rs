@@ -0,0 +1,166 @@
+use std::fmt::Write;
+
+use crate::qr_code::QrCode;
+
+// Accepts only what can be pasted into C source as an identifier.
+fn is_c_identifier(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    matches!(bytes.next(), Some(b) if b == b'_' || b.is_ascii_alphabetic())
+        && bytes.all(|b| b == b'_' || b.is_ascii_alphanumeric())
+}
+
+impl QrCode {
+    /// Returns a binary (P4) PBM image of this QR Code, one pixel per module, with the given
+    /// number of border modules.
+    ///
+    /// Dark modules are 1 bits. Each row is padded with 0 bits to a whole number of bytes,
+    /// as the format requires.
+    ///
+    /// Panics if the border is negative or too large.
+    pub fn to_pbm(&self, border: i32) -> Vec<u8> {
+        let dimension: i32 = self.bordered_size(border);
+        let mut result: Vec<u8> = format!("P4\n{0} {0}\n", dimension).into_bytes();
+        for y in 0..dimension {
+            let mut row = vec![0u8; (dimension as usize).div_ceil(8)];
+            for x in 0..dimension {
+                let i = x as usize;
+                row[i >> 3] |= u8::from(self.get_module_bordered(x, y, border)) << (7 - (i & 7));
+            }
+            result.extend_from_slice(&row);
+        }
+        result
+    }
+
+    /// Returns a binary (P5) PGM image of this QR Code, one pixel per module, with the given
+    /// number of border modules and the given gray levels (0 is black, 255 is white).
+    ///
+    /// Panics if the border is negative or too large.
+    pub fn to_pgm(&self, border: i32, dark: u8, light: u8) -> Vec<u8> {
+        let dimension: i32 = self.bordered_size(border);
+        let mut result: Vec<u8> = format!("P5\n{0} {0}\n255\n", dimension).into_bytes();
+        for y in 0..dimension {
+            for x in 0..dimension {
+                let isdark = self.get_module_bordered(x, y, border);
+                result.push(if isdark { dark } else { light });
+            }
+        }
+        result
+    }
+
+    /// Returns an XBM image of this QR Code as C source, one pixel per module and without a
+    /// border, defining `<name>_width`, `<name>_height` and `<name>_bits`.
+    ///
+    /// Dark modules are 1 bits, the least significant bit of each byte is the leftmost pixel
+    /// and each row is padded to a whole number of bytes.
+    ///
+    /// Panics if the name is not a C identifier.
+    pub fn to_xbm(&self, name: &str) -> String {
+        assert!(is_c_identifier(name), "Invalid name");
+        let mut bytes: Vec<u8> = Vec::new();
+        for y in 0..self.size() {
+            let row: Vec<bool> = (0..self.size()).map(|x| self.get_module(x, y)).collect();
+            for chunk in row.chunks(8) {
+                let byte = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u8, |byte, (i, &isdark)| byte | (u8::from(isdark) << i));
+                bytes.push(byte);
+            }
+        }
+
+        let mut result = String::new();
+        let _ = writeln!(result, "#define {}_width {}", name, self.size());
+        let _ = writeln!(result, "#define {}_height {}", name, self.size());
+        let _ = writeln!(result, "static unsigned char {}_bits[] = {{", name);
+        for line in bytes.chunks(12) {
+            let hex: Vec<String> = line.iter().map(|b| format!("0x{:02x}", b)).collect();
+            let _ = writeln!(result, "   {},", hex.join(", "));
+        }
+        result += "};\n";
+        result
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::code_ecc::CodeEcc;
+
+    fn hello_world() -> QrCode {
+        QrCode::encode_text("HELLO WORLD", CodeEcc::Medium).unwrap()
+    }
+
+    // Splits a P4/P5 image into its magic, width, height and raster.
+    fn parse_header(image: &[u8], fields: usize) -> (Vec<&str>, &[u8]) {
+        let mut rest = image;
+        let mut header = Vec::new();
+        while header.len() < fields {
+            let end = rest.iter().position(|b| b.is_ascii_whitespace()).unwrap();
+            header.push(std::str::from_utf8(&rest[..end]).unwrap());
+            rest = &rest[end + 1..];
+        }
+        (header, rest)
+    }
+
+    #[test]
+    fn write_pbm_with_padded_rows() {
+        let qr = hello_world();
+        let border = 2;
+        let pbm = qr.to_pbm(border);
+        let (header, raster) = parse_header(&pbm, 3);
+        let dimension = (qr.size() + border * 2) as usize;
+        assert_eq!(vec!["P4", "25", "25"], header);
+        assert_eq!(25, dimension);
+
+        let stride = dimension.div_ceil(8);
+        assert_eq!(stride * dimension, raster.len());
+        for (y, row) in raster.chunks(stride).enumerate() {
+            assert_eq!(0, row[stride - 1] & 0x7F, "row {} padding", y);
+            for x in 0..dimension {
+                let bit = row[x / 8] >> (7 - x % 8) & 1 != 0;
+                assert_eq!(qr.get_module(x as i32 - border, y as i32 - border), bit);
+            }
+        }
+    }
+
+    #[test]
+    fn write_pgm_with_given_levels() {
+        let qr = hello_world();
+        let pgm = qr.to_pgm(1, 16, 240);
+        let (header, raster) = parse_header(&pgm, 4);
+        assert_eq!(vec!["P5", "23", "23", "255"], header);
+        assert_eq!(23 * 23, raster.len());
+        assert_eq!(240, raster[0]);
+        let (x, y) = (3, 3); // Center of the top left finder pattern
+        assert!(qr.get_module(x, y));
+        assert_eq!(16, raster[((y + 1) * 23 + x + 1) as usize]);
+    }
+
+    #[test]
+    fn write_xbm_least_significant_bit_first() {
+        let qr = hello_world();
+        let xbm = qr.to_xbm("hello_qr");
+        assert!(xbm.starts_with("#define hello_qr_width 21\n#define hello_qr_height 21\n"));
+        assert!(xbm.contains("static unsigned char hello_qr_bits[] = {\n"));
+        assert!(xbm.ends_with(",\n};\n"));
+
+        let bytes: Vec<u8> = xbm
+            .split("0x")
+            .skip(1)
+            .map(|hex| u8::from_str_radix(&hex[..2], 16).unwrap())
+            .collect();
+        assert_eq!(21 * 3, bytes.len());
+        // The top row starts with the 7 dark modules of a finder pattern
+        assert_eq!(0x7F, bytes[0] & 0x7F);
+        for (x, y) in [(0, 0), (8, 0), (10, 5), (20, 20)] {
+            let bit = bytes[(y * 3 + x / 8) as usize] >> (x % 8) & 1 != 0;
+            assert_eq!(qr.get_module(x, y), bit);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid name")]
+    fn reject_names_which_are_not_identifiers() {
+        hello_world().to_xbm("1st code");
+    }
+}
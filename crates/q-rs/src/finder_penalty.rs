@@ -1,10 +1,41 @@
-/// Helper struct for get_penalty_score() ----*/
+use crate::{PENALTY_N1, PENALTY_N3};
+
+/// Helper struct for penalty_score() ----*/
 pub struct FinderPenalty {
     pub qr_size: i32,
     pub run_history: [i32; 7],
 }
 
 impl FinderPenalty {
+    /// Returns the penalty of one line (row or column) of modules in a QR Code of the given
+    /// size: runs of 5 or more modules of the same color (rule N1) and finder-like
+    /// 1:1:3:1:1 patterns with 4 light modules on a side (rule N3), the light border
+    /// included. A pattern with 4 light modules on both sides counts twice.
+    pub fn score_line(modules: impl Iterator<Item = bool>, size: i32) -> i32 {
+        let mut result: i32 = 0;
+        let mut runcolor = false;
+        let mut run: i32 = 0;
+        let mut runhistory = FinderPenalty::new(size);
+        for module in modules {
+            if module == runcolor {
+                run += 1;
+                if run == 5 {
+                    result += PENALTY_N1;
+                } else if run > 5 {
+                    result += 1;
+                }
+            } else {
+                runhistory.add_history(run);
+                if !runcolor {
+                    result += runhistory.count_patterns() * PENALTY_N3;
+                }
+                runcolor = module;
+                run = 1;
+            }
+        }
+        result + runhistory.terminate_and_count(runcolor, run) * PENALTY_N3
+    }
+
     pub fn new(size: i32) -> Self {
         Self {
             qr_size: size,
@@ -46,3 +77,51 @@ impl FinderPenalty {
         self.count_patterns()
     }
 }
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn line(modules: &str) -> (impl Iterator<Item = bool> + '_, i32) {
+        (modules.bytes().map(|b| b == b'#'), modules.len() as i32)
+    }
+
+    fn score(modules: &str) -> i32 {
+        let (modules, size) = line(modules);
+        FinderPenalty::score_line(modules, size)
+    }
+
+    #[test]
+    fn count_finder_like_pattern_with_light_side() {
+        assert_eq!(PENALTY_N3, score("#.#.###.#....#"));
+        assert_eq!(PENALTY_N3, score("#....#.###.#.#"));
+        assert_eq!(2 * PENALTY_N3, score("#.#.###.#....#.###.#.#"));
+    }
+
+    #[test]
+    fn count_pattern_twice_between_light_sides() {
+        // The light border counts as light on both sides
+        assert_eq!(2 * PENALTY_N3, score("#.###.#"));
+        // Scaled by 2, where the run of 6 is also penalized by rule N1
+        assert_eq!(
+            2 * PENALTY_N3 + PENALTY_N1 + 1,
+            score("....##..######..##....")
+        );
+    }
+
+    #[test]
+    fn ignore_patterns_without_light_side() {
+        assert_eq!(0, score("#.#.###.#.#.#"));
+        assert_eq!(0, score("#.#.###.#...#"));
+        assert_eq!(0, score("#.#.##.#...."));
+    }
+
+    #[test]
+    fn score_runs_of_five_or_more() {
+        assert_eq!(PENALTY_N1, score("#.#####.#"));
+        assert_eq!(PENALTY_N1 + 2, score("#.#######.#"));
+        assert_eq!(2 * PENALTY_N1, score("#####.#.#####"));
+        // All light: one run of 21, the border doesn't count towards it
+        assert_eq!(PENALTY_N1 + 16, score(&".".repeat(21)));
+    }
+}
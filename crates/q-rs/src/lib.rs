@@ -13,7 +13,7 @@
 //!
 //! - Significantly shorter code but more documentation comments compared to competing libraries
 //! - Supports encoding all 40 versions (sizes) and all 4 error correction levels, as per the QR Code Model 2 standard
-//! - Output format: Raw modules/pixels of the QR symbol
+//! - Output format: Raw modules/pixels of the QR symbol, a standalone SVG document, PBM/PGM/XBM bitmaps, or terminal text
 //! - Detects finder-like penalty patterns more accurately than other implementations
 //! - Encodes numeric and special-alphanumeric text in less space than general text
 //! - Open-source code under the permissive MIT License
@@ -30,31 +30,33 @@
 //! # Examples
 //!
 //! ```
-//! use qr::Mask;
-//! use qr::Code;
-//! use qr::CodeEcc;
-//! use qr::Segment;
-//! use qr::Version;
+//! use q_rs::CodeEcc;
+//! use q_rs::Mask;
+//! use q_rs::QrCode;
+//! use q_rs::Segment;
+//! use q_rs::Version;
 //! ```
 //!
 //! Simple operation:
 //!
 //! ```
-//! let qr = Code::encode_text("Hello, world!",
+//! # use q_rs::{CodeEcc, QrCode};
+//! let qr = QrCode::encode_text("Hello, world!",
 //!     CodeEcc::Medium).unwrap();
-//! let svg = to_svg_string(&qr, 4);  // See qrcodegen-demo
+//! let svg = qr.to_svg(4);
 //! ```
 //!
 //! Manual operation:
 //!
 //! ```
+//! # use q_rs::{CodeEcc, Mask, QrCode, Segment, Version};
 //! let text: &str = "3141592653589793238462643383";
 //! let segs = Segment::make_segments(text);
-//! let qr = Code::encode_segments_advanced(&segs, CodeEcc::High,
+//! let qr = QrCode::encode_segments_advanced(&segs, CodeEcc::High,
 //!     Version::new(5), Version::new(5), Some(Mask::new(2)), false).unwrap();
 //! for y in 0 .. qr.size() {
 //!     for x in 0 .. qr.size() {
-//!         (... paint qr.get_module(x, y) ...)
+//!         let dark = qr.get_module(x, y);  // Paint the module
 //!     }
 //! }
 //! ```
@@ -68,27 +70,33 @@ pub static ALPHANUMERIC_CHARSET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%
 
 mod bit_buffer;
 mod code_ecc;
+mod encode_report;
 mod error;
+mod export;
 mod finder_penalty;
 mod mask;
+mod module_grid;
 mod qr_code;
+mod render;
 mod segment;
 mod segment_mode;
 mod version;
 
 pub use bit_buffer::*;
 pub use code_ecc::*;
+pub use encode_report::*;
 pub use error::*;
 pub use finder_penalty::*;
 pub use mask::*;
 pub use qr_code::*;
+pub use render::*;
 pub use segment::*;
 pub use segment_mode::*;
 pub use version::*;
 
 /*---- Constants and tables ----*/
 
-// For use in get_penalty_score(), when evaluating which mask is best.
+// For use in penalty_score(), when evaluating which mask is best.
 pub const PENALTY_N1: i32 = 3;
 pub const PENALTY_N2: i32 = 3;
 pub const PENALTY_N3: i32 = 40;
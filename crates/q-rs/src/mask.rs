@@ -16,3 +16,49 @@ impl Mask {
         self.0
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Mask {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Mask {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mask = <u8 as serde::Deserialize>::deserialize(deserializer)?;
+        if mask <= 7 {
+            Ok(Self(mask))
+        } else {
+            Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(u64::from(mask)),
+                &"a mask number between 0 and 7",
+            ))
+        }
+    }
+}
+
+/// How the encoder picks the mask of a QR Code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum MaskStrategy {
+    /// Tries all 8 masks and keeps the one with the lowest full penalty score (which may be slow).
+    #[default]
+    Auto,
+    /// Tries all 8 masks but scores each one only by runs in every other row and column
+    /// (rule N1) and the balance of dark modules (rule N4). About 3 times faster than `Auto`;
+    /// the chosen mask is always legal, just not necessarily the one `Auto` would choose.
+    Fast,
+    /// Uses the given mask.
+    Fixed(Mask),
+}
+
+impl From<Option<Mask>> for MaskStrategy {
+    /// `None` chooses automatically, as the `Option<Mask>` parameters of the encoders do.
+    fn from(mask: Option<Mask>) -> Self {
+        match mask {
+            Some(mask) => MaskStrategy::Fixed(mask),
+            None => MaskStrategy::Auto,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+/// Storage for a square grid of modules (`false` for light, `true` for dark), indexed in
+/// row-major order. The `compact-modules` feature stores 8 modules per byte instead of one.
+///
+/// The method names steer clear of `Vec` and slice methods, since the default grid is a
+/// plain `Vec<bool>` and those would take precedence.
+pub(crate) trait ModuleGrid: Clone + PartialEq + Eq {
+    /// A grid of the given number of modules, all light
+    fn all_light(nummodules: usize) -> Self;
+
+    #[cfg(any(test, feature = "serde"))]
+    fn num_modules(&self) -> usize;
+
+    fn is_dark(&self, index: usize) -> bool;
+
+    fn set_dark(&mut self, index: usize, isdark: bool);
+
+    /// Flips the module between light and dark
+    fn toggle(&mut self, index: usize);
+
+    fn count_dark(&self) -> usize;
+
+    /// The bytes allocated for the modules
+    #[cfg(test)]
+    fn heap_size(&self) -> usize;
+
+    #[cfg(any(test, feature = "serde"))]
+    fn from_modules(modules: impl ExactSizeIterator<Item = bool>) -> Self {
+        let mut grid = Self::all_light(modules.len());
+        for (i, isdark) in modules.enumerate() {
+            grid.set_dark(i, isdark);
+        }
+        grid
+    }
+
+    #[cfg(any(test, feature = "serde"))]
+    fn iter_modules(&self) -> impl Iterator<Item = bool> + '_ {
+        (0..self.num_modules()).map(|i| self.is_dark(i))
+    }
+}
+
+/// The grid used by `QrCode`, kept as the `Vec<bool>` its public fields have always been
+#[cfg(not(feature = "compact-modules"))]
+pub(crate) type Modules = Vec<bool>;
+
+/// The grid used by `QrCode`
+#[cfg(feature = "compact-modules")]
+pub(crate) type Modules = PackedGrid;
+
+/// One `bool` per module
+impl ModuleGrid for Vec<bool> {
+    fn all_light(nummodules: usize) -> Self {
+        vec![false; nummodules]
+    }
+
+    #[cfg(any(test, feature = "serde"))]
+    fn num_modules(&self) -> usize {
+        self.len()
+    }
+
+    fn is_dark(&self, index: usize) -> bool {
+        self[index]
+    }
+
+    fn set_dark(&mut self, index: usize, isdark: bool) {
+        self[index] = isdark;
+    }
+
+    fn toggle(&mut self, index: usize) {
+        self[index] = !self[index];
+    }
+
+    fn count_dark(&self) -> usize {
+        self.iter().filter(|&&isdark| isdark).count()
+    }
+
+    #[cfg(test)]
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+/// One bit per module, the least significant bit of each byte first
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(not(feature = "compact-modules"), allow(dead_code))]
+pub(crate) struct PackedGrid {
+    nummodules: usize,
+    /// Bits past nummodules are always 0
+    bits: Vec<u8>,
+}
+
+impl ModuleGrid for PackedGrid {
+    fn all_light(nummodules: usize) -> Self {
+        Self {
+            nummodules,
+            bits: vec![0; nummodules.div_ceil(8)],
+        }
+    }
+
+    #[cfg(any(test, feature = "serde"))]
+    fn num_modules(&self) -> usize {
+        self.nummodules
+    }
+
+    fn is_dark(&self, index: usize) -> bool {
+        debug_assert!(index < self.nummodules);
+        (self.bits[index >> 3] >> (index & 7)) & 1 != 0
+    }
+
+    fn set_dark(&mut self, index: usize, isdark: bool) {
+        debug_assert!(index < self.nummodules);
+        let mask: u8 = 1 << (index & 7);
+        if isdark {
+            self.bits[index >> 3] |= mask;
+        } else {
+            self.bits[index >> 3] &= !mask;
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        debug_assert!(index < self.nummodules);
+        self.bits[index >> 3] ^= 1 << (index & 7);
+    }
+
+    fn count_dark(&self) -> usize {
+        self.bits.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    #[cfg(test)]
+    fn heap_size(&self) -> usize {
+        self.bits.capacity()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    // Applies the same pseudo-random sets and toggles to a grid of each kind
+    fn scramble<G: ModuleGrid>(nummodules: usize) -> G {
+        let mut grid = G::all_light(nummodules);
+        let mut seed: u32 = 99;
+        for _ in 0..nummodules * 3 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let index = (seed >> 8) as usize % nummodules;
+            if seed & 0x10000 == 0 {
+                grid.toggle(index);
+            } else {
+                grid.set_dark(index, seed & 0x20000 != 0);
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn behave_the_same_on_either_grid() {
+        for nummodules in [1, 8, 21 * 21, 177 * 177] {
+            let heap: Vec<bool> = scramble(nummodules);
+            let packed: PackedGrid = scramble(nummodules);
+            assert_eq!(nummodules, packed.num_modules());
+            assert!(heap.iter_modules().eq(packed.iter_modules()));
+            assert_eq!(heap.count_dark(), packed.count_dark());
+            assert_eq!(packed, PackedGrid::from_modules(heap.iter().copied()));
+        }
+    }
+
+    #[test]
+    fn pack_version_40_into_an_eighth() {
+        let nummodules = 177 * 177;
+        assert_eq!(31_329, Vec::<bool>::all_light(nummodules).heap_size());
+        assert_eq!(3_917, PackedGrid::all_light(nummodules).heap_size());
+    }
+}
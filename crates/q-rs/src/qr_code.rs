@@ -1,15 +1,41 @@
 use crate::bit_buffer::{get_bit, BitBuffer};
 use crate::code_ecc::CodeEcc;
+use crate::encode_report::EncodeReport;
 use crate::error::QrError;
 use crate::finder_penalty::FinderPenalty;
-use crate::mask::Mask;
+use crate::mask::{Mask, MaskStrategy};
+use crate::module_grid::{ModuleGrid, Modules};
 use crate::segment::Segment;
+use crate::segment_mode::SegmentMode;
 use crate::version::Version;
 use crate::{
-    ECC_CODEWORDS_PER_BLOCK, NUM_ERROR_CORRECTION_BLOCKS, PENALTY_N1, PENALTY_N2, PENALTY_N3,
-    PENALTY_N4,
+    ECC_CODEWORDS_PER_BLOCK, NUM_ERROR_CORRECTION_BLOCKS, PENALTY_N1, PENALTY_N2, PENALTY_N4,
 };
 
+// Powers of the generator 0x02 of GF(2^8/0x11D) (GF_EXP[i] = 2^i, repeating after 255 so that
+// the sum of two logarithms can index it directly) and their inverse (GF_LOG[2^i] = i,
+// GF_LOG[0] is unused), computed at compile time.
+static GF_EXP: [u8; 510] = gf_tables().0;
+static GF_LOG: [u8; 256] = gf_tables().1;
+
+const fn gf_tables() -> ([u8; 510], [u8; 256]) {
+    let mut exp = [0u8; 510];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        exp[i + 255] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11D;
+        }
+        i += 1;
+    }
+    (exp, log)
+}
+
 /// A QR Code symbol, which is a type of two-dimension barcode.
 ///
 /// Invented by Denso Wave and described in the ISO/IEC 18004 standard.
@@ -29,7 +55,15 @@ use crate::{
 ///   appropriate version number, and call the `QrCode::encode_codewords()` constructor.
 ///
 /// (Note that all ways require supplying the desired error correction level.)
+///
+/// With the `serde` feature a QR Code (de)serializes as its version, error correction level,
+/// mask and modules, and deserializing checks that the modules fill a symbol of that version.
 #[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "QrCodeRepr", try_from = "QrCodeRepr")
+)]
 pub struct QrCode {
     // Scalar parameters:
 
@@ -53,10 +87,20 @@ pub struct QrCode {
 
     // The modules of this QR Code (false = light, true = dark).
     // Immutable after constructor finishes. Accessed through get_module().
+    #[cfg(not(feature = "compact-modules"))]
     pub modules: Vec<bool>,
 
     // Indicates function modules that are not subjected to masking. Discarded when constructor finishes.
+    #[cfg(not(feature = "compact-modules"))]
     pub isfunction: Vec<bool>,
+
+    // The same bit-packed with the compact-modules feature, which is a breaking change: the
+    // fields are no longer public, use get_module(), rows() or iter_modules() instead.
+    #[cfg(feature = "compact-modules")]
+    pub(crate) modules: Modules,
+
+    #[cfg(feature = "compact-modules")]
+    pub(crate) isfunction: Modules,
 }
 
 impl QrCode {
@@ -76,6 +120,62 @@ impl QrCode {
         QrCode::encode_segments(&segs, ecl)
     }
 
+    /// Returns a QR Code representing the given Unicode text string at the given error correction level,
+    /// switching segment modes within the text wherever that saves space.
+    ///
+    /// Unlike `encode_text()`, which encodes the whole string in a single mode, a payload such as a URL
+    /// with a long numeric tail gets its tail encoded in numeric mode. The smallest possible QR Code version
+    /// is automatically chosen for the output, together with the optimal segmentation at that version.
+    /// The ECC level of the result may be higher than the ecl argument if it can be done without increasing the version.
+    ///
+    /// Returns a wrapped `QrCode` if successful, or `Err` if the
+    /// data is too long to fit in any version at the given ECC level.
+    pub fn encode_text_optimized(text: &str, ecl: CodeEcc) -> Result<Self, QrError> {
+        let mut segs: Vec<Segment> = Vec::new();
+        for ver in Version::MIN.value()..=Version::MAX.value() {
+            let version = Version::new(ver);
+            // The character count field widths, and so the optimal segments, only change at these versions
+            if matches!(ver, 1 | 10 | 27) {
+                segs = Segment::make_segments_optimized(text, version);
+            }
+            let datacapacitybits: usize = QrCode::get_num_data_codewords(version, ecl) * 8;
+            if Segment::get_total_bits(&segs, version).is_some_and(|n| n <= datacapacitybits) {
+                return QrCode::encode_segments_advanced(&segs, ecl, version, version, None, true);
+            }
+        }
+        QrCode::encode_segments(&segs, ecl) // Reports why the data doesn't fit
+    }
+
+    /// Returns a QR Code representing the given Unicode text string at the given error correction level,
+    /// preceded by an ECI designator with the given assignment value, i.e. `Segment::ECI_UTF8`.
+    ///
+    /// The designator tells decoders how to interpret the bytes of the text, which otherwise
+    /// default to ISO-8859-1. The text is segmented as by `encode_text()`.
+    ///
+    /// Returns a wrapped `QrCode` if successful, or `Err` if the assignment value is out of range
+    /// or the data is too long to fit in any version at the given ECC level.
+    pub fn encode_text_eci(text: &str, ecl: CodeEcc, eci: u32) -> Result<Self, QrError> {
+        let mut segs: Vec<Segment> = vec![Segment::try_make_eci(eci)?];
+        segs.extend(Segment::make_segments(text));
+        QrCode::encode_segments(&segs, ecl)
+    }
+
+    /// Returns a QR Code representing the given Unicode text string at the given error correction level,
+    /// marked as UTF-8 with an ECI designator iff the text has non-ASCII characters.
+    ///
+    /// ASCII text is encoded exactly as by `encode_text()`, so as not to spend the designator's
+    /// 12 bits where every decoder agrees on the meaning anyway.
+    ///
+    /// Returns a wrapped `QrCode` if successful, or `Err` if the
+    /// data is too long to fit in any version at the given ECC level.
+    pub fn encode_text_utf8(text: &str, ecl: CodeEcc) -> Result<Self, QrError> {
+        if text.is_ascii() {
+            QrCode::encode_text(text, ecl)
+        } else {
+            QrCode::encode_text_eci(text, ecl, Segment::ECI_UTF8)
+        }
+    }
+
     /// Returns a QR Code representing the given binary data at the given error correction level.
     ///
     /// This function always encodes using the binary segment mode, not any text mode. The maximum number of
@@ -89,6 +189,45 @@ impl QrCode {
         QrCode::encode_segments(&segs, ecl)
     }
 
+    /// Returns a QR Code representing the given Unicode text string with the given encoding parameters.
+    ///
+    /// The text is segmented as by `encode_text()`, and the parameters have the same meaning as in
+    /// `encode_segments_advanced()`. Pinning minversion and maxversion to the same version gives a
+    /// symbol of a fixed physical size.
+    ///
+    /// Returns a wrapped `QrCode` if successful, or `Err` if the data is too long to fit in any
+    /// version in the given range at the given ECC level.
+    pub fn encode_text_advanced(
+        text: &str,
+        ecl: CodeEcc,
+        minversion: Version,
+        maxversion: Version,
+        mask: Option<Mask>,
+        boostecl: bool,
+    ) -> Result<Self, QrError> {
+        let segs: Vec<Segment> = Segment::make_segments(text);
+        QrCode::encode_segments_advanced(&segs, ecl, minversion, maxversion, mask, boostecl)
+    }
+
+    /// Returns a QR Code representing the given binary data with the given encoding parameters.
+    ///
+    /// The data is encoded in byte mode as by `encode_binary()`, and the parameters have the same
+    /// meaning as in `encode_segments_advanced()`.
+    ///
+    /// Returns a wrapped `QrCode` if successful, or `Err` if the data is too long to fit in any
+    /// version in the given range at the given ECC level.
+    pub fn encode_binary_advanced(
+        data: &[u8],
+        ecl: CodeEcc,
+        minversion: Version,
+        maxversion: Version,
+        mask: Option<Mask>,
+        boostecl: bool,
+    ) -> Result<Self, QrError> {
+        let segs: [Segment; 1] = [Segment::make_bytes(data)];
+        QrCode::encode_segments_advanced(&segs, ecl, minversion, maxversion, mask, boostecl)
+    }
+
     /*---- Static factory functions (mid level) ----*/
 
     /// Returns a QR Code representing the given segments at the given error correction level.
@@ -119,16 +258,60 @@ impl QrCode {
     /// This is a mid-level API; the high-level API is `encode_text()` and `encode_binary()`.
     ///
     /// Returns a wrapped `QrCode` if successful, or `Err` if the data is too
-    /// long to fit in any version in the given range at the given ECC level.
+    /// long to fit in any version in the given range at the given ECC level,
+    /// or if minversion is greater than maxversion.
     pub fn encode_segments_advanced(
         segs: &[Segment],
-        mut ecl: CodeEcc,
+        ecl: CodeEcc,
         minversion: Version,
         maxversion: Version,
         mask: Option<Mask>,
         boostecl: bool,
     ) -> Result<Self, QrError> {
-        assert!(minversion <= maxversion, "Invalid value");
+        QrCode::encode_segments_with_mask_strategy(
+            segs,
+            ecl,
+            minversion,
+            maxversion,
+            mask.into(),
+            boostecl,
+        )
+    }
+
+    /// Returns a QR Code representing the given segments with the given encoding parameters,
+    /// choosing its mask by the given strategy.
+    ///
+    /// This is `encode_segments_advanced()` with `MaskStrategy::Fast` available for callers who
+    /// generate many codes and can trade a slightly worse mask for speed.
+    pub fn encode_segments_with_mask_strategy(
+        segs: &[Segment],
+        ecl: CodeEcc,
+        minversion: Version,
+        maxversion: Version,
+        mask: MaskStrategy,
+        boostecl: bool,
+    ) -> Result<Self, QrError> {
+        QrCode::encode_segments_with_report(segs, ecl, minversion, maxversion, mask, boostecl)
+            .map(|(qr, _)| qr)
+    }
+
+    /// Returns a QR Code representing the given segments with the given encoding parameters,
+    /// together with a report of the version, ECC level and mask that were chosen and of the
+    /// remaining data capacity.
+    ///
+    /// Takes the same parameters as `encode_segments_with_mask_strategy()`.
+    pub fn encode_segments_with_report(
+        segs: &[Segment],
+        mut ecl: CodeEcc,
+        minversion: Version,
+        maxversion: Version,
+        mask: MaskStrategy,
+        boostecl: bool,
+    ) -> Result<(Self, EncodeReport), QrError> {
+        let requestedecl: CodeEcc = ecl;
+        if minversion > maxversion {
+            return Err(QrError::InvalidVersionRange(minversion, maxversion));
+        }
 
         // Find the minimal version number to use
         let mut version: Version = minversion;
@@ -186,13 +369,19 @@ impl QrCode {
         }
 
         // Pack bits into bytes in big endian
-        let mut datacodewords = vec![0u8; bb.0.len() / 8];
-        for (i, &bit) in bb.0.iter().enumerate() {
-            datacodewords[i >> 3] |= u8::from(bit) << (7 - (i & 7));
-        }
+        let datacodewords: Vec<u8> = bb.to_bytes();
 
         // Create the QR Code object
-        Ok(QrCode::encode_codewords(version, ecl, &datacodewords, mask))
+        let qr = QrCode::encode_codewords_with_mask_strategy(version, ecl, &datacodewords, mask);
+        let report = EncodeReport {
+            version,
+            ecl,
+            mask: qr.mask,
+            data_bits_used: datausedbits,
+            data_bits_available: datacapacitybits,
+            ecl_boosted: ecl != requestedecl,
+        };
+        Ok((qr, report))
     }
 
     /*---- Constructor (low level) ----*/
@@ -206,8 +395,49 @@ impl QrCode {
         ver: Version,
         ecl: CodeEcc,
         datacodewords: &[u8],
-        mut msk: Option<Mask>,
+        msk: Option<Mask>,
+    ) -> Self {
+        QrCode::encode_codewords_with_mask_strategy(ver, ecl, datacodewords, msk.into())
+    }
+
+    /// Creates a new QR Code with the given version number, error correction level and data
+    /// codeword bytes, choosing its mask by the given strategy.
+    ///
+    /// This is a low-level API that most users should not use directly.
+    pub fn encode_codewords_with_mask_strategy(
+        ver: Version,
+        ecl: CodeEcc,
+        datacodewords: &[u8],
+        strategy: MaskStrategy,
     ) -> Self {
+        let mut result = QrCode::new_unmasked(ver, ecl, datacodewords);
+
+        // Do masking
+        let msk: Mask = match strategy {
+            MaskStrategy::Auto => result.choose_mask(QrCode::penalty_score),
+            MaskStrategy::Fast => result.choose_mask(QrCode::fast_penalty_score),
+            MaskStrategy::Fixed(msk) => msk,
+        };
+        result.mask = msk;
+        result.apply_mask(msk); // Apply the final choice of mask
+        result.draw_format_bits(msk); // Overwrite old format bits
+
+        result.isfunction = Modules::all_light(0);
+        result
+    }
+
+    /// Returns the penalty score of each of the 8 masks (indexed by mask number) for a QR Code
+    /// with the given version number, error correction level and data codeword bytes, without
+    /// choosing one. Automatic masking chooses the first mask with the lowest score.
+    ///
+    /// This is meant for diagnosing the mask choice of `encode_codewords()`.
+    pub fn evaluate_masks(ver: Version, ecl: CodeEcc, datacodewords: &[u8]) -> [i32; 8] {
+        QrCode::new_unmasked(ver, ecl, datacodewords).mask_penalties(QrCode::penalty_score)
+    }
+
+    // Returns a QR Code with the function patterns, format bits excepted, and the codewords for
+    // the given data drawn, but no mask applied yet.
+    fn new_unmasked(ver: Version, ecl: CodeEcc, datacodewords: &[u8]) -> Self {
         // Initialize fields
         let size = usize::from(ver.value()) * 4 + 17;
         let mut result = Self {
@@ -215,38 +445,14 @@ impl QrCode {
             size: size as i32,
             mask: Mask::new(0), // Dummy value
             errorcorrectionlevel: ecl,
-            modules: vec![false; size * size], // Initially all light
-            isfunction: vec![false; size * size],
+            modules: Modules::all_light(size * size), // Initially all light
+            isfunction: Modules::all_light(size * size),
         };
 
         // Compute ECC, draw modules
         result.draw_function_patterns();
         let allcodewords: Vec<u8> = result.add_ecc_and_interleave(datacodewords);
         result.draw_codewords(&allcodewords);
-
-        // Do masking
-        if msk.is_none() {
-            // Automatically choose best mask
-            let mut minpenalty = std::i32::MAX;
-            for i in 0u8..8 {
-                let i = Mask::new(i);
-                result.apply_mask(i);
-                result.draw_format_bits(i);
-                let penalty: i32 = result.get_penalty_score();
-                if penalty < minpenalty {
-                    msk = Some(i);
-                    minpenalty = penalty;
-                }
-                result.apply_mask(i); // Undoes the mask due to XOR
-            }
-        }
-        let msk: Mask = msk.unwrap();
-        result.mask = msk;
-        result.apply_mask(msk); // Apply the final choice of mask
-        result.draw_format_bits(msk); // Overwrite old format bits
-
-        result.isfunction.clear();
-        result.isfunction.shrink_to_fit();
         result
     }
 
@@ -262,6 +468,11 @@ impl QrCode {
         self.size
     }
 
+    /// Returns this QR Code's size as a `usize`, for indexing and slicing.
+    pub fn size_usize(&self) -> usize {
+        self.size as usize
+    }
+
     /// Returns this QR Code's error correction level.
     pub fn error_correction_level(&self) -> CodeEcc {
         self.errorcorrectionlevel
@@ -272,6 +483,31 @@ impl QrCode {
         self.mask
     }
 
+    /// Returns the maximum number of characters a single segment of the given mode can hold
+    /// in a QR Code of the given version number and error correction level.
+    ///
+    /// Characters are counted the way the segment's character count field counts them:
+    /// digits in numeric mode, characters in alphanumeric and kanji mode, bytes in byte mode.
+    /// The segment's 4-bit mode indicator and its version-dependent character count field
+    /// are accounted for. ECI segments hold no characters, so the result for them is 0.
+    pub fn max_payload_len(version: Version, ecl: CodeEcc, mode: SegmentMode) -> usize {
+        let ccbits: u8 = mode.num_char_count_bits(version);
+        let databits: usize = (QrCode::get_num_data_codewords(version, ecl) * 8)
+            .saturating_sub(4 + usize::from(ccbits));
+        let numchars: usize = match mode {
+            // 10 bits per 3 digits, then 7 bits for 2 or 4 bits for 1
+            SegmentMode::Numeric => {
+                databits / 10 * 3 + [0, 0, 0, 0, 1, 1, 1, 2, 2, 2][databits % 10]
+            }
+            // 11 bits per 2 characters, then 6 bits for 1
+            SegmentMode::Alphanumeric => databits / 11 * 2 + usize::from(databits % 11 >= 6),
+            SegmentMode::Byte => databits / 8,
+            SegmentMode::Kanji => databits / 13,
+            SegmentMode::Eci => 0,
+        };
+        numchars.min((1usize << ccbits).saturating_sub(1))
+    }
+
     /// Returns the color of the module (pixel) at the given coordinates,
     /// which is `false` for light or `true` for dark.
     ///
@@ -281,14 +517,125 @@ impl QrCode {
         (0..self.size).contains(&x) && (0..self.size).contains(&y) && self.module(x, y)
     }
 
+    /// Returns the width and height of this QR Code surrounded by the given number of
+    /// border (quiet zone) modules on each side.
+    ///
+    /// Panics if the border is negative or so large that the result overflows `i32`.
+    pub fn bordered_size(&self, border: i32) -> i32 {
+        assert!(border >= 0, "Border must be non-negative");
+        border
+            .checked_mul(2)
+            .and_then(|b| self.size.checked_add(b))
+            .expect("Border too large")
+    }
+
+    /// Returns the color of the module at the given coordinates of this QR Code surrounded
+    /// by the given number of border modules, where (x=0, y=0) is the top left corner of the
+    /// border. Border modules, and coordinates outside of the border, are `false` (light).
+    ///
+    /// Panics if the border is negative.
+    pub fn get_module_bordered(&self, x: i32, y: i32, border: i32) -> bool {
+        assert!(border >= 0, "Border must be non-negative");
+        self.get_module(x.saturating_sub(border), y.saturating_sub(border))
+    }
+
+    /// Returns the fraction of this QR Code's codewords that error correction can restore
+    /// when they are damaged, i.e. hidden under a logo. Each block restores up to half of
+    /// its error correction codewords, so the result is somewhat above the nominal
+    /// 7%, 15%, 25% or 30% of the error correction level.
+    pub fn damage_tolerance(&self) -> f32 {
+        let tolerated: usize = self.num_tolerated_codewords();
+        tolerated as f32 / (QrCode::get_num_raw_data_modules(self.version) / 8) as f32
+    }
+
+    /// Returns the modules inside the rectangle with the given top left corner, width and
+    /// height as (x, y, isdark), in row-major order. Parts of the rectangle outside this
+    /// QR Code are ignored, so the result is empty if it lies wholly outside.
+    pub fn modules_in_rect(&self, x: i32, y: i32, w: i32, h: i32) -> Vec<(i32, i32, bool)> {
+        let xs = x.max(0)..x.saturating_add(w).min(self.size);
+        let ys = y.max(0)..y.saturating_add(h).min(self.size);
+        ys.flat_map(|y| xs.clone().map(move |x| (x, y, self.module(x, y))))
+            .collect()
+    }
+
+    /// Returns this QR Code if its error correction can restore the given fraction of its
+    /// codewords, which a logo overlaid in the center may cover, so it can be chained onto
+    /// any factory function:
+    ///
+    /// ```
+    /// # use q_rs::{CodeEcc, QrCode};
+    /// let qr = QrCode::encode_text("https://example.com", CodeEcc::High)
+    ///     .and_then(|qr| qr.with_reserved_center(0.25))
+    ///     .unwrap();
+    /// ```
+    ///
+    /// The layout of the codewords is fixed by the standard, so it can't be moved away
+    /// from the center. Size the logo with `modules_in_rect()` so it covers no more than
+    /// the given fraction of data modules, and keep it clear of the finder patterns.
+    ///
+    /// This checks a QR Code after encoding rather than being an option of
+    /// `encode_segments_advanced()`, so it never raises the version or the error correction
+    /// level to make room. Encode with `CodeEcc::High` when a logo is planned.
+    ///
+    /// Returns `Err(QrError::DataOverCapacity(reserved, tolerated))` if the fraction exceeds
+    /// `damage_tolerance()`, or `Err(QrError::InvalidFraction)` if it's NaN or outside [0, 1].
+    /// Unlike the encoding errors the two numbers aren't data bits: `reserved` is the codewords
+    /// the logo may damage and `tolerated` the codewords error correction can restore, both
+    /// times 8, so its "Data length" and "Max capacity" message reads as bits of damage.
+    pub fn with_reserved_center(self, fraction: f32) -> Result<Self, QrError> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(QrError::InvalidFraction(fraction));
+        }
+        let rawcodewords: usize = QrCode::get_num_raw_data_modules(self.version) / 8;
+        let reserved: usize = (fraction * rawcodewords as f32).ceil() as usize;
+        let tolerated: usize = self.num_tolerated_codewords();
+        if reserved > tolerated {
+            return Err(QrError::DataOverCapacity(reserved * 8, tolerated * 8));
+        }
+        Ok(self)
+    }
+
+    // Returns the number of damaged codewords error correction can restore, half
+    // of the error correction codewords in each block (rounded down).
+    fn num_tolerated_codewords(&self) -> usize {
+        let ver: Version = self.version;
+        let ecl: CodeEcc = self.errorcorrectionlevel;
+        QrCode::table_get(&ECC_CODEWORDS_PER_BLOCK, ver, ecl) / 2
+            * QrCode::table_get(&NUM_ERROR_CORRECTION_BLOCKS, ver, ecl)
+    }
+
+    /// Returns an iterator over the rows of this QR Code from top to bottom, each row
+    /// being a slice of `size` modules from left to right (`false` for light, `true` for dark).
+    #[cfg(not(feature = "compact-modules"))]
+    pub fn rows(&self) -> impl Iterator<Item = &[bool]> {
+        self.modules.chunks_exact(self.size_usize())
+    }
+
+    /// Returns an iterator over the rows of this QR Code from top to bottom, each row
+    /// being an iterator over `size` modules from left to right (`false` for light, `true` for dark).
+    ///
+    /// Breaking: there are no `bool`s to slice with the compact-modules feature, so the rows
+    /// are iterators rather than the `&[bool]` slices of the default build.
+    #[cfg(feature = "compact-modules")]
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = bool> + '_> + '_ {
+        (0..self.size).map(move |y| (0..self.size).map(move |x| self.module(x, y)))
+    }
+
+    /// Returns an iterator over every module of this QR Code in row-major order,
+    /// as (x, y, isdark) with the top left corner at (x=0, y=0).
+    pub fn iter_modules(&self) -> impl Iterator<Item = (u32, u32, bool)> + '_ {
+        (0..self.size)
+            .flat_map(move |y| (0..self.size).map(move |x| (x as u32, y as u32, self.module(x, y))))
+    }
+
     // Returns the color of the module at the given coordinates, which must be in bounds.
     fn module(&self, x: i32, y: i32) -> bool {
-        self.modules[(y * self.size + x) as usize]
+        self.modules.is_dark((y * self.size + x) as usize)
     }
 
-    // Returns a mutable reference to the module's color at the given coordinates, which must be in bounds.
-    fn module_mut(&mut self, x: i32, y: i32) -> &mut bool {
-        &mut self.modules[(y * self.size + x) as usize]
+    // Sets the color of the module at the given coordinates, which must be in bounds.
+    fn set_module(&mut self, x: i32, y: i32, isdark: bool) {
+        self.modules.set_dark((y * self.size + x) as usize, isdark);
     }
 
     /*---- Private helper methods for constructor: Drawing function modules ----*/
@@ -418,8 +765,8 @@ impl QrCode {
     // Sets the color of a module and marks it as a function module.
     // Only used by the constructor. Coordinates must be in bounds.
     fn set_function_module(&mut self, x: i32, y: i32, isdark: bool) {
-        *self.module_mut(x, y) = isdark;
-        self.isfunction[(y * self.size + x) as usize] = true;
+        self.set_module(x, y, isdark);
+        self.isfunction.set_dark((y * self.size + x) as usize, true);
     }
 
     /*---- Private helper methods for constructor: Codewords and masking ----*/
@@ -494,9 +841,13 @@ impl QrCode {
                     let x: i32 = right - j; // Actual x coordinate
                     let upward: bool = (right + 1) & 2 == 0;
                     let y: i32 = if upward { self.size - 1 - vert } else { vert }; // Actual y coordinate
-                    if !self.isfunction[(y * self.size + x) as usize] && i < data.len() * 8 {
-                        *self.module_mut(x, y) =
-                            get_bit(u32::from(data[i >> 3]), 7 - ((i as i32) & 7));
+                    if !self.isfunction.is_dark((y * self.size + x) as usize) && i < data.len() * 8
+                    {
+                        self.set_module(
+                            x,
+                            y,
+                            get_bit(u32::from(data[i >> 3]), 7 - ((i as i32) & 7)),
+                        );
                         i += 1;
                     }
                     // If this QR Code has any remainder bits (0 to 7), they were assigned as
@@ -527,64 +878,54 @@ impl QrCode {
                     7 => ((x + y) % 2 + x * y % 3) % 2 == 0,
                     _ => unreachable!(),
                 };
-                *self.module_mut(x, y) ^= invert & !self.isfunction[(y * self.size + x) as usize];
+                let index = (y * self.size + x) as usize;
+                if invert && !self.isfunction.is_dark(index) {
+                    self.modules.toggle(index);
+                }
+            }
+        }
+    }
+
+    // Tries each mask on this QR Code and returns the first one with the lowest penalty by the
+    // given scoring function. The codewords must be drawn and no mask applied.
+    fn choose_mask(&mut self, score: fn(&Self) -> i32) -> Mask {
+        let penalties: [i32; 8] = self.mask_penalties(score);
+        let mut msk: u8 = 0;
+        for i in 1u8..8 {
+            if penalties[usize::from(i)] < penalties[usize::from(msk)] {
+                msk = i;
             }
         }
+        Mask::new(msk)
     }
 
-    // Calculates and returns the penalty score based on state of this QR Code's current modules.
-    // This is used by the automatic mask choice algorithm to find the mask pattern that yields the lowest score.
-    fn get_penalty_score(&self) -> i32 {
+    // Returns the penalty by the given scoring function of each mask applied to this QR Code,
+    // leaving it unmasked. The codewords must be drawn and no mask applied.
+    fn mask_penalties(&mut self, score: fn(&Self) -> i32) -> [i32; 8] {
+        let mut penalties = [0i32; 8];
+        for (i, penalty) in (0u8..8).zip(penalties.iter_mut()) {
+            let i = Mask::new(i);
+            self.apply_mask(i);
+            self.draw_format_bits(i);
+            *penalty = score(self);
+            self.apply_mask(i); // Undoes the mask due to XOR
+        }
+        penalties
+    }
+
+    /// Returns the penalty score of this QR Code's modules by the rules of the standard: runs of
+    /// the same color (N1), 2*2 blocks (N2), finder-like patterns (N3) and the balance of dark
+    /// and light modules (N4). Automatic masking chooses the mask with the lowest score.
+    pub fn penalty_score(&self) -> i32 {
         let mut result: i32 = 0;
         let size: i32 = self.size;
 
-        // Adjacent modules in row having same color, and finder-like patterns
-        for y in 0..size {
-            let mut runcolor = false;
-            let mut runx: i32 = 0;
-            let mut runhistory = FinderPenalty::new(size);
-            for x in 0..size {
-                if self.module(x, y) == runcolor {
-                    runx += 1;
-                    if runx == 5 {
-                        result += PENALTY_N1;
-                    } else if runx > 5 {
-                        result += 1;
-                    }
-                } else {
-                    runhistory.add_history(runx);
-                    if !runcolor {
-                        result += runhistory.count_patterns() * PENALTY_N3;
-                    }
-                    runcolor = self.module(x, y);
-                    runx = 1;
-                }
-            }
-            result += runhistory.terminate_and_count(runcolor, runx) * PENALTY_N3;
-        }
-        // Adjacent modules in column having same color, and finder-like patterns
-        for x in 0..size {
-            let mut runcolor = false;
-            let mut runy: i32 = 0;
-            let mut runhistory = FinderPenalty::new(size);
-            for y in 0..size {
-                if self.module(x, y) == runcolor {
-                    runy += 1;
-                    if runy == 5 {
-                        result += PENALTY_N1;
-                    } else if runy > 5 {
-                        result += 1;
-                    }
-                } else {
-                    runhistory.add_history(runy);
-                    if !runcolor {
-                        result += runhistory.count_patterns() * PENALTY_N3;
-                    }
-                    runcolor = self.module(x, y);
-                    runy = 1;
-                }
-            }
-            result += runhistory.terminate_and_count(runcolor, runy) * PENALTY_N3;
+        // Adjacent modules in row or column having same color, and finder-like patterns
+        for i in 0..size {
+            let row = (0..size).map(|x| self.module(x, i));
+            let column = (0..size).map(|y| self.module(i, y));
+            result += FinderPenalty::score_line(row, size);
+            result += FinderPenalty::score_line(column, size);
         }
 
         // 2*2 blocks of modules having same color
@@ -601,7 +942,7 @@ impl QrCode {
         }
 
         // Balance of dark and light modules
-        let dark: i32 = self.modules.iter().copied().map(i32::from).sum();
+        let dark: i32 = self.modules.count_dark() as i32;
         let total: i32 = size * size; // Note that size is odd, so dark/total != 1/2
                                       // Compute the smallest integer k >= 0 such that (45-5k)% <= dark/total <= (55+5k)%
         let k: i32 = ((dark * 20 - total * 10).abs() + total - 1) / total - 1;
@@ -611,6 +952,47 @@ impl QrCode {
         result
     }
 
+    // Calculates a cheaper penalty score for MaskStrategy::Fast: rule N1 over every other row
+    // and column, plus rule N4. Finder-like patterns (N3) and 2*2 blocks (N2) are not scored.
+    fn fast_penalty_score(&self) -> i32 {
+        let mut result: i32 = 0;
+        let size: i32 = self.size;
+
+        let mut score_run = |run: i32| {
+            if run == 5 {
+                result += PENALTY_N1;
+            } else if run > 5 {
+                result += 1;
+            }
+        };
+        for i in (0..size).step_by(2) {
+            let (mut rowcolor, mut runx) = (false, 0);
+            let (mut colcolor, mut runy) = (false, 0);
+            for j in 0..size {
+                if self.module(j, i) == rowcolor {
+                    runx += 1;
+                    score_run(runx);
+                } else {
+                    rowcolor = !rowcolor;
+                    runx = 1;
+                }
+                if self.module(i, j) == colcolor {
+                    runy += 1;
+                    score_run(runy);
+                } else {
+                    colcolor = !colcolor;
+                    runy = 1;
+                }
+            }
+        }
+
+        // Balance of dark and light modules, the same as in penalty_score()
+        let dark: i32 = self.modules.count_dark() as i32;
+        let total: i32 = size * size;
+        let k: i32 = ((dark * 20 - total * 10).abs() + total - 1) / total - 1;
+        result + k * PENALTY_N4
+    }
+
     /*---- Private helper functions ----*/
 
     // Returns an ascending list of positions of alignment patterns for this version number.
@@ -636,10 +1018,10 @@ impl QrCode {
         }
     }
 
-    // Returns the number of data bits that can be stored in a QR Code of the given version number, after
-    // all function modules are excluded. This includes remainder bits, so it might not be a multiple of 8.
-    // The result is in the range [208, 29648]. This could be implemented as a 40-entry lookup table.
-    fn get_num_raw_data_modules(ver: Version) -> usize {
+    /// Returns the number of data bits that can be stored in a QR Code of the given version number, after
+    /// all function modules are excluded. This includes remainder bits, so it might not be a multiple of 8.
+    /// The result is in the range [208, 29648]. This could be implemented as a 40-entry lookup table.
+    pub fn get_num_raw_data_modules(ver: Version) -> usize {
         let ver = usize::from(ver.value());
         let mut result: usize = (16 * ver + 128) * ver + 64;
         if ver >= 2 {
@@ -653,10 +1035,10 @@ impl QrCode {
         result
     }
 
-    // Returns the number of 8-bit data (i.e. not error correction) codewords contained in any
-    // QR Code of the given version number and error correction level, with remainder bits discarded.
-    // This stateless pure function could be implemented as a (40*4)-cell lookup table.
-    fn get_num_data_codewords(ver: Version, ecl: CodeEcc) -> usize {
+    /// Returns the number of 8-bit data (i.e. not error correction) codewords contained in any
+    /// QR Code of the given version number and error correction level, with remainder bits discarded.
+    /// This stateless pure function could be implemented as a (40*4)-cell lookup table.
+    pub fn get_num_data_codewords(ver: Version, ecl: CodeEcc) -> usize {
         QrCode::get_num_raw_data_modules(ver) / 8
             - QrCode::table_get(&ECC_CODEWORDS_PER_BLOCK, ver, ecl)
                 * QrCode::table_get(&NUM_ERROR_CORRECTION_BLOCKS, ver, ecl)
@@ -696,22 +1078,324 @@ impl QrCode {
 
     // Returns the Reed-Solomon error correction codeword for the given data and divisor polynomials.
     fn reed_solomon_compute_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
-        let mut result = vec![0u8; divisor.len()];
+        let n: usize = divisor.len();
+        let mut result = vec![0u8; n];
+        // result[start] holds the highest coefficient, so shifting is a step of the cursor
+        let mut start: usize = 0;
         for b in data {
             // Polynomial division
-            let factor: u8 = b ^ result.remove(0);
-            result.push(0);
-            for (x, &y) in result.iter_mut().zip(divisor.iter()) {
-                *x ^= QrCode::reed_solomon_multiply(y, factor);
+            let factor: u8 = b ^ result[start];
+            result[start] = 0;
+            start = if start + 1 == n { 0 } else { start + 1 };
+            if factor == 0 {
+                continue;
             }
+            let (tail, head) = result.split_at_mut(start);
+            let (divhead, divtail) = divisor.split_at(head.len());
+            QrCode::reed_solomon_multiply_add(head, divhead, factor);
+            QrCode::reed_solomon_multiply_add(tail, divtail, factor);
         }
+        result.rotate_left(start);
         result
     }
 
+    // XORs each coefficient of the given polynomial times the given factor into the result.
+    fn reed_solomon_multiply_add(result: &mut [u8], poly: &[u8], factor: u8) {
+        let logfactor: usize = usize::from(GF_LOG[usize::from(factor)]);
+        for (x, &y) in result.iter_mut().zip(poly) {
+            if y != 0 {
+                *x ^= GF_EXP[usize::from(GF_LOG[usize::from(y)]) + logfactor];
+            }
+        }
+    }
+
     // Returns the product of the two given field elements modulo GF(2^8/0x11D).
-    // All inputs are valid. This could be implemented as a 256*256 lookup table.
+    // All inputs are valid.
     fn reed_solomon_multiply(x: u8, y: u8) -> u8 {
-        // Russian peasant multiplication
+        if x == 0 || y == 0 {
+            return 0;
+        }
+        let exponent: usize =
+            usize::from(GF_LOG[usize::from(x)]) + usize::from(GF_LOG[usize::from(y)]);
+        GF_EXP[exponent % 255]
+    }
+}
+
+// The serialized form of a QrCode, without its size (implied by the version) and without the
+// function modules (discarded by the constructor).
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "QrCode")]
+struct QrCodeRepr {
+    version: Version,
+    ecl: CodeEcc,
+    mask: Mask,
+    modules: Vec<bool>,
+}
+
+#[cfg(feature = "serde")]
+impl From<QrCode> for QrCodeRepr {
+    fn from(qr: QrCode) -> Self {
+        Self {
+            version: qr.version,
+            ecl: qr.errorcorrectionlevel,
+            mask: qr.mask,
+            modules: qr.modules.iter_modules().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<QrCodeRepr> for QrCode {
+    type Error = String;
+
+    fn try_from(repr: QrCodeRepr) -> Result<Self, String> {
+        let size = usize::from(repr.version.value()) * 4 + 17;
+        if repr.modules.len() != size * size {
+            return Err(format!(
+                "expected {} modules for version {}, got {}",
+                size * size,
+                repr.version.value(),
+                repr.modules.len()
+            ));
+        }
+        Ok(Self {
+            version: repr.version,
+            size: size as i32,
+            errorcorrectionlevel: repr.ecl,
+            mask: repr.mask,
+            modules: Modules::from_modules(repr.modules.into_iter()),
+            isfunction: Modules::all_light(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    fn hello_world() -> QrCode {
+        QrCode::encode_text("HELLO WORLD", CodeEcc::Medium).unwrap()
+    }
+
+    #[test]
+    fn match_capacity_table() {
+        use SegmentMode::*;
+        // ISO/IEC 18004 table 7, (version, ecl, numeric, alphanumeric, byte, kanji)
+        let table = [
+            (1, CodeEcc::Low, 41, 25, 17, 10),
+            (1, CodeEcc::Medium, 34, 20, 14, 8),
+            (1, CodeEcc::High, 17, 10, 7, 4),
+            (9, CodeEcc::Quartile, 312, 189, 130, 80),
+            (10, CodeEcc::Low, 652, 395, 271, 167),
+            (27, CodeEcc::Medium, 2701, 1637, 1125, 692),
+            (40, CodeEcc::Low, 7089, 4296, 2953, 1817),
+            (40, CodeEcc::High, 3057, 1852, 1273, 784),
+        ];
+        for (ver, ecl, numeric, alphanumeric, byte, kanji) in table {
+            let version = Version::new(ver);
+            let capacity = |mode| QrCode::max_payload_len(version, ecl, mode);
+            assert_eq!(
+                (numeric, alphanumeric, byte, kanji, 0),
+                (
+                    capacity(Numeric),
+                    capacity(Alphanumeric),
+                    capacity(Byte),
+                    capacity(Kanji),
+                    capacity(Eci)
+                ),
+                "version {} {:?}",
+                ver,
+                ecl
+            );
+        }
+    }
+
+    #[test]
+    fn encode_exactly_max_payload_len() {
+        let (version, ecl) = (Version::new(5), CodeEcc::Quartile);
+        let max = QrCode::max_payload_len(version, ecl, SegmentMode::Numeric);
+        let encode = |len: usize| {
+            let segs = [Segment::make_numeric(&"7".repeat(len))];
+            QrCode::encode_segments_advanced(&segs, ecl, version, version, None, false)
+        };
+        assert!(encode(max).is_ok());
+        assert!(encode(max + 1).is_err());
+    }
+
+    #[test]
+    fn tolerate_reserved_center_with_high_ecc_only() {
+        let high = QrCode::encode_text("https://example.com", CodeEcc::High).unwrap();
+        assert!(high.damage_tolerance() > 0.3);
+        assert!(high.with_reserved_center(0.25).is_ok());
+
+        let segs = Segment::make_segments("https://example.com");
+        let low = QrCode::encode_segments_advanced(
+            &segs,
+            CodeEcc::Low,
+            Version::MIN,
+            Version::MAX,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(low.damage_tolerance() < 0.15);
+        assert!(matches!(
+            low.clone().with_reserved_center(0.25),
+            Err(QrError::DataOverCapacity(reserved, tolerated)) if reserved > tolerated
+        ));
+        assert!(low.clone().with_reserved_center(0.0).is_ok());
+        for fraction in [f32::NAN, -0.1, 1.5] {
+            assert!(matches!(
+                low.clone().with_reserved_center(fraction),
+                Err(QrError::InvalidFraction(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn clip_rect_to_symbol() {
+        let qr = hello_world();
+        let size = qr.size();
+        let center = qr.modules_in_rect(8, 8, 5, 4);
+        assert_eq!(20, center.len());
+        assert_eq!((8, 8), (center[0].0, center[0].1));
+        assert_eq!((12, 11), (center[19].0, center[19].1));
+        assert!(center
+            .iter()
+            .all(|&(x, y, isdark)| qr.get_module(x, y) == isdark));
+
+        assert_eq!(9, qr.modules_in_rect(-2, -2, 5, 5).len());
+        assert_eq!(4, qr.modules_in_rect(size - 2, size - 2, 10, 10).len());
+        assert!(qr.modules_in_rect(size, 0, 3, 3).is_empty());
+        assert!(qr.modules_in_rect(0, 0, 0, 3).is_empty());
+    }
+
+    #[test]
+    fn slice_rows_matching_get_module() {
+        let qr = hello_world();
+        assert_eq!(qr.size_usize(), qr.rows().count());
+        for (y, row) in qr.rows().enumerate() {
+            #[cfg(not(feature = "compact-modules"))]
+            let row = row.to_vec();
+            #[cfg(feature = "compact-modules")]
+            let row: Vec<bool> = row.collect();
+            assert_eq!(qr.size_usize(), row.len());
+            for (x, &isdark) in row.iter().enumerate() {
+                assert_eq!(qr.get_module(x as i32, y as i32), isdark);
+            }
+        }
+    }
+
+    #[test]
+    fn iterate_modules_in_row_major_order() {
+        let qr = hello_world();
+        let modules: Vec<(u32, u32, bool)> = qr.iter_modules().collect();
+        assert_eq!(qr.size_usize() * qr.size_usize(), modules.len());
+
+        let size = qr.size() as u32;
+        for (i, &(x, y, isdark)) in modules.iter().enumerate() {
+            assert_eq!((i as u32 % size, i as u32 / size), (x, y));
+            assert_eq!(qr.get_module(x as i32, y as i32), isdark);
+        }
+    }
+
+    fn encode_with(text: &str, mask: MaskStrategy) -> QrCode {
+        let segs = Segment::make_segments(text);
+        QrCode::encode_segments_with_mask_strategy(
+            &segs,
+            CodeEcc::Medium,
+            Version::MIN,
+            Version::MAX,
+            mask,
+            true,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn choose_lowest_full_penalty_with_auto_mask() {
+        let auto = encode_with("HELLO WORLD", MaskStrategy::Auto);
+        assert_eq!(hello_world().mask(), auto.mask());
+        for i in 0u8..8 {
+            let fixed = encode_with("HELLO WORLD", MaskStrategy::Fixed(Mask::new(i)));
+            assert!(auto.penalty_score() <= fixed.penalty_score(), "mask {}", i);
+        }
+    }
+
+    #[test]
+    fn build_legal_code_with_fast_mask() {
+        let text = "The quick brown fox jumps over the lazy dog, 0123456789 times over";
+        let fast = encode_with(text, MaskStrategy::Fast);
+        assert!(fast.mask().value() <= 7);
+        let fixed = encode_with(text, MaskStrategy::Fixed(fast.mask()));
+        assert_eq!(fixed.modules, fast.modules);
+        assert_eq!(fixed.version(), fast.version());
+    }
+
+    // Run with `cargo test -p q-rs --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn time_fast_mask_against_auto() {
+        use std::time::Instant;
+
+        let text = "0123456789".repeat(120);
+        let time = |mask: MaskStrategy| {
+            let start = Instant::now();
+            for _ in 0..20 {
+                std::hint::black_box(encode_with(&text, mask));
+            }
+            start.elapsed()
+        };
+        let auto = time(MaskStrategy::Auto);
+        let fast = time(MaskStrategy::Fast);
+        let fixed = time(MaskStrategy::Fixed(Mask::new(0)));
+        println!("auto {:?}, fast {:?}, fixed {:?}", auto, fast, fixed);
+        assert!(fast < auto);
+    }
+
+    #[test]
+    fn report_boosted_ecl() {
+        let segs = Segment::make_segments("HELLO");
+        let (qr, report) = QrCode::encode_segments_with_report(
+            &segs,
+            CodeEcc::Low,
+            Version::MIN,
+            Version::MAX,
+            MaskStrategy::Auto,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            EncodeReport {
+                version: Version::new(1),
+                ecl: CodeEcc::High,
+                mask: qr.mask(),
+                data_bits_used: 4 + 9 + 28,
+                data_bits_available: 9 * 8,
+                ecl_boosted: true,
+            },
+            report
+        );
+        assert_eq!(CodeEcc::High, qr.error_correction_level());
+
+        let (_, report) = QrCode::encode_segments_with_report(
+            &segs,
+            CodeEcc::Low,
+            Version::MIN,
+            Version::MAX,
+            MaskStrategy::Auto,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            (CodeEcc::Low, false, 19 * 8),
+            (report.ecl, report.ecl_boosted, report.data_bits_available)
+        );
+    }
+
+    // The multiplication and division this crate used before the lookup tables.
+    fn peasant_multiply(x: u8, y: u8) -> u8 {
         let mut z: u8 = 0;
         for i in (0..8).rev() {
             z = (z << 1) ^ ((z >> 7) * 0x1D);
@@ -719,4 +1403,322 @@ impl QrCode {
         }
         z
     }
+
+    fn shifting_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+        let mut result = vec![0u8; divisor.len()];
+        for b in data {
+            let factor: u8 = b ^ result.remove(0);
+            result.push(0);
+            for (x, &y) in result.iter_mut().zip(divisor.iter()) {
+                *x ^= peasant_multiply(y, factor);
+            }
+        }
+        result
+    }
+
+    // Version 40 High: 81 blocks of 15 or 16 data codewords, each with 30 ECC codewords.
+    fn version_40_blocks() -> Vec<Vec<u8>> {
+        let mut seed: u32 = 0x1234_5678;
+        (0..81)
+            .map(|i| {
+                (0..15 + usize::from(i >= 20))
+                    .map(|_| {
+                        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                        (seed >> 16) as u8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn multiply_like_russian_peasant_for_all_inputs() {
+        for x in 0..=255u8 {
+            for y in 0..=255u8 {
+                assert_eq!(
+                    peasant_multiply(x, y),
+                    QrCode::reed_solomon_multiply(x, y),
+                    "{} * {}",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn compute_remainder_like_shifting_division() {
+        for degree in [1, 7, 10, 30] {
+            let divisor = QrCode::reed_solomon_compute_divisor(degree);
+            for block in version_40_blocks() {
+                assert_eq!(
+                    shifting_remainder(&block, &divisor),
+                    QrCode::reed_solomon_compute_remainder(&block, &divisor)
+                );
+            }
+        }
+    }
+
+    // Run with `cargo test -p q-rs --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn time_lookup_tables_against_peasant_multiply() {
+        use std::time::Instant;
+
+        let divisor = QrCode::reed_solomon_compute_divisor(30);
+        let blocks = version_40_blocks();
+        let time = |remainder: fn(&[u8], &[u8]) -> Vec<u8>| {
+            let start = Instant::now();
+            for _ in 0..200 {
+                for block in &blocks {
+                    std::hint::black_box(remainder(std::hint::black_box(block), &divisor));
+                }
+            }
+            start.elapsed()
+        };
+        let old = time(shifting_remainder);
+        let new = time(QrCode::reed_solomon_compute_remainder);
+        println!(
+            "peasant {:?}, tables {:?}, {:.1}x",
+            old,
+            new,
+            old.as_secs_f64() / new.as_secs_f64()
+        );
+        assert!(new < old);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trip_through_json_and_bincode() {
+        let qr = QrCode::encode_text("https://example.com/kv", CodeEcc::Quartile).unwrap();
+
+        let json = serde_json::to_value(&qr).unwrap();
+        assert_eq!(qr.version().value(), json["version"]);
+        assert_eq!("quartile", json["ecl"]);
+        assert_eq!(qr.mask().value(), json["mask"]);
+        assert!(qr == serde_json::from_value::<QrCode>(json).unwrap());
+
+        let bytes = bincode::serialize(&qr).unwrap();
+        assert!(qr == bincode::deserialize::<QrCode>(&bytes).unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reject_invalid_serialized_codes() {
+        let json = serde_json::to_value(hello_world()).unwrap();
+        for (field, value) in [
+            ("version", serde_json::json!(41)),
+            ("version", serde_json::json!(0)),
+            ("mask", serde_json::json!(8)),
+            ("ecl", serde_json::json!("best")),
+            ("modules", serde_json::json!([true, false])),
+        ] {
+            let mut json = json.clone();
+            json[field] = value;
+            assert!(serde_json::from_value::<QrCode>(json).is_err(), "{}", field);
+        }
+    }
+
+    #[test]
+    fn shift_modules_by_border() {
+        let qr = hello_world();
+        for border in [0, 1, 4] {
+            assert_eq!(qr.size() + 2 * border, qr.bordered_size(border));
+            for y in 0..qr.bordered_size(border) {
+                for x in 0..qr.bordered_size(border) {
+                    let inside = (border..border + qr.size()).contains(&x)
+                        && (border..border + qr.size()).contains(&y);
+                    assert_eq!(
+                        inside && qr.get_module(x - border, y - border),
+                        qr.get_module_bordered(x, y, border)
+                    );
+                }
+            }
+        }
+        assert!(!qr.get_module_bordered(i32::MIN, 0, 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Border must be non-negative")]
+    fn reject_negative_border() {
+        hello_world().get_module_bordered(0, 0, -1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Border too large")]
+    fn reject_overflowing_bordered_size() {
+        hello_world().bordered_size(i32::MAX / 2);
+    }
+
+    #[test]
+    fn choose_argmin_of_evaluated_masks() {
+        let mut seed: u32 = 7;
+        for (ver, ecl) in [
+            (1, CodeEcc::Low),
+            (2, CodeEcc::High),
+            (5, CodeEcc::Medium),
+            (7, CodeEcc::Quartile),
+            (12, CodeEcc::Low),
+        ] {
+            let version = Version::new(ver);
+            let data: Vec<u8> = (0..QrCode::get_num_data_codewords(version, ecl))
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+
+            let penalties = QrCode::evaluate_masks(version, ecl, &data);
+            let min = *penalties.iter().min().unwrap();
+            let argmin = penalties.iter().position(|&p| p == min).unwrap();
+            let qr = QrCode::encode_codewords(version, ecl, &data, None);
+            assert_eq!(argmin, usize::from(qr.mask().value()), "version {}", ver);
+            assert_eq!(min, qr.penalty_score());
+
+            let fixed = QrCode::encode_codewords(version, ecl, &data, Some(Mask::new(3)));
+            assert_eq!(penalties[3], fixed.penalty_score());
+        }
+    }
+
+    #[test]
+    fn report_capacity_of_pinned_version() {
+        let version = Version::new(3);
+        let result = QrCode::encode_binary_advanced(
+            &[0xA5; 100],
+            CodeEcc::Low,
+            version,
+            version,
+            None,
+            false,
+        );
+        // Mode indicator, 8 bit count and 100 bytes, against 55 data codewords
+        assert!(
+            matches!(result, Err(QrError::DataOverCapacity(812, 440))),
+            "{:?}",
+            result.err()
+        );
+
+        let qr = QrCode::encode_binary_advanced(
+            &[0xA5; 50],
+            CodeEcc::Low,
+            version,
+            version,
+            Some(Mask::new(2)),
+            false,
+        )
+        .unwrap();
+        assert_eq!((version, Mask::new(2)), (qr.version(), qr.mask()));
+
+        let text = "HELLO WORLD";
+        let pinned = QrCode::encode_text_advanced(
+            text,
+            CodeEcc::Medium,
+            Version::new(4),
+            Version::new(6),
+            None,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            (Version::new(4), CodeEcc::High),
+            (pinned.version(), pinned.error_correction_level())
+        );
+        // 66 groups of 3 digits and one of 2, against 16 data codewords
+        let result = QrCode::encode_text_advanced(
+            &"9".repeat(200),
+            CodeEcc::High,
+            Version::MIN,
+            Version::new(2),
+            None,
+            false,
+        );
+        assert!(
+            matches!(result, Err(QrError::DataOverCapacity(681, 128))),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn return_error_for_inverted_version_range() {
+        let segs = Segment::make_segments("HELLO");
+        let (min, max) = (Version::new(10), Version::new(9));
+        let result = QrCode::encode_segments_advanced(&segs, CodeEcc::Low, min, max, None, true);
+        assert!(
+            matches!(result, Err(QrError::InvalidVersionRange(a, b)) if (a, b) == (min, max)),
+            "{:?}",
+            result.err()
+        );
+        assert_eq!(
+            "Min version 10 is greater than max version 9",
+            QrCode::encode_text_advanced("", CodeEcc::High, min, max, None, false)
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn mark_only_non_ascii_text_as_utf8() {
+        let ascii = "https://example.com";
+        assert!(
+            QrCode::encode_text_utf8(ascii, CodeEcc::Low).unwrap()
+                == QrCode::encode_text(ascii, CodeEcc::Low).unwrap()
+        );
+
+        let cyrillic = "Привет, мир";
+        let utf8 = QrCode::encode_text_utf8(cyrillic, CodeEcc::Low).unwrap();
+        assert!(
+            utf8 == QrCode::encode_text_eci(cyrillic, CodeEcc::Low, Segment::ECI_UTF8).unwrap()
+        );
+        assert!(utf8 != QrCode::encode_text(cyrillic, CodeEcc::Low).unwrap());
+
+        assert!(matches!(
+            QrCode::encode_text_eci(cyrillic, CodeEcc::Low, 1_000_000),
+            Err(QrError::InvalidEci(1_000_000))
+        ));
+    }
+
+    // FNV-1a over the modules, the same whichever grid stores them
+    fn fingerprint(qr: &QrCode) -> u64 {
+        qr.iter_modules()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, (_, _, isdark)| {
+                (hash ^ u64::from(isdark)).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+
+    // Run with and without the compact-modules feature
+    #[test]
+    fn match_fingerprints_on_either_grid() {
+        let corpus = [
+            ("HELLO WORLD", CodeEcc::Medium),
+            (
+                "314159265358979323846264338327950288419716939937510",
+                CodeEcc::High,
+            ),
+            (
+                "https://example.com/a/longer/path?with=query&and=more",
+                CodeEcc::Low,
+            ),
+            ("Привет, мир", CodeEcc::Quartile),
+        ];
+        let fingerprints: Vec<u64> = corpus
+            .iter()
+            .map(|&(text, ecl)| fingerprint(&QrCode::encode_text(text, ecl).unwrap()))
+            .collect();
+        assert_eq!(
+            vec![
+                0x084d_342a_6984_9001,
+                0xc0c9_443a_0191_b65d,
+                0x0ef6_9200_9001_8c0e,
+                0x2b60_3a5d_c5f9_1b67,
+            ],
+            fingerprints
+        );
+
+        let long = QrCode::encode_binary(&[0x5A; 2900], CodeEcc::Low).unwrap();
+        assert_eq!(Version::MAX, long.version());
+        assert_eq!(0xe204_3602_15d5_6fc2, fingerprint(&long));
+        assert!(long.modules.heap_size() <= 177 * 177);
+    }
 }
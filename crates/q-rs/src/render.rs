@@ -0,0 +1,318 @@
+use std::fmt::Write;
+
+use crate::qr_code::QrCode;
+
+/// The colors of an SVG rendering, as CSS hex colors (`#rgb`, `#rrggbb` or `#rrggbbaa`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SvgColors<'a> {
+    /// Fills the background, including the border.
+    pub light: &'a str,
+    /// Fills the dark modules.
+    pub dark: &'a str,
+}
+
+impl Default for SvgColors<'_> {
+    fn default() -> Self {
+        Self {
+            light: "#FFFFFF",
+            dark: "#000000",
+        }
+    }
+}
+
+// Accepts only what can be written into an attribute unescaped.
+fn is_hex_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(digits) => {
+            matches!(digits.len(), 3 | 6 | 8) && digits.bytes().all(|b| b.is_ascii_hexdigit())
+        }
+        None => false,
+    }
+}
+
+impl QrCode {
+    /// Returns a string of SVG code for an image depicting this QR Code, black on white,
+    /// with the given number of border modules.
+    ///
+    /// The string always uses Unix newlines (\n), regardless of the platform. All dark
+    /// modules are drawn by a single `<path>`, one unit per module.
+    ///
+    /// Panics if the border is so large that the image dimension overflows `i32`.
+    pub fn to_svg(&self, border: u32) -> String {
+        self.to_svg_with_colors(border, SvgColors::default())
+    }
+
+    /// Returns a string of SVG code for an image depicting this QR Code in the given
+    /// colors, with the given number of border modules.
+    ///
+    /// Panics if the border is so large that the image dimension overflows `i32`,
+    /// or if either color is not a hex color.
+    pub fn to_svg_with_colors(&self, border: u32, colors: SvgColors) -> String {
+        self.svg(border, colors, None)
+    }
+
+    /// Returns a string of SVG code as `to_svg_with_colors()` does, which is also rendered
+    /// `width` pixels wide and high rather than filling its container.
+    ///
+    /// Panics in the same cases as `to_svg_with_colors()`.
+    pub fn to_svg_with_width(&self, border: u32, colors: SvgColors, width: u32) -> String {
+        self.svg(border, colors, Some(width))
+    }
+
+    fn svg(&self, border: u32, colors: SvgColors, width: Option<u32>) -> String {
+        assert!(is_hex_color(colors.light), "Invalid light color");
+        assert!(is_hex_color(colors.dark), "Invalid dark color");
+        let border: i32 = i32::try_from(border).expect("Border too large");
+        let dimension: i32 = border
+            .checked_mul(2)
+            .and_then(|b| self.size().checked_add(b))
+            .expect("Border too large");
+        let size = match width {
+            Some(width) => format!(" width=\"{0}\" height=\"{0}\"", width),
+            None => String::new(),
+        };
+
+        let mut result = String::new();
+        result += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+        result += "<!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.1//EN\" \"http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd\">\n";
+        let _ = writeln!(
+            result,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\"{1} viewBox=\"0 0 {0} {0}\" stroke=\"none\">",
+            dimension, size
+        );
+        let _ = writeln!(
+            result,
+            "\t<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>",
+            colors.light
+        );
+        result += "\t<path d=\"";
+        for y in 0..self.size() {
+            for x in 0..self.size() {
+                if self.get_module(x, y) {
+                    if x != 0 || y != 0 {
+                        result += " ";
+                    }
+                    let _ = write!(result, "M{},{}h1v1h-1z", x + border, y + border);
+                }
+            }
+        }
+        let _ = writeln!(result, "\" fill=\"{}\"/>", colors.dark);
+        result += "</svg>\n";
+        result
+    }
+}
+
+impl QrCode {
+    /// Returns a text rendering of this QR Code for terminals, with the given number of
+    /// border modules.
+    ///
+    /// Each line holds two rows of modules, drawn with the half block characters
+    /// '█', '▀', '▄' and ' ' (dark being the block), so the code stays roughly square in
+    /// a terminal font. Lines end with a Unix newline (\n).
+    ///
+    /// Panics if the border is negative or too large.
+    pub fn to_ascii(&self, border: i32) -> String {
+        let dimension: i32 = self.bordered_size(border);
+        let mut result = String::new();
+        for y in (0..dimension).step_by(2) {
+            for x in 0..dimension {
+                let upper = self.get_module_bordered(x, y, border);
+                let lower = self.get_module_bordered(x, y + 1, border);
+                result.push(match (upper, lower) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                });
+            }
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Returns a plain text rendering of this QR Code for terminals without Unicode
+    /// block characters, with the given number of border modules.
+    ///
+    /// Each line holds one row of modules, two characters per module: "##" for dark and
+    /// "  " for light. Lines end with a Unix newline (\n).
+    ///
+    /// Panics if the border is negative or too large.
+    pub fn to_ascii_plain(&self, border: i32) -> String {
+        let dimension: i32 = self.bordered_size(border);
+        let mut result = String::new();
+        for y in 0..dimension {
+            for x in 0..dimension {
+                result += if self.get_module_bordered(x, y, border) {
+                    "##"
+                } else {
+                    "  "
+                };
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::code_ecc::CodeEcc;
+    use crate::module_grid::ModuleGrid;
+
+    // "HELLO WORLD" at Medium ECC, version 1
+    const SNAPSHOT: &str = concat!(
+        "█▀▀▀▀▀█ █▀ ▄  █▀▀▀▀▀█\n",
+        "█ ███ █ █  ▀▀ █ ███ █\n",
+        "█ ▀▀▀ █ ▀ █   █ ▀▀▀ █\n",
+        "▀▀▀▀▀▀▀ █ ▀ ▀ ▀▀▀▀▀▀▀\n",
+        " █▀ ▀ ▀▀▄▄▄▄▀ ▀ █▀▀▀█\n",
+        " ▄█▀▄█▀█ ▀█▄ ▄▀▄▀█▄▄ \n",
+        "▀   ▀ ▀ █▄▀█▀ █▀▀ █ █\n",
+        "█▀▀▀▀▀█ ▀▄▀▄▄ ▄█ █▀  \n",
+        "█ ███ █ ▀▄▀▄ ▄▀█▀▀▀█▀\n",
+        "█ ▀▀▀ █ █ ▄█▄█▀▀ █ ▄█\n",
+        "▀▀▀▀▀▀▀    ▀ ▀▀▀    ▀\n",
+    );
+
+    fn hello_world() -> QrCode {
+        QrCode::encode_text("HELLO WORLD", CodeEcc::Medium).unwrap()
+    }
+
+    fn attribute<'a>(svg: &'a str, name: &str) -> &'a str {
+        let start = svg.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+        let end = start + svg[start..].find('"').unwrap();
+        &svg[start..end]
+    }
+
+    #[test]
+    fn size_view_box_with_border() {
+        let qr = hello_world();
+        for border in [0, 1, 4] {
+            let dimension = qr.size() + border as i32 * 2;
+            assert_eq!(
+                format!("0 0 {0} {0}", dimension),
+                attribute(&qr.to_svg(border), "viewBox")
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip_path_data() {
+        let qr = hello_world();
+        let border = 4;
+        let svg = qr.to_svg(border as u32);
+
+        let mut dark = vec![false; (qr.size() * qr.size()) as usize];
+        for module in attribute(&svg, "d").split(' ') {
+            let corner = module
+                .strip_prefix('M')
+                .and_then(|m| m.strip_suffix("h1v1h-1z"))
+                .unwrap();
+            let (x, y) = corner.split_once(',').unwrap();
+            let (x, y) = (
+                x.parse::<i32>().unwrap() - border,
+                y.parse::<i32>().unwrap() - border,
+            );
+            assert!((0..qr.size()).contains(&x) && (0..qr.size()).contains(&y));
+            dark[(y * qr.size() + x) as usize] = true;
+        }
+
+        for y in 0..qr.size() {
+            for x in 0..qr.size() {
+                assert_eq!(qr.get_module(x, y), dark[(y * qr.size() + x) as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn use_given_colors() {
+        let colors = SvgColors {
+            light: "#fef",
+            dark: "#112233",
+        };
+        let svg = hello_world().to_svg_with_colors(0, colors);
+        assert!(svg.contains("<rect width=\"100%\" height=\"100%\" fill=\"#fef\"/>"));
+        assert!(svg.contains("h1v1h-1z\" fill=\"#112233\"/>"));
+    }
+
+    #[test]
+    fn size_only_when_given_a_width() {
+        let qr = hello_world();
+        let svg = qr.to_svg_with_width(4, SvgColors::default(), 145);
+        assert_eq!("145", attribute(&svg, "width"));
+        assert_eq!("145", attribute(&svg, "height"));
+        assert_eq!("0 0 29 29", attribute(&svg, "viewBox"));
+        assert_eq!(
+            qr.to_svg(4),
+            svg.replace(" width=\"145\" height=\"145\"", "")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid dark color")]
+    fn reject_colors_which_are_not_hex() {
+        let colors = SvgColors {
+            dark: "black\"/><script/>",
+            ..SvgColors::default()
+        };
+        hello_world().to_svg_with_colors(0, colors);
+    }
+
+    #[test]
+    fn snapshot_half_blocks() {
+        assert_eq!(SNAPSHOT, hello_world().to_ascii(0));
+    }
+
+    #[test]
+    fn count_rows_and_columns_with_border() {
+        let qr = hello_world();
+        for border in [0, 1, 4] {
+            let dimension = (qr.size() + border * 2) as usize;
+            let text = qr.to_ascii(border);
+            assert_eq!(dimension.div_ceil(2), text.lines().count());
+            assert!(text.lines().all(|line| line.chars().count() == dimension));
+
+            let plain = qr.to_ascii_plain(border);
+            assert_eq!(dimension, plain.lines().count());
+            assert!(plain.lines().all(|line| line.len() == dimension * 2));
+        }
+        assert!(qr
+            .to_ascii(2)
+            .lines()
+            .next()
+            .unwrap()
+            .chars()
+            .all(|c| c == ' '));
+    }
+
+    #[test]
+    fn change_character_with_flipped_module() {
+        let mut qr = hello_world();
+        let (x, y) = (10, 5);
+        let character = |qr: &QrCode| qr.to_ascii(0).lines().nth(2).unwrap().chars().nth(10);
+        let plain = |qr: &QrCode| qr.to_ascii_plain(0).lines().nth(5).unwrap()[20..22].to_owned();
+        let (before, before_plain) = (character(&qr), plain(&qr));
+
+        let index = (y * qr.size() + x) as usize;
+        qr.modules.toggle(index);
+        let below = if qr.get_module(x, y - 1) {
+            '█'
+        } else {
+            '▄'
+        };
+        let above = if qr.get_module(x, y - 1) { '▀' } else { ' ' };
+        assert_ne!(before, character(&qr));
+        assert_eq!(
+            Some(if qr.get_module(x, y) { below } else { above }),
+            character(&qr)
+        );
+        assert_ne!(before_plain, plain(&qr));
+    }
+
+    #[test]
+    #[should_panic(expected = "Border too large")]
+    fn panic_when_border_overflows() {
+        hello_world().to_svg(i32::MAX as u32 / 2);
+    }
+}
@@ -1,4 +1,7 @@
+use std::fmt;
+
 use crate::bit_buffer::BitBuffer;
+use crate::error::QrError;
 use crate::segment_mode::SegmentMode;
 use crate::version::Version;
 use crate::ALPHANUMERIC_CHARSET;
@@ -16,6 +19,7 @@ use crate::ALPHANUMERIC_CHARSET;
 /// Even in the most favorable conditions, a QR Code can only hold 7089 characters of data.
 /// Any segment longer than this is meaningless for the purpose of generating QR Codes.
 #[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Segment {
     // The mode indicator of this segment. Accessed through mode().
     pub mode: SegmentMode,
@@ -38,10 +42,8 @@ impl Segment {
     ///
     /// Any text string can be converted to UTF-8 bytes and encoded as a byte mode segment.
     pub fn make_bytes(data: &[u8]) -> Self {
-        let mut bb = BitBuffer(Vec::with_capacity(data.len() * 8));
-        for &b in data {
-            bb.append_bits(u32::from(b), 8);
-        }
+        let mut bb = BitBuffer(Vec::new());
+        bb.append_bytes(data);
         Segment::new(SegmentMode::Byte, data.len(), bb.0)
     }
 
@@ -49,14 +51,20 @@ impl Segment {
     ///
     /// Panics if the string contains non-digit characters.
     pub fn make_numeric(text: &str) -> Self {
+        Segment::try_make_numeric(text).expect("String contains non-numeric characters")
+    }
+
+    /// Returns a segment representing the given string of decimal digits encoded in numeric mode.
+    ///
+    /// Returns `Err` with the first non-digit character.
+    pub fn try_make_numeric(text: &str) -> Result<Self, QrError> {
+        if let Some(c) = text.chars().find(|c| !c.is_ascii_digit()) {
+            return Err(QrError::NonNumericCharacter(c));
+        }
         let mut bb = BitBuffer(Vec::with_capacity(text.len() * 3 + (text.len() + 2) / 3));
         let mut accumdata: u32 = 0;
         let mut accumcount: u8 = 0;
         for b in text.bytes() {
-            assert!(
-                (b'0'..=b'9').contains(&b),
-                "String contains non-numeric characters"
-            );
             accumdata = accumdata * 10 + u32::from(b - b'0');
             accumcount += 1;
             if accumcount == 3 {
@@ -69,7 +77,7 @@ impl Segment {
             // 1 or 2 digits remaining
             bb.append_bits(accumdata, accumcount * 3 + 1);
         }
-        Segment::new(SegmentMode::Numeric, text.len(), bb.0)
+        Ok(Segment::new(SegmentMode::Numeric, text.len(), bb.0))
     }
 
     /// Returns a segment representing the given text string encoded in alphanumeric mode.
@@ -79,13 +87,23 @@ impl Segment {
     ///
     /// Panics if the string contains non-encodable characters.
     pub fn make_alphanumeric(text: &str) -> Self {
+        Segment::try_make_alphanumeric(text)
+            .expect("String contains unencodable characters in alphanumeric mode")
+    }
+
+    /// Returns a segment representing the given text string encoded in alphanumeric mode.
+    ///
+    /// The characters allowed are the same as for `make_alphanumeric()`.
+    ///
+    /// Returns `Err` with the first non-encodable character.
+    pub fn try_make_alphanumeric(text: &str) -> Result<Self, QrError> {
         let mut bb = BitBuffer(Vec::with_capacity(text.len() * 5 + (text.len() + 1) / 2));
         let mut accumdata: u32 = 0;
         let mut accumcount: u32 = 0;
         for c in text.chars() {
             let i: usize = ALPHANUMERIC_CHARSET
                 .find(c)
-                .expect("String contains unencodable characters in alphanumeric mode");
+                .ok_or(QrError::NonAlphanumericCharacter(c))?;
             accumdata = accumdata * 45 + u32::try_from(i).unwrap();
             accumcount += 1;
             if accumcount == 2 {
@@ -98,7 +116,26 @@ impl Segment {
             // 1 character remaining
             bb.append_bits(accumdata, 6);
         }
-        Segment::new(SegmentMode::Alphanumeric, text.len(), bb.0)
+        Ok(Segment::new(SegmentMode::Alphanumeric, text.len(), bb.0))
+    }
+
+    /// Returns a segment representing the given text string encoded in kanji mode.
+    ///
+    /// Each character is converted to its Shift JIS double-byte code and packed into 13 bits.
+    /// The characters allowed are those in the ranges 0x8140 to 0x9FFC and 0xE040 to 0xEBBF
+    /// of Shift JIS, which covers kanji, kana, and full-width Latin and Greek letters.
+    ///
+    /// Returns `Err` with the first character outside those ranges.
+    #[cfg(feature = "kanji")]
+    pub fn make_kanji(text: &str) -> Result<Self, QrError> {
+        let mut bb = BitBuffer(Vec::with_capacity(text.chars().count() * 13));
+        let mut numchars: usize = 0;
+        for c in text.chars() {
+            let code: u16 = kanji_code(c).ok_or(QrError::NonKanjiCharacter(c))?;
+            bb.append_bits(u32::from(code), 13);
+            numchars += 1;
+        }
+        Ok(Segment::new(SegmentMode::Kanji, numchars, bb.0))
     }
 
     /// Returns a list of zero or more segments to represent the given Unicode text string.
@@ -119,9 +156,102 @@ impl Segment {
         }
     }
 
+    /// Returns a list of zero or more segments to represent the given Unicode text string,
+    /// switching between byte, alphanumeric and numeric mode wherever that minimizes the
+    /// total bit length at the given version.
+    ///
+    /// The segment header sizes depend on the version, so the result is only optimal for
+    /// versions sharing the same character count field widths (1 to 9, 10 to 26, 27 to 40).
+    /// `QrCode::encode_text_optimized()` picks the version and segmentation together.
+    pub fn make_segments_optimized(text: &str, version: Version) -> Vec<Self> {
+        use SegmentMode::*;
+        const MODES: [SegmentMode; 3] = [Byte, Alphanumeric, Numeric];
+
+        // Costs are in sixths of a bit, so a digit (10 bits per 3) and an
+        // alphanumeric character (11 bits per 2) both cost a whole number
+        let chars: Vec<char> = text.chars().collect();
+        let headcosts: [usize; 3] =
+            MODES.map(|mode| (4 + usize::from(mode.num_char_count_bits(version))) * 6);
+
+        // charmodes[i][j] is the mode of the i'th character on the cheapest
+        // encoding of the first i + 1 characters whose last segment is MODES[j]
+        let mut charmodes: Vec<[Option<SegmentMode>; 3]> = vec![[None; 3]; chars.len()];
+        let mut prevcosts: [usize; 3] = headcosts;
+        for (i, &c) in chars.iter().enumerate() {
+            // Extend the segment in each mode which can encode the character
+            let mut curcosts: [usize; 3] = [usize::MAX; 3];
+            curcosts[0] = prevcosts[0] + c.len_utf8() * 8 * 6;
+            charmodes[i][0] = Some(Byte);
+            if ALPHANUMERIC_CHARSET.contains(c) {
+                curcosts[1] = prevcosts[1] + 33;
+                charmodes[i][1] = Some(Alphanumeric);
+            }
+            if c.is_ascii_digit() {
+                curcosts[2] = prevcosts[2] + 20;
+                charmodes[i][2] = Some(Numeric);
+            }
+
+            // Or end it here, rounded up to whole bits, and start a segment in another mode
+            let extended: [usize; 3] = curcosts;
+            for j in 0..MODES.len() {
+                for k in 0..MODES.len() {
+                    if extended[k] == usize::MAX {
+                        continue;
+                    }
+                    let newcost: usize = extended[k].div_ceil(6) * 6 + headcosts[j];
+                    if newcost < curcosts[j] {
+                        curcosts[j] = newcost;
+                        charmodes[i][j] = Some(MODES[k]);
+                    }
+                }
+            }
+            prevcosts = curcosts;
+        }
+
+        // Trace back from the cheapest final mode to the mode of every character
+        let mut state: usize = (0..MODES.len()).min_by_key(|&j| prevcosts[j]).unwrap();
+        let mut modes: Vec<SegmentMode> = vec![Byte; chars.len()];
+        for i in (0..chars.len()).rev() {
+            modes[i] = charmodes[i][state].unwrap();
+            state = MODES.iter().position(|&mode| mode == modes[i]).unwrap();
+        }
+
+        // Cut the text into runs of characters sharing a mode
+        let make = |run: &str, mode: SegmentMode| match mode {
+            Numeric => Segment::make_numeric(run),
+            Alphanumeric => Segment::make_alphanumeric(run),
+            _ => Segment::make_bytes(run.as_bytes()),
+        };
+        let mut result: Vec<Self> = Vec::new();
+        let mut start: usize = 0;
+        for (i, (offset, _)) in text.char_indices().enumerate().skip(1) {
+            if modes[i] != modes[i - 1] {
+                result.push(make(&text[start..offset], modes[i - 1]));
+                start = offset;
+            }
+        }
+        if let Some(&mode) = modes.last() {
+            result.push(make(&text[start..], mode));
+        }
+        result
+    }
+
+    /// The ECI assignment value for UTF-8 text.
+    pub const ECI_UTF8: u32 = 26;
+
     /// Returns a segment representing an Extended Channel Interpretation
     /// (ECI) designator with the given assignment value.
+    ///
+    /// Panics if the value is 1000000 or more.
     pub fn make_eci(assignval: u32) -> Self {
+        Segment::try_make_eci(assignval).expect("ECI assignment value out of range")
+    }
+
+    /// Returns a segment representing an Extended Channel Interpretation
+    /// (ECI) designator with the given assignment value.
+    ///
+    /// Returns `Err` if the value is 1000000 or more.
+    pub fn try_make_eci(assignval: u32) -> Result<Self, QrError> {
         let mut bb = BitBuffer(Vec::with_capacity(24));
         if assignval < (1 << 7) {
             bb.append_bits(assignval, 8);
@@ -132,9 +262,9 @@ impl Segment {
             bb.append_bits(0b110, 3);
             bb.append_bits(assignval, 21);
         } else {
-            panic!("ECI assignment value out of range");
+            return Err(QrError::InvalidEci(assignval));
         }
-        Segment::new(SegmentMode::Eci, 0, bb.0)
+        Ok(Segment::new(SegmentMode::Eci, 0, bb.0))
     }
 
     /*---- Constructor (low level) ----*/
@@ -168,6 +298,30 @@ impl Segment {
         &self.data
     }
 
+    /// Returns the data bits of this segment as a string of '0' and '1', for debugging.
+    pub fn bits_as_string(&self) -> String {
+        self.data
+            .iter()
+            .map(|&bit| if bit { '1' } else { '0' })
+            .collect()
+    }
+
+    /// Returns the bits which precede the data of this segment in a QR Code of the given
+    /// version: the 4-bit mode indicator and the character count field.
+    ///
+    /// Returns `None` if the character count doesn't fit the field's bit width.
+    pub fn header_bits(&self, version: Version) -> Option<BitBuffer> {
+        let ccbits: u8 = self.mode.num_char_count_bits(version);
+        let numchars = u32::try_from(self.numchars).ok()?;
+        if u64::from(numchars) >> ccbits != 0 {
+            return None;
+        }
+        let mut bb = BitBuffer(Vec::with_capacity(4 + usize::from(ccbits)));
+        bb.append_bits(self.mode.mode_bits(), 4);
+        bb.append_bits(numchars, ccbits);
+        Some(bb)
+    }
+
     /*---- Other static functions ----*/
 
     // Calculates and returns the number of bits needed to encode the given
@@ -203,4 +357,279 @@ impl Segment {
     pub fn is_alphanumeric(text: &str) -> bool {
         text.chars().all(|c| ALPHANUMERIC_CHARSET.contains(c))
     }
+
+    /// Tests whether the given string can be encoded as a segment in kanji mode.
+    ///
+    /// A string is encodable iff each character has a Shift JIS double-byte code
+    /// in the range 0x8140 to 0x9FFC or 0xE040 to 0xEBBF.
+    #[cfg(feature = "kanji")]
+    pub fn is_kanji(text: &str) -> bool {
+        text.chars().all(|c| kanji_code(c).is_some())
+    }
+}
+
+impl fmt::Debug for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Segment")
+            .field("mode", &self.mode)
+            .field("num_chars", &self.numchars)
+            .field("bits", &self.data.len())
+            .finish()
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} segment of {} chars in {} bits",
+            self.mode,
+            self.numchars,
+            self.data.len()
+        )
+    }
+}
+
+// Returns the 13-bit kanji mode value of the given character, or None if its
+// Shift JIS code isn't a double-byte code in one of the kanji mode ranges.
+#[cfg(feature = "kanji")]
+fn kanji_code(c: char) -> Option<u16> {
+    let mut utf8 = [0; 4];
+    let (bytes, _, had_errors) = encoding_rs::SHIFT_JIS.encode(c.encode_utf8(&mut utf8));
+    let sjis: u16 = match *bytes {
+        [hi, lo] if !had_errors => u16::from_be_bytes([hi, lo]),
+        _ => return None,
+    };
+    let offset: u16 = match sjis {
+        0x8140..=0x9FFC => sjis - 0x8140,
+        0xE040..=0xEBBF => sjis - 0xC140,
+        _ => return None,
+    };
+    Some((offset >> 8) * 0xC0 + (offset & 0xFF))
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::code_ecc::CodeEcc;
+    use crate::qr_code::QrCode;
+
+    const URL: &str = "https://example.com/ABC123456789012345";
+
+    fn modes(segs: &[Segment]) -> Vec<(SegmentMode, usize)> {
+        segs.iter()
+            .map(|seg| (seg.mode(), seg.num_chars()))
+            .collect()
+    }
+
+    #[test]
+    fn switch_to_numeric_for_digit_runs() {
+        let segs = Segment::make_segments_optimized(URL, Version::new(1));
+        assert_eq!(
+            vec![(SegmentMode::Byte, 23), (SegmentMode::Numeric, 15)],
+            modes(&segs)
+        );
+    }
+
+    #[test]
+    fn keep_single_mode_when_switching_costs_more() {
+        for text in ["12345", "HELLO WORLD", "hello, world"] {
+            let optimized = Segment::make_segments_optimized(text, Version::new(1));
+            assert_eq!(modes(&Segment::make_segments(text)), modes(&optimized));
+        }
+        assert!(Segment::make_segments_optimized("", Version::new(1)).is_empty());
+    }
+
+    #[test]
+    fn never_use_more_bits_than_a_single_segment() {
+        let texts = [
+            URL,
+            "a1",
+            "ABCdef0123456789",
+            "Ünïcödé 2024-01-01",
+            "99 BOTTLES",
+            "x",
+        ];
+        for text in texts {
+            for ver in [1, 10, 27] {
+                let version = Version::new(ver);
+                let single = Segment::get_total_bits(&Segment::make_segments(text), version);
+                let optimized = Segment::make_segments_optimized(text, version);
+                assert!(
+                    Segment::get_total_bits(&optimized, version) <= single,
+                    "{}",
+                    text
+                );
+                // Numeric and alphanumeric characters are ASCII, so every mode counts bytes here
+                let numchars: usize = optimized.iter().map(Segment::num_chars).sum();
+                assert_eq!(text.len(), numchars, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_mixed_urls_in_a_smaller_version() {
+        let single = QrCode::encode_text(URL, CodeEcc::Low).unwrap();
+        let optimized = QrCode::encode_text_optimized(URL, CodeEcc::Low).unwrap();
+        assert!(optimized.version() < single.version());
+    }
+
+    fn bits(value: u32, len: u8) -> Vec<bool> {
+        let mut bb = BitBuffer(Vec::new());
+        bb.append_bits(value, len);
+        bb.0
+    }
+
+    #[test]
+    #[cfg(feature = "kanji")]
+    fn pack_kanji_into_13_bits() {
+        // The worked example in ISO/IEC 18004 section 7.4.6
+        let seg = Segment::make_kanji("点茗").unwrap();
+        assert_eq!(SegmentMode::Kanji, seg.mode());
+        assert_eq!(2, seg.num_chars());
+        assert_eq!([bits(0x0D9F, 13), bits(0x1AAA, 13)].concat(), *seg.data());
+    }
+
+    #[test]
+    #[cfg(feature = "kanji")]
+    fn encode_kana_and_full_width_digits() {
+        let seg = Segment::make_kanji("日本のコード１").unwrap();
+        assert_eq!(7, seg.num_chars());
+        assert_eq!(7 * 13, seg.data().len());
+        assert_eq!(bits(0x0E3A, 13), seg.data()[..13]);
+    }
+
+    #[test]
+    #[cfg(feature = "kanji")]
+    fn reject_non_kanji_characters() {
+        assert!(Segment::is_kanji("日本"));
+        assert!(Segment::is_kanji(""));
+        assert!(!Segment::is_kanji("日本A"));
+        assert!(!Segment::is_kanji("😀"));
+        assert!(matches!(
+            Segment::make_kanji("日本A"),
+            Err(QrError::NonKanjiCharacter('A'))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "kanji")]
+    fn fit_a_smaller_symbol_than_byte_mode() {
+        let text: String = "点茗".repeat(15);
+        let kanji = QrCode::encode_segments(&[Segment::make_kanji(&text).unwrap()], CodeEcc::Low);
+        let bytes = QrCode::encode_binary(text.as_bytes(), CodeEcc::Low);
+        assert!(kanji.unwrap().size() < bytes.unwrap().size());
+    }
+
+    #[test]
+    fn snapshot_debug_output() {
+        let segs = [
+            Segment::make_numeric("01234567"),
+            Segment::make_alphanumeric("AC-42"),
+            Segment::make_bytes(b"hi"),
+            Segment::make_eci(26),
+        ];
+        let debug: Vec<String> = segs.iter().map(|seg| format!("{:?}", seg)).collect();
+        assert_eq!(
+            vec![
+                "Segment { mode: Numeric, num_chars: 8, bits: 27 }",
+                "Segment { mode: Alphanumeric, num_chars: 5, bits: 28 }",
+                "Segment { mode: Byte, num_chars: 2, bits: 16 }",
+                "Segment { mode: Eci, num_chars: 0, bits: 8 }",
+            ],
+            debug
+        );
+        assert_eq!("Numeric segment of 8 chars in 27 bits", segs[0].to_string());
+    }
+
+    #[test]
+    fn dump_data_and_header_bits() {
+        let seg = Segment::make_numeric("01234567");
+        assert_eq!("000000110001010110011000011", seg.bits_as_string());
+        let header = seg.header_bits(Version::new(1)).unwrap();
+        assert_eq!([bits(0b0001, 4), bits(8, 10)].concat(), header.0);
+        assert_eq!(
+            4 + 12,
+            seg.header_bits(Version::new(10)).unwrap().len_bits()
+        );
+        assert_eq!("01101000", Segment::make_bytes(b"h").bits_as_string());
+        assert_eq!(
+            bits(0b0111, 4),
+            Segment::make_eci(26).header_bits(Version::MAX).unwrap().0
+        );
+
+        let long = Segment::make_bytes(&[0; 256]);
+        assert!(long.header_bits(Version::new(9)).is_none());
+        assert!(long.header_bits(Version::new(10)).is_some());
+    }
+
+    #[test]
+    fn encode_eci_assignment_in_each_length_class() {
+        let version = Version::new(1);
+        // (value, prefix, prefix bits, value bits)
+        for (assignval, prefix, prefixlen, valuelen) in [
+            (Segment::ECI_UTF8, 0b0, 1, 7),
+            (127, 0b0, 1, 7),
+            (128, 0b10, 2, 14),
+            (16_383, 0b10, 2, 14),
+            (16_384, 0b110, 3, 21),
+            (999_999, 0b110, 3, 21),
+        ] {
+            let seg = Segment::try_make_eci(assignval).unwrap();
+            let expected = [
+                bits(0b0111, 4),
+                bits(prefix, prefixlen),
+                bits(assignval, valuelen),
+            ]
+            .concat();
+            let mut actual = seg.header_bits(version).unwrap().0;
+            actual.extend_from_slice(seg.data());
+            assert_eq!(expected, actual, "{}", assignval);
+        }
+        assert!(matches!(
+            Segment::try_make_eci(1_000_000),
+            Err(QrError::InvalidEci(1_000_000))
+        ));
+    }
+
+    #[test]
+    fn return_errors_for_unencodable_characters() {
+        for (text, c) in [("12😀3", '😀'), ("１２", '１'), ("0x10", 'x'), ("42 ", ' ')] {
+            assert!(
+                matches!(Segment::try_make_numeric(text), Err(QrError::NonNumericCharacter(e)) if e == c),
+                "{:?}",
+                text
+            );
+        }
+        for (text, c) in [("HELLO world", 'w'), ("A😀", '😀'), ("\0", '\0')] {
+            assert!(
+                matches!(Segment::try_make_alphanumeric(text), Err(QrError::NonAlphanumericCharacter(e)) if e == c),
+                "{:?}",
+                text
+            );
+        }
+        assert!(Segment::make_numeric("0123") == Segment::try_make_numeric("0123").unwrap());
+        assert!(
+            Segment::make_alphanumeric("AC-42") == Segment::try_make_alphanumeric("AC-42").unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "String contains non-numeric characters")]
+    fn panic_for_non_numeric_characters() {
+        Segment::make_numeric("12a");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn round_trip_through_json_and_bincode() {
+        let segs = Segment::make_segments_optimized(URL, Version::new(2));
+
+        let json = serde_json::to_string(&segs).unwrap();
+        assert!(json.starts_with("[{\"mode\":\"byte\""), "{}", json);
+        assert!(segs == serde_json::from_str::<Vec<Segment>>(&json).unwrap());
+
+        let bytes = bincode::serialize(&segs).unwrap();
+        assert!(segs == bincode::deserialize::<Vec<Segment>>(&bytes).unwrap());
+    }
 }
@@ -2,6 +2,11 @@ use crate::version::Version;
 
 /// Describes how a segment's data bits are interpreted.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum SegmentMode {
     Numeric,
     Alphanumeric,
@@ -25,3 +25,25 @@ impl Version {
         self.0
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Version {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Version {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ver = <u8 as serde::Deserialize>::deserialize(deserializer)?;
+        if (Version::MIN.value()..=Version::MAX.value()).contains(&ver) {
+            Ok(Self(ver))
+        } else {
+            Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(u64::from(ver)),
+                &"a version number between 1 and 40",
+            ))
+        }
+    }
+}
@@ -0,0 +1,18 @@
+/// What to do when the source holds more than one frame (animated GIF or WebP)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AnimationPolicy {
+    /// Keep the first frame as a still image, the frames are still counted
+    #[default]
+    FirstFrame,
+    /// Refuse animated sources with `Error::Animated`
+    Error,
+    /// Keep every frame and its delay so `Recoder::to_animated_gif` can write them back out
+    PreserveAnimation,
+}
+
+/// One fully composited frame of an animation, always 8-bit RGBA at the size of the canvas
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AnimationFrame {
+    pub delay_ms: u32,
+    pub data: Vec<u8>,
+}
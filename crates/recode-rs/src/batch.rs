@@ -0,0 +1,141 @@
+use crate::{Error, Format, Outcome, RecodeOptions, Recoder};
+
+/// Decoded pixels allowed in memory at once across a parallel batch
+pub const BATCH_MEMORY_BUDGET: usize = 256 * 1024 * 1024;
+
+/// Decodes and re-encodes every input to the target format, returning one result per input in
+/// the same order and under the same id. A failing item never stops the rest of the batch.
+///
+/// The target and options are validated once up front, if they're unusable every item gets
+/// the same error without being decoded. With the "rayon" feature items are processed in
+/// parallel, see `recode_batch_with_budget`.
+pub fn recode_batch(
+    inputs: Vec<(String, Vec<u8>)>,
+    target: Format,
+    options: &RecodeOptions,
+) -> Vec<(String, Result<Outcome, Error>)> {
+    recode_batch_with_budget(inputs, target, options, BATCH_MEMORY_BUDGET)
+}
+
+/// As `recode_batch` with a caller supplied memory budget. The decoded size of each item is
+/// estimated from its header and parallel work is split into consecutive waves whose estimates
+/// fit the budget together, an item larger than the budget runs alone.
+pub fn recode_batch_with_budget(
+    inputs: Vec<(String, Vec<u8>)>,
+    target: Format,
+    options: &RecodeOptions,
+    budget: usize,
+) -> Vec<(String, Result<Outcome, Error>)> {
+    if let Err(err) = validate(target, options) {
+        return inputs.into_iter().map(|(id, _)| (id, Err(err.clone()))).collect();
+    }
+    let recode = |bytes: &[u8]| Recoder::new(None, bytes)?.to_format(target, options);
+    let mut results = Vec::with_capacity(inputs.len());
+    let sizes: Vec<usize> = inputs.iter().map(|(_, bytes)| decoded_size(bytes)).collect();
+    let mut remaining = inputs.as_slice();
+    for len in waves(&sizes, budget) {
+        let (wave, rest) = remaining.split_at(len);
+        remaining = rest;
+        #[cfg(feature = "rayon")]
+        let outcomes: Vec<_> = {
+            use rayon::prelude::*;
+            wave.par_iter().map(|(_, bytes)| recode(bytes)).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let outcomes: Vec<_> = wave.iter().map(|(_, bytes)| recode(bytes)).collect();
+        results.extend(wave.iter().map(|(id, _)| id.clone()).zip(outcomes));
+    }
+    results
+}
+
+// Catches what would fail every item the same way
+fn validate(target: Format, options: &RecodeOptions) -> Result<(), Error> {
+    if !target.is_encodable() {
+        return Err(Error::UnsupportedFormat);
+    }
+    if target == Format::Jpeg && !(1..=100).contains(&options.quality) {
+        return Err(Error::InvalidQuality(options.quality));
+    }
+    Ok(())
+}
+
+// Bytes the decoded pixels will take, read from the header. Unreadable inputs count as nothing
+// since they fail before allocating.
+fn decoded_size(bytes: &[u8]) -> usize {
+    image::ImageReader::new(std::io::Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .map_or(0, |decoder| usize::try_from(image::ImageDecoder::total_bytes(&decoder)).unwrap_or(usize::MAX))
+}
+
+// Splits the items into runs of consecutive items whose sizes fit the budget together
+fn waves(sizes: &[usize], budget: usize) -> Vec<usize> {
+    let mut waves = Vec::new();
+    let (mut len, mut total) = (0, 0usize);
+    for &size in sizes {
+        if len > 0 && total.saturating_add(size) > budget {
+            waves.push(len);
+            (len, total) = (0, 0);
+        }
+        len += 1;
+        total = total.saturating_add(size);
+    }
+    if len > 0 {
+        waves.push(len);
+    }
+    waves
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 4, Rgb([1, 2, 3])));
+        let mut buffer = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
+    }
+
+    fn inputs() -> Vec<(String, Vec<u8>)> {
+        let png = encode(ImageFormat::Png);
+        vec![
+            ("png".to_owned(), png.clone()),
+            ("corrupt".to_owned(), png[..png.len() / 2].to_vec()),
+            ("svg".to_owned(), b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec()),
+            ("jpeg".to_owned(), encode(ImageFormat::Jpeg)),
+        ]
+    }
+
+    #[test]
+    fn line_results_up_with_inputs() {
+        let results = recode_batch(inputs(), Format::WebP, &RecodeOptions::default());
+        let ids: Vec<&str> = results.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(vec!["png", "corrupt", "svg", "jpeg"], ids);
+        assert!(matches!(&results[0].1, Ok(outcome) if outcome.src == Format::Png && outcome.dest == Format::WebP));
+        assert!(matches!(&results[1].1, Err(Error::Decode { format: Some(Format::Png), .. })));
+        assert!(matches!(&results[2].1, Err(Error::Decode { format: None, .. })));
+        assert!(matches!(&results[3].1, Ok(outcome) if outcome.src == Format::Jpeg));
+    }
+
+    #[test]
+    fn fail_every_item_for_unusable_options() {
+        let results = recode_batch(inputs(), Format::Bmp, &RecodeOptions::default());
+        assert!(results.iter().all(|(_, result)| matches!(result, Err(Error::UnsupportedFormat))));
+        let results = recode_batch(inputs(), Format::Jpeg, &RecodeOptions::default().with_quality(0));
+        assert!(results.iter().all(|(_, result)| matches!(result, Err(Error::InvalidQuality(0)))));
+    }
+
+    #[test]
+    fn split_waves_within_budget() {
+        assert_eq!(vec![2, 1, 2], waves(&[40, 60, 80, 10, 90], 100));
+        assert_eq!(vec![1, 1], waves(&[500, 20], 100));
+        assert_eq!(Vec::<usize>::new(), waves(&[], 100));
+        // Results are the same whatever the budget
+        let tight = recode_batch_with_budget(inputs(), Format::Png, &RecodeOptions::default(), 1);
+        assert_eq!(4, tight.len());
+        assert!(tight[0].1.is_ok() && tight[3].1.is_ok());
+    }
+}
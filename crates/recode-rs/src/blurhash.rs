@@ -0,0 +1,146 @@
+use std::f64::consts::PI;
+
+use image::RgbImage;
+
+/// The most components BlurHash can carry on either axis
+pub const MAX_COMPONENTS: u32 = 9;
+
+const BASE83: &[u8; 83] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// Encodes the image as a BlurHash with the given number of components on each axis, the caller
+/// checks they're within 1..=MAX_COMPONENTS. Follows the reference implementation so the hashes
+/// match byte for byte.
+pub(crate) fn encode(x_components: u32, y_components: u32, image: &RgbImage) -> String {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let linear: Vec<[f64; 3]> = image.pixels().map(|p| p.0.map(srgb_to_linear)).collect();
+    let factors: Vec<[f64; 3]> = (0..y_components)
+        .flat_map(|j| (0..x_components).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let normalisation = if i == 0 && j == 0 { 1.0 } else { 2.0 };
+            let x_basis = basis(i, width);
+            let y_basis = basis(j, height);
+            let mut factor = [0.0; 3];
+            for (y, row) in linear.chunks(width).enumerate() {
+                for (x, pixel) in row.iter().enumerate() {
+                    let weight = normalisation * x_basis[x] * y_basis[y];
+                    for (f, c) in factor.iter_mut().zip(pixel) {
+                        *f += weight * c;
+                    }
+                }
+            }
+            factor.map(|f| f / (width * height) as f64)
+        })
+        .collect();
+
+    let (dc, ac) = factors.split_first().expect("At least one component");
+    let mut hash = String::with_capacity(4 + 2 * factors.len());
+    push_base83(&mut hash, (x_components - 1) + (y_components - 1) * 9, 1);
+
+    let maximum = match ac.iter().flatten().map(|c| c.abs()).reduce(f64::max) {
+        Some(actual) => {
+            let quantised = ((actual * 166.0 - 0.5).floor() as i64).clamp(0, 82) as u32;
+            push_base83(&mut hash, quantised, 1);
+            (quantised + 1) as f64 / 166.0
+        }
+        None => {
+            push_base83(&mut hash, 0, 1);
+            1.0
+        }
+    };
+
+    let [r, g, b] = dc.map(linear_to_srgb);
+    push_base83(&mut hash, (r << 16) + (g << 8) + b, 4);
+    for component in ac {
+        let [r, g, b] = component.map(|c| {
+            ((sign_pow(c / maximum, 0.5) * 9.0 + 9.5).floor() as i64).clamp(0, 18) as u32
+        });
+        push_base83(&mut hash, r * 19 * 19 + g * 19 + b, 2);
+    }
+    hash
+}
+
+// Cosine weight of each pixel along one axis for the given component
+fn basis(component: u32, len: usize) -> Vec<f64> {
+    (0..len)
+        .map(|n| (PI * component as f64 * n as f64 / len as f64).cos())
+        .collect()
+}
+
+fn push_base83(hash: &mut String, value: u32, digits: u32) {
+    for digit in (0..digits).rev() {
+        hash.push(BASE83[(value / 83u32.pow(digit) % 83) as usize] as char);
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+    let v = f64::from(value) / 255.0;
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f64) -> u32 {
+    let v = value.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        (v * 12.92 * 255.0 + 0.5) as u32
+    } else {
+        ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u32
+    }
+}
+
+fn sign_pow(value: f64, exponent: f64) -> f64 {
+    value.abs().powf(exponent).copysign(value)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    use image::Rgb;
+
+    // Red, green, blue and white quarters
+    fn quadrants() -> RgbImage {
+        RgbImage::from_fn(8, 8, |x, y| {
+            [Rgb([255, 0, 0]), Rgb([0, 255, 0]), Rgb([0, 0, 255]), Rgb([255, 255, 255])]
+                [usize::from(x >= 4) + 2 * usize::from(y >= 4)]
+        })
+    }
+
+    #[test]
+    fn match_reference_hashes() {
+        assert_eq!("K~TI:j|cfQ|c$5fQfQfQfQ", encode(3, 3, &RgbImage::from_pixel(4, 4, Rgb([255, 0, 0]))));
+        assert_eq!("L~Lqe9z5d_%y%8[W#oOlfK$3sUSK", encode(4, 3, &quadrants()));
+        let gradient = RgbImage::from_fn(16, 12, |x, y| Rgb([(x * 255 / 15) as u8, (y * 255 / 11) as u8, 128]));
+        assert_eq!("L$Hx+i2?wxoyqSR-jte=g0fjfQfj", encode(4, 3, &gradient));
+    }
+
+    #[test]
+    fn match_reference_hash_of_fixture() {
+        // A 32x24 sky, sun and ground scene, hashed by a port of the reference TypeScript encoder
+        let image = image::load_from_memory(include_bytes!("../fixtures/sunset.png")).unwrap().to_rgb8();
+        assert_eq!("L#D,+xOGS5xBtWWZWYslE3n$n~S5", encode(4, 3, &image));
+        assert_eq!("V#D,+xOGS5xBS6tWWZWYslWYE3n$n~S5oIS*sloINysl", encode(5, 4, &image));
+    }
+
+    #[test]
+    fn encode_only_the_average_color_for_one_component() {
+        assert_eq!("00TSUA", encode(1, 1, &RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]))));
+        assert_eq!("000000", encode(1, 1, &RgbImage::from_pixel(2, 2, Rgb([0, 0, 0]))));
+    }
+
+    #[test]
+    fn grow_two_characters_per_component() {
+        let hash = encode(MAX_COMPONENTS, MAX_COMPONENTS, &quadrants());
+        assert_eq!(4 + 2 * 81, hash.len());
+        assert!(hash.starts_with("|~Lqe9"));
+    }
+
+    #[test]
+    fn round_trip_srgb_through_linear() {
+        for value in 0..=255u8 {
+            assert_eq!(u32::from(value), linear_to_srgb(srgb_to_linear(value)));
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{Budget, Error, Format, Outcome, PngCompression, PngFilter, RecodeOptions, Recoder};
+
+/// The most encodes `Recoder::to_format_with_budget` makes before settling on the best fit
+pub const MAX_BUDGET_ATTEMPTS: u32 = 7;
+/// The lowest JPEG quality tried before a budget is declared unreachable
+pub const MIN_BUDGET_QUALITY: u8 = 10;
+
+impl Recoder {
+    /// Encodes to the format at the best setting whose output fits in max_bytes.
+    ///
+    /// JPEG binary-searches the quality between `MIN_BUDGET_QUALITY` and the quality in the
+    /// options, keeping the highest that fits, in at most `MAX_BUDGET_ATTEMPTS` encodes. PNG
+    /// steps up to the best compression with adaptive filtering instead, finishing with an oxipng
+    /// pass (which includes palette reduction) when the "oxipng" feature is on. WebP is lossless
+    /// so it gets a single attempt.
+    ///
+    /// When nothing fits the error is `BudgetUnreachable` with the smallest size achieved.
+    pub fn to_format_with_budget(&self, format: Format, max_bytes: usize, options: &RecodeOptions) -> Result<Outcome, Error> {
+        let mut search = Search::new(max_bytes);
+        match format {
+            Format::Jpeg => self.fit_quality(&mut search, options)?,
+            Format::Png => self.fit_compression(&mut search, options)?,
+            format => {
+                search.attempt(self.to_format(format, options)?, None);
+            }
+        }
+        search.finish()
+    }
+
+    fn fit_quality(&self, search: &mut Search, options: &RecodeOptions) -> Result<(), Error> {
+        let ceiling = options.quality;
+        if !(1..=100).contains(&ceiling) {
+            return Err(Error::InvalidQuality(ceiling));
+        }
+        let floor = MIN_BUDGET_QUALITY.min(ceiling);
+        let encode = |quality: u8| self.to_jpeg(&options.clone().with_quality(quality));
+        if search.attempt(encode(ceiling)?, Some(ceiling)) || floor == ceiling {
+            return Ok(());
+        }
+        if !search.attempt(encode(floor)?, Some(floor)) {
+            return Ok(());
+        }
+        // The floor fits and the ceiling doesn't, so the best quality lies in between
+        let (mut low, mut high) = (floor, ceiling);
+        while high - low > 1 && search.attempts < MAX_BUDGET_ATTEMPTS {
+            let quality = low + (high - low) / 2;
+            if search.attempt(encode(quality)?, Some(quality)) {
+                low = quality;
+            } else {
+                high = quality;
+            }
+        }
+        Ok(())
+    }
+
+    fn fit_compression(&self, search: &mut Search, options: &RecodeOptions) -> Result<(), Error> {
+        if search.attempt(self.to_png(options)?, None) {
+            return Ok(());
+        }
+        let best = options
+            .clone()
+            .with_png_compression(PngCompression::Best)
+            .with_png_filter(PngFilter::Adaptive);
+        if best != *options && search.attempt(self.to_png(&best)?, None) {
+            return Ok(());
+        }
+        #[cfg(feature = "oxipng")]
+        {
+            let optimization = crate::PngOptimization { level: 6, ..best.png_optimization };
+            search.attempt(self.to_png_optimized(&best.with_png_optimization(optimization))?, None);
+        }
+        Ok(())
+    }
+}
+
+// Tracks the attempts, a later fit replaces an earlier one so callers try in increasing quality
+struct Search {
+    max_bytes: usize,
+    attempts: u32,
+    smallest: usize,
+    millis: u64,
+    best: Option<(Outcome, Option<u8>)>,
+}
+
+impl Search {
+    fn new(max_bytes: usize) -> Self {
+        Search { max_bytes, attempts: 0, smallest: usize::MAX, millis: 0, best: None }
+    }
+
+    fn attempt(&mut self, outcome: Outcome, quality: Option<u8>) -> bool {
+        self.attempts += 1;
+        self.millis += outcome.encode_millis;
+        self.smallest = self.smallest.min(outcome.size());
+        let fits = outcome.size() <= self.max_bytes;
+        if fits {
+            self.best = Some((outcome, quality));
+        }
+        fits
+    }
+
+    fn finish(self) -> Result<Outcome, Error> {
+        let Some((outcome, quality)) = self.best else {
+            return Err(Error::BudgetUnreachable { max_bytes: self.max_bytes, smallest: self.smallest });
+        };
+        Ok(Outcome {
+            budget: Some(Budget { max_bytes: self.max_bytes, quality, attempts: self.attempts }),
+            encode_millis: self.millis,
+            ..outcome
+        })
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::ColorType;
+
+    // A smooth gradient with grain, which compresses roughly like a photo
+    fn photo() -> Recoder {
+        let (width, height) = (256, 192);
+        let data = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                let grain = (x * 7919 + y * 104_729) % 23;
+                [(x + grain) as u8, (y + grain) as u8, ((x + y) / 2) as u8]
+            })
+            .collect();
+        Recoder::from_parts(width, height, ColorType::Rgb8, data).unwrap()
+    }
+
+    #[test]
+    fn fit_jpeg_under_budget() {
+        let recoder = photo();
+        let options = RecodeOptions::default().with_quality(95);
+        let max_bytes = recoder.to_jpeg(&options.clone().with_quality(40)).unwrap().size();
+        let outcome = recoder.to_format_with_budget(Format::Jpeg, max_bytes, &options).unwrap();
+        assert!(outcome.size() <= max_bytes);
+        let budget = outcome.budget.unwrap();
+        assert!(matches!(budget.quality, Some(quality) if (MIN_BUDGET_QUALITY..95).contains(&quality)));
+        assert!(budget.attempts <= MAX_BUDGET_ATTEMPTS);
+        assert_eq!(max_bytes, budget.max_bytes);
+    }
+
+    #[test]
+    fn keep_the_requested_quality_when_it_fits() {
+        let outcome = photo().to_format_with_budget(Format::Jpeg, usize::MAX, &RecodeOptions::default()).unwrap();
+        assert_eq!(Some(Budget { max_bytes: usize::MAX, quality: Some(80), attempts: 1 }), outcome.budget);
+    }
+
+    #[test]
+    fn report_the_smallest_size_when_unreachable() {
+        let recoder = photo();
+        let floor = recoder.to_jpeg(&RecodeOptions::default().with_quality(MIN_BUDGET_QUALITY)).unwrap();
+        let result = recoder.to_format_with_budget(Format::Jpeg, 100, &RecodeOptions::default());
+        assert!(matches!(
+            result,
+            Err(Error::BudgetUnreachable { max_bytes: 100, smallest }) if smallest == floor.size()
+        ));
+    }
+
+    #[test]
+    fn step_up_png_compression() {
+        let recoder = photo();
+        let best = RecodeOptions::default()
+            .with_png_compression(PngCompression::Best)
+            .with_png_filter(PngFilter::Adaptive);
+        let max_bytes = recoder.to_png(&best).unwrap().size();
+        let options = RecodeOptions::default().with_png_compression(PngCompression::Fast);
+        let outcome = recoder.to_format_with_budget(Format::Png, max_bytes, &options).unwrap();
+        assert!(outcome.size() <= max_bytes);
+        let budget = outcome.budget.unwrap();
+        assert_eq!(None, budget.quality);
+        assert!((1..=2).contains(&budget.attempts));
+    }
+}
@@ -77,6 +77,12 @@ impl ColorType {
         let e: ExtendedColorType = self.into();
         e.channel_count()
     }
+
+    /// Returns the number of bits used by each channel of the pixel
+    #[must_use]
+    pub fn bits_per_channel(self) -> u16 {
+        self.bits_per_pixel() / <u16 as From<u8>>::from(self.channel_count())
+    }
 }
 
 /// An enumeration of color types encountered in image formats.
@@ -0,0 +1,175 @@
+use crate::{Error, Recoder};
+
+use image::imageops::{self, FilterType};
+use image::RgbImage;
+
+/// SSIM is computed per window of this size and averaged
+const WINDOW: u32 = 8;
+/// Stabilizers from the SSIM paper for 8-bit samples, (0.01 * 255)^2 and (0.03 * 255)^2
+const C1: f64 = 6.5025;
+const C2: f64 = 58.5225;
+
+/// How alike two images look, see `compare`
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Similarity {
+    /// Mean absolute difference per channel, 0 (identical) to 255
+    pub mean_absolute_error: f64,
+    /// Structural similarity of the luma, 1.0 for identical images and near 0 for unrelated ones
+    pub ssim: f64,
+    /// The largest difference in any channel of any pixel
+    pub max_delta: u8,
+}
+
+impl Similarity {
+    /// True if the SSIM reaches the threshold
+    pub fn is_similar(&self, threshold: f64) -> bool {
+        self.ssim >= threshold
+    }
+
+    /// Panics with the scores unless the SSIM reaches the threshold, for regression tests which
+    /// shouldn't depend on the exact bytes an encoder produces
+    #[track_caller]
+    pub fn assert_similar(&self, threshold: f64) {
+        assert!(self.is_similar(threshold), "{self} is below the SSIM threshold {threshold}");
+    }
+}
+
+impl std::fmt::Display for Similarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Similarity ( mae: {:.3}, ssim: {:.4}, max delta: {} )",
+            self.mean_absolute_error, self.ssim, self.max_delta
+        )
+    }
+}
+
+/// Compares what two images look like rather than their bytes. Both are normalized to RGB8
+/// (dropping any alpha) and, when their dimensions differ, resized to the smaller width and
+/// height before scoring.
+pub fn compare(a: &Recoder, b: &Recoder) -> Result<Similarity, Error> {
+    let (width, height) = (a.width().min(b.width()), a.height().min(b.height()));
+    let a = to_rgb(a, width, height)?;
+    let b = to_rgb(b, width, height)?;
+    let mut total = 0u64;
+    let mut max_delta = 0u8;
+    for (a, b) in a.pixels().zip(b.pixels()) {
+        for (a, b) in a.0.into_iter().zip(b.0) {
+            let delta = a.abs_diff(b);
+            total += u64::from(delta);
+            max_delta = max_delta.max(delta);
+        }
+    }
+    Ok(Similarity {
+        mean_absolute_error: total as f64 / a.as_raw().len() as f64,
+        ssim: ssim(&luma(&a), &luma(&b), width, height),
+        max_delta,
+    })
+}
+
+fn to_rgb(recoder: &Recoder, width: u32, height: u32) -> Result<RgbImage, Error> {
+    let image = recoder.to_image()?.to_rgb8();
+    Ok(match (image.width(), image.height()) == (width, height) {
+        true => image,
+        false => imageops::resize(&image, width, height, FilterType::Triangle),
+    })
+}
+
+// Rec. 601 luma
+fn luma(image: &RgbImage) -> Vec<f64> {
+    image
+        .pixels()
+        .map(|p| 0.299 * f64::from(p[0]) + 0.587 * f64::from(p[1]) + 0.114 * f64::from(p[2]))
+        .collect()
+}
+
+// Mean SSIM over non-overlapping windows, windows at the right and bottom edges may be smaller
+fn ssim(a: &[f64], b: &[f64], width: u32, height: u32) -> f64 {
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..height).step_by(WINDOW as usize) {
+        for left in (0..width).step_by(WINDOW as usize) {
+            let indices = || {
+                (top..(top + WINDOW).min(height))
+                    .flat_map(move |y| (left..(left + WINDOW).min(width)).map(move |x| (y * width + x) as usize))
+            };
+            let n = indices().count() as f64;
+            let mean_a = indices().map(|i| a[i]).sum::<f64>() / n;
+            let mean_b = indices().map(|i| b[i]).sum::<f64>() / n;
+            let (mut var_a, mut var_b, mut covariance) = (0.0, 0.0, 0.0);
+            for i in indices() {
+                let (da, db) = (a[i] - mean_a, b[i] - mean_b);
+                var_a += da * da;
+                var_b += db * db;
+                covariance += da * db;
+            }
+            let (var_a, var_b, covariance) = (var_a / n, var_b / n, covariance / n);
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::{ColorType, RecodeOptions};
+
+    fn gradient(width: u32, height: u32) -> Recoder {
+        let data = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                let grain = (x * 7919 + y * 104_729) % 5;
+                [(x * 3 + grain) as u8, (y * 4 + grain) as u8, ((x + y) * 2) as u8]
+            })
+            .collect();
+        Recoder::from_parts(width, height, ColorType::Rgb8, data).unwrap()
+    }
+
+    fn checkerboard(width: u32, height: u32) -> Recoder {
+        let data = (0..width * height)
+            .map(|i| if (i % width / 4 + i / width / 4) % 2 == 0 { 0 } else { 255 })
+            .collect();
+        Recoder::from_parts(width, height, ColorType::L8, data).unwrap()
+    }
+
+    #[test]
+    fn score_identical_images_perfectly() {
+        let image = gradient(64, 48);
+        let similarity = compare(&image, &image).unwrap();
+        assert_eq!(Similarity { mean_absolute_error: 0.0, ssim: 1.0, max_delta: 0 }, similarity);
+        similarity.assert_similar(1.0);
+    }
+
+    #[test]
+    fn score_recompressed_jpeg_as_similar() {
+        let image = gradient(64, 48);
+        let jpeg = image.to_jpeg(&RecodeOptions::default().with_quality(85)).unwrap();
+        let recompressed = Recoder::new(None, &jpeg.data).unwrap();
+        let similarity = compare(&image, &recompressed).unwrap();
+        similarity.assert_similar(0.9);
+        assert!(similarity.mean_absolute_error < 8.0, "{similarity}");
+        assert!(similarity.max_delta > 0);
+    }
+
+    #[test]
+    fn score_unrelated_images_low() {
+        let similarity = compare(&gradient(64, 48), &checkerboard(64, 48)).unwrap();
+        assert!(!similarity.is_similar(0.3), "{similarity}");
+    }
+
+    #[test]
+    fn normalize_color_type_and_dimensions() {
+        let gray = Recoder::from_parts(16, 8, ColorType::L8, vec![128; 16 * 8]).unwrap();
+        let rgb = Recoder::from_parts(32, 16, ColorType::Rgb8, vec![128; 32 * 16 * 3]).unwrap();
+        assert_eq!(1.0, compare(&gray, &rgb).unwrap().ssim);
+    }
+
+    #[test]
+    #[should_panic(expected = "below the SSIM threshold")]
+    fn panic_when_asserting_dissimilar_images() {
+        compare(&gradient(64, 48), &checkerboard(64, 48)).unwrap().assert_similar(0.9);
+    }
+}
@@ -1,27 +1,153 @@
+use crate::{ColorType, Format, Limit};
 use image::ImageError;
+use std::sync::Arc;
 
+/// Errors serialize so Workers can hand them across a boundary as JSON. The underlying
+/// `ImageError` is kept as the `source` for local callers but is skipped by serde, only its
+/// message survives a round trip.
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum Error {
-    LoadError,
-    ImageError(String),
-    // PngError(PngError),
+    /// The source couldn't be decoded, the format is `None` when it couldn't be recognized
+    Decode {
+        format: Option<Format>,
+        reason: String,
+        #[serde(skip)]
+        source: Option<Arc<ImageError>>,
+    },
+    /// Reading the input (or writing the output) failed before the image library saw the bytes
+    Io {
+        message: String,
+        #[serde(skip)]
+        source: Option<Arc<std::io::Error>>,
+    },
+    /// The image library failed outside of decoding, i.e. while encoding
+    Image {
+        message: String,
+        #[serde(skip)]
+        source: Option<Arc<ImageError>>,
+    },
     UnsupportedFormat,
+    /// Lossy quality must be within 1..=100
+    InvalidQuality(u8),
+    /// Width and height must both be non-zero
+    InvalidDimensions { width: u32, height: u32 },
+    /// The requested output exceeds the allowed maximum width or height
+    DimensionsTooLarge { width: u32, height: u32, max: u32 },
+    /// The crop rectangle extends past the edge of the image
+    OutOfBounds { x: u32, y: u32, width: u32, height: u32 },
+    /// Conversions may keep or reduce the bit depth but never increase it
+    UnsupportedConversion { from: ColorType, to: ColorType },
+    /// A raw pixel buffer's length doesn't match its dimensions and color type
+    BufferMismatch { expected: usize, actual: usize },
+    /// The input was refused before decoding, see `Limits`
+    LimitsExceeded { what: Limit, limit: u64, actual: u64 },
+    /// The source has more than one frame and the animation policy refuses it
+    Animated,
+    /// BlurHash components must be within 1..=9 on each axis
+    InvalidComponents { x: u32, y: u32 },
+    /// Even the smallest output tried was larger than the byte budget
+    BudgetUnreachable { max_bytes: usize, smallest: usize },
+    /// The requested frame or page is past the last one in the source
+    FrameOutOfRange { index: usize, count: usize },
+}
+
+impl Error {
+    /// Wraps a failure to decode the given format, keeping the image error as the source
+    pub fn decode(format: Option<Format>, src: ImageError) -> Self {
+        Error::Decode {
+            format,
+            reason: src.to_string(),
+            source: Some(Arc::new(src)),
+        }
+    }
 }
 
 impl From<ImageError> for Error {
     fn from(src: ImageError) -> Self {
-        Error::ImageError(src.to_string())
+        match src {
+            // The encoders surface failures of the output writer this way
+            ImageError::IoError(err) => err.into(),
+            src => Error::Image {
+                message: src.to_string(),
+                source: Some(Arc::new(src)),
+            },
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(src: std::io::Error) -> Self {
+        Error::Io {
+            message: src.to_string(),
+            source: Some(Arc::new(src)),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode { source, .. } | Error::Image { source, .. } => {
+                source.as_deref().map(|src| src as &(dyn std::error::Error + 'static))
+            }
+            Error::Io { source, .. } => source.as_deref().map(|src| src as &(dyn std::error::Error + 'static)),
+            _ => None,
+        }
     }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let message = match self {
-            Error::LoadError => "load error".to_owned(),
-            Error::ImageError(msg) => format!("image error: {msg}"),
-            // Error::PngError(err) => format!("png error: {err}"),
+            Error::Decode { format: Some(format), reason, .. } => format!("decode error ({format:?}): {reason}"),
+            Error::Decode { format: None, reason, .. } => format!("decode error: {reason}"),
+            Error::Io { message, .. } => format!("io error: {message}"),
+            Error::Image { message, .. } => format!("image error: {message}"),
             Error::UnsupportedFormat => "unsupported format".to_owned(),
+            Error::InvalidQuality(quality) => format!("invalid quality: {quality} (expected 1-100)"),
+            Error::InvalidDimensions { width, height } => format!("invalid dimensions: {width}x{height}"),
+            Error::DimensionsTooLarge { width, height, max } => {
+                format!("dimensions too large: {width}x{height} (max {max})")
+            }
+            Error::OutOfBounds { x, y, width, height } => {
+                format!("rectangle out of bounds: {width}x{height} at ({x}, {y})")
+            }
+            Error::UnsupportedConversion { from, to } => format!("unsupported conversion: {from:?} to {to:?}"),
+            Error::BufferMismatch { expected, actual } => {
+                format!("buffer mismatch: expected {expected} bytes, got {actual}")
+            }
+            Error::LimitsExceeded { what, limit, actual } => format!("{what:?} limit exceeded: {actual} > {limit}"),
+            Error::Animated => "animated images are not accepted".to_owned(),
+            Error::InvalidComponents { x, y } => format!("invalid components: {x}x{y} (expected 1-9)"),
+            Error::BudgetUnreachable { max_bytes, smallest } => {
+                format!("budget unreachable: smallest output {smallest}b > {max_bytes}b")
+            }
+            Error::FrameOutOfRange { index, count } => format!("frame {index} out of range ({count} frames)"),
         };
         write!(f, "Error ( {message} )")
     }
 }
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn keep_image_error_as_source() {
+        let err = Error::decode(Some(Format::Png), ImageError::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+        let source = err.source().expect("source should be populated");
+        assert!(source.downcast_ref::<ImageError>().is_some());
+        assert!(Error::UnsupportedFormat.source().is_none());
+    }
+
+    #[test]
+    fn round_trip_through_serde_without_source() {
+        let err = Error::decode(Some(Format::Png), ImageError::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+        let json = serde_json::to_string(&err).unwrap();
+        let restored: Error = serde_json::from_str(&json).unwrap();
+        assert_eq!(err.to_string(), restored.to_string());
+        assert!(matches!(&restored, Error::Decode { format: Some(Format::Png), source: None, .. }));
+        assert!(restored.source().is_none());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::Error;
+
 use image::ImageFormat;
 
 pub static AVIF: &str = "avif";
@@ -12,13 +14,14 @@ pub static JPEG: &str = "jpeg";
 pub static PNG: &str = "png";
 pub static PNM: &str = "pnm";
 pub static QOI: &str = "qoi";
+pub static RAW: &str = "raw";
 pub static TGA: &str = "tga";
 pub static TIFF: &str = "tiff";
 pub static WEBP: &str = "webp";
 
 // ["avif", "bmp", "dds", "exr", "ff", "gif", "hdr", "ico", "jpeg", "png", "pnm", "qoi", "tga", "tiff", "webp"]
 
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum Format {
     Avif,
     Bmp,
@@ -32,6 +35,8 @@ pub enum Format {
     Png,
     Pnm,
     Qoi,
+    /// Pixels handed over directly through `Recoder::from_parts`, never decoded from a file
+    Raw,
     Tga,
     Tiff,
     WebP,
@@ -43,7 +48,7 @@ impl Format {
         S: AsRef<std::ffi::OsStr>
     {
         image::ImageFormat::from_extension(ext)
-            .map(|o| o.into())
+            .and_then(|o| o.try_into().ok())
     }
 
     pub fn from_mime_type<M>(mime_type: M) -> Option<Format>
@@ -51,7 +56,19 @@ impl Format {
         M: AsRef<str>
     {
         image::ImageFormat::from_mime_type(mime_type)
-            .map(|o| o.into())
+            .and_then(|o| o.try_into().ok())
+    }
+}
+
+impl Format {
+    /// The image crate format used to decode this one, `Raw` has none
+    pub fn to_image_format(self) -> Result<ImageFormat, Error> {
+        self.try_into()
+    }
+
+    /// True if `Recoder::to_format` can produce this format, everything else can only be decoded
+    pub fn is_encodable(self) -> bool {
+        matches!(self, Format::Jpeg | Format::Png | Format::WebP)
     }
 }
 
@@ -70,6 +87,7 @@ impl ToString for Format {
             Format::Png => PNG.to_owned(),
             Format::Pnm => PNM.to_owned(),
             Format::Qoi => QOI.to_owned(),
+            Format::Raw => RAW.to_owned(),
             Format::Tga => TGA.to_owned(),
             Format::Tiff => TIFF.to_owned(),
             Format::WebP => WEBP.to_owned(),
@@ -77,73 +95,110 @@ impl ToString for Format {
     }
 }
 
-impl From<Format> for ImageFormat {
-    fn from(value: Format) -> Self {
+impl TryFrom<Format> for ImageFormat {
+    type Error = Error;
+
+    fn try_from(value: Format) -> Result<Self, Self::Error> {
         match value {
-            Format::Avif => ImageFormat::Avif,
-            Format::Bmp => ImageFormat::Bmp,
-            Format::Dds => ImageFormat::Dds,
-            Format::Farbfeld => ImageFormat::Farbfeld,
-            Format::Gif => ImageFormat::Gif,
-            Format::Hdr => ImageFormat::Hdr,
-            Format::Ico => ImageFormat::Ico,
-            Format::Jpeg => ImageFormat::Jpeg,
-            Format::OpenExr => ImageFormat::OpenExr,
-            Format::Png => ImageFormat::Png,
-            Format::Pnm => ImageFormat::Pnm,
-            Format::Qoi => ImageFormat::Qoi,
-            Format::Tga => ImageFormat::Tga,
-            Format::Tiff => ImageFormat::Tiff,
-            Format::WebP => ImageFormat::WebP,
-            // _ => ImageFormat::WebP,
+            Format::Avif => Ok(ImageFormat::Avif),
+            Format::Bmp => Ok(ImageFormat::Bmp),
+            Format::Dds => Ok(ImageFormat::Dds),
+            Format::Farbfeld => Ok(ImageFormat::Farbfeld),
+            Format::Gif => Ok(ImageFormat::Gif),
+            Format::Hdr => Ok(ImageFormat::Hdr),
+            Format::Ico => Ok(ImageFormat::Ico),
+            Format::Jpeg => Ok(ImageFormat::Jpeg),
+            Format::OpenExr => Ok(ImageFormat::OpenExr),
+            Format::Png => Ok(ImageFormat::Png),
+            Format::Pnm => Ok(ImageFormat::Pnm),
+            Format::Qoi => Ok(ImageFormat::Qoi),
+            Format::Tga => Ok(ImageFormat::Tga),
+            Format::Tiff => Ok(ImageFormat::Tiff),
+            Format::WebP => Ok(ImageFormat::WebP),
+            // Raw pixels have no file format to decode with
+            Format::Raw => Err(Error::UnsupportedFormat),
         }
     }
 }
 
-impl From<ImageFormat> for Format {
-    fn from(value: ImageFormat) -> Self {
+impl TryFrom<ImageFormat> for Format {
+    type Error = Error;
+
+    fn try_from(value: ImageFormat) -> Result<Self, Self::Error> {
         match value {
-            ImageFormat::Avif => Format::Avif,
-            ImageFormat::Bmp => Format::Bmp,
-            ImageFormat::Dds => Format::Dds,
-            ImageFormat::Farbfeld => Format::Farbfeld,
-            ImageFormat::Gif => Format::Gif,
-            ImageFormat::Hdr => Format::Hdr,
-            ImageFormat::Ico => Format::Ico,
-            ImageFormat::Jpeg => Format::Jpeg,
-            ImageFormat::OpenExr => Format::OpenExr,
-            ImageFormat::Png => Format::Png,
-            ImageFormat::Pnm => Format::Pnm,
-            ImageFormat::Qoi => Format::Qoi,
-            ImageFormat::Tga => Format::Tga,
-            ImageFormat::Tiff => Format::Tiff,
-            ImageFormat::WebP => Format::WebP,
-            _ => Format::WebP,
+            ImageFormat::Avif => Ok(Format::Avif),
+            ImageFormat::Bmp => Ok(Format::Bmp),
+            ImageFormat::Dds => Ok(Format::Dds),
+            ImageFormat::Farbfeld => Ok(Format::Farbfeld),
+            ImageFormat::Gif => Ok(Format::Gif),
+            ImageFormat::Hdr => Ok(Format::Hdr),
+            ImageFormat::Ico => Ok(Format::Ico),
+            ImageFormat::Jpeg => Ok(Format::Jpeg),
+            ImageFormat::OpenExr => Ok(Format::OpenExr),
+            ImageFormat::Png => Ok(Format::Png),
+            ImageFormat::Pnm => Ok(Format::Pnm),
+            ImageFormat::Qoi => Ok(Format::Qoi),
+            ImageFormat::Tga => Ok(Format::Tga),
+            ImageFormat::Tiff => Ok(Format::Tiff),
+            ImageFormat::WebP => Ok(Format::WebP),
+            _ => Err(Error::UnsupportedFormat),
         }
     }
 }
 
-// impl TryFrom<ImageFormat> for Format {
-//     type Error = Error;
-
-//     fn try_from(value: ImageFormat) -> Result<Self, Self::Error> {
-//         match value {
-//             ImageFormat::Avif => Ok(Format::Avif),
-//             ImageFormat::Bmp => Ok(Format::Bmp),
-//             ImageFormat::Dds => Ok(Format::Dds),
-//             ImageFormat::Farbfeld => Ok(Format::Farbfeld),
-//             ImageFormat::Gif => Ok(Format::Gif),
-//             ImageFormat::Hdr => Ok(Format::Hdr),
-//             ImageFormat::Ico => Ok(Format::Ico),
-//             ImageFormat::Jpeg => Ok(Format::Jpeg),
-//             ImageFormat::OpenExr => Ok(Format::OpenExr),
-//             ImageFormat::Png => Ok(Format::Png),
-//             ImageFormat::Pnm => Ok(Format::Pnm),
-//             ImageFormat::Qoi => Ok(Format::Qoi),
-//             ImageFormat::Tga => Ok(Format::Tga),
-//             ImageFormat::Tiff => Ok(Format::Tiff),
-//             ImageFormat::WebP => Ok(Format::WebP),
-//             _ => Err(Error::UnsupportedFormat)
-//         }
-//     }
-// }
\ No newline at end of file
+/// Parses the extension strings above, ignoring case
+impl std::str::FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let formats = [
+            (AVIF, Format::Avif),
+            (BMP, Format::Bmp),
+            (DDS, Format::Dds),
+            (OPENEXR, Format::OpenExr),
+            (FARBFELD, Format::Farbfeld),
+            (GIF, Format::Gif),
+            (HDR, Format::Hdr),
+            (ICO, Format::Ico),
+            (JPEG, Format::Jpeg),
+            (PNG, Format::Png),
+            (PNM, Format::Pnm),
+            (QOI, Format::Qoi),
+            (RAW, Format::Raw),
+            (TGA, Format::Tga),
+            (TIFF, Format::Tiff),
+            (WEBP, Format::WebP),
+        ];
+        formats
+            .into_iter()
+            .find(|(ext, _)| ext.eq_ignore_ascii_case(s))
+            .map(|(_, format)| format)
+            .ok_or(Error::UnsupportedFormat)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn map_every_image_format_both_ways() {
+        for image_format in ImageFormat::all() {
+            match Format::try_from(image_format) {
+                Ok(format) => assert_eq!(image_format, format.to_image_format().unwrap()),
+                Err(err) => assert!(matches!(err, Error::UnsupportedFormat), "{image_format:?}"),
+            }
+        }
+        assert_eq!(Format::Jpeg, Format::try_from(ImageFormat::Jpeg).unwrap());
+        assert!(matches!(Format::Raw.to_image_format(), Err(Error::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn parse_extension_strings() {
+        for format in [Format::Avif, Format::Farbfeld, Format::Jpeg, Format::OpenExr, Format::Raw, Format::WebP] {
+            assert_eq!(format, format.to_string().parse().unwrap());
+        }
+        assert_eq!(Format::WebP, "WebP".parse().unwrap());
+        assert!(matches!("jpg2000".parse::<Format>(), Err(Error::UnsupportedFormat)));
+    }
+}
@@ -0,0 +1,29 @@
+/// The ICO directory stores each side in a byte, where 0 means 256
+pub const MAX_ICO_DIMENSION: u32 = 256;
+
+const HEADER_LEN: usize = 6;
+const ENTRY_LEN: usize = 16;
+
+// Lays out the ICO header, one directory entry per image, and then the PNG data of each entry
+// in the same order. Every entry is a square 32-bit PNG.
+pub(crate) fn assemble(entries: &[(u32, Vec<u8>)]) -> Vec<u8> {
+    let data_len: usize = entries.iter().map(|(_, png)| png.len()).sum();
+    let mut ico = Vec::with_capacity(HEADER_LEN + ENTRY_LEN * entries.len() + data_len);
+    ico.extend_from_slice(&0u16.to_le_bytes()); // Reserved
+    ico.extend_from_slice(&1u16.to_le_bytes()); // Icon (2 would be a cursor)
+    ico.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    let mut offset = HEADER_LEN + ENTRY_LEN * entries.len();
+    for (size, png) in entries {
+        let side = if *size >= MAX_ICO_DIMENSION { 0 } else { *size as u8 };
+        ico.extend_from_slice(&[side, side, 0, 0]); // Width, height, palette size, reserved
+        ico.extend_from_slice(&1u16.to_le_bytes()); // Color planes
+        ico.extend_from_slice(&32u16.to_le_bytes()); // Bits per pixel
+        ico.extend_from_slice(&(png.len() as u32).to_le_bytes());
+        ico.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += png.len();
+    }
+    for (_, png) in entries {
+        ico.extend_from_slice(png);
+    }
+    ico
+}
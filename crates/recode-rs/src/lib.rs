@@ -1,11 +1,39 @@
+mod animation;
+mod batch;
+mod blurhash;
+mod budget;
 mod color_type;
+mod compare;
 mod error;
 mod format;
+mod ico;
+mod limits;
+mod meta;
+mod metadata;
+mod options;
+mod orientation;
 mod outcome;
+mod overlay;
 mod recoder;
+mod resize;
+mod thumbnail;
 
+pub use animation::{ AnimationFrame, AnimationPolicy };
+pub use batch::{ recode_batch, recode_batch_with_budget, BATCH_MEMORY_BUDGET };
+pub use blurhash::MAX_COMPONENTS;
+pub use budget::{ MAX_BUDGET_ATTEMPTS, MIN_BUDGET_QUALITY };
 pub use color_type::{ ColorType, ExtendedColorType };
+pub use compare::{ compare, Similarity };
 pub use error::Error;
 pub use format::Format;
-pub use outcome::Outcome;
-pub use recoder::Recoder;
+pub use ico::MAX_ICO_DIMENSION;
+pub use limits::{ Limit, Limits };
+pub use meta::Meta;
+pub use metadata::MetadataPolicy;
+pub use options::{ PngCompression, PngFilter, PngOptimization, PngStrip, RecodeOptions };
+pub use orientation::Orientation;
+pub use outcome::{ Budget, Optimization, Outcome, Written };
+pub use overlay::{ Anchor, DEFAULT_OVERLAY_FRACTION };
+pub use recoder::{ Recoder, DEFAULT_BACKGROUND };
+pub use resize::{ Fit, Filter, MAX_DIMENSION };
+pub use thumbnail::ThumbnailOptions;
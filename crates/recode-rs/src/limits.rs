@@ -0,0 +1,87 @@
+use crate::{Error, MAX_DIMENSION};
+
+/// The dimension or size which went over its limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Limit {
+    Width,
+    Height,
+    Pixels,
+    InputBytes,
+    Frames,
+}
+
+/// Bounds checked before decoding so a small file declaring huge dimensions (a decompression
+/// bomb) is refused before its pixel buffer is allocated.
+///
+/// The defaults allow anything up to `MAX_DIMENSION` on a side, 64 megapixels, 64 MiB of input,
+/// and 1000 frames or pages. Use `Limits::unlimited` only for trusted inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    pub max_width: u32,
+    pub max_height: u32,
+    pub max_pixels: u64,
+    pub max_input_bytes: usize,
+    /// Frames or pages decoded by `Recoder::split_frames`
+    pub max_frames: u32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_width: MAX_DIMENSION,
+            max_height: MAX_DIMENSION,
+            max_pixels: 64 * 1024 * 1024,
+            max_input_bytes: 64 * 1024 * 1024,
+            max_frames: 1000,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Self {
+        Limits {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_input_bytes: usize::MAX,
+            max_frames: u32::MAX,
+        }
+    }
+
+    pub(crate) fn check_input(&self, len: u64) -> Result<(), Error> {
+        exceeds(Limit::InputBytes, self.max_input_bytes as u64, len)
+    }
+
+    pub(crate) fn check_dimensions(&self, width: u32, height: u32) -> Result<(), Error> {
+        exceeds(Limit::Width, u64::from(self.max_width), u64::from(width))?;
+        exceeds(Limit::Height, u64::from(self.max_height), u64::from(height))?;
+        exceeds(Limit::Pixels, self.max_pixels, u64::from(width) * u64::from(height))
+    }
+
+    pub(crate) fn check_pixels(&self, pixels: u64) -> Result<(), Error> {
+        exceeds(Limit::Pixels, self.max_pixels, pixels)
+    }
+
+    pub(crate) fn check_frames(&self, frames: u64) -> Result<(), Error> {
+        exceeds(Limit::Frames, u64::from(self.max_frames), frames)
+    }
+
+    /// The same bounds for the image library to enforce while decoding, allocations are capped
+    /// at the largest pixel type (four 32-bit float channels)
+    pub(crate) fn to_image_limits(self) -> image::Limits {
+        let mut limits = image::Limits::no_limits();
+        limits.max_image_width = Some(self.max_width);
+        limits.max_image_height = Some(self.max_height);
+        limits.max_alloc = Some(self.max_pixels.saturating_mul(16));
+        limits
+    }
+}
+
+fn exceeds(what: Limit, limit: u64, actual: u64) -> Result<(), Error> {
+    if actual > limit {
+        Err(Error::LimitsExceeded { what, limit, actual })
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use crate::{ColorType, Format};
+
+/// What can be learned about an image from its header without decoding the pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Meta {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    pub color: ColorType,
+}
+
+impl std::fmt::Display for Meta {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Meta ( format: {:?}, w: {}, h: {}, color: {:?} )",
+            self.format, self.width, self.height, self.color
+        )
+    }
+}
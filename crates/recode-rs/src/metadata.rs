@@ -0,0 +1,12 @@
+/// Controls whether metadata captured from the source (currently the ICC color profile) is
+/// written into the encoded output.
+///
+/// Formats which can't carry the metadata drop it, the pixels are never converted to sRGB.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MetadataPolicy {
+    /// Re-attach the source metadata wherever the output format supports it
+    #[default]
+    Preserve,
+    /// Never write source metadata
+    Strip,
+}
@@ -0,0 +1,193 @@
+use crate::MetadataPolicy;
+
+use image::codecs::png::{CompressionType, FilterType};
+
+/// How hard the PNG encoder works to shrink the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    #[default]
+    Best,
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(value: PngCompression) -> Self {
+        match value {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+/// The PNG row filter applied before compression
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PngFilter {
+    #[default]
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    /// Picks a filter per row
+    Adaptive,
+}
+
+impl From<PngFilter> for FilterType {
+    fn from(value: PngFilter) -> Self {
+        match value {
+            PngFilter::NoFilter => FilterType::NoFilter,
+            PngFilter::Sub => FilterType::Sub,
+            PngFilter::Up => FilterType::Up,
+            PngFilter::Avg => FilterType::Avg,
+            PngFilter::Paeth => FilterType::Paeth,
+            PngFilter::Adaptive => FilterType::Adaptive,
+        }
+    }
+}
+
+/// Which ancillary chunks the PNG optimizer removes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PngStrip {
+    None,
+    /// Only chunks which don't affect rendering, the ICC profile is kept
+    #[default]
+    Safe,
+    All,
+}
+
+/// Settings for `Recoder::to_png_optimized` (with the "oxipng" feature)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct PngOptimization {
+    /// The oxipng preset, 0 (fastest) to 6 (smallest)
+    pub level: u8,
+    pub strip: PngStrip,
+}
+
+impl Default for PngOptimization {
+    fn default() -> Self {
+        PngOptimization { level: 2, strip: PngStrip::default() }
+    }
+}
+
+/// Encoding choices for the `Recoder` encoders, each only applies to the formats it names.
+///
+/// The defaults reproduce the original output: PNG at best compression with no filter, JPEG at
+/// quality 80, metadata preserved, no size limit, and alpha dropped rather than flattened. Any
+/// field missing from JSON takes its default.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RecodeOptions {
+    pub png_compression: PngCompression,
+    pub png_filter: PngFilter,
+    /// Quality used when the target format is lossy (1-100), WebP and PNG are always lossless
+    pub quality: u8,
+    pub metadata: MetadataPolicy,
+    /// Encoding fails with `DimensionsTooLarge` rather than resizing when exceeded
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    /// When set, alpha is blended onto this color for formats without an alpha channel
+    pub background: Option<[u8; 3]>,
+    pub png_optimization: PngOptimization,
+    /// Progressive JPEG, only honored with the "jpeg-encoder" feature, otherwise baseline is written
+    pub progressive: bool,
+    /// Adam7 interlaced PNG, only honored with the "oxipng" feature, otherwise written sequentially
+    pub interlace: bool,
+}
+
+impl Default for RecodeOptions {
+    fn default() -> Self {
+        RecodeOptions {
+            png_compression: PngCompression::default(),
+            png_filter: PngFilter::default(),
+            quality: 80,
+            metadata: MetadataPolicy::default(),
+            max_width: None,
+            max_height: None,
+            background: None,
+            png_optimization: PngOptimization::default(),
+            progressive: false,
+            interlace: false,
+        }
+    }
+}
+
+impl RecodeOptions {
+    pub fn with_png_compression(mut self, compression: PngCompression) -> Self {
+        self.png_compression = compression;
+        self
+    }
+
+    pub fn with_png_filter(mut self, filter: PngFilter) -> Self {
+        self.png_filter = filter;
+        self
+    }
+
+    pub fn with_quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub fn with_metadata(mut self, policy: MetadataPolicy) -> Self {
+        self.metadata = policy;
+        self
+    }
+
+    pub fn with_max_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.max_width = Some(width);
+        self.max_height = Some(height);
+        self
+    }
+
+    pub fn with_background(mut self, background: [u8; 3]) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn with_png_optimization(mut self, optimization: PngOptimization) -> Self {
+        self.png_optimization = optimization;
+        self
+    }
+
+    pub fn with_progressive(mut self, progressive: bool) -> Self {
+        self.progressive = progressive;
+        self
+    }
+
+    pub fn with_interlace(mut self, interlace: bool) -> Self {
+        self.interlace = interlace;
+        self
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn keep_defaults_stable() {
+        let options = RecodeOptions::default();
+        assert_eq!(PngCompression::Best, options.png_compression);
+        assert_eq!(PngFilter::NoFilter, options.png_filter);
+        assert_eq!(80, options.quality);
+        assert_eq!(MetadataPolicy::Preserve, options.metadata);
+        assert_eq!((None, None, None), (options.max_width, options.max_height, options.background));
+        assert_eq!((false, false), (options.progressive, options.interlace));
+        assert_eq!(options, serde_json::from_str("{}").unwrap());
+    }
+
+    #[test]
+    fn deserialize_partial_json() {
+        let options: RecodeOptions =
+            serde_json::from_str(r#"{ "png_compression": "Fast", "quality": 60, "background": [0, 0, 0] }"#).unwrap();
+        assert_eq!(
+            RecodeOptions::default()
+                .with_png_compression(PngCompression::Fast)
+                .with_quality(60)
+                .with_background([0, 0, 0]),
+            options
+        );
+    }
+}
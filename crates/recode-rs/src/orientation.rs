@@ -0,0 +1,184 @@
+use crate::Format;
+
+use image::DynamicImage;
+
+/// The EXIF orientation tag (0x0112)
+const ORIENTATION_TAG: u16 = 0x0112;
+/// Marks the APP1 payload which carries EXIF in JPEG files
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// How the stored pixels must be transformed to display upright, as recorded by the EXIF
+/// orientation tag. Variants are named by the transform that corrects them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Orientation {
+    /// 1: Already upright
+    #[default]
+    Normal,
+    /// 2: Mirrored left to right
+    FlipHorizontal,
+    /// 3: Upside down
+    Rotate180,
+    /// 4: Mirrored top to bottom
+    FlipVertical,
+    /// 5: Mirrored along the top-left to bottom-right diagonal
+    Transpose,
+    /// 6: Needs a 90 degree clockwise turn
+    Rotate90,
+    /// 7: Mirrored along the top-right to bottom-left diagonal
+    Transverse,
+    /// 8: Needs a 270 degree clockwise turn
+    Rotate270,
+}
+
+impl Orientation {
+    /// Maps the value of the EXIF tag (1-8)
+    pub fn from_exif(value: u16) -> Option<Orientation> {
+        match value {
+            1 => Some(Orientation::Normal),
+            2 => Some(Orientation::FlipHorizontal),
+            3 => Some(Orientation::Rotate180),
+            4 => Some(Orientation::FlipVertical),
+            5 => Some(Orientation::Transpose),
+            6 => Some(Orientation::Rotate90),
+            7 => Some(Orientation::Transverse),
+            8 => Some(Orientation::Rotate270),
+            _ => None,
+        }
+    }
+
+    /// Reads the orientation from the EXIF metadata of JPEG, TIFF, and WebP files, any other
+    /// format or a missing or unreadable tag is treated as `Normal`.
+    pub fn read(format: Format, buffer: &[u8]) -> Orientation {
+        let tiff = match format {
+            Format::Jpeg => jpeg_exif(buffer),
+            Format::WebP => webp_exif(buffer),
+            Format::Tiff => Some(buffer),
+            _ => None,
+        };
+        tiff.and_then(tiff_orientation)
+            .and_then(Orientation::from_exif)
+            .unwrap_or_default()
+    }
+
+    /// Reads the orientation from EXIF metadata as the decoders hand it over, with or without
+    /// the JPEG style header in front of the TIFF structure
+    pub(crate) fn from_exif_metadata(exif: &[u8]) -> Orientation {
+        tiff_orientation(exif.strip_prefix(EXIF_HEADER).unwrap_or(exif))
+            .and_then(Orientation::from_exif)
+            .unwrap_or_default()
+    }
+
+    /// True if the width and height are exchanged by the transform
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Orientation::Transpose | Orientation::Rotate90 | Orientation::Transverse | Orientation::Rotate270
+        )
+    }
+
+    pub(crate) fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Orientation::Normal => image,
+            Orientation::FlipHorizontal => image.fliph(),
+            Orientation::Rotate180 => image.rotate180(),
+            Orientation::FlipVertical => image.flipv(),
+            Orientation::Transpose => image.rotate90().fliph(),
+            Orientation::Rotate90 => image.rotate90(),
+            Orientation::Transverse => image.rotate270().fliph(),
+            Orientation::Rotate270 => image.rotate270(),
+        }
+    }
+}
+
+// Finds the TIFF structure inside the APP1 segment, scanning stops at the start of the image data
+fn jpeg_exif(buffer: &[u8]) -> Option<&[u8]> {
+    let mut i = 2; // Skip SOI
+    while i + 4 <= buffer.len() && buffer[i] == 0xFF {
+        let marker = buffer[i + 1];
+        if marker == 0xDA {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes([buffer[i + 2], buffer[i + 3]]));
+        let payload = buffer.get(i + 4..i + 2 + len)?;
+        if marker == 0xE1 && payload.starts_with(EXIF_HEADER) {
+            return Some(&payload[EXIF_HEADER.len()..]);
+        }
+        i += 2 + len;
+    }
+    None
+}
+
+// Finds the EXIF chunk in the RIFF container, some writers keep the JPEG style header
+fn webp_exif(buffer: &[u8]) -> Option<&[u8]> {
+    if buffer.get(0..4)? != b"RIFF" || buffer.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut i = 12;
+    while i + 8 <= buffer.len() {
+        let len = u32::from_le_bytes(buffer[i + 4..i + 8].try_into().ok()?) as usize;
+        // The length comes from the file so it can overflow where usize is 32 bits, i.e. wasm32
+        let end = (i + 8).checked_add(len)?;
+        let data = buffer.get(i + 8..end)?;
+        if &buffer[i..i + 4] == b"EXIF" {
+            return Some(data.strip_prefix(EXIF_HEADER).unwrap_or(data));
+        }
+        i = end.checked_add(len % 2)?; // Chunks are padded to an even length
+    }
+    None
+}
+
+// Reads the orientation entry from the first IFD
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(0..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(at..at.checked_add(2)?)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(at..at.checked_add(4)?)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let ifd = u32_at(4)? as usize;
+    let count = usize::from(u16_at(ifd)?);
+    (0..count)
+        .map_while(|n| ifd.checked_add(2 + n * 12))
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry.checked_add(8)?))
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn read_orientation_from_little_endian_tiff() {
+        let mut tiff = b"II*\0\x08\0\0\0\x01\0".to_vec();
+        tiff.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(Orientation::Rotate90, Orientation::read(Format::Tiff, &tiff));
+    }
+
+    #[test]
+    fn default_to_normal_without_exif() {
+        assert_eq!(Orientation::Normal, Orientation::read(Format::Jpeg, &[0xFF, 0xD8, 0xFF, 0xDA, 0, 2]));
+        assert_eq!(Orientation::Normal, Orientation::read(Format::Png, b"II*\0"));
+        assert_eq!(None, Orientation::from_exif(9));
+    }
+
+    #[test]
+    fn ignore_lengths_and_offsets_past_the_end() {
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\xFF\xFF\xFF\xFF".to_vec();
+        webp.extend_from_slice(&[0; 10]);
+        assert_eq!(None, webp_exif(&webp));
+        webp[16..20].copy_from_slice(&(u32::MAX - 1).to_le_bytes());
+        assert_eq!(None, webp_exif(&webp));
+        assert_eq!(None, tiff_orientation(b"II*\0\xFF\xFF\xFF\xFF"));
+        assert_eq!(None, tiff_orientation(b"II*\0\xF8\xFF\xFF\xFF"));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::Format;
+use crate::{ColorType, Format};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Outcome {
@@ -7,24 +7,200 @@ pub struct Outcome {
     pub width: u32,
     pub height: u32,
     pub data: Vec<u8>,
+    /// True if the EXIF orientation was baked into the pixels before encoding
+    #[serde(default)]
+    pub oriented: bool,
+    /// Set when the output went through a post-encode optimizer
+    #[serde(default)]
+    pub optimization: Option<Optimization>,
+    /// Color type of the encoded output, which may be reduced from the source (i.e. JPEG)
+    #[serde(default = "default_color")]
+    pub color: ColorType,
+    /// Size of the encoded source, 0 if unknown
+    #[serde(default)]
+    pub input_bytes: usize,
+    /// Time spent encoding, always 0 on wasm32 where there is no clock
+    #[serde(default)]
+    pub encode_millis: u64,
+    /// True if the JPEG was written progressive rather than baseline
+    #[serde(default)]
+    pub progressive: bool,
+    /// True if the PNG was written Adam7 interlaced
+    #[serde(default)]
+    pub interlaced: bool,
+    /// Set when the output was fit into a byte budget
+    #[serde(default)]
+    pub budget: Option<Budget>,
+}
+
+fn default_color() -> ColorType {
+    ColorType::Rgba8
+}
+
+/// How `Recoder::to_format_with_budget` arrived at the output
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Budget {
+    pub max_bytes: usize,
+    /// The chosen quality, `None` for lossless formats
+    pub quality: Option<u8>,
+    /// Number of encodes made, including those which didn't fit
+    pub attempts: u32,
+}
+
+/// Sizes before and after a post-encode optimization pass
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Optimization {
+    pub original_size: usize,
+    pub optimized_size: usize,
+    /// Why the unoptimized bytes were kept, if the optimizer failed
+    pub error: Option<String>,
 }
 
 impl Outcome {
     pub fn new(src: Format, dest: Format, width: u32, height: u32, data: Vec<u8>) -> Self {
-        Outcome { src, dest, width, height, data }
+        Outcome {
+            src,
+            dest,
+            width,
+            height,
+            data,
+            oriented: false,
+            optimization: None,
+            color: default_color(),
+            input_bytes: 0,
+            encode_millis: 0,
+            progressive: false,
+            interlaced: false,
+            budget: None,
+        }
     }
+
+    /// Size in bytes of the encoded output
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Output size relative to the input size (below 1.0 means the output is smaller), `None`
+    /// when the input size is unknown
+    pub fn ratio(&self) -> Option<f64> {
+        ratio(self.size() as u64, self.input_bytes)
+    }
+}
+
+fn ratio(output: u64, input: usize) -> Option<f64> {
+    (input > 0).then(|| output as f64 / input as f64)
+}
+
+// Formats the ratio for display, or a dash when it's unknown
+fn display_ratio(ratio: Option<f64>) -> String {
+    ratio.map_or_else(|| "-".to_owned(), |ratio| format!("{ratio:.2}"))
 }
 
 impl std::fmt::Display for Outcome {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "Recoder ( src: {:?}, dest: {:?}, w: {}, h: {}, data: {}b )",
+            "Recoder ( src: {:?}, dest: {:?}, w: {}, h: {}, color: {:?}, data: {}b, in: {}b, ratio: {}, {}ms )",
             self.src,
             self.dest,
             self.width,
             self.height,
-            self.data.len()
+            self.color,
+            self.size(),
+            self.input_bytes,
+            display_ratio(self.ratio()),
+            self.encode_millis
         )
     }
 }
+
+/// The result of encoding into a caller supplied writer, as `Outcome` but with the number of
+/// bytes written in place of the data
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Written {
+    pub src: Format,
+    pub dest: Format,
+    pub width: u32,
+    pub height: u32,
+    pub bytes_written: u64,
+    #[serde(default)]
+    pub oriented: bool,
+    #[serde(default = "default_color")]
+    pub color: ColorType,
+    #[serde(default)]
+    pub input_bytes: usize,
+    #[serde(default)]
+    pub encode_millis: u64,
+    #[serde(default)]
+    pub progressive: bool,
+    #[serde(default)]
+    pub interlaced: bool,
+}
+
+impl Written {
+    /// Output size relative to the input size, as `Outcome::ratio`
+    pub fn ratio(&self) -> Option<f64> {
+        ratio(self.bytes_written, self.input_bytes)
+    }
+
+    // Completes the outcome of an encode into a Vec
+    pub(crate) fn with_data(self, data: Vec<u8>) -> Outcome {
+        Outcome {
+            src: self.src,
+            dest: self.dest,
+            width: self.width,
+            height: self.height,
+            data,
+            oriented: self.oriented,
+            optimization: None,
+            color: self.color,
+            input_bytes: self.input_bytes,
+            encode_millis: self.encode_millis,
+            progressive: self.progressive,
+            interlaced: self.interlaced,
+            budget: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Written {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Recoder ( src: {:?}, dest: {:?}, w: {}, h: {}, color: {:?}, written: {}b, in: {}b, ratio: {}, {}ms )",
+            self.src,
+            self.dest,
+            self.width,
+            self.height,
+            self.color,
+            self.bytes_written,
+            self.input_bytes,
+            display_ratio(self.ratio()),
+            self.encode_millis
+        )
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[test]
+    fn default_new_fields_when_deserializing_older_outcomes() {
+        let json = r#"{ "src": "Png", "dest": "WebP", "width": 2, "height": 1, "data": [1, 2, 3] }"#;
+        let outcome: Outcome = serde_json::from_str(json).unwrap();
+        assert_eq!((ColorType::Rgba8, 0, 0), (outcome.color, outcome.input_bytes, outcome.encode_millis));
+        assert_eq!(None, outcome.ratio());
+        assert!(outcome.to_string().contains("ratio: -"));
+    }
+
+    #[test]
+    fn compute_ratio_against_input() {
+        let outcome = Outcome {
+            input_bytes: 12,
+            ..Outcome::new(Format::Png, Format::WebP, 2, 1, vec![0; 3])
+        };
+        assert_eq!(Some(0.25), outcome.ratio());
+        assert!(outcome.to_string().contains("ratio: 0.25"));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{Error, Filter, Fit, Recoder};
+
+use image::{DynamicImage, Rgba32FImage};
+
+/// The largest share of the base width and height an overlay may cover before it's scaled down
+pub const DEFAULT_OVERLAY_FRACTION: f32 = 0.25;
+
+/// Where an overlay is placed on the base image, the margin keeps it away from the edges it's
+/// anchored to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    // The top left corner of the overlay on the base, negative or past the end when it doesn't fit
+    fn origin(self, base: (u32, u32), overlay: (u32, u32), margin: u32) -> (i64, i64) {
+        let place = |base: u32, overlay: u32, start: bool, end: bool| {
+            let (base, overlay, margin) = (i64::from(base), i64::from(overlay), i64::from(margin));
+            match (start, end) {
+                (true, _) => margin,
+                (_, true) => base - overlay - margin,
+                _ => (base - overlay) / 2,
+            }
+        };
+        let (left, right, top, bottom) = match self {
+            Anchor::TopLeft => (true, false, true, false),
+            Anchor::Top => (false, false, true, false),
+            Anchor::TopRight => (false, true, true, false),
+            Anchor::Left => (true, false, false, false),
+            Anchor::Center => (false, false, false, false),
+            Anchor::Right => (false, true, false, false),
+            Anchor::BottomLeft => (true, false, false, true),
+            Anchor::Bottom => (false, false, false, true),
+            Anchor::BottomRight => (false, true, false, true),
+        };
+        (place(base.0, overlay.0, left, right), place(base.1, overlay.1, top, bottom))
+    }
+}
+
+impl Recoder {
+    /// Alpha-composites the overlay (i.e. a logo) onto a copy of this image at the anchor, with
+    /// the overlay's own alpha scaled by the opacity (clamped to 0.0-1.0). Overlays covering
+    /// more than `DEFAULT_OVERLAY_FRACTION` of the width or height are scaled down to fit, and
+    /// anything extending past the edge is clipped.
+    ///
+    /// The result is RGB, or RGBA when the base has alpha, at the base's bit depth.
+    pub fn overlay(&self, overlay: &Recoder, position: Anchor, opacity: f32, margin: u32) -> Result<Recoder, Error> {
+        self.overlay_within(overlay, position, opacity, margin, DEFAULT_OVERLAY_FRACTION)
+    }
+
+    /// Composites as `overlay` but with a caller supplied limit on the share of the base width
+    /// and height the overlay may cover
+    pub fn overlay_within(
+        &self,
+        overlay: &Recoder,
+        position: Anchor,
+        opacity: f32,
+        margin: u32,
+        max_fraction: f32,
+    ) -> Result<Recoder, Error> {
+        let opacity = if opacity.is_nan() { 0.0 } else { opacity.clamp(0.0, 1.0) };
+        let max_width = ((self.width() as f32 * max_fraction) as u32).max(1);
+        let max_height = ((self.height() as f32 * max_fraction) as u32).max(1);
+        let top = if overlay.width() > max_width || overlay.height() > max_height {
+            overlay.resize(max_width, max_height, Fit::Contain, Filter::Lanczos3)?.to_image()?
+        } else {
+            overlay.to_image()?
+        }
+        .to_rgba32f();
+        let mut canvas = self.to_image()?.to_rgba32f();
+        let (x0, y0) = position.origin(canvas.dimensions(), top.dimensions(), margin);
+        composite(&mut canvas, &top, (x0, y0), opacity);
+        let composed = DynamicImage::ImageRgba32F(canvas);
+        let image = match (self.color().bits_per_channel(), self.color().has_alpha()) {
+            (8, true) => DynamicImage::ImageRgba8(composed.to_rgba8()),
+            (8, false) => DynamicImage::ImageRgb8(composed.to_rgb8()),
+            (16, true) => DynamicImage::ImageRgba16(composed.to_rgba16()),
+            (16, false) => DynamicImage::ImageRgb16(composed.to_rgb16()),
+            (_, true) => composed,
+            (_, false) => DynamicImage::ImageRgb32F(composed.to_rgb32f()),
+        };
+
+        Ok(self.with_image(&image))
+    }
+}
+
+// Source-over blending, pixels of the overlay which fall outside the canvas are skipped
+fn composite(canvas: &mut Rgba32FImage, overlay: &Rgba32FImage, (x0, y0): (i64, i64), opacity: f32) {
+    for (x, y, top) in overlay.enumerate_pixels() {
+        let (Ok(cx), Ok(cy)) = (u32::try_from(x0 + i64::from(x)), u32::try_from(y0 + i64::from(y))) else {
+            continue;
+        };
+        let Some(bottom) = canvas.get_pixel_mut_checked(cx, cy) else {
+            continue;
+        };
+        let top_alpha = top[3] * opacity;
+        let bottom_alpha = bottom[3] * (1.0 - top_alpha);
+        let alpha = top_alpha + bottom_alpha;
+        for c in 0..3 {
+            bottom[c] = match alpha > 0.0 {
+                true => (top[c] * top_alpha + bottom[c] * bottom_alpha) / alpha,
+                false => 0.0,
+            };
+        }
+        bottom[3] = alpha;
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::ColorType;
+
+    fn gray(width: u32, height: u32) -> Recoder {
+        Recoder::from_parts(width, height, ColorType::Rgb8, vec![100; (width * height * 3) as usize]).unwrap()
+    }
+
+    fn red(width: u32, height: u32) -> Recoder {
+        let data = [255, 0, 0, 255].repeat((width * height) as usize);
+        Recoder::from_parts(width, height, ColorType::Rgba8, data).unwrap()
+    }
+
+    fn pixel(recoder: &Recoder, x: u32, y: u32) -> &[u8] {
+        let bpp = usize::from(recoder.color().bytes_per_pixel());
+        let at = (y * recoder.width() + x) as usize * bpp;
+        &recoder.as_bytes()[at..at + bpp]
+    }
+
+    // The (x, y) of every pixel which differs between the two
+    fn changed(a: &Recoder, b: &Recoder) -> Vec<(u32, u32)> {
+        (0..a.height())
+            .flat_map(|y| (0..a.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| pixel(a, x, y) != pixel(b, x, y))
+            .collect()
+    }
+
+    #[test]
+    fn place_overlay_at_each_anchor() {
+        let origin = |anchor: Anchor| anchor.origin((10, 8), (4, 2), 1);
+        assert_eq!((1, 1), origin(Anchor::TopLeft));
+        assert_eq!((3, 1), origin(Anchor::Top));
+        assert_eq!((5, 1), origin(Anchor::TopRight));
+        assert_eq!((1, 3), origin(Anchor::Left));
+        assert_eq!((3, 3), origin(Anchor::Center));
+        assert_eq!((5, 3), origin(Anchor::Right));
+        assert_eq!((1, 5), origin(Anchor::BottomLeft));
+        assert_eq!((3, 5), origin(Anchor::Bottom));
+        assert_eq!((5, 5), origin(Anchor::BottomRight));
+    }
+
+    #[test]
+    fn composite_onto_base_without_alpha() {
+        let base = gray(20, 10);
+        let stamped = base.overlay_within(&red(4, 2), Anchor::BottomRight, 1.0, 1, 0.5).unwrap();
+        assert_eq!(ColorType::Rgb8, stamped.color());
+        assert_eq!([255, 0, 0], pixel(&stamped, 15, 7));
+        assert_eq!([255, 0, 0], pixel(&stamped, 18, 8));
+        let expected: Vec<_> = (7..9).flat_map(|y| (15..19).map(move |x| (x, y))).collect();
+        assert_eq!(expected, changed(&base, &stamped));
+    }
+
+    #[test]
+    fn blend_by_opacity() {
+        let stamped = gray(20, 10).overlay(&red(2, 2), Anchor::TopLeft, 0.5, 0).unwrap();
+        let blended = pixel(&stamped, 0, 0);
+        assert!(blended[0].abs_diff(178) <= 1 && blended[1].abs_diff(50) <= 1, "{blended:?}");
+        assert_eq!([100, 100, 100], pixel(&stamped, 2, 0));
+    }
+
+    #[test]
+    fn keep_alpha_of_base() {
+        let base = Recoder::from_parts(4, 4, ColorType::Rgba8, vec![0; 4 * 4 * 4]).unwrap();
+        let stamped = base.overlay_within(&red(2, 2), Anchor::Center, 0.5, 0, 1.0).unwrap();
+        assert_eq!(ColorType::Rgba8, stamped.color());
+        let blended = pixel(&stamped, 1, 1);
+        assert!(blended[..3] == [255, 0, 0] && blended[3].abs_diff(128) <= 1, "{blended:?}");
+        assert_eq!([0, 0, 0, 0], pixel(&stamped, 0, 0));
+    }
+
+    #[test]
+    fn scale_down_large_overlays() {
+        let base = gray(20, 12);
+        let stamped = base.overlay(&red(16, 16), Anchor::Center, 1.0, 0).unwrap();
+        // Contained within 5x3, so 3x3 in the middle
+        let expected: Vec<_> = (4..7).flat_map(|y| (8..11).map(move |x| (x, y))).collect();
+        assert_eq!(expected, changed(&base, &stamped));
+    }
+
+    #[test]
+    fn clip_at_the_edges() {
+        let base = gray(10, 10);
+        let stamped = base.overlay_within(&red(4, 4), Anchor::BottomRight, 1.0, 8, 1.0).unwrap();
+        // Anchored at (-2, -2) so only the bottom right quarter lands on the base
+        let expected: Vec<_> = (0..2).flat_map(|y| (0..2).map(move |x| (x, y))).collect();
+        assert_eq!(expected, changed(&base, &stamped));
+    }
+}
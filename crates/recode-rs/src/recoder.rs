@@ -1,14 +1,28 @@
-use crate::{ColorType, Error, Format, Outcome};
+use crate::{
+    AnimationFrame, AnimationPolicy, ColorType, Error, Filter, Fit, Format, Limits, Meta, MetadataPolicy, Orientation, Outcome, RecodeOptions,
+    ThumbnailOptions, Written, MAX_DIMENSION, MAX_ICO_DIMENSION,
+};
 
-use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-use image::codecs::webp::WebPEncoder;
-use image::ImageEncoder;
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::{WebPDecoder, WebPEncoder};
+use image::{AnimationDecoder, Delay, DynamicImage, Frame, ImageBuffer, ImageDecoder, ImageEncoder, Rgb, RgbaImage};
+use std::io::{BufRead, Seek, SeekFrom, Write};
+#[cfg(feature = "oxipng")]
+use crate::{outcome::Optimization, PngStrip};
 // use image::{
 //     // guess_format, load_from_memory, EncodableLayout, ImageEncoder
 //     // guess_format, load_from_memory, ImageEncoder //, ImageFormat
     
 // };
 
+/// Sources are downscaled to fit within this before computing a BlurHash
+const BLURHASH_MAX_DIMENSION: u32 = 64;
+
+/// Background that alpha is flattened onto unless another is given
+pub const DEFAULT_BACKGROUND: [u8; 3] = [255, 255, 255];
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct Recoder {
     format: Format,
@@ -16,6 +30,28 @@ pub struct Recoder {
     height: u32,
     color: ColorType,
     data: Vec<u8>,
+    /// EXIF orientation of the stored pixels which hasn't been applied yet
+    #[serde(default)]
+    orientation: Orientation,
+    /// Whether an orientation transform was baked into the pixels
+    #[serde(default)]
+    oriented: bool,
+    /// ICC color profile embedded in the source
+    #[serde(default)]
+    icc_profile: Option<Vec<u8>>,
+    /// Number of frames in the source, 1 for still images
+    #[serde(default = "one")]
+    frame_count: u32,
+    /// Every frame of an animated source when it was decoded to preserve the animation
+    #[serde(default)]
+    frames: Vec<AnimationFrame>,
+    /// Size of the encoded source
+    #[serde(default)]
+    input_bytes: usize,
+}
+
+fn one() -> u32 {
+    1
 }
 
 impl Recoder {
@@ -25,6 +61,143 @@ impl Recoder {
     /// Known supported formats that aren't implemented here are:
     /// ["avif", "bmp", "dds", "ff"/"farbfeld", "gif", "hdr", "ico", "jpeg", "exr"/"openexr", "png", "pnm", "qoi", "tga", "tiff", "webp"]
     pub fn new(format: Option<Format>, buffer: &[u8]) -> Result<Self, Error> {
+        Recoder::new_with_auto_orient(format, buffer, false)
+    }
+
+    /// Decodes with the given format without guessing, for sources whose declared content type
+    /// is trusted more than their leading bytes. Formats the image library can't decode (i.e.
+    /// `Raw`) are `UnsupportedFormat` before the bytes are looked at.
+    ///
+    /// Bytes which don't decode as the format are a `Decode` error, when the content looks like
+    /// another format the reason names both.
+    pub fn new_with_format(buffer: &[u8], format: Format) -> Result<Self, Error> {
+        image::ImageFormat::try_from(format)?;
+        Recoder::new(Some(format), buffer).map_err(|err| match (err, image::guess_format(buffer)) {
+            (Error::Decode { format: hinted, reason, source }, Ok(guessed)) => {
+                let reason = match Format::try_from(guessed) {
+                    Ok(guessed) if Some(guessed) != hinted => {
+                        format!("content looks like {guessed:?} but was decoded as {format:?}: {reason}")
+                    }
+                    _ => reason,
+                };
+                Error::Decode { format: hinted, reason, source }
+            }
+            (err, _) => err,
+        })
+    }
+
+    /// Decodes as `new`, recording the EXIF orientation of JPEG, TIFF, and WebP sources. With
+    /// auto_orient the orientation is applied immediately, otherwise it's only recorded and can
+    /// be applied later through `apply_orientation`.
+    pub fn new_with_auto_orient(format: Option<Format>, buffer: &[u8], auto_orient: bool) -> Result<Self, Error> {
+        Recoder::decode(format, buffer, auto_orient, &Limits::default(), AnimationPolicy::default())
+    }
+
+    /// Decodes as `new` under the given limits instead of the defaults, i.e. `Limits::unlimited`
+    /// for trusted inputs which may be larger.
+    pub fn new_with_limits(format: Option<Format>, buffer: &[u8], limits: &Limits) -> Result<Self, Error> {
+        Recoder::decode(format, buffer, false, limits, AnimationPolicy::default())
+    }
+
+    /// Decodes as `new` with control over animated GIF and WebP sources, which otherwise keep
+    /// only their first frame. See `AnimationPolicy`.
+    pub fn new_with_animation_policy(
+        format: Option<Format>,
+        buffer: &[u8],
+        animation: AnimationPolicy,
+    ) -> Result<Self, Error> {
+        Recoder::decode(format, buffer, false, &Limits::default(), animation)
+    }
+
+    /// Decodes from a stream such as an upload body, the image library buffers only what it
+    /// needs. Failures reading the stream are `Io` errors, failures in the data are `Decode`.
+    ///
+    /// The format is guessed from the leading bytes, and the EXIF orientation is recorded as in
+    /// `new`.
+    pub fn from_reader<R: BufRead + Seek>(reader: R) -> Result<Self, Error> {
+        Recoder::from_reader_with_limits(reader, &Limits::default())
+    }
+
+    /// Decodes from a stream as `from_reader` under the given limits, the input size is measured
+    /// by seeking to the end of the stream and back.
+    pub fn from_reader_with_limits<R: BufRead + Seek>(mut reader: R, limits: &Limits) -> Result<Self, Error> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+        limits.check_input(end.saturating_sub(start))?;
+        let reader = image::ImageReader::new(reader).with_guessed_format()?;
+        let recoder = Recoder::decode_with(reader, None, false, limits, AnimationPolicy::default())?;
+        Ok(Recoder {
+            input_bytes: usize::try_from(end - start).unwrap_or(usize::MAX),
+            ..recoder
+        })
+    }
+
+    /// Decodes every frame of an animated GIF or WebP, or every page of a TIFF, into its own
+    /// Recoder under the default limits. Any other format, or a still GIF or WebP, gives one.
+    ///
+    /// Animation frames are the full canvas as displayed, in RGBA8, while TIFF pages keep their
+    /// own dimensions and color type. More than `Limits::max_frames` is `LimitsExceeded`.
+    pub fn split_frames(buffer: &[u8]) -> Result<Vec<Recoder>, Error> {
+        Recoder::split_frames_with_limits(buffer, &Limits::default())
+    }
+
+    /// Splits as `split_frames` under the given limits, the pixel limit applies to all of the
+    /// frames together
+    pub fn split_frames_with_limits(buffer: &[u8], limits: &Limits) -> Result<Vec<Recoder>, Error> {
+        Recoder::decode_pages(buffer, limits, None).map(|(pages, _)| pages)
+    }
+
+    /// Decodes only the frame or page at the index (from 0), as `split_frames` would give it.
+    /// TIFF pages before it are skipped without decoding their pixels, animation frames build on
+    /// each other so the earlier ones are decoded but dropped.
+    pub fn new_frame(buffer: &[u8], index: usize) -> Result<Recoder, Error> {
+        let (mut pages, count) = Recoder::decode_pages(buffer, &Limits::default(), Some(index))?;
+        pages.pop().ok_or(Error::FrameOutOfRange { index, count })
+    }
+
+    // Decodes every frame, or only the one at the index, along with the number of frames seen
+    fn decode_pages(buffer: &[u8], limits: &Limits, only: Option<usize>) -> Result<(Vec<Recoder>, usize), Error> {
+        limits.check_input(buffer.len() as u64)?;
+        let format = image::guess_format(buffer)
+            .map_err(|err| Error::decode(None, err))
+            .and_then(Format::try_from)?;
+        let decode_error = |err| Error::decode(Some(format), err);
+        let cursor = std::io::Cursor::new(buffer);
+        match format {
+            Format::Gif => {
+                let mut decoder = GifDecoder::new(cursor).map_err(decode_error)?;
+                prepare_frames(&mut decoder, format, limits)?;
+                split_animation(decoder.into_frames(), format, limits, only)
+            }
+            Format::WebP => {
+                let mut decoder = WebPDecoder::new(cursor).map_err(decode_error)?;
+                if decoder.has_animation() {
+                    prepare_frames(&mut decoder, format, limits)?;
+                    return split_animation(decoder.into_frames(), format, limits, only);
+                }
+                Recoder::split_still(format, buffer, limits, only)
+            }
+            Format::Tiff => split_tiff(cursor, limits, only),
+            _ => Recoder::split_still(format, buffer, limits, only),
+        }
+    }
+
+    fn split_still(format: Format, buffer: &[u8], limits: &Limits, only: Option<usize>) -> Result<(Vec<Recoder>, usize), Error> {
+        match only {
+            Some(index) if index > 0 => Ok((Vec::new(), 1)),
+            _ => Ok((vec![Recoder::new_with_limits(Some(format), buffer, limits)?], 1)),
+        }
+    }
+
+    fn decode(
+        format: Option<Format>,
+        buffer: &[u8],
+        auto_orient: bool,
+        limits: &Limits,
+        animation: AnimationPolicy,
+    ) -> Result<Self, Error> {
+        limits.check_input(buffer.len() as u64)?;
         // let format = match format {
         //     Some(f) => f,
         //     None => {
@@ -40,7 +213,7 @@ impl Recoder {
         let cursor = std::io::Cursor::new(buffer);
         let reader = match format {
             Some(f) => {
-                image::ImageReader::with_format(cursor, f.into())
+                image::ImageReader::with_format(cursor, f.try_into()?)
                     // .with_guessed_format()
                     // .expect("Cursor io never fails")
             }
@@ -50,24 +223,292 @@ impl Recoder {
                     .expect("Cursor io never fails")
             }
         };
+        let orientation = match reader.format().map(Format::try_from) {
+            Some(Ok(format)) => Some(Orientation::read(format, buffer)),
+            _ => None,
+        };
+        let recoder = Recoder::decode_with(reader, orientation, auto_orient, limits, animation)?;
+        Ok(Recoder {
+            input_bytes: buffer.len(),
+            ..recoder
+        })
+    }
+
+    // GIF and animated WebP are decoded frame by frame so the frames can be counted, everything
+    // else goes through the decoder the image library picks
+    fn decode_with<R: BufRead + Seek>(
+        mut reader: image::ImageReader<R>,
+        orientation: Option<Orientation>,
+        auto_orient: bool,
+        limits: &Limits,
+        animation: AnimationPolicy,
+    ) -> Result<Self, Error> {
         let format = match reader.format() {
-            Some(f) => f,
+            Some(f) => Format::try_from(f)?,
             None => {
-                return Err(Error::LoadError);
+                return Err(Error::Decode {
+                    format: None,
+                    reason: "unrecognized image format".to_owned(),
+                    source: None,
+                });
             }
         };
-        // assert_eq!(reader.format(), format);
-        let dynamic_image = reader.decode()?;
-        let (width, height) = (dynamic_image.width(), dynamic_image.height());
-        let color = dynamic_image.color();
-        let data =  dynamic_image.as_bytes().to_vec();
+        let decode_error = |err| Error::decode(Some(format), err);
+        reader.no_limits();
+        let recoder = match format {
+            Format::Gif => {
+                let mut decoder = GifDecoder::new(reader.into_inner()).map_err(decode_error)?;
+                let header = prepare(&mut decoder, format, orientation, limits)?;
+                decode_frames(decoder.into_frames(), header, format, limits, animation)?
+            }
+            Format::WebP => {
+                let mut decoder = WebPDecoder::new(reader.into_inner()).map_err(decode_error)?;
+                let header = prepare(&mut decoder, format, orientation, limits)?;
+                if decoder.has_animation() {
+                    decode_frames(decoder.into_frames(), header, format, limits, animation)?
+                } else {
+                    decode_still(decoder, header, format)?
+                }
+            }
+            _ => {
+                let mut decoder = reader.into_decoder().map_err(decode_error)?;
+                let header = prepare(&mut decoder, format, orientation, limits)?;
+                decode_still(decoder, header, format)?
+            }
+        };
+
+        if auto_orient {
+            recoder.apply_orientation()
+        } else {
+            Ok(recoder)
+        }
+    }
 
+    /// Reads the format, dimensions, and color type from the image header without decoding the
+    /// pixel data, so a truncated upload still probes as long as its header is intact.
+    ///
+    /// Only PNG, JPEG, WebP, and GIF are probed, anything else is `UnsupportedFormat`.
+    pub fn probe(buffer: &[u8]) -> Result<Meta, Error> {
+        let reader = image::ImageReader::new(std::io::Cursor::new(buffer))
+            .with_guessed_format()
+            .expect("Cursor io never fails");
+        let format = match reader.format().map(Format::try_from) {
+            Some(Ok(f @ (Format::Png | Format::Jpeg | Format::WebP | Format::Gif))) => f,
+            _ => return Err(Error::UnsupportedFormat),
+        };
+        let decoder = reader.into_decoder().map_err(|err| Error::decode(Some(format), err))?;
+        let (width, height) = decoder.dimensions();
+        Ok(Meta {
+            format,
+            width,
+            height,
+            color: decoder.color_type().into(),
+        })
+    }
+
+    /// Wraps pixels produced elsewhere (e.g. an `image::ImageBuffer`) so they can be encoded
+    /// without a round trip through a file format, the source format is reported as `Raw`.
+    ///
+    /// Rows are tightly packed and multi-byte channels are in native endian order, the length
+    /// must be exactly width * height * bytes per pixel.
+    pub fn from_parts(width: u32, height: u32, color: ColorType, data: Vec<u8>) -> Result<Self, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(usize::from(color.bytes_per_pixel())))
+            .ok_or(Error::DimensionsTooLarge { width, height, max: MAX_DIMENSION })?;
+        if data.len() != expected {
+            return Err(Error::BufferMismatch { expected, actual: data.len() });
+        }
         Ok(Recoder {
-            format: format.into(),//: reader.format().expect("Should have figured"),//: format.try_into()?,
+            format: Format::Raw,
             width,
             height,
-            color: color.into(),
+            color,
             data,
+            orientation: Orientation::Normal,
+            oriented: false,
+            icc_profile: None,
+            frame_count: 1,
+            frames: Vec::new(),
+            input_bytes: expected,
+        })
+    }
+
+    /// The format the pixels were decoded from
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn color(&self) -> ColorType {
+        self.color
+    }
+
+    /// The raw pixel buffer, see `from_parts` for the layout
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The raw pixel buffer, as `as_bytes`
+    pub fn pixels(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Rebuilds an `image::DynamicImage` over a copy of the pixels for operations this crate
+    /// doesn't offer, `from_dynamic_image` wraps the result again
+    pub fn to_dynamic_image(&self) -> Result<DynamicImage, Error> {
+        self.to_image()
+    }
+
+    /// Wraps a `DynamicImage` as `from_parts` does, the pixel bytes are kept exactly (16-bit and
+    /// float channels in native endian order) so a round trip through `to_dynamic_image` is
+    /// lossless for every color type.
+    pub fn from_dynamic_image(image: &DynamicImage) -> Result<Self, Error> {
+        let (width, height) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        Ok(Recoder {
+            input_bytes: image.as_bytes().len(),
+            ..Recoder::from_image(Format::Raw, image)
+        })
+    }
+
+    // Captures the pixel buffer of a decoded image
+    fn from_image(format: Format, image: &DynamicImage) -> Self {
+        Recoder {
+            format,
+            width: image.width(),
+            height: image.height(),
+            color: image.color().into(),
+            data: image.as_bytes().to_vec(),
+            orientation: Orientation::Normal,
+            oriented: false,
+            icc_profile: None,
+            frame_count: 1,
+            frames: Vec::new(),
+            input_bytes: 0,
+        }
+    }
+
+    // Wraps the result of an operation, carrying over the details of this source. Operations
+    // only apply to the first frame so any preserved animation frames are dropped.
+    pub(crate) fn with_image(&self, image: &DynamicImage) -> Self {
+        Recoder {
+            orientation: self.orientation,
+            oriented: self.oriented,
+            icc_profile: self.icc_profile.clone(),
+            frame_count: self.frame_count,
+            input_bytes: self.input_bytes,
+            ..Recoder::from_image(self.format, image)
+        }
+    }
+
+    /// Number of frames in the source, more than 1 for animated GIF and WebP
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    /// Every frame of the animation when decoded with `AnimationPolicy::PreserveAnimation`,
+    /// otherwise empty
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// Encodes an infinitely looping GIF from the preserved frames and their delays, a still
+    /// image (or one decoded without preserving its frames) becomes a single frame GIF.
+    ///
+    /// The image library can't encode animated WebP so GIF is the only animated output.
+    pub fn to_animated_gif(&self, options: &RecodeOptions) -> Result<Outcome, Error> {
+        let timer = Timer::start();
+        self.check_max_dimensions(options)?;
+        let frames = if self.frames.is_empty() {
+            vec![Frame::new(self.to_image()?.to_rgba8())]
+        } else {
+            self.frames
+                .iter()
+                .map(|frame| {
+                    let buffer = RgbaImage::from_raw(self.width, self.height, frame.data.clone()).ok_or(
+                        Error::BufferMismatch {
+                            expected: self.width as usize * self.height as usize * 4,
+                            actual: frame.data.len(),
+                        },
+                    )?;
+                    Ok(Frame::from_parts(buffer, 0, 0, Delay::from_numer_denom_ms(frame.delay_ms, 1)))
+                })
+                .collect::<Result<Vec<_>, Error>>()?
+        };
+        let mut out_buffer = Vec::<u8>::new();
+        {
+            let mut gif_encoder = GifEncoder::new(&mut out_buffer);
+            gif_encoder.set_repeat(Repeat::Infinite)?;
+            gif_encoder.encode_frames(frames)?;
+        }
+        Ok(Outcome {
+            color: ColorType::Rgba8,
+            encode_millis: timer.millis(),
+            ..self.to_outcome(Format::Gif, out_buffer)
+        })
+    }
+
+    /// The ICC color profile captured from the source, if it had one
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.icc_profile.as_deref()
+    }
+
+    // Refuses to encode beyond the maximum output dimensions in the options
+    fn check_max_dimensions(&self, options: &RecodeOptions) -> Result<(), Error> {
+        let too_wide = options.max_width.is_some_and(|max| self.width > max);
+        let too_tall = options.max_height.is_some_and(|max| self.height > max);
+        if too_wide || too_tall {
+            let max = if too_wide { options.max_width } else { options.max_height };
+            return Err(Error::DimensionsTooLarge {
+                width: self.width,
+                height: self.height,
+                max: max.unwrap_or_default(),
+            });
+        }
+        Ok(())
+    }
+
+    // Hands the source ICC profile to the encoder when the metadata policy allows it. Refusing it
+    // as unsupported is the only error an encoder gives, then the profile is dropped as
+    // `MetadataPolicy` documents rather than failing the whole recode.
+    fn attach_icc_profile(&self, encoder: &mut impl ImageEncoder, options: &RecodeOptions) {
+        if let (MetadataPolicy::Preserve, Some(profile)) = (options.metadata, &self.icc_profile) {
+            let _ = encoder.set_icc_profile(profile.clone());
+        }
+    }
+
+    // Rebuilds a DynamicImage over a copy of the pixel buffer so the image crate operations can
+    // be applied. Multi-byte channels are stored in native endian order, as `as_bytes` gives them.
+    pub(crate) fn to_image(&self) -> Result<DynamicImage, Error> {
+        let (width, height, data) = (self.width, self.height, self.data.clone());
+        let image = match self.color {
+            ColorType::L8 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+            ColorType::La8 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8),
+            ColorType::Rgb8 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+            ColorType::Rgba8 => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8),
+            ColorType::L16 => ImageBuffer::from_raw(width, height, to_u16s(&data)).map(DynamicImage::ImageLuma16),
+            ColorType::La16 => ImageBuffer::from_raw(width, height, to_u16s(&data)).map(DynamicImage::ImageLumaA16),
+            ColorType::Rgb16 => ImageBuffer::from_raw(width, height, to_u16s(&data)).map(DynamicImage::ImageRgb16),
+            ColorType::Rgba16 => ImageBuffer::from_raw(width, height, to_u16s(&data)).map(DynamicImage::ImageRgba16),
+            ColorType::Rgb32F => ImageBuffer::from_raw(width, height, to_f32s(&data)).map(DynamicImage::ImageRgb32F),
+            ColorType::Rgba32F => ImageBuffer::from_raw(width, height, to_f32s(&data)).map(DynamicImage::ImageRgba32F),
+        };
+        image.ok_or(Error::BufferMismatch {
+            expected: width as usize * height as usize * usize::from(self.color.bytes_per_pixel()),
+            actual: self.data.len(),
         })
     }
 
@@ -78,73 +519,1739 @@ impl Recoder {
             height: self.height,
             dest: new_format,
             data: new_data,
+            oriented: self.oriented,
+            optimization: None,
+            color: self.color,
+            input_bytes: self.input_bytes,
+            encode_millis: 0,
+            progressive: false,
+            interlaced: false,
+            budget: None,
         }
     }
 
-    pub fn to_png(&self) -> Result<Outcome, Error> {
+    /// The EXIF orientation which still has to be applied to display the pixels upright
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Returns a new Recoder with the EXIF orientation baked into the pixel buffer, after which
+    /// the orientation reads as `Normal`.
+    pub fn apply_orientation(&self) -> Result<Recoder, Error> {
+        if self.orientation == Orientation::Normal {
+            return Ok(self.clone());
+        }
+        let image = self.orientation.apply(self.to_image()?);
+
+        Ok(Recoder {
+            orientation: Orientation::Normal,
+            oriented: true,
+            ..self.with_image(&image)
+        })
+    }
+
+    fn to_written(&self, new_format: Format, color: ColorType, bytes_written: u64, timer: Timer) -> Written {
+        Written {
+            src: self.format,
+            dest: new_format,
+            width: self.width,
+            height: self.height,
+            bytes_written,
+            oriented: self.oriented,
+            color,
+            input_bytes: self.input_bytes,
+            encode_millis: timer.millis(),
+            progressive: false,
+            interlaced: false,
+        }
+    }
+
+    pub fn to_png(&self, options: &RecodeOptions) -> Result<Outcome, Error> {
         // Make a buffer to write into
         let mut out_buffer = Vec::<u8>::new();
-        // Setup the encoder with fast and no filter to try and avoid any compression or other data loss
-        let png_encoder = PngEncoder::new_with_quality(
-            &mut out_buffer,
-            CompressionType::Best,
-            FilterType::NoFilter,
+        let written = self.write_png(&mut out_buffer, options)?;
+        Ok(written.with_data(out_buffer))
+    }
+
+    /// Encodes a PNG straight into the writer, failures of the writer are `Io` errors
+    ///
+    /// `interlace` in the options is only honored with the "oxipng" feature, otherwise the PNG is
+    /// written sequentially and `interlaced` stays false in the result.
+    pub fn write_png<W: Write>(&self, writer: W, options: &RecodeOptions) -> Result<Written, Error> {
+        let timer = Timer::start();
+        self.check_max_dimensions(options)?;
+        #[cfg(feature = "oxipng")]
+        if options.interlace {
+            return self.write_png_interlaced(writer, options, timer);
+        }
+        let mut writer = CountingWriter::new(writer);
+        self.encode_png(&mut writer, options)?;
+
+        Ok(self.to_written(Format::Png, self.color, writer.count, timer))
+    }
+
+    fn encode_png<W: Write>(&self, writer: W, options: &RecodeOptions) -> Result<(), Error> {
+        let mut png_encoder = PngEncoder::new_with_quality(
+            writer,
+            options.png_compression.into(),
+            options.png_filter.into(),
         );
+        self.attach_icc_profile(&mut png_encoder, options);
         // Try to write the image as a PNG to the buffer
         // png_encoder.write_image(image_16bit.as_bytes(), width, height, ColorType::Rgba16)?;
         // png_encoder.write_image(image_16bit.as_bytes(), width, height, ExtendedColorType::Rgba16)?;
         png_encoder.write_image(&self.data, self.width, self.height, self.color.into())?;
+        Ok(())
+    }
+
+    // The image PNG encoder can't write Adam7 so oxipng rewrites the sequential output, keeping
+    // the color type and chunks. If oxipng fails the sequential bytes are written instead.
+    #[cfg(feature = "oxipng")]
+    fn write_png_interlaced<W: Write>(&self, mut writer: W, options: &RecodeOptions, timer: Timer) -> Result<Written, Error> {
+        let mut sequential = Vec::new();
+        self.encode_png(&mut sequential, options)?;
+        let mut oxipng_options = oxipng::Options::from_preset(0);
+        oxipng_options.interlace = Some(oxipng::Interlacing::Adam7);
+        oxipng_options.strip = oxipng::StripChunks::None;
+        oxipng_options.bit_depth_reduction = false;
+        oxipng_options.color_type_reduction = false;
+        oxipng_options.palette_reduction = false;
+        oxipng_options.grayscale_reduction = false;
+        oxipng_options.force = true;
+        let (bytes, interlaced) = match oxipng::optimize_from_memory(&sequential, &oxipng_options) {
+            Ok(interlaced) => (interlaced, true),
+            Err(_) => (sequential, false),
+        };
+        writer.write_all(&bytes)?;
 
-        Ok(self.to_outcome(Format::Png, out_buffer))
+        Ok(Written {
+            interlaced,
+            ..self.to_written(Format::Png, self.color, bytes.len() as u64, timer)
+        })
     }
 
-    pub fn to_webp(&self) -> Result<Outcome, Error> {
+    /// Encodes a lossless WebP, the quality option doesn't apply
+    pub fn to_webp(&self, options: &RecodeOptions) -> Result<Outcome, Error> {
         // Make a buffer to write into
         let mut out_buffer = Vec::<u8>::new();
-        let webp_encoder = WebPEncoder::new_lossless(&mut out_buffer);
+        let written = self.write_webp(&mut out_buffer, options)?;
+        Ok(written.with_data(out_buffer))
+    }
+
+    /// Encodes a lossless WebP straight into the writer, failures of the writer are `Io` errors
+    pub fn write_webp<W: Write>(&self, writer: W, options: &RecodeOptions) -> Result<Written, Error> {
+        let timer = Timer::start();
+        self.check_max_dimensions(options)?;
+        let mut writer = CountingWriter::new(writer);
+        let mut webp_encoder = WebPEncoder::new_lossless(&mut writer);
+        self.attach_icc_profile(&mut webp_encoder, options);
         // Try to write the image as a WebP to the buffer
         webp_encoder.write_image(&self.data, self.width, self.height, self.color.into())?;
 
-        Ok(self.to_outcome(Format::WebP, out_buffer))
-    }
-}
-
-// pub fn to_webp_enhanced(&self, buffer: &[u8]) -> Result<Outcome, Error> {
-//     let base_webp = self.to_webp(buffer)?;
-//     // let options = Options {
-//     //     fix_errors: false,
-//     //     force: false,
-//     //     filter: indexset! {RowFilter::None, RowFilter::Sub, RowFilter::Entropy, RowFilter::Bigrams},
-//     //     interlace: Some(Interlacing::None),
-//     //     optimize_alpha: false,
-//     //     bit_depth_reduction: true,
-//     //     color_type_reduction: true,
-//     //     palette_reduction: true,
-//     //     grayscale_reduction: true,
-//     //     idat_recoding: true,
-//     //     scale_16: false,
-//     //     strip: StripChunks::None,
-//     //     deflate: Deflaters::Libdeflater { compression: 11 },
-//     //     fast_evaluation: true,
-//     //     timeout: None,
-//     // };
-//     // Create custom options for Oxipng
-//     let options = Options {
-//         // strip: Some(oxipng::Metadata::All),
-//         strip: StripChunks::All,
-//         // interlace: Some(1),
-//         interlace: Some(Interlacing::Adam7),
-//         // compression: Deflate::new(9),
-//         deflate: Deflaters::Libdeflater { compression: 9 },
-//         // filter: vec![FilterType::Paeth],
-//         filter: indexset! {RowFilter::None, RowFilter::Sub, RowFilter::Entropy, RowFilter::Bigrams},
-//         ..Options::default()
-//     };
-//     let png_data = PngData::from_slice(&base_webp.data, &options)?;
-//     let output = png_data.output();
-//     Ok(Outcome::new(
-//         base_webp.src,
-//         base_webp.dest,
-//         output,
-//     ))
-// }
+        Ok(self.to_written(Format::WebP, self.color, writer.count, timer))
+    }
+
+    /// Returns a new Recoder over the resized pixels, see `Fit` for how the aspect ratio is
+    /// treated. Fails if either dimension is zero or larger than `MAX_DIMENSION`.
+    pub fn resize(&self, width: u32, height: u32, fit: Fit, filter: Filter) -> Result<Recoder, Error> {
+        self.resize_within(width, height, fit, filter, MAX_DIMENSION)
+    }
+
+    /// Resizes as `resize` but with a caller supplied bound on the output width and height
+    pub fn resize_within(
+        &self,
+        width: u32,
+        height: u32,
+        fit: Fit,
+        filter: Filter,
+        max_dimension: u32,
+    ) -> Result<Recoder, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        // Contain never produces more than was asked for, so checking the request covers all fits
+        if width > max_dimension || height > max_dimension {
+            return Err(Error::DimensionsTooLarge { width, height, max: max_dimension });
+        }
+        let image = self.to_image()?;
+        let resized = match fit {
+            Fit::Exact => image.resize_exact(width, height, filter.into()),
+            Fit::Contain => image.resize(width, height, filter.into()),
+            Fit::Cover => image.resize_to_fill(width, height, filter.into()),
+        };
+
+        Ok(self.with_image(&resized))
+    }
+
+    /// Returns a new Recoder over the pixels within the given rectangle, which must have a
+    /// non-zero area and lie entirely within the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Recoder, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidDimensions { width, height });
+        }
+        let fits = |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if !fits(x, width, self.width) || !fits(y, height, self.height) {
+            return Err(Error::OutOfBounds { x, y, width, height });
+        }
+        // Copy the covered span of each row, every pixel is bytes_per_pixel wide
+        let bpp = self.color.bytes_per_pixel() as usize;
+        let (x, y, width, height) = (x as usize, y as usize, width as usize, height as usize);
+        let stride = self.width as usize * bpp;
+        let mut data = Vec::with_capacity(width * height * bpp);
+        for row in y..y + height {
+            let start = row * stride + x * bpp;
+            data.extend_from_slice(&self.data[start..start + width * bpp]);
+        }
+
+        Ok(Recoder {
+            format: self.format,
+            width: width as u32,
+            height: height as u32,
+            color: self.color,
+            data,
+            orientation: self.orientation,
+            oriented: self.oriented,
+            icc_profile: self.icc_profile.clone(),
+            frame_count: self.frame_count,
+            frames: Vec::new(),
+            input_bytes: self.input_bytes,
+        })
+    }
+
+    /// Returns a new Recoder with the pixels converted to the target color type, flattening any
+    /// alpha onto `DEFAULT_BACKGROUND` (white) when the target has none.
+    ///
+    /// See `convert_color_with_background` for the supported conversions.
+    pub fn convert_color(&self, target: ColorType) -> Result<Recoder, Error> {
+        self.convert_color_with_background(target, DEFAULT_BACKGROUND)
+    }
+
+    /// Returns a new Recoder with the pixels converted to the target color type.
+    ///
+    /// - Color to gray uses Rec. 709 luminance weighting
+    /// - Removing alpha blends each pixel onto the background color rather than discarding it,
+    ///   adding alpha makes every pixel opaque
+    /// - The bit depth per channel may be kept or reduced (i.e. 16 to 8 bit) but never increased
+    ///   since that only inflates the buffer, those conversions are refused
+    pub fn convert_color_with_background(&self, target: ColorType, background: [u8; 3]) -> Result<Recoder, Error> {
+        if target.bits_per_channel() > self.color.bits_per_channel() {
+            return Err(Error::UnsupportedConversion { from: self.color, to: target });
+        }
+        if target == self.color {
+            return Ok(self.clone());
+        }
+        let mut image = self.to_image()?;
+        if self.color.has_alpha() && !target.has_alpha() {
+            image = flatten(&image, background);
+        }
+        let converted = match target {
+            ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+            ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+            ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+            ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+            ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+            ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+            ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+            ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+            ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+            ColorType::Rgba32F => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        };
+
+        Ok(self.with_image(&converted))
+    }
+
+    /// Computes a BlurHash placeholder with the given number of components on each axis, which
+    /// must be within 1..=9. Larger sources are downscaled to at most 64 pixels a side first, the
+    /// hash only carries a handful of colors so the detail is lost anyway.
+    pub fn blurhash(&self, x_components: u32, y_components: u32) -> Result<String, Error> {
+        let valid = 1..=crate::MAX_COMPONENTS;
+        if !valid.contains(&x_components) || !valid.contains(&y_components) {
+            return Err(Error::InvalidComponents { x: x_components, y: y_components });
+        }
+        let mut image = self.to_image()?;
+        if image.width() > BLURHASH_MAX_DIMENSION || image.height() > BLURHASH_MAX_DIMENSION {
+            image = image.resize(BLURHASH_MAX_DIMENSION, BLURHASH_MAX_DIMENSION, Filter::Triangle.into());
+        }
+        Ok(crate::blurhash::encode(x_components, y_components, &image.to_rgb8()))
+    }
+
+    /// Builds a multi-size icon (i.e. a favicon with 16, 32, and 48) with one PNG entry per size,
+    /// non-square sources are center-cropped. Sizes must be within 1..=256.
+    pub fn to_ico(&self, sizes: &[u32]) -> Result<Outcome, Error> {
+        let timer = Timer::start();
+        if sizes.is_empty() {
+            return Err(Error::InvalidDimensions { width: 0, height: 0 });
+        }
+        let entries = sizes
+            .iter()
+            .map(|&size| {
+                let image = self
+                    .resize_within(size, size, Fit::Cover, Filter::Lanczos3, MAX_ICO_DIMENSION)?
+                    .to_image()?
+                    .to_rgba8();
+                let mut png = Vec::new();
+                PngEncoder::new(&mut png).write_image(image.as_raw(), size, size, image::ExtendedColorType::Rgba8)?;
+                Ok((size, png))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let largest = sizes.iter().copied().max().unwrap_or_default();
+        Ok(Outcome {
+            width: largest,
+            height: largest,
+            color: ColorType::Rgba8,
+            encode_millis: timer.millis(),
+            ..self.to_outcome(Format::Ico, crate::ico::assemble(&entries))
+        })
+    }
+
+    /// Downscales (never upscales) so neither side exceeds max_dimension, preserving the aspect
+    /// ratio, and encodes the result in the requested format. Sources already within bounds are
+    /// only re-encoded. The EXIF orientation is applied first unless disabled in the options.
+    ///
+    /// Unless overridden in the options a fast filter is used for large downscale ratios, where
+    /// the extra sharpness is lost anyway, and Lanczos3 when the sizes are close.
+    pub fn thumbnail(&self, max_dimension: u32, format: Format, options: &ThumbnailOptions) -> Result<Outcome, Error> {
+        let oriented;
+        let source = if options.auto_orient && self.orientation != Orientation::Normal {
+            oriented = self.apply_orientation()?;
+            &oriented
+        } else {
+            self
+        };
+        let largest = source.width.max(source.height);
+        let recode_options = RecodeOptions {
+            quality: options.quality,
+            ..RecodeOptions::default()
+        };
+        if largest <= max_dimension {
+            return source.to_format(format, &recode_options);
+        }
+        let filter = options.filter.unwrap_or(if largest > max_dimension.saturating_mul(2) {
+            Filter::Triangle
+        } else {
+            Filter::Lanczos3
+        });
+        let resized = source.resize(max_dimension, max_dimension, Fit::Contain, filter)?;
+        resized.to_format(format, &recode_options)
+    }
+
+    /// Encodes to a format chosen at runtime (e.g. from an Accept header), formats which can be
+    /// decoded but not encoded (see `Format::is_encodable`) are `UnsupportedFormat`.
+    ///
+    /// The quality applies to JPEG, the compression to PNG. WebP is always lossless.
+    pub fn to_format(&self, format: Format, options: &RecodeOptions) -> Result<Outcome, Error> {
+        let mut out_buffer = Vec::<u8>::new();
+        let written = self.write_format(&mut out_buffer, format, options)?;
+        Ok(written.with_data(out_buffer))
+    }
+
+    /// Encodes as `to_format` straight into the writer
+    pub fn write_format<W: Write>(&self, writer: W, format: Format, options: &RecodeOptions) -> Result<Written, Error> {
+        match format {
+            Format::Png => self.write_png(writer, options),
+            Format::WebP => self.write_webp(writer, options),
+            Format::Jpeg => self.write_jpeg(writer, options),
+            _ => Err(Error::UnsupportedFormat),
+        }
+    }
+
+    /// Encodes a baseline JPEG at the quality from the options, which must be within 1..=100.
+    ///
+    /// JPEG can't carry an alpha channel so any alpha is dropped, or blended onto the background
+    /// when the options have one, and 16-bit or float buffers are reduced to 8 bits per channel.
+    pub fn to_jpeg(&self, options: &RecodeOptions) -> Result<Outcome, Error> {
+        // Make a buffer to write into
+        let mut out_buffer = Vec::<u8>::new();
+        let written = self.write_jpeg(&mut out_buffer, options)?;
+        Ok(written.with_data(out_buffer))
+    }
+
+    /// Encodes a JPEG as `to_jpeg` straight into the writer, failures of the writer are `Io` errors
+    pub fn write_jpeg<W: Write>(&self, writer: W, options: &RecodeOptions) -> Result<Written, Error> {
+        let timer = Timer::start();
+        let quality = options.quality;
+        if !(1..=100).contains(&quality) {
+            return Err(Error::InvalidQuality(quality));
+        }
+        self.check_max_dimensions(options)?;
+        let mut image = self.to_image()?;
+        if let (true, Some(background)) = (self.color.has_alpha(), options.background) {
+            image = flatten(&image, background);
+        }
+        // Reduce to one of the color types the JPEG encoder accepts
+        let image = match image {
+            image if self.color.has_color() => DynamicImage::ImageRgb8(image.to_rgb8()),
+            image => DynamicImage::ImageLuma8(image.to_luma8()),
+        };
+        #[cfg(feature = "jpeg-encoder")]
+        if options.progressive {
+            return self.write_progressive_jpeg(writer, &image, quality, options, timer);
+        }
+        let mut writer = CountingWriter::new(writer);
+        let mut jpeg_encoder = JpegEncoder::new_with_quality(&mut writer, quality);
+        self.attach_icc_profile(&mut jpeg_encoder, options);
+        // Try to write the image as a JPEG to the buffer
+        jpeg_encoder.write_image(image.as_bytes(), self.width, self.height, image.color().into())?;
+
+        Ok(self.to_written(Format::Jpeg, image.color().into(), writer.count, timer))
+    }
+
+    // The image JPEG encoder only writes baseline, jpeg-encoder is used for progressive output
+    #[cfg(feature = "jpeg-encoder")]
+    fn write_progressive_jpeg<W: Write>(
+        &self,
+        writer: W,
+        image: &DynamicImage,
+        quality: u8,
+        options: &RecodeOptions,
+        timer: Timer,
+    ) -> Result<Written, Error> {
+        let (Ok(width), Ok(height)) = (u16::try_from(self.width), u16::try_from(self.height)) else {
+            return Err(Error::DimensionsTooLarge {
+                width: self.width,
+                height: self.height,
+                max: u32::from(u16::MAX),
+            });
+        };
+        let color_type = match image.color().has_color() {
+            true => jpeg_encoder::ColorType::Rgb,
+            false => jpeg_encoder::ColorType::Luma,
+        };
+        let mut writer = CountingWriter::new(writer);
+        let mut jpeg_encoder = jpeg_encoder::Encoder::new(&mut writer, quality);
+        jpeg_encoder.set_progressive(true);
+        if let (MetadataPolicy::Preserve, Some(profile)) = (options.metadata, &self.icc_profile) {
+            jpeg_encoder.add_icc_profile(profile).map_err(jpeg_error)?;
+        }
+        jpeg_encoder.encode(image.as_bytes(), width, height, color_type).map_err(jpeg_error)?;
+
+        Ok(Written {
+            progressive: true,
+            ..self.to_written(Format::Jpeg, image.color().into(), writer.count, timer)
+        })
+    }
+}
+
+// Measures encode time, `Instant` panics on wasm32-unknown-unknown so there it always reads 0
+struct Timer {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+}
+
+impl Timer {
+    fn start() -> Self {
+        Timer {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn millis(&self) -> u64 {
+        0
+    }
+}
+
+// Tracks how many bytes the encoders have handed to the caller's writer
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        CountingWriter { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// What's read from the header before any pixels are decoded
+struct Header {
+    orientation: Orientation,
+    icc_profile: Option<Vec<u8>>,
+}
+
+// The dimensions from the header are checked before any pixels are decoded, the image library
+// then enforces the same limits while it allocates. Without a known orientation it's taken from
+// the EXIF metadata the decoder found.
+fn prepare<D: ImageDecoder>(
+    decoder: &mut D,
+    format: Format,
+    orientation: Option<Orientation>,
+    limits: &Limits,
+) -> Result<Header, Error> {
+    let decode_error = |err| Error::decode(Some(format), err);
+    let (width, height) = decoder.dimensions();
+    limits.check_dimensions(width, height)?;
+    decoder.set_limits(limits.to_image_limits()).map_err(decode_error)?;
+    let orientation = match orientation {
+        Some(orientation) => orientation,
+        None => decoder
+            .exif_metadata()
+            .map_err(decode_error)?
+            .map(|exif| Orientation::from_exif_metadata(&exif))
+            .unwrap_or_default(),
+    };
+    let icc_profile = decoder.icc_profile().map_err(decode_error)?;
+    Ok(Header { orientation, icc_profile })
+}
+
+// Refuses an oversized canvas before any frame is decoded
+fn prepare_frames<D: ImageDecoder>(decoder: &mut D, format: Format, limits: &Limits) -> Result<(), Error> {
+    let (width, height) = decoder.dimensions();
+    limits.check_dimensions(width, height)?;
+    decoder
+        .set_limits(limits.to_image_limits())
+        .map_err(|err| Error::decode(Some(format), err))
+}
+
+// Keeps each frame of an animation as its own image, or only the one at the index. Frames are
+// composited onto the canvas by the decoder so every one is the full size.
+fn split_animation(
+    frames: image::Frames,
+    format: Format,
+    limits: &Limits,
+    only: Option<usize>,
+) -> Result<(Vec<Recoder>, usize), Error> {
+    let mut recoders = Vec::new();
+    let mut pixels = 0u64;
+    let mut count = 0;
+    for frame in frames {
+        limits.check_frames(count as u64 + 1)?;
+        let frame = frame.map_err(|err| Error::decode(Some(format), err))?;
+        let index = count;
+        count += 1;
+        if only.is_none_or(|only| only == index) {
+            let buffer = frame.into_buffer();
+            pixels += u64::from(buffer.width()) * u64::from(buffer.height());
+            limits.check_pixels(pixels)?;
+            recoders.push(Recoder::from_image(format, &DynamicImage::ImageRgba8(buffer)));
+            if only.is_some() {
+                break;
+            }
+        }
+    }
+    Ok((recoders, count))
+}
+
+// The image library only reads the first page of a TIFF so the tiff decoder is used directly,
+// pages before the one asked for are skipped without reading their pixels
+fn split_tiff<R: std::io::Read + Seek>(
+    reader: R,
+    limits: &Limits,
+    only: Option<usize>,
+) -> Result<(Vec<Recoder>, usize), Error> {
+    let mut decoder = tiff::decoder::Decoder::new(reader).map_err(tiff_error)?;
+    let mut pages = Vec::new();
+    let mut pixels = 0u64;
+    let mut count = 0;
+    loop {
+        limits.check_frames(count as u64 + 1)?;
+        let index = count;
+        count += 1;
+        if only.is_none_or(|only| only == index) {
+            let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+            limits.check_dimensions(width, height)?;
+            pixels += u64::from(width) * u64::from(height);
+            limits.check_pixels(pixels)?;
+            pages.push(tiff_page(&mut decoder, width, height)?);
+            if only.is_some() {
+                break;
+            }
+        }
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(tiff_error)?;
+    }
+    Ok((pages, count))
+}
+
+fn tiff_page<R: std::io::Read + Seek>(
+    decoder: &mut tiff::decoder::Decoder<R>,
+    width: u32,
+    height: u32,
+) -> Result<Recoder, Error> {
+    use tiff::decoder::DecodingResult;
+    let unsupported = |what: String| Error::Decode {
+        format: Some(Format::Tiff),
+        reason: format!("unsupported {what}"),
+        source: None,
+    };
+    let color = match decoder.colortype().map_err(tiff_error)? {
+        tiff::ColorType::Gray(8) => ColorType::L8,
+        tiff::ColorType::GrayA(8) => ColorType::La8,
+        tiff::ColorType::RGB(8) => ColorType::Rgb8,
+        tiff::ColorType::RGBA(8) => ColorType::Rgba8,
+        tiff::ColorType::Gray(16) => ColorType::L16,
+        tiff::ColorType::GrayA(16) => ColorType::La16,
+        tiff::ColorType::RGB(16) => ColorType::Rgb16,
+        tiff::ColorType::RGBA(16) => ColorType::Rgba16,
+        tiff::ColorType::RGB(32) => ColorType::Rgb32F,
+        tiff::ColorType::RGBA(32) => ColorType::Rgba32F,
+        other => return Err(unsupported(format!("color type {other:?}"))),
+    };
+    let data = match decoder.read_image().map_err(tiff_error)? {
+        DecodingResult::U8(data) => data,
+        DecodingResult::U16(data) => data.iter().flat_map(|v| v.to_ne_bytes()).collect(),
+        DecodingResult::F32(data) if color.bits_per_channel() == 32 => data.iter().flat_map(|v| v.to_ne_bytes()).collect(),
+        _ => return Err(unsupported("sample format".to_owned())),
+    };
+    Ok(Recoder {
+        format: Format::Tiff,
+        ..Recoder::from_parts(width, height, color, data)?
+    })
+}
+
+fn tiff_error(err: tiff::TiffError) -> Error {
+    Error::Decode {
+        format: Some(Format::Tiff),
+        reason: err.to_string(),
+        source: None,
+    }
+}
+
+fn decode_still<D: ImageDecoder>(decoder: D, header: Header, format: Format) -> Result<Recoder, Error> {
+    let dynamic_image = DynamicImage::from_decoder(decoder).map_err(|err| Error::decode(Some(format), err))?;
+    Ok(Recoder {
+        orientation: header.orientation,
+        icc_profile: header.icc_profile,
+        ..Recoder::from_image(format, &dynamic_image)
+    })
+}
+
+// Counts every frame, keeping the first as the still image and the rest only when preserving the
+// animation. Preserved frames count against the pixel limit together.
+fn decode_frames(
+    frames: image::Frames,
+    header: Header,
+    format: Format,
+    limits: &Limits,
+    animation: AnimationPolicy,
+) -> Result<Recoder, Error> {
+    let mut first = None;
+    let mut kept = Vec::new();
+    let mut frame_count = 0u32;
+    for frame in frames {
+        let frame = frame.map_err(|err| Error::decode(Some(format), err))?;
+        frame_count += 1;
+        if frame_count > 1 && animation == AnimationPolicy::Error {
+            return Err(Error::Animated);
+        }
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        let buffer = frame.into_buffer();
+        if animation == AnimationPolicy::PreserveAnimation {
+            let pixels = u64::from(buffer.width()) * u64::from(buffer.height()) * u64::from(frame_count);
+            if pixels > limits.max_pixels {
+                return Err(Error::LimitsExceeded {
+                    what: crate::Limit::Pixels,
+                    limit: limits.max_pixels,
+                    actual: pixels,
+                });
+            }
+            kept.push(AnimationFrame {
+                delay_ms: numer / denom.max(1),
+                data: buffer.as_raw().clone(),
+            });
+        }
+        if first.is_none() {
+            first = Some(DynamicImage::ImageRgba8(buffer));
+        }
+    }
+    let first = first.ok_or_else(|| Error::Decode {
+        format: Some(format),
+        reason: "no frames".to_owned(),
+        source: None,
+    })?;
+    // A single frame is a still image either way
+    if frame_count == 1 {
+        kept.clear();
+    }
+    Ok(Recoder {
+        orientation: header.orientation,
+        icc_profile: header.icc_profile,
+        frame_count,
+        frames: kept,
+        ..Recoder::from_image(format, &first)
+    })
+}
+
+#[cfg(feature = "jpeg-encoder")]
+fn jpeg_error(err: jpeg_encoder::EncodingError) -> Error {
+    match err {
+        jpeg_encoder::EncodingError::IoError(err) => err.into(),
+        err => Error::Image { message: err.to_string(), source: None },
+    }
+}
+
+// Blends every pixel onto the opaque background color at float precision
+fn flatten(image: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let rgba = image.to_rgba32f();
+    let background = background.map(|c| f32::from(c) / 255.0);
+    let flattened = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: f32, bg: f32| c * a + bg * (1.0 - a);
+        Rgb([blend(r, background[0]), blend(g, background[1]), blend(b, background[2])])
+    });
+    DynamicImage::ImageRgb32F(flattened)
+}
+
+fn to_u16s(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|b| u16::from_ne_bytes([b[0], b[1]])).collect()
+}
+
+fn to_f32s(bytes: &[u8]) -> Vec<f32> {
+    bytes.chunks_exact(4).map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]])).collect()
+}
+
+#[cfg(feature = "oxipng")]
+impl Recoder {
+    /// Encodes as `to_png` and then runs oxipng over the result at the effort and strip settings
+    /// from `RecodeOptions::png_optimization`.
+    ///
+    /// Optimization never fails the encode, if oxipng errors or produces something larger the
+    /// unoptimized bytes are returned. Either way the outcome records both sizes.
+    pub fn to_png_optimized(&self, options: &RecodeOptions) -> Result<Outcome, Error> {
+        let timer = Timer::start();
+        let mut outcome = self.to_png(options)?;
+        let original_size = outcome.size();
+        let settings = &options.png_optimization;
+        let mut oxipng_options = oxipng::Options::from_preset(settings.level);
+        oxipng_options.strip = match settings.strip {
+            PngStrip::None => oxipng::StripChunks::None,
+            PngStrip::Safe => oxipng::StripChunks::Safe,
+            PngStrip::All => oxipng::StripChunks::All,
+        };
+        let optimization = match oxipng::optimize_from_memory(&outcome.data, &oxipng_options) {
+            Ok(optimized) if optimized.len() <= original_size => {
+                let optimized_size = optimized.len();
+                outcome.data = optimized;
+                Optimization { original_size, optimized_size, error: None }
+            }
+            Ok(_) => Optimization { original_size, optimized_size: original_size, error: None },
+            Err(err) => Optimization {
+                original_size,
+                optimized_size: original_size,
+                error: Some(err.to_string()),
+            },
+        };
+        outcome.optimization = Some(optimization);
+        outcome.encode_millis = timer.millis();
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::Limit;
+    use image::{GenericImageView, ImageFormat, Rgb, RgbImage, Rgba, RgbaImage};
+    use std::io::Cursor;
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        image.write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
+    }
+
+    fn solid_png(width: u32, height: u32, color: [u8; 3]) -> Vec<u8> {
+        encode(DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb(color))), ImageFormat::Png)
+    }
+
+    #[test]
+    fn encode_jpeg_with_matching_dimensions_and_colors() {
+        let recoder = Recoder::new(None, &solid_png(40, 24, [200, 100, 50])).unwrap();
+        let outcome = recoder.to_jpeg(&RecodeOptions::default().with_quality(95)).unwrap();
+        assert!(matches!(outcome.dest, Format::Jpeg));
+        assert_eq!(outcome.size(), outcome.data.len());
+
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::Jpeg).unwrap();
+        assert_eq!((40, 24), decoded.dimensions());
+        let pixel = decoded.to_rgb8().get_pixel(20, 12).0;
+        for (actual, expected) in pixel.iter().zip([200u8, 100, 50]) {
+            assert!(actual.abs_diff(expected) <= 4, "{pixel:?}");
+        }
+    }
+
+    #[test]
+    fn drop_alpha_when_encoding_jpeg() {
+        let image = RgbaImage::from_pixel(8, 8, Rgba([10, 20, 30, 0]));
+        let recoder = Recoder::new(None, &encode(DynamicImage::ImageRgba8(image), ImageFormat::Png)).unwrap();
+        let decoded = image::load_from_memory(&recoder.to_jpeg(&RecodeOptions::default().with_quality(90)).unwrap().data).unwrap();
+        assert_eq!(image::ColorType::Rgb8, decoded.color());
+    }
+
+    #[test]
+    fn resize_to_exact_dimensions() {
+        let recoder = Recoder::new(None, &solid_png(40, 20, [1, 2, 3])).unwrap();
+        let resized = recoder.resize(100, 30, Fit::Exact, Filter::Triangle).unwrap();
+        assert_eq!((100, 30), (resized.width, resized.height));
+    }
+
+    #[test]
+    fn resize_inside_box_preserving_aspect() {
+        let recoder = Recoder::new(None, &solid_png(40, 20, [1, 2, 3])).unwrap();
+        let resized = recoder.resize(50, 50, Fit::Contain, Filter::Lanczos3).unwrap();
+        assert_eq!((50, 25), (resized.width, resized.height));
+        let resized = recoder.resize(10, 50, Fit::Contain, Filter::Nearest).unwrap();
+        assert_eq!((10, 5), (resized.width, resized.height));
+    }
+
+    #[test]
+    fn resize_to_cover_box_with_center_crop() {
+        let recoder = Recoder::new(None, &solid_png(40, 20, [1, 2, 3])).unwrap();
+        let resized = recoder.resize(30, 30, Fit::Cover, Filter::Triangle).unwrap();
+        assert_eq!((30, 30), (resized.width, resized.height));
+        assert_eq!(resized.data.len(), 30 * 30 * 3);
+    }
+
+    #[test]
+    fn encode_resized_recoder() {
+        let recoder = Recoder::new(None, &solid_png(40, 20, [1, 2, 3])).unwrap();
+        let resized = recoder.resize(20, 10, Fit::Exact, Filter::Nearest).unwrap();
+        let decoded = image::load_from_memory(&resized.to_png(&RecodeOptions::default()).unwrap().data).unwrap();
+        assert_eq!((20, 10), decoded.dimensions());
+        assert_eq!((20, 10), image::load_from_memory(&resized.to_webp(&RecodeOptions::default()).unwrap().data).unwrap().dimensions());
+    }
+
+    #[test]
+    fn reject_zero_and_oversized_resize() {
+        let recoder = Recoder::new(None, &solid_png(4, 4, [0, 0, 0])).unwrap();
+        assert!(matches!(
+            recoder.resize(0, 10, Fit::Exact, Filter::Nearest),
+            Err(Error::InvalidDimensions { width: 0, height: 10 })
+        ));
+        assert!(matches!(
+            recoder.resize_within(200, 10, Fit::Contain, Filter::Nearest, 100),
+            Err(Error::DimensionsTooLarge { width: 200, height: 10, max: 100 })
+        ));
+    }
+
+    fn quadrants_png() -> Vec<u8> {
+        // 4x4 with a distinct color in each 2x2 quadrant
+        let image = RgbImage::from_fn(4, 4, |x, y| match (x < 2, y < 2) {
+            (true, true) => Rgb([255, 0, 0]),
+            (false, true) => Rgb([0, 255, 0]),
+            (true, false) => Rgb([0, 0, 255]),
+            (false, false) => Rgb([255, 255, 255]),
+        });
+        encode(DynamicImage::ImageRgb8(image), ImageFormat::Png)
+    }
+
+    #[test]
+    fn crop_each_corner() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let corners = [
+            ((0, 0), [255, 0, 0]),
+            ((2, 0), [0, 255, 0]),
+            ((0, 2), [0, 0, 255]),
+            ((2, 2), [255, 255, 255]),
+        ];
+        for ((x, y), color) in corners {
+            let cropped = recoder.crop(x, y, 2, 2).unwrap();
+            assert_eq!((2, 2), (cropped.width, cropped.height));
+            assert_eq!(color.repeat(4), cropped.data);
+        }
+    }
+
+    #[test]
+    fn crop_full_image_to_identical_bytes() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        assert_eq!(recoder.data, recoder.crop(0, 0, 4, 4).unwrap().data);
+    }
+
+    #[test]
+    fn reject_out_of_bounds_and_empty_crops() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        assert!(matches!(recoder.crop(3, 0, 2, 2), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(recoder.crop(0, 0, 4, 5), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(recoder.crop(u32::MAX, 0, 2, 2), Err(Error::OutOfBounds { .. })));
+        assert!(matches!(recoder.crop(0, 0, 0, 2), Err(Error::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn crop_sixteen_bit_pixels() {
+        let image = image::ImageBuffer::from_fn(3, 3, |x, y| Rgb([x as u16 * 1000, y as u16 * 1000, 65535u16]));
+        let recoder = Recoder::new(None, &encode(DynamicImage::ImageRgb16(image), ImageFormat::Png)).unwrap();
+        assert_eq!(ColorType::Rgb16, recoder.color);
+
+        let cropped = recoder.crop(1, 2, 2, 1).unwrap();
+        assert_eq!(2 * 6, cropped.data.len());
+        let channels = to_u16s(&cropped.data);
+        assert_eq!(vec![1000, 2000, 65535, 2000, 2000, 65535], channels);
+    }
+
+    fn thumbnail_dimensions(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+        let recoder = Recoder::new(None, &solid_png(width, height, [9, 9, 9])).unwrap();
+        let outcome = recoder.thumbnail(max_dimension, Format::WebP, &ThumbnailOptions::default()).unwrap();
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::WebP).unwrap();
+        assert_eq!((outcome.width, outcome.height), decoded.dimensions());
+        decoded.dimensions()
+    }
+
+    #[test]
+    fn thumbnail_landscape_and_portrait() {
+        assert_eq!((256, 128), thumbnail_dimensions(400, 200, 256));
+        assert_eq!((85, 256), thumbnail_dimensions(100, 300, 256));
+    }
+
+    #[test]
+    fn reencode_thumbnail_already_within_bounds() {
+        assert_eq!((100, 50), thumbnail_dimensions(100, 50, 256));
+        assert_eq!((256, 10), thumbnail_dimensions(256, 10, 256));
+    }
+
+    #[test]
+    fn never_upscale_thumbnail() {
+        assert_eq!((10, 10), thumbnail_dimensions(10, 10, 512));
+    }
+
+    fn rgba_png(color: [u8; 4]) -> Vec<u8> {
+        encode(DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba(color))), ImageFormat::Png)
+    }
+
+    #[test]
+    fn convert_rgb_to_luma_with_luminance_weighting() {
+        let recoder = Recoder::new(None, &solid_png(2, 2, [255, 0, 0])).unwrap();
+        let gray = recoder.convert_color(ColorType::L8).unwrap();
+        assert_eq!(ColorType::L8, gray.color);
+        assert_eq!(vec![54; 4], gray.data);
+
+        let gray = Recoder::new(None, &solid_png(2, 2, [0, 255, 0])).unwrap().convert_color(ColorType::L8).unwrap();
+        assert_eq!(vec![182; 4], gray.data);
+    }
+
+    #[test]
+    fn convert_luma_back_to_rgb() {
+        let gray = Recoder::new(None, &solid_png(2, 2, [255, 0, 0])).unwrap().convert_color(ColorType::L8).unwrap();
+        let rgb = gray.convert_color(ColorType::Rgb8).unwrap();
+        assert_eq!([54u8, 54, 54].repeat(4), rgb.data);
+    }
+
+    #[test]
+    fn flatten_alpha_onto_background() {
+        let transparent = Recoder::new(None, &rgba_png([255, 0, 0, 0])).unwrap();
+        assert_eq!([255u8, 255, 255].repeat(4), transparent.convert_color(ColorType::Rgb8).unwrap().data);
+        let on_black = transparent.convert_color_with_background(ColorType::Rgb8, [0, 0, 0]).unwrap();
+        assert_eq!([0u8, 0, 0].repeat(4), on_black.data);
+
+        let opaque = Recoder::new(None, &rgba_png([255, 0, 0, 255])).unwrap();
+        assert_eq!([255u8, 0, 0].repeat(4), opaque.convert_color(ColorType::Rgb8).unwrap().data);
+
+        let half = Recoder::new(None, &rgba_png([255, 0, 0, 51])).unwrap();
+        assert_eq!([255u8, 204, 204].repeat(4), half.convert_color(ColorType::Rgb8).unwrap().data);
+        assert_eq!([51u8, 0, 0].repeat(4), half.convert_color_with_background(ColorType::Rgb8, [0, 0, 0]).unwrap().data);
+    }
+
+    #[test]
+    fn add_opaque_alpha() {
+        let recoder = Recoder::new(None, &solid_png(2, 2, [1, 2, 3])).unwrap();
+        assert_eq!([1u8, 2, 3, 255].repeat(4), recoder.convert_color(ColorType::Rgba8).unwrap().data);
+    }
+
+    #[test]
+    fn reduce_sixteen_bit_depth() {
+        let image = image::ImageBuffer::from_pixel(2, 2, Rgb([65535u16, 0, 32896]));
+        let recoder = Recoder::new(None, &encode(DynamicImage::ImageRgb16(image), ImageFormat::Png)).unwrap();
+        let reduced = recoder.convert_color(ColorType::Rgb8).unwrap();
+        assert_eq!([255u8, 0, 128].repeat(4), reduced.data);
+    }
+
+    #[test]
+    fn refuse_increasing_bit_depth() {
+        let recoder = Recoder::new(None, &solid_png(2, 2, [1, 2, 3])).unwrap();
+        assert!(matches!(
+            recoder.convert_color(ColorType::Rgb16),
+            Err(Error::UnsupportedConversion { from: ColorType::Rgb8, to: ColorType::Rgb16 })
+        ));
+    }
+
+    // 64x32 JPEG with red, green / blue, white quadrants and the given EXIF orientation value
+    fn oriented_jpeg(orientation: u8) -> Vec<u8> {
+        let image = RgbImage::from_fn(64, 32, |x, y| match (x < 32, y < 16) {
+            (true, true) => Rgb([255, 0, 0]),
+            (false, true) => Rgb([0, 255, 0]),
+            (true, false) => Rgb([0, 0, 255]),
+            (false, false) => Rgb([255, 255, 255]),
+        });
+        let jpeg = encode(DynamicImage::ImageRgb8(image), ImageFormat::Jpeg);
+        let mut tiff = b"MM\0*\0\0\0\x08\0\x01".to_vec();
+        tiff.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, orientation, 0, 0, 0, 0, 0, 0]);
+        let mut result = jpeg[..2].to_vec();
+        result.extend_from_slice(&[0xFF, 0xE1]);
+        result.extend_from_slice(&(2 + 6 + tiff.len() as u16).to_be_bytes());
+        result.extend_from_slice(b"Exif\0\0");
+        result.extend_from_slice(&tiff);
+        result.extend_from_slice(&jpeg[2..]);
+        result
+    }
+
+    #[test]
+    fn apply_each_exif_orientation() {
+        const R: [u8; 3] = [255, 0, 0];
+        const G: [u8; 3] = [0, 255, 0];
+        const B: [u8; 3] = [0, 0, 255];
+        const W: [u8; 3] = [255, 255, 255];
+        let expected = [
+            (1, [[R, G], [B, W]]),
+            (2, [[G, R], [W, B]]),
+            (3, [[W, B], [G, R]]),
+            (4, [[B, W], [R, G]]),
+            (5, [[R, B], [G, W]]),
+            (6, [[B, R], [W, G]]),
+            (7, [[W, G], [B, R]]),
+            (8, [[G, W], [R, B]]),
+        ];
+        for (value, quadrants) in expected {
+            let recoder = Recoder::new_with_auto_orient(None, &oriented_jpeg(value), true).unwrap();
+            assert_eq!(Orientation::Normal, recoder.orientation());
+            let (width, height) = if value >= 5 { (32, 64) } else { (64, 32) };
+            assert_eq!((width, height), (recoder.width, recoder.height), "orientation {value}");
+
+            let outcome = recoder.to_png(&RecodeOptions::default()).unwrap();
+            assert_eq!(value != 1, outcome.oriented);
+            let decoded = image::load_from_memory(&outcome.data).unwrap().to_rgb8();
+            for (row, colors) in quadrants.iter().enumerate() {
+                for (col, color) in colors.iter().enumerate() {
+                    let x = width / 4 + col as u32 * width / 2;
+                    let y = height / 4 + row as u32 * height / 2;
+                    let pixel = decoded.get_pixel(x, y).0;
+                    let close = pixel.iter().zip(color).all(|(a, b)| a.abs_diff(*b) < 40);
+                    assert!(close, "orientation {value} at ({x}, {y}): {pixel:?} != {color:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn record_orientation_without_applying() {
+        let recoder = Recoder::new(None, &oriented_jpeg(6)).unwrap();
+        assert_eq!(Orientation::Rotate90, recoder.orientation());
+        assert_eq!((64, 32), (recoder.width, recoder.height));
+        assert!(!recoder.to_png(&RecodeOptions::default()).unwrap().oriented);
+
+        let thumbnail = recoder.thumbnail(16, Format::Png, &ThumbnailOptions::default()).unwrap();
+        assert_eq!((8, 16), (thumbnail.width, thumbnail.height));
+        assert!(thumbnail.oriented);
+    }
+
+    fn png_with_profile(profile: &[u8]) -> Vec<u8> {
+        let image = RgbImage::from_pixel(8, 8, Rgb([12, 34, 56]));
+        let mut buffer = Vec::new();
+        let mut encoder = PngEncoder::new(&mut buffer);
+        encoder.set_icc_profile(profile.to_vec()).unwrap();
+        encoder.write_image(image.as_raw(), 8, 8, image::ExtendedColorType::Rgb8).unwrap();
+        buffer
+    }
+
+    fn decoded_profile(outcome: &Outcome) -> Option<Vec<u8>> {
+        let cursor = Cursor::new(outcome.data.as_slice());
+        let mut decoder = image::ImageReader::new(cursor).with_guessed_format().unwrap().into_decoder().unwrap();
+        decoder.icc_profile().unwrap()
+    }
+
+    #[test]
+    fn preserve_icc_profile_through_each_encoder() {
+        let profile: Vec<u8> = (0..=255u8).cycle().take(600).collect();
+        let recoder = Recoder::new(None, &png_with_profile(&profile)).unwrap();
+        assert_eq!(Some(profile.as_slice()), recoder.icc_profile());
+
+        assert_eq!(Some(&profile), decoded_profile(&recoder.to_png(&RecodeOptions::default()).unwrap()).as_ref());
+        assert_eq!(Some(&profile), decoded_profile(&recoder.to_jpeg(&RecodeOptions::default().with_quality(90)).unwrap()).as_ref());
+        assert_eq!(Some(&profile), decoded_profile(&recoder.to_webp(&RecodeOptions::default()).unwrap()).as_ref());
+        let resized = recoder.resize(4, 4, Fit::Exact, Filter::Nearest).unwrap();
+        assert_eq!(Some(&profile), decoded_profile(&resized.to_png(&RecodeOptions::default()).unwrap()).as_ref());
+    }
+
+    #[test]
+    fn strip_icc_profile_when_policy_says_so() {
+        let profile = vec![7u8; 300];
+        let recoder = Recoder::new(None, &png_with_profile(&profile)).unwrap();
+        let options = RecodeOptions::default().with_metadata(MetadataPolicy::Strip);
+        assert_eq!(None, decoded_profile(&recoder.to_png(&options).unwrap()));
+    }
+
+    // Stands in for an encoder which can't carry an ICC profile
+    struct NoIccEncoder;
+
+    impl ImageEncoder for NoIccEncoder {
+        fn write_image(self, _: &[u8], _: u32, _: u32, _: image::ExtendedColorType) -> image::ImageResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn drop_icc_profile_the_format_cannot_carry() {
+        let recoder = Recoder::new(None, &png_with_profile(&[7u8; 300])).unwrap();
+        assert!(NoIccEncoder.set_icc_profile(vec![7u8; 300]).is_err());
+        recoder.attach_icc_profile(&mut NoIccEncoder, &RecodeOptions::default());
+
+        let gif = recoder.to_animated_gif(&RecodeOptions::default()).unwrap();
+        assert_eq!((Format::Gif, 8, 8), (gif.dest, gif.width, gif.height));
+        assert_eq!(None, decoded_profile(&gif));
+    }
+
+    #[test]
+    fn probe_same_meta_as_full_decode() {
+        let rgb = DynamicImage::ImageRgb8(RgbImage::from_pixel(37, 21, Rgb([10, 20, 30])));
+        let rgba = DynamicImage::ImageRgba8(RgbaImage::from_pixel(19, 44, Rgba([10, 20, 30, 40])));
+        let fixtures = [
+            (Format::Png, encode(rgba.clone(), ImageFormat::Png)),
+            (Format::Jpeg, encode(rgb.clone(), ImageFormat::Jpeg)),
+            (Format::WebP, encode(rgba, ImageFormat::WebP)),
+            (Format::Gif, encode(rgb, ImageFormat::Gif)),
+        ];
+        for (format, buffer) in fixtures {
+            let meta = Recoder::probe(&buffer).unwrap();
+            let recoder = Recoder::new(None, &buffer).unwrap();
+            assert_eq!(format, meta.format);
+            assert_eq!((recoder.width, recoder.height, recoder.color), (meta.width, meta.height, meta.color));
+        }
+    }
+
+    #[test]
+    fn probe_truncated_file_with_intact_header() {
+        // Noise keeps the pixel data large so the cut lands well past the header
+        let buffer = noise_png(64, 48);
+        let truncated = &buffer[..buffer.len() / 2];
+        assert!(Recoder::new(None, truncated).is_err());
+        let meta = Recoder::probe(truncated).unwrap();
+        assert_eq!((64, 48, ColorType::Rgb8), (meta.width, meta.height, meta.color));
+    }
+
+    #[test]
+    fn refuse_to_probe_other_formats() {
+        let bmp = encode(DynamicImage::ImageRgb8(RgbImage::new(2, 2)), ImageFormat::Bmp);
+        assert!(matches!(Recoder::probe(&bmp), Err(Error::UnsupportedFormat)));
+        assert!(matches!(Recoder::probe(b"not an image"), Err(Error::UnsupportedFormat)));
+    }
+
+    #[test]
+    fn encode_every_encodable_format_and_refuse_the_rest() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let formats = [
+            Format::Avif,
+            Format::Bmp,
+            Format::Dds,
+            Format::Farbfeld,
+            Format::Gif,
+            Format::Hdr,
+            Format::Ico,
+            Format::Jpeg,
+            Format::OpenExr,
+            Format::Png,
+            Format::Pnm,
+            Format::Qoi,
+            Format::Raw,
+            Format::Tga,
+            Format::Tiff,
+            Format::WebP,
+        ];
+        for format in formats {
+            match recoder.to_format(format, &RecodeOptions::default()) {
+                Ok(outcome) => {
+                    assert!(format.is_encodable(), "{format:?}");
+                    assert_eq!(format, outcome.dest);
+                    assert_eq!(Format::try_from(image::guess_format(&outcome.data).unwrap()).unwrap(), format);
+                }
+                Err(Error::UnsupportedFormat) => assert!(!format.is_encodable(), "{format:?}"),
+                Err(err) => panic!("{format:?}: {err}"),
+            }
+        }
+    }
+
+    fn noise_png(width: u32, height: u32) -> Vec<u8> {
+        let noise = RgbImage::from_fn(width, height, |x, y| Rgb([(x * 31 + y * 17) as u8, (x * y) as u8, (x ^ y) as u8]));
+        encode(DynamicImage::ImageRgb8(noise), ImageFormat::Png)
+    }
+
+    #[test]
+    fn apply_options_deserialized_from_json() {
+        let recoder = Recoder::new(None, &noise_png(64, 64)).unwrap();
+        let fast: RecodeOptions = serde_json::from_str(r#"{ "png_compression": "Fast" }"#).unwrap();
+        let best = recoder.to_format(Format::Png, &RecodeOptions::default()).unwrap();
+        assert!(recoder.to_format(Format::Png, &fast).unwrap().size() > best.size());
+
+        let low: RecodeOptions = serde_json::from_str(r#"{ "quality": 0 }"#).unwrap();
+        assert!(matches!(recoder.to_format(Format::Jpeg, &low), Err(Error::InvalidQuality(0))));
+
+        let capped: RecodeOptions = serde_json::from_str(r#"{ "max_width": 32, "max_height": 32 }"#).unwrap();
+        assert!(matches!(
+            recoder.to_format(Format::WebP, &capped),
+            Err(Error::DimensionsTooLarge { width: 64, height: 64, max: 32 })
+        ));
+
+        let red: RecodeOptions = serde_json::from_str(r#"{ "quality": 100, "background": [255, 0, 0] }"#).unwrap();
+        let transparent = Recoder::new(None, &rgba_png([0, 0, 0, 0])).unwrap();
+        let outcome = transparent.to_format(Format::Jpeg, &red).unwrap();
+        assert_eq!(Format::Jpeg, outcome.dest);
+        let [r, g, b] = image::load_from_memory(&outcome.data).unwrap().to_rgb8().get_pixel(0, 0).0;
+        assert!(r > 240 && g < 16 && b < 16, "{:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn refuse_output_beyond_max_dimensions() {
+        let recoder = Recoder::new(None, &noise_png(64, 32)).unwrap();
+        let options = RecodeOptions::default().with_max_dimensions(48, 48);
+        assert!(matches!(
+            recoder.to_png(&options),
+            Err(Error::DimensionsTooLarge { width: 64, height: 32, max: 48 })
+        ));
+        assert!(recoder.to_png(&RecodeOptions::default().with_max_dimensions(64, 32)).is_ok());
+    }
+
+    #[test]
+    fn flatten_alpha_onto_background_for_jpeg() {
+        let recoder = Recoder::new(None, &rgba_png([0, 0, 0, 0])).unwrap();
+        let options = RecodeOptions::default().with_quality(100).with_background([255, 0, 0]);
+        let decoded = image::load_from_memory(&recoder.to_jpeg(&options).unwrap().data).unwrap().to_rgb8();
+        let [r, g, b] = decoded.get_pixel(0, 0).0;
+        assert!(r > 240 && g < 16 && b < 16, "{:?}", [r, g, b]);
+    }
+
+    #[test]
+    fn encode_hand_built_pixels() {
+        let pixels = vec![
+            255, 0, 0, 255, 0, 255, 0, 128, //
+            0, 0, 255, 0, 10, 20, 30, 40,
+        ];
+        let recoder = Recoder::from_parts(2, 2, ColorType::Rgba8, pixels.clone()).unwrap();
+        assert_eq!((Format::Raw, 2, 2, ColorType::Rgba8), (recoder.format(), recoder.width(), recoder.height(), recoder.color()));
+        let outcome = recoder.to_png(&RecodeOptions::default()).unwrap();
+        assert_eq!(Format::Raw, outcome.src);
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::Png).unwrap();
+        assert_eq!(pixels, decoded.to_rgba8().into_raw());
+        assert_eq!(pixels, Recoder::new(None, &outcome.data).unwrap().as_bytes());
+    }
+
+    #[test]
+    fn reject_wrong_length_pixel_buffer() {
+        assert!(matches!(
+            Recoder::from_parts(2, 2, ColorType::Rgb8, vec![0; 11]),
+            Err(Error::BufferMismatch { expected: 12, actual: 11 })
+        ));
+        assert!(matches!(
+            Recoder::from_parts(0, 2, ColorType::Rgb8, Vec::new()),
+            Err(Error::InvalidDimensions { width: 0, height: 2 })
+        ));
+    }
+
+    #[test]
+    fn report_decode_failures_with_format_and_source() {
+        let buffer = quadrants_png();
+        let err = Recoder::new(Some(Format::Png), &buffer[..40]).unwrap_err();
+        assert!(matches!(&err, Error::Decode { format: Some(Format::Png), source: Some(_), .. }), "{err}");
+        assert!(std::error::Error::source(&err).is_some());
+        assert!(matches!(Recoder::new(None, b"not an image"), Err(Error::Decode { format: None, .. })));
+    }
+
+    // A PNG which is only a header and an empty IDAT chunk but declares the given dimensions
+    fn bomb_png(width: u32, height: u32) -> Vec<u8> {
+        fn crc32(bytes: &[u8]) -> u32 {
+            let mut crc = !0u32;
+            for &byte in bytes {
+                crc ^= u32::from(byte);
+                for _ in 0..8 {
+                    crc = if crc & 1 == 1 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 };
+                }
+            }
+            !crc
+        }
+        fn chunk(png: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            let start = png.len();
+            png.extend_from_slice(kind);
+            png.extend_from_slice(data);
+            let crc = crc32(&png[start..]);
+            png.extend_from_slice(&crc.to_be_bytes());
+        }
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[8, 2, 0, 0, 0]); // 8-bit RGB
+        chunk(&mut png, b"IHDR", &ihdr);
+        chunk(&mut png, b"IDAT", &[]);
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    #[test]
+    fn refuse_huge_dimensions_before_decoding() {
+        let bomb = bomb_png(40_000, 40_000);
+        assert!(bomb.len() < 100);
+        assert_eq!((40_000, 40_000), {
+            let meta = Recoder::probe(&bomb).unwrap();
+            (meta.width, meta.height)
+        });
+        assert!(matches!(
+            Recoder::new(None, &bomb),
+            Err(Error::LimitsExceeded { what: Limit::Width, limit: 16_384, actual: 40_000 })
+        ));
+        // Within each side but too many pixels overall
+        let limits = Limits { max_pixels: 1_000_000, ..Limits::default() };
+        assert!(matches!(
+            Recoder::new_with_limits(None, &bomb_png(2_000, 1_000), &limits),
+            Err(Error::LimitsExceeded { what: Limit::Pixels, limit: 1_000_000, actual: 2_000_000 })
+        ));
+    }
+
+    #[test]
+    fn refuse_oversized_input_and_allow_trusted_input() {
+        let buffer = quadrants_png();
+        let limits = Limits { max_input_bytes: 16, ..Limits::default() };
+        assert!(matches!(
+            Recoder::new_with_limits(None, &buffer, &limits),
+            Err(Error::LimitsExceeded { what: Limit::InputBytes, limit: 16, .. })
+        ));
+        assert!(Recoder::new_with_limits(None, &buffer, &Limits::unlimited()).is_ok());
+    }
+
+    // Hands out at most a few bytes per read so the decoders see partial reads
+    struct Throttled<R>(R);
+
+    impl<R: std::io::Read> std::io::Read for Throttled<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(7);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl<R: Seek> Seek for Throttled<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    #[test]
+    fn decode_from_throttled_reader() {
+        for buffer in [quadrants_png(), oriented_jpeg(6), noise_png(64, 48)] {
+            let expected = Recoder::new(None, &buffer).unwrap();
+            let reader = std::io::BufReader::with_capacity(5, Throttled(Cursor::new(buffer)));
+            let recoder = Recoder::from_reader(reader).unwrap();
+            assert_eq!(
+                (expected.format, expected.width, expected.height, expected.orientation),
+                (recoder.format, recoder.width, recoder.height, recoder.orientation)
+            );
+            assert_eq!(expected.data, recoder.data);
+        }
+    }
+
+    struct Failing;
+
+    impl std::io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::ConnectionReset.into())
+        }
+    }
+
+    impl Seek for Failing {
+        fn seek(&mut self, _: SeekFrom) -> std::io::Result<u64> {
+            Ok(0)
+        }
+    }
+
+    #[test]
+    fn distinguish_io_errors_from_decode_errors() {
+        let err = Recoder::from_reader(std::io::BufReader::new(Failing)).unwrap_err();
+        assert!(matches!(&err, Error::Io { source: Some(_), .. }), "{err}");
+        let truncated = quadrants_png()[..40].to_vec();
+        let err = Recoder::from_reader(Cursor::new(truncated)).unwrap_err();
+        assert!(matches!(&err, Error::Decode { format: Some(Format::Png), .. }), "{err}");
+    }
+
+    #[test]
+    fn limit_input_size_from_reader() {
+        let limits = Limits { max_input_bytes: 16, ..Limits::default() };
+        assert!(matches!(
+            Recoder::from_reader_with_limits(Cursor::new(quadrants_png()), &limits),
+            Err(Error::LimitsExceeded { what: Limit::InputBytes, limit: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn write_same_bytes_as_vec_returning_methods() {
+        let recoder = Recoder::new(None, &noise_png(32, 24)).unwrap();
+        let options = RecodeOptions::default();
+        for format in [Format::Png, Format::Jpeg, Format::WebP] {
+            let mut cursor = Cursor::new(Vec::new());
+            let written = recoder.write_format(&mut cursor, format, &options).unwrap();
+            let outcome = recoder.to_format(format, &options).unwrap();
+            assert_eq!(outcome.data, cursor.into_inner(), "{format:?}");
+            assert_eq!(outcome.size() as u64, written.bytes_written);
+            assert_eq!((format, 32, 24), (written.dest, written.width, written.height));
+        }
+    }
+
+    // Accepts a few bytes and then fails every write
+    struct BrokenPipe(usize);
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 == 0 {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            let len = buf.len().min(self.0);
+            self.0 -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn propagate_writer_failure_as_io_error() {
+        let recoder = Recoder::new(None, &noise_png(32, 24)).unwrap();
+        for format in [Format::Png, Format::Jpeg, Format::WebP] {
+            let err = recoder.write_format(BrokenPipe(64), format, &RecodeOptions::default()).unwrap_err();
+            assert!(matches!(&err, Error::Io { source: Some(_), .. }), "{format:?}: {err}");
+        }
+    }
+
+    #[cfg(feature = "oxipng")]
+    #[test]
+    fn optimize_png_without_changing_pixels() {
+        let recoder = Recoder::new(None, &noise_png(64, 48)).unwrap();
+        let options = RecodeOptions::default().with_png_compression(crate::PngCompression::Fast);
+        let outcome = recoder.to_png_optimized(&options).unwrap();
+        let optimization = outcome.optimization.clone().unwrap();
+        assert_eq!(None, optimization.error);
+        assert_eq!(recoder.to_png(&options).unwrap().size(), optimization.original_size);
+        assert_eq!(outcome.size(), optimization.optimized_size);
+        assert!(optimization.optimized_size <= optimization.original_size);
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::Png).unwrap();
+        assert_eq!(recoder.data, decoded.to_rgb8().into_raw());
+    }
+
+    // Three 4x2 frames of solid red, green, and blue
+    fn animated_gif() -> Vec<u8> {
+        let mut buffer = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut buffer);
+            let frames = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]].map(|color| {
+                Frame::from_parts(RgbaImage::from_pixel(4, 2, Rgba(color)), 0, 0, Delay::from_numer_denom_ms(50, 1))
+            });
+            encoder.encode_frames(frames).unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn count_frames_and_keep_the_first() {
+        let recoder = Recoder::new(None, &animated_gif()).unwrap();
+        assert_eq!(3, recoder.frame_count());
+        assert!(recoder.frames().is_empty());
+        assert_eq!(&[255, 0, 0, 255], &recoder.as_bytes()[..4]);
+        let still = Recoder::new(None, &encode(DynamicImage::ImageRgb8(RgbImage::new(2, 2)), ImageFormat::Gif)).unwrap();
+        assert_eq!(1, still.frame_count());
+    }
+
+    #[test]
+    fn refuse_animation_when_policy_says_so() {
+        assert!(matches!(
+            Recoder::new_with_animation_policy(None, &animated_gif(), AnimationPolicy::Error),
+            Err(Error::Animated)
+        ));
+        let still = encode(DynamicImage::ImageRgb8(RgbImage::new(2, 2)), ImageFormat::Gif);
+        assert!(Recoder::new_with_animation_policy(None, &still, AnimationPolicy::Error).is_ok());
+    }
+
+    #[test]
+    fn preserve_animation_through_gif() {
+        let recoder =
+            Recoder::new_with_animation_policy(None, &animated_gif(), AnimationPolicy::PreserveAnimation).unwrap();
+        assert_eq!(3, recoder.frames().len());
+        assert!(recoder.frames().iter().all(|frame| frame.delay_ms == 50));
+        let outcome = recoder.to_animated_gif(&RecodeOptions::default()).unwrap();
+        assert_eq!(Format::Gif, outcome.dest);
+        let decoder = GifDecoder::new(Cursor::new(outcome.data)).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(3, frames.len());
+        assert_eq!(&Rgba([0, 0, 255, 255]), frames[2].buffer().get_pixel(3, 1));
+    }
+
+    #[test]
+    fn build_icon_with_an_entry_per_size() {
+        let recoder = Recoder::new(None, &noise_png(64, 48)).unwrap();
+        let outcome = recoder.to_ico(&[16, 32, 48, 256]).unwrap();
+        assert_eq!(Format::Ico, outcome.dest);
+        let ico = &outcome.data;
+        let u16_at = |at: usize| u16::from_le_bytes([ico[at], ico[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(ico[at..at + 4].try_into().unwrap());
+        assert_eq!((0, 1, 4), (u16_at(0), u16_at(2), u16_at(4)));
+        for (n, size) in [16u32, 32, 48, 256].into_iter().enumerate() {
+            let entry = 6 + n * 16;
+            assert_eq!((size % 256) as u8, ico[entry]);
+            assert_eq!(ico[entry], ico[entry + 1]);
+            let (len, offset) = (u32_at(entry + 8) as usize, u32_at(entry + 12) as usize);
+            let png = image::load_from_memory_with_format(&ico[offset..offset + len], ImageFormat::Png).unwrap();
+            assert_eq!((size, size), png.dimensions());
+        }
+        // The image crate reads the icon back too, picking the largest entry
+        assert_eq!((256, 256), image::load_from_memory_with_format(ico, ImageFormat::Ico).unwrap().dimensions());
+    }
+
+    #[test]
+    fn reject_icon_sizes_over_256() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        assert!(matches!(
+            recoder.to_ico(&[16, 512]),
+            Err(Error::DimensionsTooLarge { width: 512, height: 512, max: 256 })
+        ));
+        assert!(matches!(recoder.to_ico(&[0]), Err(Error::InvalidDimensions { .. })));
+    }
+
+    #[test]
+    fn report_color_and_sizes_for_each_encoder() {
+        let input = rgba_png([10, 20, 30, 40]);
+        let recoder = Recoder::new(None, &input).unwrap();
+        let options = RecodeOptions::default();
+        let outcomes = [
+            (ColorType::Rgba8, recoder.to_png(&options).unwrap()),
+            (ColorType::Rgba8, recoder.to_webp(&options).unwrap()),
+            (ColorType::Rgb8, recoder.to_jpeg(&options).unwrap()),
+            (ColorType::Rgba8, recoder.to_animated_gif(&options).unwrap()),
+            (ColorType::Rgba8, recoder.to_ico(&[16]).unwrap()),
+        ];
+        for (color, outcome) in outcomes {
+            assert_eq!(color, outcome.color, "{:?}", outcome.dest);
+            assert_eq!(input.len(), outcome.input_bytes, "{:?}", outcome.dest);
+            assert_eq!(Some(outcome.size() as f64 / input.len() as f64), outcome.ratio());
+        }
+        let mut sink = Vec::new();
+        let written = recoder.write_jpeg(&mut sink, &options).unwrap();
+        assert_eq!((ColorType::Rgb8, input.len()), (written.color, written.input_bytes));
+    }
+
+    #[test]
+    fn blurhash_any_color_type() {
+        let rgb = Recoder::new(None, &solid_png(4, 4, [255, 0, 0])).unwrap();
+        assert_eq!("K~TI:j|cfQ|c$5fQfQfQfQ", rgb.blurhash(3, 3).unwrap());
+        let luma = rgb.convert_color(ColorType::L8).unwrap();
+        assert_eq!(luma.blurhash(3, 3).unwrap().len(), 4 + 2 * 9);
+        let large = Recoder::new(None, &noise_png(640, 480)).unwrap();
+        assert_eq!(4 + 2 * 12, large.blurhash(4, 3).unwrap().len());
+    }
+
+    #[test]
+    fn reject_blurhash_components_outside_one_to_nine() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        assert!(matches!(recoder.blurhash(0, 3), Err(Error::InvalidComponents { x: 0, y: 3 })));
+        assert!(matches!(recoder.blurhash(4, 10), Err(Error::InvalidComponents { x: 4, y: 10 })));
+    }
+
+    // Offset of the interlace method in the IHDR chunk, after the signature and chunk header
+    const IHDR_INTERLACE: usize = 28;
+
+    fn has_marker(jpeg: &[u8], marker: u8) -> bool {
+        jpeg.windows(2).any(|pair| pair == [0xFF, marker])
+    }
+
+    #[test]
+    fn write_sequential_png_and_baseline_jpeg_by_default() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let png = recoder.to_png(&RecodeOptions::default()).unwrap();
+        assert_eq!(0, png.data[IHDR_INTERLACE]);
+        assert!(!png.interlaced);
+        let jpeg = recoder.to_jpeg(&RecodeOptions::default()).unwrap();
+        assert!(has_marker(&jpeg.data, 0xC0) && !has_marker(&jpeg.data, 0xC2));
+        assert!(!jpeg.progressive);
+    }
+
+    #[cfg(feature = "oxipng")]
+    #[test]
+    fn interlace_png_with_oxipng() {
+        let recoder = Recoder::new(None, &noise_png(32, 24)).unwrap();
+        let outcome = recoder.to_png(&RecodeOptions::default().with_interlace(true)).unwrap();
+        assert_eq!(1, outcome.data[IHDR_INTERLACE]);
+        assert!(outcome.interlaced);
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::Png).unwrap();
+        assert_eq!(recoder.data, decoded.to_rgb8().into_raw());
+    }
+
+    #[cfg(not(feature = "oxipng"))]
+    #[test]
+    fn fall_back_to_sequential_png_without_oxipng() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let outcome = recoder.to_png(&RecodeOptions::default().with_interlace(true)).unwrap();
+        assert_eq!(0, outcome.data[IHDR_INTERLACE]);
+        assert!(!outcome.interlaced);
+    }
+
+    #[cfg(feature = "jpeg-encoder")]
+    #[test]
+    fn write_progressive_jpeg_with_jpeg_encoder() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let outcome = recoder.to_jpeg(&RecodeOptions::default().with_progressive(true)).unwrap();
+        assert!(has_marker(&outcome.data, 0xC2) && !has_marker(&outcome.data, 0xC0));
+        assert!(outcome.progressive);
+        let decoded = image::load_from_memory_with_format(&outcome.data, ImageFormat::Jpeg).unwrap();
+        assert_eq!((recoder.width, recoder.height), decoded.dimensions());
+    }
+
+    #[cfg(not(feature = "jpeg-encoder"))]
+    #[test]
+    fn fall_back_to_baseline_jpeg_without_jpeg_encoder() {
+        let recoder = Recoder::new(None, &quadrants_png()).unwrap();
+        let outcome = recoder.to_jpeg(&RecodeOptions::default().with_progressive(true)).unwrap();
+        assert!(has_marker(&outcome.data, 0xC0) && !has_marker(&outcome.data, 0xC2));
+        assert!(!outcome.progressive);
+    }
+
+    #[test]
+    fn round_trip_bytes_through_dynamic_image() {
+        let colors = [
+            ColorType::L8,
+            ColorType::La8,
+            ColorType::Rgb8,
+            ColorType::Rgba8,
+            ColorType::L16,
+            ColorType::La16,
+            ColorType::Rgb16,
+            ColorType::Rgba16,
+            ColorType::Rgb32F,
+            ColorType::Rgba32F,
+        ];
+        for color in colors {
+            let len = 3 * 2 * usize::from(color.bytes_per_pixel());
+            let data: Vec<u8> = (0..len).map(|i| (i * 37) as u8).collect();
+            let recoder = Recoder::from_parts(3, 2, color, data.clone()).unwrap();
+            let image = recoder.to_dynamic_image().unwrap();
+            assert_eq!(color, ColorType::from(image.color()));
+            let round_tripped = Recoder::from_dynamic_image(&image).unwrap();
+            assert_eq!((3, 2, color), (round_tripped.width(), round_tripped.height(), round_tripped.color()));
+            assert_eq!(data, round_tripped.pixels(), "{color:?}");
+            assert_eq!(Format::Raw, round_tripped.format());
+        }
+    }
+
+    #[test]
+    fn keep_16_bit_values_in_dynamic_image() {
+        let data: Vec<u8> = [1000u16, 2000, 65535].iter().flat_map(|c| c.to_ne_bytes()).collect();
+        let recoder = Recoder::from_parts(1, 1, ColorType::Rgb16, data).unwrap();
+        let image = recoder.to_dynamic_image().unwrap().to_rgb16();
+        assert_eq!([1000, 2000, 65535], image.get_pixel(0, 0).0);
+    }
+
+    #[test]
+    fn reject_empty_dynamic_image() {
+        let empty = DynamicImage::ImageRgba8(RgbaImage::new(0, 3));
+        assert!(matches!(
+            Recoder::from_dynamic_image(&empty),
+            Err(Error::InvalidDimensions { width: 0, height: 3 })
+        ));
+    }
+
+    // Two pages, 4x2 RGB then 2x3 grayscale
+    fn two_page_tiff() -> Vec<u8> {
+        let mut buffer = Cursor::new(Vec::new());
+        {
+            let mut encoder = tiff::encoder::TiffEncoder::new(&mut buffer).unwrap();
+            encoder.write_image::<tiff::encoder::colortype::RGB8>(4, 2, &[200; 4 * 2 * 3]).unwrap();
+            encoder.write_image::<tiff::encoder::colortype::Gray8>(2, 3, &[50; 2 * 3]).unwrap();
+        }
+        buffer.into_inner()
+    }
+
+    #[test]
+    fn split_animation_into_frames() {
+        let frames = Recoder::split_frames(&animated_gif()).unwrap();
+        assert_eq!(3, frames.len());
+        for (frame, red) in frames.iter().zip([255, 0, 0]) {
+            assert_eq!((4, 2, ColorType::Rgba8), (frame.width(), frame.height(), frame.color()));
+            assert_eq!(red, frame.as_bytes()[0]);
+        }
+        assert_eq!([0, 255, 0, 255], Recoder::new_frame(&animated_gif(), 1).unwrap().as_bytes()[..4]);
+    }
+
+    #[test]
+    fn split_tiff_into_pages() {
+        let pages = Recoder::split_frames(&two_page_tiff()).unwrap();
+        let shapes: Vec<_> = pages.iter().map(|page| (page.format(), page.width(), page.height(), page.color())).collect();
+        assert_eq!(vec![(Format::Tiff, 4, 2, ColorType::Rgb8), (Format::Tiff, 2, 3, ColorType::L8)], shapes);
+        let second = Recoder::new_frame(&two_page_tiff(), 1).unwrap();
+        assert_eq!(vec![50; 6], second.as_bytes());
+        assert_eq!(Format::Png, Recoder::new(None, &second.to_png(&RecodeOptions::default()).unwrap().data).unwrap().format());
+    }
+
+    #[test]
+    fn split_still_image_into_one() {
+        let frames = Recoder::split_frames(&quadrants_png()).unwrap();
+        assert_eq!(1, frames.len());
+        assert_eq!(Recoder::new(None, &quadrants_png()).unwrap().as_bytes(), frames[0].as_bytes());
+    }
+
+    #[test]
+    fn reject_frame_index_out_of_range() {
+        assert!(matches!(
+            Recoder::new_frame(&animated_gif(), 3),
+            Err(Error::FrameOutOfRange { index: 3, count: 3 })
+        ));
+        assert!(matches!(
+            Recoder::new_frame(&two_page_tiff(), 2),
+            Err(Error::FrameOutOfRange { index: 2, count: 2 })
+        ));
+        assert!(matches!(
+            Recoder::new_frame(&quadrants_png(), 1),
+            Err(Error::FrameOutOfRange { index: 1, count: 1 })
+        ));
+    }
+
+    #[test]
+    fn refuse_more_frames_than_the_limit() {
+        let limits = Limits { max_frames: 2, ..Limits::default() };
+        assert!(matches!(
+            Recoder::split_frames_with_limits(&animated_gif(), &limits),
+            Err(Error::LimitsExceeded { what: Limit::Frames, limit: 2, actual: 3 })
+        ));
+    }
+
+    #[test]
+    fn decode_with_the_given_format() {
+        let recoder = Recoder::new_with_format(&quadrants_png(), Format::Png).unwrap();
+        assert_eq!((Format::Png, 4, 4), (recoder.format(), recoder.width(), recoder.height()));
+    }
+
+    #[test]
+    fn name_both_formats_when_the_hint_is_wrong() {
+        let err = Recoder::new_with_format(&quadrants_png(), Format::Jpeg).unwrap_err();
+        assert!(matches!(&err, Error::Decode { format: Some(Format::Jpeg), .. }), "{err}");
+        let message = err.to_string();
+        assert!(message.contains("looks like Png") && message.contains("decoded as Jpeg"), "{message}");
+    }
+
+    #[test]
+    fn reject_undecodable_format_hint() {
+        assert!(matches!(
+            Recoder::new_with_format(&quadrants_png(), Format::Raw),
+            Err(Error::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn reject_out_of_range_jpeg_quality() {
+        let recoder = Recoder::new(None, &solid_png(4, 4, [0, 0, 0])).unwrap();
+        for quality in [0, 101] {
+            let options = RecodeOptions::default().with_quality(quality);
+            assert!(matches!(recoder.to_jpeg(&options), Err(Error::InvalidQuality(q)) if q == quality));
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use image::imageops::FilterType;
+
+/// Largest width or height a resize may produce unless a different bound is given
+pub const MAX_DIMENSION: u32 = 16_384;
+
+/// How the source is fitted into the requested dimensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Fit {
+    /// Stretch to exactly the requested width and height, ignoring the aspect ratio
+    Exact,
+    /// Preserve the aspect ratio and fit inside the requested box, so one dimension may be smaller
+    Contain,
+    /// Preserve the aspect ratio and fill the requested box, center cropping the overflow
+    Cover,
+}
+
+/// Sampling filter used when resizing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Filter {
+    /// Fastest, blocky output; best for pixel art and large downscale ratios
+    Nearest,
+    /// Linear filter, a reasonable balance of speed and quality
+    Triangle,
+    /// Slowest and sharpest
+    Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+    fn from(value: Filter) -> Self {
+        match value {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
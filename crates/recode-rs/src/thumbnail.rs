@@ -0,0 +1,22 @@
+use crate::Filter;
+
+/// Encoding choices for `Recoder::thumbnail`
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct ThumbnailOptions {
+    /// Quality used when the target format is lossy (1-100)
+    pub quality: u8,
+    /// Overrides the filter which is otherwise picked from the downscale ratio
+    pub filter: Option<Filter>,
+    /// Applies the EXIF orientation first so the thumbnail is upright
+    pub auto_orient: bool,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        ThumbnailOptions {
+            quality: 80,
+            filter: None,
+            auto_orient: true,
+        }
+    }
+}
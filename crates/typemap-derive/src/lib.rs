@@ -0,0 +1,18 @@
+//! `#[derive(StateData)]` for the typemap crate, use it through typemap's `derive` feature.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Marks the type as storable in a `TypeMap` when typemap's `strict` feature is enabled
+#[proc_macro_derive(StateData)]
+pub fn derive_state_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    // typemap decides whether the impl is needed, only it knows if `strict` is enabled
+    quote! {
+        ::typemap::__impl_state_data!({ #impl_generics } { #name #ty_generics } { #where_clause });
+    }
+    .into()
+}
@@ -0,0 +1,52 @@
+//! Compares TypeId lookups in a `TypeIdMap` against a `HashMap` with the default SipHash
+//! hasher, run with `cargo bench -p typemap`. Plain std timing, no bench framework needed.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use typemap::{type_id_map_with_capacity, TypeIdMap};
+
+const ROUNDS: u32 = 1_000_000;
+
+macro_rules! type_ids {
+    ($($t:ty),*) => {
+        [$(TypeId::of::<$t>()),*]
+    };
+}
+
+fn time(name: &str, mut lookup: impl FnMut(&TypeId) -> bool, keys: &[TypeId]) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for key in keys {
+            black_box(lookup(black_box(key)));
+        }
+    }
+    let elapsed = start.elapsed();
+    let lookups = u128::from(ROUNDS) * keys.len() as u128;
+    println!(
+        "{name:>10}: {elapsed:>10.2?} for {lookups} lookups, {:.2} ns each",
+        elapsed.as_nanos() as f64 / lookups as f64
+    );
+    elapsed
+}
+
+fn main() {
+    let keys = type_ids!(u8, u16, u32, u64, i8, i16, i32, i64, String, Vec<u8>, (), bool);
+
+    let mut type_id_map: TypeIdMap<dyn Any> = type_id_map_with_capacity(keys.len());
+    let mut sip_map: HashMap<TypeId, Box<dyn Any>> = HashMap::with_capacity(keys.len());
+    for (i, key) in keys.iter().enumerate() {
+        type_id_map.insert(*key, Box::new(i));
+        sip_map.insert(*key, Box::new(i));
+    }
+
+    let type_id = time("TypeIdMap", |key| type_id_map.contains_key(key), &keys);
+    let sip = time("SipHash", |key| sip_map.contains_key(key), &keys);
+    println!(
+        "{:>10}: {:.2}x",
+        "speedup",
+        sip.as_secs_f64() / type_id.as_secs_f64()
+    );
+}
@@ -0,0 +1,233 @@
+use std::any::{Any, TypeId};
+use std::fmt;
+
+use crate::{type_id_map_with_capacity, MissingStateError, TypeIdMap};
+
+/// `Any` for values which can also be cloned behind a `Box`, implemented for every
+/// `T: Any + Clone`
+pub trait CloneAny: Any {
+    /// Clones the value into a fresh box
+    fn clone_box(&self) -> Box<dyn CloneAny>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Clone> CloneAny for T {
+    fn clone_box(&self) -> Box<dyn CloneAny> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl Clone for Box<dyn CloneAny> {
+    fn clone(&self) -> Self {
+        // Deref to the trait object first, otherwise the blanket impl for the Box itself is used
+        (**self).clone_box()
+    }
+}
+
+impl fmt::Debug for dyn CloneAny {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("CloneAny { .. }")
+    }
+}
+
+/// A [`TypeMap`](crate::TypeMap) which only stores `Clone` values, so the whole map can be
+/// cloned, i.e. to fork request state into subtasks. Each clone holds independent copies of
+/// the values.
+///
+/// # Examples
+///
+/// ```rust
+/// use typemap::CloneTypeMap;
+///
+/// #[derive(Clone)]
+/// struct MyStruct {
+///     value: i32,
+/// }
+///
+/// let mut map = CloneTypeMap::new();
+/// map.put(MyStruct { value: 1 });
+///
+/// let mut fork = map.clone();
+/// fork.get_mut::<MyStruct>().value = 2;
+///
+/// assert_eq!(map.get::<MyStruct>().value, 1);
+/// assert_eq!(fork.get::<MyStruct>().value, 2);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CloneTypeMap {
+    inner: TypeIdMap<dyn CloneAny>,
+}
+
+impl CloneTypeMap {
+    /// Creates a new, empty `CloneTypeMap`. It doesn't allocate until the first `put`.
+    pub fn new() -> CloneTypeMap {
+        Self::default()
+    }
+
+    /// Creates an empty `CloneTypeMap` with room for at least `capacity` types before it
+    /// reallocates.
+    pub fn with_capacity(capacity: usize) -> CloneTypeMap {
+        Self {
+            inner: type_id_map_with_capacity(capacity),
+        }
+    }
+
+    /// Puts a value into the `CloneTypeMap`, replacing any existing value of the same type.
+    pub fn put<T: Any + Clone>(&mut self, t: T) {
+        let type_id = TypeId::of::<T>();
+        self.inner.insert(type_id, Box::new(t));
+    }
+
+    /// Determines if a value of type `T` exists in the `CloneTypeMap`.
+    pub fn has<T: Any + Clone>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.inner.contains_key(&type_id)
+    }
+
+    /// Tries to borrow a value from the `CloneTypeMap`.
+    pub fn try_get<T: Any + Clone>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.inner
+            .get(&type_id)
+            .and_then(|b| (**b).as_any().downcast_ref::<T>())
+    }
+
+    /// Borrows a value from the `CloneTypeMap`.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `CloneTypeMap`.
+    pub fn get<T: Any + Clone>(&self) -> &T {
+        self.try_get()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Tries to mutably borrow a value from the `CloneTypeMap`.
+    pub fn try_get_mut<T: Any + Clone>(&mut self) -> Option<&mut T> {
+        let type_id = TypeId::of::<T>();
+        self.inner
+            .get_mut(&type_id)
+            .and_then(|b| (**b).as_any_mut().downcast_mut::<T>())
+    }
+
+    /// Mutably borrows a value from the `CloneTypeMap`.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `CloneTypeMap`.
+    pub fn get_mut<T: Any + Clone>(&mut self) -> &mut T {
+        self.try_get_mut()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Tries to move a value out of the `CloneTypeMap` storage and return ownership.
+    pub fn try_take<T: Any + Clone>(&mut self) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        self.inner
+            .remove(&type_id)
+            .and_then(|b| b.into_any().downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Moves a value out of the `CloneTypeMap` storage and returns ownership.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `CloneTypeMap`.
+    pub fn take<T: Any + Clone>(&mut self) -> T {
+        self.try_take()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Drops the value of type `T`, returning whether there was one.
+    pub fn remove<T: Any + Clone>(&mut self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.inner.remove(&type_id).is_some()
+    }
+
+    /// Drops every value, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    /// The number of types with a value in the `CloneTypeMap`.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether the `CloneTypeMap` holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Counter(u32);
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Names(Vec<String>);
+
+    #[test]
+    fn keep_values_stored_before_clone_in_both() {
+        let mut map = CloneTypeMap::new();
+        map.put(Counter(1));
+        map.put(Names(vec!["first".to_owned()]));
+
+        let fork = map.clone();
+        assert_eq!(2, fork.len());
+        assert_eq!(&Counter(1), fork.get::<Counter>());
+        assert_eq!(map.get::<Names>(), fork.get::<Names>());
+    }
+
+    #[test]
+    fn clone_into_independent_values() {
+        let mut map = CloneTypeMap::new();
+        map.put(Counter(1));
+        map.put(Names(vec!["first".to_owned()]));
+
+        let mut fork = map.clone();
+        fork.get_mut::<Counter>().0 += 10;
+        fork.get_mut::<Names>().0.push("second".to_owned());
+        map.put(7u8);
+
+        assert_eq!(&Counter(1), map.get::<Counter>());
+        assert_eq!(&Counter(11), fork.get::<Counter>());
+        assert_eq!(1, map.get::<Names>().0.len());
+        assert_eq!(2, fork.get::<Names>().0.len());
+        assert!(!fork.has::<u8>());
+    }
+
+    #[test]
+    fn take_and_remove_like_typemap() {
+        let mut map = CloneTypeMap::with_capacity(1);
+        map.put(Counter(3));
+        assert_eq!(Some(Counter(3)), map.try_take::<Counter>());
+        assert_eq!(None, map.try_get::<Counter>());
+
+        map.put(Counter(4));
+        assert!(map.remove::<Counter>());
+        assert!(!map.remove::<Counter>());
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,25 @@
+use std::any::type_name;
+
+/// A value was required from a map which doesn't hold one of its type. Frameworks can turn it
+/// into an internal server error which names the missing state, where `get` would panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingStateError {
+    /// The missing type as [`std::any::type_name`] gives it
+    pub type_name: &'static str,
+}
+
+impl MissingStateError {
+    pub(crate) fn of<T: ?Sized>() -> MissingStateError {
+        MissingStateError {
+            type_name: type_name::<T>(),
+        }
+    }
+}
+
+impl std::error::Error for MissingStateError {}
+
+impl std::fmt::Display for MissingStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "required type `{}` is not present in the map", self.type_name)
+    }
+}
@@ -0,0 +1,388 @@
+use std::any::{type_name, Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{PoisonError, RwLock};
+
+use crate::type_id_hasher::TypeIdHasher;
+use crate::{MissingStateError, SharedTypeMap, StateData, TypeMap};
+
+/// Counters per type, keyed like the maps they describe
+type Counters<C> = HashMap<TypeId, (&'static str, C), BuildHasherDefault<TypeIdHasher>>;
+
+/// How often one type was accessed. Every read is a `get`, and then either a `hit` or a `miss`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    pub puts: u64,
+    pub gets: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl AccessCounts {
+    fn read(&mut self, hit: bool) {
+        self.gets += 1;
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+    }
+}
+
+/// Sorts a snapshot by type name so it reads the same on every run
+fn sorted(mut metrics: Vec<(&'static str, AccessCounts)>) -> Vec<(&'static str, AccessCounts)> {
+    metrics.sort_unstable_by_key(|(name, _)| *name);
+    metrics
+}
+
+/// A [`TypeMap`] which counts the puts and reads of each type, i.e. to find state which is put
+/// into every request and never read. Reads through `&self` count too, so the counters sit in a
+/// `RefCell` and the map is not `Sync`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use typemap::{AccessCounts, InstrumentedTypeMap};
+/// #
+/// # struct Unused;
+/// # typemap::__impl_state_data!({} {Unused} {});
+/// #
+/// let mut map = InstrumentedTypeMap::new();
+/// map.put(Unused);
+///
+/// let (_, counts) = map.metrics()[0];
+/// assert_eq!(counts, AccessCounts { puts: 1, ..AccessCounts::default() });
+/// ```
+#[derive(Debug, Default)]
+pub struct InstrumentedTypeMap {
+    map: TypeMap,
+    counters: RefCell<Counters<AccessCounts>>,
+}
+
+impl InstrumentedTypeMap {
+    /// Creates a new, empty `InstrumentedTypeMap`.
+    pub fn new() -> InstrumentedTypeMap {
+        Self::default()
+    }
+
+    /// Puts a value into the map, replacing any existing value of the same type.
+    pub fn put<T: StateData>(&mut self, t: T) {
+        self.count::<T>(|counts| counts.puts += 1);
+        self.map.put(t)
+    }
+
+    /// Determines if a value of type `T` exists in the map, counted as a read.
+    pub fn has<T: StateData>(&self) -> bool {
+        let has = self.map.has::<T>();
+        self.count::<T>(|counts| counts.read(has));
+        has
+    }
+
+    /// Tries to borrow a value from the map.
+    pub fn try_get<T: StateData>(&self) -> Option<&T> {
+        let t = self.map.try_get::<T>();
+        self.count::<T>(|counts| counts.read(t.is_some()));
+        t
+    }
+
+    /// Borrows a value from the map.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in the map, the miss is counted first.
+    pub fn get<T: StateData>(&self) -> &T {
+        self.try_get()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Tries to mutably borrow a value from the map.
+    pub fn try_get_mut<T: StateData>(&mut self) -> Option<&mut T> {
+        let hit = self.map.has::<T>();
+        self.count::<T>(|counts| counts.read(hit));
+        self.map.try_get_mut()
+    }
+
+    /// Tries to move a value out of the map, counted as a read.
+    pub fn try_take<T: StateData>(&mut self) -> Option<T> {
+        let t = self.map.try_take::<T>();
+        self.count::<T>(|counts| counts.read(t.is_some()));
+        t
+    }
+
+    /// Drops the value of type `T`, returning whether there was one. Not counted.
+    pub fn remove<T: StateData>(&mut self) -> bool {
+        self.map.remove::<T>()
+    }
+
+    /// The counters of every type accessed since the last `reset`, sorted by type name.
+    pub fn metrics(&self) -> Vec<(&'static str, AccessCounts)> {
+        sorted(self.counters.borrow().values().copied().collect())
+    }
+
+    /// Zeroes every counter, the stored values are kept.
+    pub fn reset(&mut self) {
+        self.counters.get_mut().clear()
+    }
+
+    /// The underlying map, reads through it are not counted.
+    pub fn inner(&self) -> &TypeMap {
+        &self.map
+    }
+
+    /// Drops the counters and returns the underlying map.
+    pub fn into_inner(self) -> TypeMap {
+        self.map
+    }
+
+    fn count<T: Any>(&self, f: impl FnOnce(&mut AccessCounts)) {
+        let mut counters = self.counters.borrow_mut();
+        let (_, counts) = counters
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| (type_name::<T>(), AccessCounts::default()));
+        f(counts)
+    }
+}
+
+/// [`AccessCounts`] which any thread can bump without a write lock
+#[derive(Debug, Default)]
+struct AtomicCounts {
+    puts: AtomicU64,
+    gets: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl AtomicCounts {
+    fn read(&self, hit: bool) {
+        self.gets.fetch_add(1, Ordering::Relaxed);
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> AccessCounts {
+        AccessCounts {
+            puts: self.puts.load(Ordering::Relaxed),
+            gets: self.gets.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A [`SharedTypeMap`] which counts the inserts and reads of each type. The counters are
+/// atomics, a type's first access takes a write lock on the counter table and every later one
+/// only a read lock, so counting doesn't serialize the readers.
+///
+/// A snapshot taken while other threads access the map may count a `get` before its `hit` or
+/// `miss`, the counters are exact once those threads are done.
+#[derive(Debug, Default)]
+pub struct InstrumentedSharedTypeMap {
+    map: SharedTypeMap,
+    counters: RwLock<Counters<AtomicCounts>>,
+}
+
+impl InstrumentedSharedTypeMap {
+    /// Creates a new, empty `InstrumentedSharedTypeMap`.
+    pub fn new() -> InstrumentedSharedTypeMap {
+        Self::default()
+    }
+
+    /// Puts a value into the map, returning the value of the same type it replaced.
+    pub fn insert<T: Any + Send + Sync>(&self, t: T) -> Option<T> {
+        self.count::<T>(|counts| {
+            counts.puts.fetch_add(1, Ordering::Relaxed);
+        });
+        self.map.insert(t)
+    }
+
+    /// Moves the value of type `T` out of the map, counted as a read.
+    pub fn remove<T: Any + Send + Sync>(&self) -> Option<T> {
+        let t = self.map.remove::<T>();
+        self.count::<T>(|counts| counts.read(t.is_some()));
+        t
+    }
+
+    /// Determines if a value of type `T` exists in the map, counted as a read.
+    pub fn has<T: Any + Send + Sync>(&self) -> bool {
+        let has = self.map.has::<T>();
+        self.count::<T>(|counts| counts.read(has));
+        has
+    }
+
+    /// A copy of the value of type `T`, taken under the read lock.
+    pub fn get_cloned<T: Any + Send + Sync + Clone>(&self) -> Option<T> {
+        self.with_borrow(T::clone)
+    }
+
+    /// Runs `f` on the value of type `T` under the read lock, see
+    /// [`SharedTypeMap::with_borrow`].
+    pub fn with_borrow<T: Any + Send + Sync, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let r = self.map.with_borrow(f);
+        self.count::<T>(|counts| counts.read(r.is_some()));
+        r
+    }
+
+    /// Runs `f` on the value of type `T` under the write lock, see
+    /// [`SharedTypeMap::with_borrow_mut`].
+    pub fn with_borrow_mut<T: Any + Send + Sync, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let r = self.map.with_borrow_mut(f);
+        self.count::<T>(|counts| counts.read(r.is_some()));
+        r
+    }
+
+    /// The counters of every type accessed since the last `reset`, sorted by type name.
+    pub fn metrics(&self) -> Vec<(&'static str, AccessCounts)> {
+        let counters = self.counters.read().unwrap_or_else(PoisonError::into_inner);
+        sorted(
+            counters
+                .values()
+                .map(|(name, counts)| (*name, counts.snapshot()))
+                .collect(),
+        )
+    }
+
+    /// Zeroes every counter, the stored values are kept.
+    pub fn reset(&self) {
+        self.counters
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear()
+    }
+
+    /// The underlying map, accesses through it are not counted.
+    pub fn inner(&self) -> &SharedTypeMap {
+        &self.map
+    }
+
+    fn count<T: Any>(&self, f: impl FnOnce(&AtomicCounts)) {
+        let type_id = TypeId::of::<T>();
+        let counters = self.counters.read().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, counts)) = counters.get(&type_id) {
+            return f(counts);
+        }
+        drop(counters);
+
+        let mut counters = self.counters.write().unwrap_or_else(PoisonError::into_inner);
+        let (_, counts) = counters
+            .entry(type_id)
+            .or_insert_with(|| (type_name::<T>(), AtomicCounts::default()));
+        f(counts)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Read(u32);
+    crate::__impl_state_data!({} {Read} {});
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Unread;
+    crate::__impl_state_data!({} {Unread} {});
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Absent;
+    crate::__impl_state_data!({} {Absent} {});
+
+    fn counts(puts: u64, hits: u64, misses: u64) -> AccessCounts {
+        AccessCounts {
+            puts,
+            gets: hits + misses,
+            hits,
+            misses,
+        }
+    }
+
+    fn of<T>(metrics: &[(&'static str, AccessCounts)]) -> AccessCounts {
+        metrics
+            .iter()
+            .find(|(name, _)| *name == type_name::<T>())
+            .map(|(_, counts)| *counts)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn count_scripted_accesses() {
+        let mut map = InstrumentedTypeMap::new();
+        for i in 0..3 {
+            map.put(Read(i));
+            map.put(Unread);
+            assert_eq!(&Read(i), map.get::<Read>());
+            assert_eq!(None, map.try_get::<Absent>());
+        }
+        map.try_get_mut::<Read>().unwrap().0 += 1;
+        assert_eq!(Some(Read(3)), map.try_take::<Read>());
+        assert!(!map.has::<Read>());
+
+        let metrics = map.metrics();
+        assert_eq!(3, metrics.len());
+        assert_eq!(counts(3, 5, 1), of::<Read>(&metrics));
+        assert_eq!(counts(3, 0, 0), of::<Unread>(&metrics));
+        assert_eq!(counts(0, 0, 3), of::<Absent>(&metrics));
+    }
+
+    #[test]
+    fn sort_metrics_by_type_name() {
+        let mut map = InstrumentedTypeMap::new();
+        map.put(Unread);
+        map.put(Read(0));
+        map.try_get::<Absent>();
+
+        let names: Vec<_> = map.metrics().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(vec![type_name::<Absent>(), type_name::<Read>(), type_name::<Unread>()], names);
+    }
+
+    #[test]
+    fn reset_counters_and_keep_values() {
+        let mut map = InstrumentedTypeMap::new();
+        map.put(Read(1));
+        map.get::<Read>();
+        map.reset();
+
+        assert!(map.metrics().is_empty());
+        assert_eq!(Some(&Read(1)), map.inner().try_get::<Read>());
+        assert!(map.metrics().is_empty());
+        map.get::<Read>();
+        assert_eq!(counts(0, 1, 0), of::<Read>(&map.metrics()));
+    }
+
+    #[test]
+    fn count_shared_accesses_across_threads() {
+        let map = Arc::new(InstrumentedSharedTypeMap::new());
+        map.insert(Read(0));
+        map.insert(Unread);
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        map.with_borrow_mut(|read: &mut Read| read.0 += 1);
+                        map.get_cloned::<Absent>();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(Some(Read(40)), map.get_cloned::<Read>());
+        assert_eq!(Some(Unread), map.remove::<Unread>());
+        assert!(!map.has::<Unread>());
+
+        let metrics = map.metrics();
+        assert_eq!(counts(1, 41, 0), of::<Read>(&metrics));
+        assert_eq!(counts(1, 1, 1), of::<Unread>(&metrics));
+        assert_eq!(counts(0, 0, 40), of::<Absent>(&metrics));
+
+        map.reset();
+        assert!(map.metrics().is_empty());
+        assert!(map.inner().has::<Read>());
+    }
+}
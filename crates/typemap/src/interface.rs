@@ -0,0 +1,142 @@
+use std::any::{Any, TypeId};
+
+use crate::{StateData, TypeMap};
+
+/// Unsizes a reference to the concrete value into the interface `I`, after checking the value
+/// is the registered concrete type
+type Cast<I> = Box<dyn for<'a> Fn(&'a (dyn Any + 'static)) -> Option<&'a I>>;
+
+/// Where to find the value behind an interface and how to view it as one
+pub(crate) struct Interface {
+    concrete: TypeId,
+    /// A `Cast<I>` for the interface this entry is keyed by, boxed again since `I` varies
+    cast: Box<dyn Any>,
+}
+
+impl TypeMap {
+    /// Registers the stored `T` as the implementation of the interface `I` (usually a
+    /// `dyn Trait`), so it can be looked up with [`get_interface`](TypeMap::get_interface)
+    /// without naming `T`. `coerce` is almost always `|t| t`, the unsizing happens on return.
+    ///
+    /// The registration outlives the value, `get_interface` sees whatever `T` is stored at the
+    /// time of the lookup. Registering `I` again points it at the new `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// trait Logger {
+    ///     fn prefix(&self) -> &str;
+    /// }
+    ///
+    /// struct Console;
+    /// # typemap::__impl_state_data!({} {Console} {});
+    ///
+    /// impl Logger for Console {
+    ///     fn prefix(&self) -> &str {
+    ///         "console"
+    ///     }
+    /// }
+    ///
+    /// let mut map = TypeMap::new();
+    /// map.put(Console);
+    /// map.register_interface::<dyn Logger, Console>(|console| console);
+    ///
+    /// assert_eq!(map.get_interface::<dyn Logger>().unwrap().prefix(), "console");
+    /// ```
+    pub fn register_interface<I: ?Sized + 'static, T: StateData>(&mut self, coerce: fn(&T) -> &I) {
+        let cast: Cast<I> = Box::new(move |value| value.downcast_ref::<T>().map(coerce));
+        self.interfaces.insert(
+            TypeId::of::<I>(),
+            Interface {
+                concrete: TypeId::of::<T>(),
+                cast: Box::new(cast),
+            },
+        );
+    }
+
+    /// Borrows the value registered for the interface `I`, `None` if nothing is registered for
+    /// `I` or its concrete type has no value in the `TypeMap`.
+    pub fn get_interface<I: ?Sized + 'static>(&self) -> Option<&I> {
+        let interface = self.interfaces.get(&TypeId::of::<I>())?;
+        let stored = self.inner.get(&interface.concrete)?;
+        let cast = interface.cast.downcast_ref::<Cast<I>>()?;
+        cast(stored.value.as_ref())
+    }
+
+    /// Whether an implementation is registered for the interface `I`, whether or not its value
+    /// is currently stored.
+    pub fn has_interface<I: ?Sized + 'static>(&self) -> bool {
+        self.interfaces.contains_key(&TypeId::of::<I>())
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::fmt::Display;
+
+    trait Logger {
+        fn log(&self, line: &str) -> String;
+    }
+
+    trait Named {
+        fn name(&self) -> &str;
+    }
+
+    struct Console {
+        name: &'static str,
+    }
+    crate::__impl_state_data!({} {Console} {});
+
+    impl Logger for Console {
+        fn log(&self, line: &str) -> String {
+            format!("[{}] {line}", self.name)
+        }
+    }
+
+    impl Named for Console {
+        fn name(&self) -> &str {
+            self.name
+        }
+    }
+
+    #[test]
+    fn fetch_one_value_through_two_interfaces() {
+        let mut map = TypeMap::new();
+        map.put_boxed(Box::new(Console { name: "console" }));
+        map.register_interface::<dyn Logger, Console>(|console| console);
+        map.register_interface::<dyn Named, Console>(|console| console);
+
+        assert_eq!("[console] started", map.get_interface::<dyn Logger>().unwrap().log("started"));
+        assert_eq!("console", map.get_interface::<dyn Named>().unwrap().name());
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn follow_the_currently_stored_value() {
+        let mut map = TypeMap::new();
+        map.register_interface::<dyn Named, Console>(|console| console);
+        assert!(map.has_interface::<dyn Named>());
+        assert!(map.get_interface::<dyn Named>().is_none());
+
+        map.put(Console { name: "first" });
+        map.put(Console { name: "second" });
+        assert_eq!("second", map.get_interface::<dyn Named>().unwrap().name());
+
+        map.remove::<Console>();
+        assert!(map.get_interface::<dyn Named>().is_none());
+    }
+
+    #[test]
+    fn miss_unregistered_interface() {
+        let mut map = TypeMap::new();
+        map.put(7u32);
+        assert!(!map.has_interface::<dyn Display>());
+        assert!(map.get_interface::<dyn Display>().is_none());
+
+        map.register_interface::<dyn Display, u32>(|n| n);
+        assert_eq!("7", map.get_interface::<dyn Display>().unwrap().to_string());
+    }
+}
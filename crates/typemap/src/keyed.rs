@@ -0,0 +1,153 @@
+use std::any::{Any, TypeId};
+use std::hash::{Hash, Hasher};
+
+use crate::{StateData, Stored, TypeMap};
+
+/// A keyed entry is found by its type and its name together
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Key {
+    type_id: TypeId,
+    name: &'static str,
+}
+
+impl Key {
+    fn of<T: Any>(name: &'static str) -> Key {
+        Key {
+            type_id: TypeId::of::<T>(),
+            name,
+        }
+    }
+}
+
+impl Hash for Key {
+    // The TypeId goes in first so KeyedHasher passes it through untouched and mixes the name in
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.type_id.hash(state);
+        self.name.hash(state);
+    }
+}
+
+/// Keyed values hold several values of one type under distinct names, i.e. a request id and a
+/// correlation id which are both `String`. They live apart from the one per type values, so
+/// `put::<String>` never touches `put_keyed::<String>("")`. `len`, `type_names` and iteration
+/// only see the one per type values, `clear` drops both.
+impl TypeMap {
+    /// Puts a value into the `TypeMap` under `key`, replacing any value of the same type and key.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// #[derive(Debug, PartialEq)]
+    /// struct Id(&'static str);
+    /// # typemap::__impl_state_data!({} {Id} {});
+    ///
+    /// let mut map = TypeMap::new();
+    /// map.put_keyed("request-id", Id("r-1"));
+    /// map.put_keyed("correlation-id", Id("c-9"));
+    ///
+    /// assert_eq!(map.get_keyed::<Id>("request-id"), Some(&Id("r-1")));
+    /// assert_eq!(map.get_keyed::<Id>("correlation-id"), Some(&Id("c-9")));
+    /// assert!(!map.has::<Id>());
+    /// ```
+    pub fn put_keyed<T: StateData>(&mut self, key: &'static str, t: T) {
+        self.keyed.insert(Key::of::<T>(key), Stored::new(t));
+    }
+
+    /// Determines if a value of type `T` exists under `key`.
+    pub fn has_keyed<T: StateData>(&self, key: &'static str) -> bool {
+        self.keyed.contains_key(&Key::of::<T>(key))
+    }
+
+    /// Tries to borrow the value of type `T` under `key`.
+    pub fn get_keyed<T: StateData>(&self, key: &'static str) -> Option<&T> {
+        self.keyed
+            .get(&Key::of::<T>(key))
+            .and_then(|s| s.value.downcast_ref::<T>())
+    }
+
+    /// Tries to mutably borrow the value of type `T` under `key`.
+    pub fn get_keyed_mut<T: StateData>(&mut self, key: &'static str) -> Option<&mut T> {
+        self.keyed
+            .get_mut(&Key::of::<T>(key))
+            .and_then(|s| s.value.downcast_mut::<T>())
+    }
+
+    /// Tries to move the value of type `T` under `key` out of the `TypeMap`.
+    pub fn take_keyed<T: StateData>(&mut self, key: &'static str) -> Option<T> {
+        self.keyed
+            .remove(&Key::of::<T>(key))
+            .and_then(|s| s.value.downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Drops the value of type `T` under `key`, returning whether there was one.
+    pub fn remove_keyed<T: StateData>(&mut self, key: &'static str) -> bool {
+        self.keyed.remove(&Key::of::<T>(key)).is_some()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use crate::type_id_hasher::KeyedHasher;
+    use std::hash::{BuildHasher, BuildHasherDefault};
+
+    #[test]
+    fn keep_same_type_under_different_keys() {
+        let mut map = TypeMap::new();
+        map.put_keyed("request-id", String::from("r-1"));
+        map.put_keyed("correlation-id", String::from("c-9"));
+        map.put_keyed("request-id", String::from("r-2"));
+
+        assert_eq!(Some("r-2"), map.get_keyed::<String>("request-id").map(String::as_str));
+        assert_eq!(Some("c-9"), map.get_keyed::<String>("correlation-id").map(String::as_str));
+
+        map.get_keyed_mut::<String>("correlation-id").unwrap().push_str("-x");
+        assert_eq!(Some(String::from("c-9-x")), map.take_keyed::<String>("correlation-id"));
+        assert!(!map.has_keyed::<String>("correlation-id"));
+        assert!(map.has_keyed::<String>("request-id"));
+    }
+
+    #[test]
+    fn miss_wrong_key_or_type() {
+        let mut map = TypeMap::new();
+        map.put_keyed("count", 1u32);
+
+        assert_eq!(None, map.get_keyed::<u32>("total"));
+        assert_eq!(None, map.get_keyed::<u64>("count"));
+        assert_eq!(None, map.take_keyed::<u32>("total"));
+        assert!(!map.remove_keyed::<u64>("count"));
+        assert!(map.remove_keyed::<u32>("count"));
+        assert!(!map.remove_keyed::<u32>("count"));
+    }
+
+    #[test]
+    fn separate_keyed_and_unkeyed_values() {
+        let mut map = TypeMap::new();
+        map.put(String::from("unkeyed"));
+        map.put_keyed("", String::from("empty key"));
+
+        assert_eq!("unkeyed", map.get::<String>());
+        assert_eq!(Some("empty key"), map.get_keyed::<String>("").map(String::as_str));
+
+        assert!(map.remove::<String>());
+        assert!(map.has_keyed::<String>(""));
+        assert_eq!(0, map.len());
+
+        map.clear();
+        assert!(!map.has_keyed::<String>(""));
+    }
+
+    #[test]
+    fn spread_names_of_one_type() {
+        let build = BuildHasherDefault::<KeyedHasher>::default();
+        let hashes = ["a", "b", "request-id", "correlation-id"]
+            .map(|name| build.hash_one(Key::of::<String>(name)));
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(hash), "{hashes:?}");
+        }
+        assert_ne!(build.hash_one(Key::of::<String>("a")), build.hash_one(Key::of::<u32>("a")));
+    }
+}
@@ -1,19 +1,71 @@
 //! 
 //! Derived from https://github.com/gotham-rs/gotham/tree/main/gotham/src/state
 
-use std::any::{Any, TypeId};
+use std::any::{type_name, Any, TypeId};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::fmt;
 use std::hash::{BuildHasherDefault};
 
+mod clone_type_map;
+mod error;
+mod instrumented;
+mod interface;
+mod keyed;
+mod provider;
+mod scoped;
+#[cfg(feature = "serde")]
+mod serde_registry;
+mod send_type_map;
+mod shared_type_map;
+mod state_data;
+mod take_guard;
 mod type_id_hasher;
-use type_id_hasher::{TypeIdHasher};
+pub use clone_type_map::{CloneAny, CloneTypeMap};
+pub use error::MissingStateError;
+pub use instrumented::{AccessCounts, InstrumentedSharedTypeMap, InstrumentedTypeMap};
+pub use provider::ProviderRegistry;
+pub use scoped::ScopedTypeMap;
+#[cfg(feature = "serde")]
+pub use serde_registry::{OnUnregistered, SerdeError, SerdeRegistry};
+pub use send_type_map::SendTypeMap;
+pub use shared_type_map::SharedTypeMap;
+pub use state_data::StateData;
+pub use take_guard::TakeGuard;
+#[cfg(feature = "derive")]
+pub use typemap_derive::StateData;
+pub use type_id_hasher::TypeIdHasher;
+use type_id_hasher::KeyedHasher;
 
 /// Simplified type signature over underlying HashMap
 pub type TypeIdMap<T> = HashMap<TypeId, Box<T>, BuildHasherDefault<TypeIdHasher>>;
 
-/// Provides storage for request state, and stores one item of each type. The types used for
-/// storage must implement the [`StateData`] trait to allow its storage, which is usually done
-/// by adding `#[derive(StateData)]` on the type in question.
+/// Creates a new, empty [`TypeIdMap`], for crates keeping TypeId keyed maps of their own.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::any::{Any, TypeId};
+/// use typemap::{new_type_id_map, TypeIdMap};
+///
+/// let mut handlers: TypeIdMap<dyn Any> = new_type_id_map();
+/// handlers.insert(TypeId::of::<u32>(), Box::new("u32 handler"));
+///
+/// assert!(handlers.contains_key(&TypeId::of::<u32>()));
+/// ```
+pub fn new_type_id_map<V: ?Sized>() -> TypeIdMap<V> {
+    TypeIdMap::default()
+}
+
+/// Creates an empty [`TypeIdMap`] with room for at least `capacity` types before it reallocates.
+pub fn type_id_map_with_capacity<V: ?Sized>(capacity: usize) -> TypeIdMap<V> {
+    TypeIdMap::with_capacity_and_hasher(capacity, Default::default())
+}
+
+/// Provides storage for request state, and stores one item of each type. Any `'static` type can
+/// be stored, the type itself is the key. With the `strict` feature only types implementing
+/// [`StateData`] can be stored, which is usually done by adding `#[derive(StateData)]` on the
+/// type in question.
 ///
 /// # Examples
 ///
@@ -23,41 +75,66 @@ pub type TypeIdMap<T> = HashMap<TypeId, Box<T>, BuildHasherDefault<TypeIdHasher>
 /// struct MyStruct {
 ///     value: i32,
 /// }
-/// # fn main() {
-/// #   TypeMap::with_new(|map| {
-/// #
+/// # typemap::__impl_state_data!({} {MyStruct} {});
+///
+/// let mut map = TypeMap::new();
 /// map.put(MyStruct { value: 1 });
-/// assert_eq!(map.borrow::<MyStruct>().value, 1);
-/// #
-/// #   });
-/// # }
+/// assert_eq!(map.get::<MyStruct>().value, 1);
 /// ```
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct TypeMap {
-    // inner: HashMap<TypeId, Box<dyn Any>, BuildHasherDefault<TypeIdHasher>>,
-    inner: TypeIdMap<dyn Any>,
+    inner: HashMap<TypeId, Stored, BuildHasherDefault<TypeIdHasher>>,
+    interfaces: HashMap<TypeId, interface::Interface, BuildHasherDefault<TypeIdHasher>>,
+    keyed: HashMap<keyed::Key, Stored, BuildHasherDefault<KeyedHasher>>,
+}
+
+/// A value along with the name of its type, which `Any` can't recover after the fact
+struct Stored {
+    type_name: &'static str,
+    value: Box<dyn Any>,
+}
+
+impl Stored {
+    fn new<T: Any>(t: T) -> Stored {
+        Self::from_box(Box::new(t))
+    }
+
+    fn from_box<T: Any>(t: Box<T>) -> Stored {
+        Stored {
+            type_name: type_name::<T>(),
+            value: t,
+        }
+    }
 }
 
 impl TypeMap {
-    /// Creates a new, empty `State` container. This is for internal Gotham use, because the
-    /// ability to create a new `State` container would allow for libraries and applications to
-    /// incorrectly discard important internal data.
-    // pub fn new() -> TypeMap {
-    //     Self {
-    //         inner: HashMap::default(),
-    //         // inner: HashMap::with_capacity(capacity)
-    //     }
-    // }
-
-    /// Creates a new, empty `State` and yields it mutably into the provided closure. This is
-    /// intended only for use in the documentation tests for `State`, since the `State` container
-    /// cannot be constructed otherwise.
-    #[doc(hidden)]
-    pub fn with_new<F>(f: F)
-    where
-        F: FnOnce(&mut TypeMap),
-    {
-        f(&mut TypeMap::default())
+    /// Creates a new, empty `TypeMap`. It doesn't allocate until the first `put`.
+    pub fn new() -> TypeMap {
+        Self::default()
+    }
+
+    /// Creates an empty `TypeMap` with room for at least `capacity` types before it reallocates.
+    pub fn with_capacity(capacity: usize) -> TypeMap {
+        Self {
+            inner: HashMap::with_capacity_and_hasher(capacity, Default::default()),
+            interfaces: HashMap::default(),
+            keyed: HashMap::default(),
+        }
+    }
+
+    /// The number of types the `TypeMap` can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+
+    /// Reserves room for at least `additional` more types, see [`HashMap::reserve`].
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional)
+    }
+
+    /// Shrinks the capacity as much as possible while keeping the stored values.
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit()
     }
 
     /// Puts a value into the `State` storage. One value of each type is retained. Successive calls
@@ -71,29 +148,63 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// #     value: &'static str
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 1 });
-    /// assert_eq!(map.borrow::<MyStruct>().value, 1);
+    /// assert_eq!(map.get::<MyStruct>().value, 1);
     ///
     /// map.put(AnotherStruct { value: "a string" });
     /// map.put(MyStruct { value: 100 });
     ///
-    /// assert_eq!(map.borrow::<AnotherStruct>().value, "a string");
-    /// assert_eq!(map.borrow::<MyStruct>().value, 100);
+    /// assert_eq!(map.get::<AnotherStruct>().value, "a string");
+    /// assert_eq!(map.get::<MyStruct>().value, 100);
+    /// ```
+    pub fn put<T: StateData>(&mut self, t: T) {
+        let type_id = TypeId::of::<T>();
+        self.inner.insert(type_id, Stored::new(t));
+    }
+
+    /// Puts an already boxed value into the `TypeMap` without moving it to a new allocation,
+    /// otherwise the same as `put`.
+    pub fn put_boxed<T: StateData>(&mut self, t: Box<T>) {
+        let type_id = TypeId::of::<T>();
+        self.inner.insert(type_id, Stored::from_box(t));
+    }
+
+    /// Puts a value into the `TypeMap` only if there isn't one of the same type yet, otherwise
+    /// hands it back untouched.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
     /// #
-    /// #   });
+    /// # struct MyStruct {
+    /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// assert!(map.try_put(MyStruct { value: 1 }).is_ok());
+    ///
+    /// let rejected = map.try_put(MyStruct { value: 2 }).unwrap_err();
+    /// assert_eq!(rejected.value, 2);
+    /// assert_eq!(map.get::<MyStruct>().value, 1);
     /// ```
-    pub fn put<T: Any>(&mut self, t: T) {
-        let type_id = TypeId::of::<T>();
-        self.inner.insert(type_id, Box::new(t));
+    pub fn try_put<T: StateData>(&mut self, t: T) -> Result<(), T> {
+        match self.inner.entry(TypeId::of::<T>()) {
+            Entry::Occupied(_) => Err(t),
+            Entry::Vacant(entry) => {
+                entry.insert(Stored::new(t));
+                Ok(())
+            }
+        }
     }
 
     /// Determines if the current entry exists in `TypeMap`.
@@ -106,25 +217,22 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 1 });
     /// assert!(map.has::<MyStruct>());
-    /// assert_eq!(map.borrow::<MyStruct>().value, 1);
+    /// assert_eq!(map.get::<MyStruct>().value, 1);
     ///
     /// assert!(!map.has::<AnotherStruct>());
-    /// #
-    /// #   });
-    /// # }
     /// ```
-    pub fn has<T: Any>(&self) -> bool {
+    pub fn has<T: StateData>(&self) -> bool {
         let type_id = TypeId::of::<T>();
-        self.inner.get(&type_id).is_some()
+        self.inner.contains_key(&type_id)
     }
 
     /// Tries to borrow a value from the `TypeMap`.
@@ -137,25 +245,22 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 1 });
-    /// assert!(map.try_borrow::<MyStruct>().is_some());
-    /// assert_eq!(map.try_borrow::<MyStruct>().unwrap().value, 1);
+    /// assert!(map.try_get::<MyStruct>().is_some());
+    /// assert_eq!(map.try_get::<MyStruct>().unwrap().value, 1);
     ///
-    /// assert!(map.try_borrow::<AnotherStruct>().is_none());
-    /// #
-    /// #   });
-    /// # }
+    /// assert!(map.try_get::<AnotherStruct>().is_none());
     /// ```
-    pub fn try_get<T: Any>(&self) -> Option<&T> {
+    pub fn try_get<T: StateData>(&self) -> Option<&T> {
         let type_id = TypeId::of::<T>();
-        self.inner.get(&type_id).and_then(|b| b.downcast_ref::<T>())
+        self.inner.get(&type_id).and_then(|s| s.value.downcast_ref::<T>())
     }
 
     /// Borrows a value from the `TypeMap`.
@@ -172,19 +277,37 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 1 });
-    /// assert_eq!(map.borrow::<MyStruct>().value, 1);
+    /// assert_eq!(map.get::<MyStruct>().value, 1);
+    /// ```
+    pub fn get<T: StateData>(&self) -> &T {
+        self.get_checked().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Borrows a value from the `TypeMap`, or says which type is missing.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
     /// #
-    /// #   });
+    /// # struct MyStruct {
+    /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// let err = map.get_checked::<MyStruct>().err().unwrap();
+    /// assert!(err.type_name.ends_with("MyStruct"));
+    ///
+    /// map.put(MyStruct { value: 1 });
+    /// assert_eq!(map.get_checked::<MyStruct>().unwrap().value, 1);
     /// ```
-    pub fn get<T: Any>(&self) -> &T {
-        self.try_get()
-            .expect("required type is not present in TypeMap container")
+    pub fn get_checked<T: StateData>(&self) -> Result<&T, MissingStateError> {
+        self.try_get().ok_or_else(MissingStateError::of::<T>)
     }
 
     /// Tries to mutably borrow a value from the `TypeMap`.
@@ -197,13 +320,13 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 100 });
     ///
     /// if let Some(a) = map.try_get_mut::<MyStruct>() {
@@ -213,13 +336,12 @@ impl TypeMap {
     /// assert_eq!(map.get::<MyStruct>().value, 110);
     ///
     /// assert!(map.try_get_mut::<AnotherStruct>().is_none());
-    /// #   });
-    /// # }
-    pub fn try_get_mut<T: Any>(&mut self) -> Option<&mut T> {
+    /// ```
+    pub fn try_get_mut<T: StateData>(&mut self) -> Option<&mut T> {
         let type_id = TypeId::of::<T>();
         self.inner
             .get_mut(&type_id)
-            .and_then(|b| b.downcast_mut::<T>())
+            .and_then(|s| s.value.downcast_mut::<T>())
     }
 
     /// Mutably borrows a value from the `TypeMap`.
@@ -236,13 +358,13 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 100 });
     ///
     /// {
@@ -253,12 +375,123 @@ impl TypeMap {
     /// assert_eq!(map.get::<MyStruct>().value, 110);
     ///
     /// assert!(map.try_get_mut::<AnotherStruct>().is_none());
+    /// ```
+    pub fn get_mut<T: StateData>(&mut self) -> &mut T {
+        self.get_mut_checked().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Mutably borrows a value from the `TypeMap`, or says which type is missing.
+    pub fn get_mut_checked<T: StateData>(&mut self) -> Result<&mut T, MissingStateError> {
+        self.try_get_mut().ok_or_else(MissingStateError::of::<T>)
+    }
+
+    /// Mutably borrows the values of two different types at once, i.e. to append to a log buffer
+    /// while reading the request id, which two `get_mut` calls can't do.
+    ///
+    /// # Panics
+    ///
+    /// If `A` and `B` are the same type, since that would hand out two `&mut` to one value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
     /// #
-    /// #   });
+    /// # struct RequestId(u64);
+    /// # typemap::__impl_state_data!({} {RequestId} {});
+    /// #
+    /// # struct Log(Vec<String>);
+    /// # typemap::__impl_state_data!({} {Log} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(RequestId(7));
+    /// map.put(Log(Vec::new()));
+    ///
+    /// if let (Some(id), Some(log)) = map.get_two_mut::<RequestId, Log>() {
+    ///     id.0 += 1;
+    ///     log.0.push(format!("request {}", id.0));
+    /// }
+    ///
+    /// assert_eq!(map.get::<Log>().0, ["request 8"]);
+    /// ```
+    pub fn get_two_mut<A: StateData, B: StateData>(&mut self) -> (Option<&mut A>, Option<&mut B>) {
+        assert_distinct::<A, B>("get_two_mut");
+        let [a, b] = self.inner.get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()]);
+        (
+            a.and_then(|s| s.value.downcast_mut::<A>()),
+            b.and_then(|s| s.value.downcast_mut::<B>()),
+        )
+    }
+
+    /// Mutably borrows the values of three different types at once, see
+    /// [`get_two_mut`](TypeMap::get_two_mut).
+    ///
+    /// # Panics
+    ///
+    /// If any two of `A`, `B` and `C` are the same type.
+    pub fn get_three_mut<A: StateData, B: StateData, C: StateData>(
+        &mut self,
+    ) -> (Option<&mut A>, Option<&mut B>, Option<&mut C>) {
+        assert_distinct::<A, B>("get_three_mut");
+        assert_distinct::<A, C>("get_three_mut");
+        assert_distinct::<B, C>("get_three_mut");
+        let [a, b, c] = self
+            .inner
+            .get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>(), &TypeId::of::<C>()]);
+        (
+            a.and_then(|s| s.value.downcast_mut::<A>()),
+            b.and_then(|s| s.value.downcast_mut::<B>()),
+            c.and_then(|s| s.value.downcast_mut::<C>()),
+        )
+    }
+
+    /// Mutably borrows the value of type `T`, first putting the result of `f` if there isn't one.
+    /// `f` only runs on a miss, making this a single lookup for lazily built state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct MyStruct {
+    /// #     value: i32
     /// # }
-    pub fn get_mut<T: Any>(&mut self) -> &mut T {
-        self.try_get_mut()
-            .expect("required type is not present in State container")
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.get_or_insert_with(|| MyStruct { value: 100 }).value += 10;
+    /// map.get_or_insert_with(|| MyStruct { value: 0 }).value += 10;
+    ///
+    /// assert_eq!(map.get::<MyStruct>().value, 120);
+    /// ```
+    pub fn get_or_insert_with<T: StateData, F: FnOnce() -> T>(&mut self, f: F) -> &mut T {
+        self.inner
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Stored::new(f()))
+            .value
+            .downcast_mut::<T>()
+            .expect("TypeMap value is stored under its own type")
+    }
+
+    /// Mutably borrows the value of type `T`, first putting `T::default()` if there isn't one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// #[derive(Default)]
+    /// struct Hits(u32);
+    /// # typemap::__impl_state_data!({} {Hits} {});
+    ///
+    /// let mut map = TypeMap::new();
+    /// map.get_or_default::<Hits>().0 += 1;
+    /// map.get_or_default::<Hits>().0 += 1;
+    ///
+    /// assert_eq!(map.get::<Hits>().0, 2);
+    /// ```
+    pub fn get_or_default<T: StateData + Default>(&mut self) -> &mut T {
+        self.get_or_insert_with(T::default)
     }
 
     /// Tries to move a value out of the `TypeMap` storage and return ownership.
@@ -271,13 +504,13 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
     /// # struct AnotherStruct {
     /// # }
+    /// # typemap::__impl_state_data!({} {AnotherStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 110 });
     ///
     /// assert_eq!(map.try_take::<MyStruct>().unwrap().value, 110);
@@ -287,14 +520,12 @@ impl TypeMap {
     /// assert!(map.try_get::<MyStruct>().is_none());
     ///
     /// assert!(map.try_take::<AnotherStruct>().is_none());
-    /// #
-    /// #   });
-    /// # }
-    pub fn try_take<T: Any>(&mut self) -> Option<T> {
+    /// ```
+    pub fn try_take<T: StateData>(&mut self) -> Option<T> {
         let type_id = TypeId::of::<T>();
         self.inner
             .remove(&type_id)
-            .and_then(|b| b.downcast::<T>().ok())
+            .and_then(|s| s.value.downcast::<T>().ok())
             .map(|b| *b)
     }
 
@@ -312,22 +543,589 @@ impl TypeMap {
     /// # struct MyStruct {
     /// #     value: i32
     /// # }
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
     /// #
-    /// # fn main() {
-    /// #   TypeMap::with_new(|map| {
-    /// #
+    /// let mut map = TypeMap::new();
     /// map.put(MyStruct { value: 110 });
     ///
     /// assert_eq!(map.take::<MyStruct>().value, 110);
     ///
     /// assert!(map.try_take::<MyStruct>().is_none());
-    /// assert!(map.try_borrow_mut::<MyStruct>().is_none());
-    /// assert!(map.try_borrow::<MyStruct>().is_none());
+    /// assert!(map.try_get_mut::<MyStruct>().is_none());
+    /// assert!(map.try_get::<MyStruct>().is_none());
+    /// ```
+    pub fn take<T: StateData>(&mut self) -> T {
+        self.take_checked().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Moves a value out of the `TypeMap` storage, or says which type is missing.
+    pub fn take_checked<T: StateData>(&mut self) -> Result<T, MissingStateError> {
+        self.try_take().ok_or_else(MissingStateError::of::<T>)
+    }
+
+    /// Moves a value out of the `TypeMap` storage, or returns `T::default()` if there isn't one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
     /// #
-    /// #   });
+    /// #[derive(Default)]
+    /// struct Tags(Vec<&'static str>);
+    /// # typemap::__impl_state_data!({} {Tags} {});
+    ///
+    /// let mut map = TypeMap::new();
+    /// map.put(Tags(vec!["a", "b"]));
+    ///
+    /// assert_eq!(map.take_or_default::<Tags>().0, ["a", "b"]);
+    /// assert!(map.take_or_default::<Tags>().0.is_empty());
+    /// ```
+    pub fn take_or_default<T: StateData + Default>(&mut self) -> T {
+        self.try_take().unwrap_or_default()
+    }
+
+    /// Takes the value of type `T` out for the duration of `f` and puts back whatever `f`
+    /// returns in its place. `f` gets the rest of the map too, so it can change other entries
+    /// while holding the value. `None`, without calling `f`, if there is no value of type `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Log(Vec<String>);
+    /// # typemap::__impl_state_data!({} {Log} {});
+    /// #
+    /// # struct RequestId(u64);
+    /// # typemap::__impl_state_data!({} {RequestId} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(Log(Vec::new()));
+    /// map.put(RequestId(7));
+    ///
+    /// let lines = map.with_taken(|map, mut log: Log| {
+    ///     log.0.push(format!("request {}", map.get::<RequestId>().0));
+    ///     let lines = log.0.len();
+    ///     (log, lines)
+    /// });
+    ///
+    /// assert_eq!(lines, Some(1));
+    /// assert_eq!(map.get::<Log>().0, ["request 7"]);
+    /// ```
+    pub fn with_taken<T: StateData, R>(&mut self, f: impl FnOnce(&mut TypeMap, T) -> (T, R)) -> Option<R> {
+        let t = self.try_take::<T>()?;
+        let (t, result) = f(self, t);
+        self.put(t);
+        Some(result)
+    }
+
+    /// Drops the value of type `T`, returning whether there was one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct MyStruct {
+    /// #     value: i32
     /// # }
-    pub fn take<T: Any>(&mut self) -> T {
-        self.try_take()
-            .expect("required type is not present in State container")
+    /// # typemap::__impl_state_data!({} {MyStruct} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(MyStruct { value: 110 });
+    ///
+    /// assert!(map.remove::<MyStruct>());
+    /// assert!(!map.remove::<MyStruct>());
+    /// assert!(!map.has::<MyStruct>());
+    /// ```
+    pub fn remove<T: StateData>(&mut self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.inner.remove(&type_id).is_some()
+    }
+
+    /// Drops every value, keyed ones included, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.inner.clear();
+        self.keyed.clear();
+    }
+
+    /// The number of types with a value in the `TypeMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Count(u8);
+    /// # typemap::__impl_state_data!({} {Count} {});
+    /// # struct Label(&'static str);
+    /// # typemap::__impl_state_data!({} {Label} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// assert!(map.is_empty());
+    ///
+    /// map.put(Count(1));
+    /// map.put(Label("a string"));
+    /// map.put(Count(2));
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether the `TypeMap` holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// The names of the stored types as [`std::any::type_name`] gives them, sorted, i.e. for
+    /// logging what a request's state holds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Count(u8);
+    /// # typemap::__impl_state_data!({} {Count} {});
+    /// # struct Label(String);
+    /// # typemap::__impl_state_data!({} {Label} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(Label(String::from("a string")));
+    /// map.put(Count(1));
+    ///
+    /// let names = map.type_names();
+    /// assert!(names[0].ends_with("::Count"));
+    /// assert!(names[1].ends_with("::Label"));
+    /// ```
+    pub fn type_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<_> = self.inner.values().map(|s| s.type_name).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Visits every value (not the keyed ones) with the name of its type. The order is arbitrary,
+    /// but repeats between calls as long as the `TypeMap` isn't changed in between.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Small(u8);
+    /// # typemap::__impl_state_data!({} {Small} {});
+    /// # struct Wide(u16);
+    /// # typemap::__impl_state_data!({} {Wide} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(Small(1));
+    /// map.put(Wide(2));
+    ///
+    /// let total: u32 = map
+    ///     .iter()
+    ///     .map(|(_, value)| match value.downcast_ref::<Small>() {
+    ///         Some(n) => u32::from(n.0),
+    ///         None => u32::from(value.downcast_ref::<Wide>().unwrap().0),
+    ///     })
+    ///     .sum();
+    /// assert_eq!(total, 3);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &dyn Any)> + '_ {
+        self.inner.values().map(|s| (s.type_name, s.value.as_ref()))
+    }
+
+    /// Visits every value mutably, in the same order as [`iter`](TypeMap::iter).
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&'static str, &mut dyn Any)> + '_ {
+        self.inner.values_mut().map(|s| (s.type_name, s.value.as_mut()))
+    }
+
+    /// Keeps only the values (not the keyed ones) for which `f` returns `true`, i.e. to drop
+    /// everything from one module by type name.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Count(u8);
+    /// # typemap::__impl_state_data!({} {Count} {});
+    /// # struct Label(String);
+    /// # typemap::__impl_state_data!({} {Label} {});
+    /// #
+    /// let mut map = TypeMap::new();
+    /// map.put(Count(1));
+    /// map.put(Label(String::from("a string")));
+    ///
+    /// map.retain(|type_name, _| !type_name.ends_with("::Label"));
+    /// assert!(map.has::<Count>());
+    /// assert!(!map.has::<Label>());
+    /// ```
+    pub fn retain<F: FnMut(&'static str, &mut dyn Any) -> bool>(&mut self, mut f: F) {
+        self.inner.retain(|_, s| f(s.type_name, s.value.as_mut()))
+    }
+}
+
+// HashMap::get_disjoint_mut panics on overlapping keys too, but without saying which types
+fn assert_distinct<A: Any, B: Any>(method: &str) {
+    assert!(
+        TypeId::of::<A>() != TypeId::of::<B>(),
+        "{method} needs different types, got `{}` twice",
+        type_name::<A>()
+    );
+}
+
+impl fmt::Debug for TypeMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypeMap")
+            .field("len", &self.len())
+            .field("types", &self.type_names())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Counter(u32);
+    crate::__impl_state_data!({} {Counter} {});
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+    crate::__impl_state_data!({} {Name} {});
+
+    #[test]
+    fn start_empty_without_allocating() {
+        let map = TypeMap::new();
+        assert_eq!(0, map.capacity());
+        assert!(!map.has::<Counter>());
+    }
+
+    #[test]
+    fn hold_requested_capacity() {
+        let mut map = TypeMap::with_capacity(8);
+        assert!(map.capacity() >= 8);
+
+        map.reserve(32);
+        assert!(map.capacity() >= 32);
+
+        map.put(Counter(1));
+        map.shrink_to_fit();
+        assert!(map.capacity() >= 1 && map.capacity() < 32);
+        assert_eq!(&Counter(1), map.get::<Counter>());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn keep_one_value_per_type() {
+        let mut map = TypeMap::with_capacity(2);
+        map.put(Counter(1));
+        map.put(Name("first"));
+        map.put(Counter(2));
+
+        assert_eq!(&Counter(2), map.get::<Counter>());
+        assert_eq!(Some(&Name("first")), map.try_get::<Name>());
+        map.get_mut::<Counter>().0 += 1;
+        assert_eq!(Some(Counter(3)), map.try_take::<Counter>());
+        assert_eq!(None, map.try_get::<Counter>());
+    }
+
+    #[test]
+    fn remove_only_the_named_type() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("kept"));
+
+        assert!(map.remove::<Counter>());
+        assert!(!map.has::<Counter>());
+        assert_eq!(&Name("kept"), map.get::<Name>());
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn report_removing_a_type_never_inserted() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+
+        assert!(!map.remove::<Name>());
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    fn clear_every_value() {
+        let mut map = TypeMap::with_capacity(4);
+        map.put(Counter(1));
+        map.put(Name("gone"));
+        let capacity = map.capacity();
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(None, map.try_get::<Counter>());
+        assert_eq!(capacity, map.capacity());
+    }
+
+    #[test]
+    fn count_one_entry_per_type() {
+        let mut map = TypeMap::new();
+        assert_eq!(0, map.len());
+        assert!(map.is_empty());
+
+        map.put(Counter(1));
+        map.put(Counter(2));
+        map.put(Name("second"));
+        assert_eq!(2, map.len());
+        assert!(!map.is_empty());
+    }
+
+    #[test]
+    fn run_initializer_only_on_first_access() {
+        let mut map = TypeMap::new();
+        let mut calls = 0;
+        for _ in 0..3 {
+            map.get_or_insert_with(|| {
+                calls += 1;
+                Counter(0)
+            })
+            .0 += 1;
+        }
+        assert_eq!(1, calls);
+        assert_eq!(&Counter(3), map.get::<Counter>());
+    }
+
+    #[test]
+    fn leave_existing_value_for_default() {
+        let mut map = TypeMap::new();
+        map.put(7u32);
+        *map.get_or_default::<u32>() += 1;
+        map.get_or_default::<String>().push_str("lazy");
+
+        assert_eq!(&8, map.get::<u32>());
+        assert_eq!("lazy", map.get::<String>());
+    }
+
+    #[test]
+    fn hand_back_value_try_put_rejects() {
+        let mut map = TypeMap::new();
+        assert_eq!(Ok(()), map.try_put(Counter(1)));
+        assert_eq!(Err(Counter(2)), map.try_put(Counter(2)));
+        assert_eq!(&Counter(1), map.get::<Counter>());
+    }
+
+    #[test]
+    fn list_stored_type_names() {
+        let mut map = TypeMap::new();
+        map.put(Name("first"));
+        map.put(Counter(1));
+        map.get_or_default::<u32>();
+
+        let names = map.type_names();
+        assert_eq!(3, names.len());
+        assert!(names.contains(&type_name::<Counter>()));
+        assert!(names.contains(&type_name::<Name>()));
+        assert!(names.contains(&"u32"));
+        assert!(names.is_sorted());
+    }
+
+    #[test]
+    fn drop_names_of_removed_types() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("taken"));
+        map.put(7u32);
+
+        map.remove::<Counter>();
+        map.take::<Name>();
+        assert_eq!(vec!["u32"], map.type_names());
+
+        map.clear();
+        assert!(map.type_names().is_empty());
+    }
+
+    #[test]
+    fn debug_print_count_and_names() {
+        let mut map = TypeMap::new();
+        map.put(7u32);
+        map.put(String::new());
+
+        let debug = format!("{map:?}");
+        assert_eq!(r#"TypeMap { len: 2, types: ["alloc::string::String", "u32"] }"#, debug);
+    }
+
+    #[test]
+    fn take_or_fall_back_to_default() {
+        let mut map = TypeMap::new();
+        map.put(Counter(3));
+
+        assert_eq!(3, map.take_or_default::<Counter>().0);
+        assert!(!map.has::<Counter>());
+        assert_eq!(0, map.take_or_default::<Counter>().0);
+    }
+
+    #[test]
+    fn hide_value_while_taken() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+
+        let seen = map.with_taken(|map, counter: Counter| {
+            let present = map.has::<Counter>();
+            (Counter(counter.0 + 1), present)
+        });
+        assert_eq!(Some(false), seen);
+        assert_eq!(&Counter(2), map.get::<Counter>());
+    }
+
+    #[test]
+    fn skip_closure_for_missing_value() {
+        let mut map = TypeMap::new();
+        let result = map.with_taken(|_, counter: Counter| -> (Counter, ()) { unreachable!("{counter:?}") });
+        assert_eq!(None, result);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn nest_takes_of_different_types() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("outer"));
+
+        let result = map.with_taken(|map, counter: Counter| {
+            let inner = map.with_taken(|map, _: Name| {
+                assert!(!map.has::<Counter>() && !map.has::<Name>());
+                map.put(7u32);
+                (Name("inner"), counter.0)
+            });
+            (Counter(counter.0 * 10), inner)
+        });
+
+        assert_eq!(Some(Some(1)), result);
+        assert_eq!(&Counter(10), map.get::<Counter>());
+        assert_eq!(&Name("inner"), map.get::<Name>());
+        assert_eq!(&7, map.get::<u32>());
+    }
+
+    #[test]
+    fn iterate_every_value_once() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("first"));
+        map.put(7u32);
+        map.put_keyed("not iterated", 8u32);
+
+        assert_eq!(map.len(), map.iter().count());
+        let mut names: Vec<_> = map.iter().map(|(name, _)| name).collect();
+        names.sort_unstable();
+        assert_eq!(map.type_names(), names);
+
+        let order: Vec<_> = map.iter().map(|(name, _)| name).collect();
+        let order_mut: Vec<_> = map.iter_mut().map(|(name, _)| name).collect();
+        assert_eq!(order, order_mut);
+    }
+
+    #[test]
+    fn downcast_iterated_values() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("first"));
+
+        let counter = map.iter().find_map(|(_, value)| value.downcast_ref::<Counter>());
+        assert_eq!(Some(&Counter(1)), counter);
+
+        for (_, value) in map.iter_mut() {
+            if let Some(counter) = value.downcast_mut::<Counter>() {
+                counter.0 += 1;
+            }
+        }
+        assert_eq!(&Counter(2), map.get::<Counter>());
+    }
+
+    #[test]
+    fn retain_only_matching_values() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("first"));
+        map.put(7u32);
+
+        map.retain(|name, value| name == type_name::<Name>() || value.is::<u32>());
+        assert_eq!(2, map.len());
+        assert!(!map.has::<Counter>());
+        assert!(map.has::<Name>() && map.has::<u32>());
+
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "required type `typemap::should::Counter` is not present")]
+    fn name_missing_type_in_panic() {
+        TypeMap::new().get::<Counter>();
+    }
+
+    #[test]
+    #[should_panic(expected = "`typemap::should::Name`")]
+    fn name_missing_type_in_take_panic() {
+        TypeMap::new().take::<Name>();
+    }
+
+    #[test]
+    fn name_missing_type_in_error() {
+        let mut map = TypeMap::new();
+        let err = map.get_checked::<Counter>().unwrap_err();
+        assert_eq!(type_name::<Counter>(), err.type_name);
+        assert_eq!(
+            "required type `typemap::should::Counter` is not present in the map",
+            err.to_string()
+        );
+        assert_eq!(Err(err), map.get_mut_checked::<Counter>().map(|_| ()));
+        assert_eq!(Err(err), map.take_checked::<Counter>());
+
+        map.put(Counter(1));
+        map.get_mut_checked::<Counter>().unwrap().0 += 1;
+        assert_eq!(Ok(&Counter(2)), map.get_checked::<Counter>());
+        assert_eq!(Ok(Counter(2)), map.take_checked::<Counter>());
+    }
+
+    #[test]
+    fn mutate_two_entries_at_once() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.put(Name("first"));
+
+        let (counter, name) = map.get_two_mut::<Counter, Name>();
+        let (counter, name) = (counter.unwrap(), name.unwrap());
+        counter.0 += 1;
+        *name = Name("second");
+
+        assert_eq!(&Counter(2), map.get::<Counter>());
+        assert_eq!(&Name("second"), map.get::<Name>());
+    }
+
+    #[test]
+    fn miss_absent_entries_of_the_pair() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+
+        let (counter, name) = map.get_two_mut::<Counter, Name>();
+        assert_eq!(Some(&mut Counter(1)), counter);
+        assert_eq!(None, name);
+
+        let (counter, name, number) = map.get_three_mut::<Counter, Name, u32>();
+        counter.unwrap().0 += 1;
+        assert!(name.is_none() && number.is_none());
+        assert_eq!(&Counter(2), map.get::<Counter>());
+    }
+
+    #[test]
+    #[should_panic(expected = "get_two_mut needs different types, got `typemap::should::Counter` twice")]
+    fn reject_the_same_type_twice() {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map.get_two_mut::<Counter, Counter>();
+    }
+
+    #[test]
+    #[should_panic(expected = "get_three_mut needs different types, got `u32` twice")]
+    fn reject_any_repeat_of_three() {
+        TypeMap::new().get_three_mut::<Counter, u32, u32>();
+    }
+}
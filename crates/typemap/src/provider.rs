@@ -0,0 +1,169 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::Entry;
+
+use crate::{MissingStateError, StateData, Stored, TypeIdMap, TypeMap};
+
+/// Builds a `T` for a map which doesn't hold one yet
+type Factory<T> = Box<dyn Fn() -> T + Send + Sync>;
+
+/// Factories for state which should be built on first use (a metrics sink, a parsed config)
+/// rather than put into every request's map up front. One registry is set up at startup and
+/// shared by every map, see [`TypeMap::get_or_provide`].
+#[derive(Debug, Default)]
+pub struct ProviderRegistry {
+    /// Each value is a `Factory<T>` for the `T` it is keyed by
+    factories: TypeIdMap<dyn Any + Send + Sync>,
+}
+
+impl ProviderRegistry {
+    /// Creates a new, empty `ProviderRegistry`.
+    pub fn new() -> ProviderRegistry {
+        Self::default()
+    }
+
+    /// Registers `f` to build missing values of type `T`, replacing any earlier factory for `T`.
+    pub fn register<T: StateData>(&mut self, f: impl Fn() -> T + Send + Sync + 'static) {
+        let factory: Factory<T> = Box::new(f);
+        self.factories.insert(TypeId::of::<T>(), Box::new(factory));
+    }
+
+    /// Determines if a factory for `T` is registered.
+    pub fn has<T: StateData>(&self) -> bool {
+        self.factories.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Builds a fresh `T`, `None` if no factory for `T` is registered.
+    pub fn provide<T: StateData>(&self) -> Option<T> {
+        self.factories
+            .get(&TypeId::of::<T>())
+            .and_then(|b| b.downcast_ref::<Factory<T>>())
+            .map(|factory| factory())
+    }
+}
+
+impl TypeMap {
+    /// Borrows the value of type `T`, first building it with the factory from `providers` if the
+    /// `TypeMap` doesn't hold one. The factory runs at most once per map, later calls (and plain
+    /// `get`) see the stored value.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is neither present nor registered in `providers`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::{ProviderRegistry, TypeMap};
+    /// #
+    /// # struct Config {
+    /// #     retries: u32
+    /// # }
+    /// # typemap::__impl_state_data!({} {Config} {});
+    /// #
+    /// let mut providers = ProviderRegistry::new();
+    /// providers.register(|| Config { retries: 3 });
+    ///
+    /// let mut map = TypeMap::new();
+    /// assert_eq!(map.get_or_provide::<Config>(&providers).retries, 3);
+    /// assert_eq!(map.get::<Config>().retries, 3);
+    /// ```
+    pub fn get_or_provide<T: StateData>(&mut self, providers: &ProviderRegistry) -> &T {
+        self.get_or_provide_checked(providers)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Borrows the value of type `T`, first building it with the factory from `providers` if the
+    /// `TypeMap` doesn't hold one, or says which type is missing from both.
+    pub fn get_or_provide_checked<T: StateData>(
+        &mut self,
+        providers: &ProviderRegistry,
+    ) -> Result<&T, MissingStateError> {
+        let stored = match self.inner.entry(TypeId::of::<T>()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let t = providers.provide::<T>().ok_or_else(MissingStateError::of::<T>)?;
+                entry.insert(Stored::new(t))
+            }
+        };
+        Ok(stored
+            .value
+            .downcast_ref::<T>()
+            .expect("TypeMap value is stored under its own type"))
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        retries: u32,
+    }
+    crate::__impl_state_data!({} {Config} {});
+
+    #[derive(Debug, PartialEq)]
+    struct Metrics(&'static str);
+    crate::__impl_state_data!({} {Metrics} {});
+
+    #[test]
+    fn build_once_per_map() {
+        let built = Arc::new(AtomicU32::new(0));
+        let mut providers = ProviderRegistry::new();
+        let counter = Arc::clone(&built);
+        providers.register(move || Config {
+            retries: counter.fetch_add(1, Ordering::SeqCst) + 1,
+        });
+
+        let mut first = TypeMap::new();
+        for _ in 0..3 {
+            assert_eq!(1, first.get_or_provide::<Config>(&providers).retries);
+        }
+        let mut second = TypeMap::new();
+        assert_eq!(2, second.get_or_provide::<Config>(&providers).retries);
+        assert_eq!(2, built.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn prefer_stored_value() {
+        let mut providers = ProviderRegistry::new();
+        providers.register(|| Config { retries: 3 });
+
+        let mut map = TypeMap::new();
+        map.put(Config { retries: 9 });
+        assert_eq!(&Config { retries: 9 }, map.get_or_provide::<Config>(&providers));
+    }
+
+    #[test]
+    fn keep_provided_value_for_plain_get() {
+        let mut providers = ProviderRegistry::new();
+        providers.register(|| Metrics("sink"));
+
+        let mut map = TypeMap::new();
+        map.get_or_provide::<Metrics>(&providers);
+        assert_eq!(&Metrics("sink"), map.get::<Metrics>());
+        assert_eq!(Some(Metrics("sink")), map.try_take::<Metrics>());
+    }
+
+    #[test]
+    fn fall_through_unregistered_types() {
+        let mut providers = ProviderRegistry::new();
+        providers.register(|| Config { retries: 3 });
+        assert!(providers.has::<Config>());
+        assert!(!providers.has::<Metrics>());
+        assert_eq!(None, providers.provide::<Metrics>());
+
+        let mut map = TypeMap::new();
+        let err = map.get_or_provide_checked::<Metrics>(&providers).unwrap_err();
+        assert_eq!(MissingStateError::of::<Metrics>(), err);
+        assert!(!map.has::<Metrics>());
+    }
+
+    #[test]
+    #[should_panic(expected = "`typemap::provider::should::Metrics` is not present")]
+    fn panic_for_unregistered_types() {
+        TypeMap::new().get_or_provide::<Metrics>(&ProviderRegistry::new());
+    }
+}
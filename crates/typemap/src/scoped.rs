@@ -0,0 +1,201 @@
+use std::any::{Any, TypeId};
+
+use crate::{MissingStateError, StateData, TypeMap};
+
+/// A layer of state a lookup can fall through, so one lookup serves a `TypeMap` and any depth
+/// of scopes over it
+trait Layer {
+    fn find(&self, type_id: &TypeId) -> Option<&(dyn Any + 'static)>;
+}
+
+impl Layer for TypeMap {
+    fn find(&self, type_id: &TypeId) -> Option<&(dyn Any + 'static)> {
+        self.inner.get(type_id).map(|s| s.value.as_ref())
+    }
+}
+
+impl Layer for ScopedTypeMap<'_> {
+    fn find(&self, type_id: &TypeId) -> Option<&(dyn Any + 'static)> {
+        self.local.find(type_id).or_else(|| self.parent.find(type_id))
+    }
+}
+
+/// State for a nested execution context (i.e. a sub-request) over its parent's. Reads look in
+/// the scope first and then in the parent, writes only ever go to the scope: the parent's
+/// entries are read only through it, `get_mut`, `take` and `remove` only see the scope's own.
+///
+/// # Examples
+///
+/// ```rust
+/// # use typemap::TypeMap;
+/// #
+/// # struct User(&'static str);
+/// # typemap::__impl_state_data!({} {User} {});
+/// #
+/// # struct Deadline(u64);
+/// # typemap::__impl_state_data!({} {Deadline} {});
+/// #
+/// let mut parent = TypeMap::new();
+/// parent.put(User("alice"));
+/// parent.put(Deadline(30));
+///
+/// let mut child = parent.child();
+/// child.put(Deadline(5));
+///
+/// assert_eq!(child.get::<User>().0, "alice");
+/// assert_eq!(child.get::<Deadline>().0, 5);
+/// assert_eq!(parent.get::<Deadline>().0, 30);
+/// ```
+pub struct ScopedTypeMap<'a> {
+    parent: &'a dyn Layer,
+    local: TypeMap,
+}
+
+impl TypeMap {
+    /// Opens a scope over this map, see [`ScopedTypeMap`].
+    pub fn child(&self) -> ScopedTypeMap<'_> {
+        ScopedTypeMap {
+            parent: self,
+            local: TypeMap::new(),
+        }
+    }
+}
+
+impl<'a> ScopedTypeMap<'a> {
+    /// Opens a scope over this one, reads fall through both to the root `TypeMap`.
+    pub fn child(&self) -> ScopedTypeMap<'_> {
+        ScopedTypeMap {
+            parent: self,
+            local: TypeMap::new(),
+        }
+    }
+
+    /// Puts a value into the scope, shadowing any value of the same type in the parent.
+    pub fn put<T: StateData>(&mut self, t: T) {
+        self.local.put(t)
+    }
+
+    /// Determines if a value of type `T` exists in the scope or its parent.
+    pub fn has<T: StateData>(&self) -> bool {
+        self.find(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Tries to borrow a value from the scope, then from its parent.
+    pub fn try_get<T: StateData>(&self) -> Option<&T> {
+        self.find(&TypeId::of::<T>()).and_then(|v| v.downcast_ref::<T>())
+    }
+
+    /// Borrows a value from the scope, then from its parent.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is present in neither.
+    pub fn get<T: StateData>(&self) -> &T {
+        self.get_checked().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Borrows a value from the scope, then from its parent, or says which type is missing.
+    pub fn get_checked<T: StateData>(&self) -> Result<&T, MissingStateError> {
+        self.try_get().ok_or_else(MissingStateError::of::<T>)
+    }
+
+    /// Tries to mutably borrow a value the scope itself holds, the parent's are read only.
+    pub fn try_get_mut<T: StateData>(&mut self) -> Option<&mut T> {
+        self.local.try_get_mut()
+    }
+
+    /// Tries to move a value the scope itself holds out of it, the parent's are left alone.
+    pub fn try_take<T: StateData>(&mut self) -> Option<T> {
+        self.local.try_take()
+    }
+
+    /// Drops a value the scope itself holds, returning whether there was one. A parent's value
+    /// of the same type shows through again afterwards.
+    pub fn remove<T: StateData>(&mut self) -> bool {
+        self.local.remove::<T>()
+    }
+
+    /// The values put into the scope itself.
+    pub fn local(&self) -> &TypeMap {
+        &self.local
+    }
+
+    /// Closes the scope, keeping the values put into it.
+    pub fn into_local(self) -> TypeMap {
+        self.local
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct User(&'static str);
+    crate::__impl_state_data!({} {User} {});
+
+    #[derive(Debug, PartialEq)]
+    struct Deadline(u64);
+    crate::__impl_state_data!({} {Deadline} {});
+
+    fn parent() -> TypeMap {
+        let mut parent = TypeMap::new();
+        parent.put(User("alice"));
+        parent.put(Deadline(30));
+        parent
+    }
+
+    #[test]
+    fn shadow_parent_entries() {
+        let parent = parent();
+        let mut child = parent.child();
+        child.put(Deadline(5));
+
+        assert_eq!(&Deadline(5), child.get::<Deadline>());
+        assert!(child.remove::<Deadline>());
+        assert_eq!(&Deadline(30), child.get::<Deadline>());
+    }
+
+    #[test]
+    fn fall_through_to_parent_for_reads() {
+        let parent = parent();
+        let child = parent.child();
+
+        assert!(child.has::<User>());
+        assert_eq!(Some(&User("alice")), child.try_get::<User>());
+        assert!(child.local().is_empty());
+        assert!(!child.has::<u32>());
+        assert!(child.get_checked::<u32>().is_err());
+    }
+
+    #[test]
+    fn leave_parent_untouched() {
+        let parent = parent();
+        let mut child = parent.child();
+
+        assert_eq!(None, child.try_get_mut::<User>());
+        assert_eq!(None, child.try_take::<User>());
+        assert!(!child.remove::<User>());
+        child.put(User("bob"));
+        child.put(7u32);
+
+        let local = child.into_local();
+        assert_eq!(2, local.len());
+        assert_eq!(&User("alice"), parent.get::<User>());
+        assert!(!parent.has::<u32>());
+    }
+
+    #[test]
+    fn nest_scopes() {
+        let parent = parent();
+        let mut child = parent.child();
+        child.put(Deadline(5));
+        let mut grandchild = child.child();
+        grandchild.put(7u32);
+
+        assert_eq!(&User("alice"), grandchild.get::<User>());
+        assert_eq!(&Deadline(5), grandchild.get::<Deadline>());
+        assert_eq!(Some(7), grandchild.try_take::<u32>());
+        assert!(!child.has::<u32>());
+    }
+}
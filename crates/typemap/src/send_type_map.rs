@@ -0,0 +1,204 @@
+use std::any::{Any, TypeId};
+
+use crate::{type_id_map_with_capacity, MissingStateError, TypeIdMap};
+
+/// A [`TypeMap`](crate::TypeMap) which only stores `Send + Sync` values, so the map itself is
+/// `Send + Sync` and can be shared between threads (i.e. behind an `Arc<RwLock<_>>`). The
+/// auto traits follow from the boxed values, there is no `unsafe impl`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::{Arc, RwLock};
+/// use typemap::SendTypeMap;
+///
+/// struct MyStruct {
+///     value: i32,
+/// }
+///
+/// let map = Arc::new(RwLock::new(SendTypeMap::new()));
+/// let writer = Arc::clone(&map);
+/// std::thread::spawn(move || writer.write().unwrap().put(MyStruct { value: 1 }))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(map.read().unwrap().get::<MyStruct>().value, 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct SendTypeMap {
+    inner: TypeIdMap<dyn Any + Send + Sync>,
+}
+
+// Proves the map can cross threads, fails to compile if a field ever stops being Send + Sync
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SendTypeMap>();
+};
+
+impl SendTypeMap {
+    /// Creates a new, empty `SendTypeMap`. It doesn't allocate until the first `put`.
+    pub fn new() -> SendTypeMap {
+        Self::default()
+    }
+
+    /// Creates an empty `SendTypeMap` with room for at least `capacity` types before it
+    /// reallocates.
+    pub fn with_capacity(capacity: usize) -> SendTypeMap {
+        Self {
+            inner: type_id_map_with_capacity(capacity),
+        }
+    }
+
+    /// Puts a value into the `SendTypeMap`, replacing any existing value of the same type.
+    pub fn put<T: Any + Send + Sync>(&mut self, t: T) {
+        let type_id = TypeId::of::<T>();
+        self.inner.insert(type_id, Box::new(t));
+    }
+
+    /// Determines if a value of type `T` exists in the `SendTypeMap`.
+    pub fn has<T: Any + Send + Sync>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.inner.contains_key(&type_id)
+    }
+
+    /// Tries to borrow a value from the `SendTypeMap`.
+    pub fn try_get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        let type_id = TypeId::of::<T>();
+        self.inner.get(&type_id).and_then(|b| b.downcast_ref::<T>())
+    }
+
+    /// Borrows a value from the `SendTypeMap`.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `SendTypeMap`.
+    pub fn get<T: Any + Send + Sync>(&self) -> &T {
+        self.try_get()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Tries to mutably borrow a value from the `SendTypeMap`.
+    pub fn try_get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        let type_id = TypeId::of::<T>();
+        self.inner
+            .get_mut(&type_id)
+            .and_then(|b| b.downcast_mut::<T>())
+    }
+
+    /// Mutably borrows a value from the `SendTypeMap`.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `SendTypeMap`.
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> &mut T {
+        self.try_get_mut()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Tries to move a value out of the `SendTypeMap` storage and return ownership.
+    pub fn try_take<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        self.inner
+            .remove(&type_id)
+            .and_then(|b| b.downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Moves a value out of the `SendTypeMap` storage and returns ownership.
+    ///
+    /// # Panics
+    ///
+    /// If a value of type `T` is not present in `SendTypeMap`.
+    pub fn take<T: Any + Send + Sync>(&mut self) -> T {
+        self.try_take()
+            .unwrap_or_else(|| panic!("{}", MissingStateError::of::<T>()))
+    }
+
+    /// Drops the value of type `T`, returning whether there was one.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.inner.remove(&type_id).is_some()
+    }
+
+    /// Drops every value, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    /// The number of types with a value in the `SendTypeMap`.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether the `SendTypeMap` holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn keep_typemap_semantics() {
+        let mut map = SendTypeMap::with_capacity(2);
+        map.put(Counter(1));
+        map.put(Name("first"));
+        map.put(Counter(2));
+        assert_eq!(2, map.len());
+
+        map.get_mut::<Counter>().0 += 1;
+        assert_eq!(&Counter(3), map.get::<Counter>());
+        assert_eq!(Some(Counter(3)), map.try_take::<Counter>());
+        assert!(!map.has::<Counter>());
+        assert!(map.remove::<Name>());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn share_between_threads() {
+        let map = Arc::new(RwLock::new(SendTypeMap::new()));
+        map.write().unwrap().put(Counter(0));
+
+        for name in ["first", "second"] {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                let mut map = map.write().unwrap();
+                map.get_mut::<Counter>().0 += 1;
+                map.put(Name(name));
+            })
+            .join()
+            .unwrap();
+        }
+
+        let map = map.read().unwrap();
+        assert_eq!(&Counter(2), map.get::<Counter>());
+        assert_eq!(&Name("second"), map.get::<Name>());
+    }
+
+    #[test]
+    fn read_from_many_threads() {
+        let mut map = SendTypeMap::new();
+        map.put(Name("shared"));
+        let map = Arc::new(RwLock::new(map));
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || map.read().unwrap().get::<Name>().0)
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!("shared", reader.join().unwrap());
+        }
+    }
+}
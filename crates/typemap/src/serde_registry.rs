@@ -0,0 +1,269 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{StateData, Stored, TypeMap};
+
+/// How one registered type crosses the wire
+#[derive(Debug)]
+struct Codec {
+    name: &'static str,
+    serialize: fn(&(dyn Any + 'static)) -> serde_json::Result<Value>,
+    deserialize: fn(Value) -> serde_json::Result<Stored>,
+}
+
+fn serialize<T: Serialize + Any>(value: &(dyn Any + 'static)) -> serde_json::Result<Value> {
+    let t = value
+        .downcast_ref::<T>()
+        .expect("TypeMap value is stored under its own type");
+    serde_json::to_value(t)
+}
+
+fn deserialize<T: DeserializeOwned + Any>(value: Value) -> serde_json::Result<Stored> {
+    serde_json::from_value::<T>(value).map(Stored::new)
+}
+
+/// What [`TypeMap::serialize_with`] does with a value whose type isn't registered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnUnregistered {
+    /// Leave it out, request state often holds values which needn't survive (a client handle)
+    #[default]
+    Skip,
+    /// Fail with [`SerdeError::Unregistered`]
+    Error,
+}
+
+/// A value couldn't be serialized or deserialized through a [`SerdeRegistry`]
+#[derive(Debug)]
+pub enum SerdeError {
+    /// The map holds a type which isn't registered, with [`OnUnregistered::Error`]
+    Unregistered { type_name: &'static str },
+    /// serde_json failed on the value registered under `name`
+    Json {
+        name: &'static str,
+        source: serde_json::Error,
+    },
+}
+
+impl std::error::Error for SerdeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerdeError::Unregistered { .. } => None,
+            SerdeError::Json { source, .. } => Some(source),
+        }
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerdeError::Unregistered { type_name } => {
+                write!(f, "type `{type_name}` is not registered for serialization")
+            }
+            SerdeError::Json { name, source } => write!(f, "invalid value for `{name}`: {source}"),
+        }
+    }
+}
+
+/// The types whose values can be (de)serialized with [`TypeMap::serialize_with`] and
+/// [`TypeMap::deserialize_with`], i.e. to carry request state across a Worker's `waitUntil`.
+/// Each type is registered under a stable name which is used on the wire instead of its Rust
+/// path, so types can be moved or renamed without breaking persisted state.
+///
+/// # Examples
+///
+/// ```rust
+/// # use serde::{Deserialize, Serialize};
+/// # use typemap::{SerdeRegistry, TypeMap};
+/// #
+/// #[derive(Serialize, Deserialize)]
+/// struct User(String);
+/// # typemap::__impl_state_data!({} {User} {});
+///
+/// let mut registry = SerdeRegistry::new();
+/// registry.register::<User>("user");
+///
+/// let mut map = TypeMap::new();
+/// map.put(User(String::from("alice")));
+/// let json = map.serialize_with(&registry).unwrap();
+/// assert_eq!(json["user"], "alice");
+///
+/// let map = TypeMap::deserialize_with(&registry, json).unwrap();
+/// assert_eq!(map.get::<User>().0, "alice");
+/// ```
+#[derive(Debug, Default)]
+pub struct SerdeRegistry {
+    codecs: HashMap<TypeId, Codec>,
+    names: HashMap<&'static str, TypeId>,
+    on_unregistered: OnUnregistered,
+}
+
+impl SerdeRegistry {
+    /// Creates a new, empty `SerdeRegistry` which skips unregistered types.
+    pub fn new() -> SerdeRegistry {
+        Self::default()
+    }
+
+    /// Registers `T` under `name`, replacing any earlier registration of `T` or of `name`.
+    pub fn register<T: StateData + Serialize + DeserializeOwned>(&mut self, name: &'static str) {
+        let type_id = TypeId::of::<T>();
+        if let Some(old) = self.names.insert(name, type_id) {
+            self.codecs.remove(&old);
+        }
+        let codec = Codec {
+            name,
+            serialize: serialize::<T>,
+            deserialize: deserialize::<T>,
+        };
+        if let Some(old) = self.codecs.insert(type_id, codec) {
+            if old.name != name {
+                self.names.remove(old.name);
+            }
+        }
+    }
+
+    /// Sets what serializing does with values of unregistered types.
+    pub fn on_unregistered(&mut self, on_unregistered: OnUnregistered) {
+        self.on_unregistered = on_unregistered;
+    }
+
+    /// The name `T` is registered under.
+    pub fn name_of<T: StateData>(&self) -> Option<&'static str> {
+        self.codecs.get(&TypeId::of::<T>()).map(|codec| codec.name)
+    }
+}
+
+impl TypeMap {
+    /// Serializes the values of the types registered in `registry` into a JSON object keyed by
+    /// their registered names. Values of other types are skipped, or fail the whole call with
+    /// [`OnUnregistered::Error`]. Keyed values are not serialized.
+    pub fn serialize_with(&self, registry: &SerdeRegistry) -> Result<Map<String, Value>, SerdeError> {
+        let mut json = Map::new();
+        for (type_id, stored) in &self.inner {
+            let Some(codec) = registry.codecs.get(type_id) else {
+                match registry.on_unregistered {
+                    OnUnregistered::Skip => continue,
+                    OnUnregistered::Error => {
+                        return Err(SerdeError::Unregistered {
+                            type_name: stored.type_name,
+                        })
+                    }
+                }
+            };
+            let value = (codec.serialize)(stored.value.as_ref()).map_err(|source| SerdeError::Json {
+                name: codec.name,
+                source,
+            })?;
+            json.insert(codec.name.to_owned(), value);
+        }
+        Ok(json)
+    }
+
+    /// Builds a fresh `TypeMap` from a JSON object made by [`TypeMap::serialize_with`]. Names
+    /// which aren't registered in `registry` are ignored, so state written by a newer build
+    /// still loads.
+    pub fn deserialize_with(
+        registry: &SerdeRegistry,
+        json: Map<String, Value>,
+    ) -> Result<TypeMap, SerdeError> {
+        let mut map = TypeMap::with_capacity(json.len());
+        for (name, value) in json {
+            let Some(&type_id) = registry.names.get(name.as_str()) else {
+                continue;
+            };
+            let codec = &registry.codecs[&type_id];
+            let stored = (codec.deserialize)(value).map_err(|source| SerdeError::Json {
+                name: codec.name,
+                source,
+            })?;
+            map.inner.insert(type_id, stored);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq)]
+    struct Client(&'static str);
+    crate::__impl_state_data!({} {Client} {});
+
+    fn registry() -> SerdeRegistry {
+        let mut registry = SerdeRegistry::new();
+        registry.register::<String>("user");
+        registry.register::<Vec<u32>>("attempts");
+        registry
+    }
+
+    fn map() -> TypeMap {
+        let mut map = TypeMap::new();
+        map.put(String::from("alice"));
+        map.put(vec![1u32, 2]);
+        map.put(Client("pooled"));
+        map
+    }
+
+    #[test]
+    fn round_trip_registered_types() {
+        let registry = registry();
+        let json = map().serialize_with(&registry).unwrap();
+        assert_eq!(json!({ "user": "alice", "attempts": [1, 2] }), Value::Object(json.clone()));
+
+        let map = TypeMap::deserialize_with(&registry, json).unwrap();
+        assert_eq!(2, map.len());
+        assert_eq!("alice", map.get::<String>());
+        assert_eq!(&vec![1u32, 2], map.get::<Vec<u32>>());
+        assert!(!map.has::<Client>());
+    }
+
+    #[test]
+    fn error_on_unregistered_when_asked() {
+        let mut registry = registry();
+        registry.on_unregistered(OnUnregistered::Error);
+
+        match map().serialize_with(&registry) {
+            Err(SerdeError::Unregistered { type_name }) => {
+                assert_eq!(std::any::type_name::<Client>(), type_name)
+            }
+            other => panic!("expected an unregistered error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn ignore_unknown_names() {
+        let json = json!({ "user": "bob", "session": { "id": 7 } });
+        let Value::Object(json) = json else { unreachable!() };
+
+        let map = TypeMap::deserialize_with(&registry(), json).unwrap();
+        assert_eq!(1, map.len());
+        assert_eq!("bob", map.get::<String>());
+    }
+
+    #[test]
+    fn name_the_invalid_value() {
+        let Value::Object(json) = json!({ "attempts": "many" }) else { unreachable!() };
+
+        let err = TypeMap::deserialize_with(&registry(), json).err().unwrap();
+        assert!(matches!(err, SerdeError::Json { name: "attempts", .. }), "{err:?}");
+        assert!(err.to_string().starts_with("invalid value for `attempts`"));
+    }
+
+    #[test]
+    fn replace_earlier_registrations() {
+        let mut registry = registry();
+        registry.register::<String>("login");
+        registry.register::<u8>("attempts");
+
+        assert_eq!(Some("login"), registry.name_of::<String>());
+        assert_eq!(None, registry.name_of::<Vec<u32>>());
+        let json = map().serialize_with(&registry).unwrap();
+        assert_eq!(json!({ "login": "alice" }), Value::Object(json));
+    }
+}
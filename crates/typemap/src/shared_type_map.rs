@@ -0,0 +1,210 @@
+use std::any::{Any, TypeId};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::TypeIdMap;
+
+/// A process wide registry (config, pooled clients) with one value of each type, shared by
+/// reference between threads. Values are only reached through closures so no borrow can
+/// outlive the lock.
+///
+/// # Poisoning
+///
+/// A closure which panics while holding the lock poisons it. `SharedTypeMap` ignores poisoning
+/// and keeps serving the stored values, a registry shouldn't fail every later caller because of
+/// one panic. The value a panicking `with_borrow_mut` closure was changing may be left
+/// partially updated, `insert` a fresh one if that matters.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use typemap::SharedTypeMap;
+///
+/// #[derive(Clone)]
+/// struct Config {
+///     retries: u32,
+/// }
+///
+/// let registry = Arc::new(SharedTypeMap::new());
+/// registry.insert(Config { retries: 3 });
+///
+/// let worker = Arc::clone(&registry);
+/// std::thread::spawn(move || worker.with_borrow_mut(|config: &mut Config| config.retries += 1))
+///     .join()
+///     .unwrap();
+///
+/// assert_eq!(registry.get_cloned::<Config>().unwrap().retries, 4);
+/// ```
+#[derive(Debug, Default)]
+pub struct SharedTypeMap {
+    inner: RwLock<TypeIdMap<dyn Any + Send + Sync>>,
+}
+
+impl SharedTypeMap {
+    /// Creates a new, empty `SharedTypeMap`.
+    pub fn new() -> SharedTypeMap {
+        Self::default()
+    }
+
+    /// Puts a value into the `SharedTypeMap`, returning the value of the same type it replaced.
+    pub fn insert<T: Any + Send + Sync>(&self, t: T) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        self.write()
+            .insert(type_id, Box::new(t))
+            .and_then(|b| b.downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Moves the value of type `T` out of the `SharedTypeMap`.
+    pub fn remove<T: Any + Send + Sync>(&self) -> Option<T> {
+        let type_id = TypeId::of::<T>();
+        self.write()
+            .remove(&type_id)
+            .and_then(|b| b.downcast::<T>().ok())
+            .map(|b| *b)
+    }
+
+    /// Determines if a value of type `T` exists in the `SharedTypeMap`.
+    pub fn has<T: Any + Send + Sync>(&self) -> bool {
+        let type_id = TypeId::of::<T>();
+        self.read().contains_key(&type_id)
+    }
+
+    /// A copy of the value of type `T`, taken under the read lock.
+    pub fn get_cloned<T: Any + Send + Sync + Clone>(&self) -> Option<T> {
+        self.with_borrow(T::clone)
+    }
+
+    /// Runs `f` on the value of type `T` under the read lock, `None` if there is no value.
+    /// Other readers run concurrently, writers wait until `f` returns.
+    pub fn with_borrow<T: Any + Send + Sync, R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        let type_id = TypeId::of::<T>();
+        self.read()
+            .get(&type_id)
+            .and_then(|b| b.downcast_ref::<T>())
+            .map(f)
+    }
+
+    /// Runs `f` on the value of type `T` under the write lock, `None` if there is no value.
+    /// Every other access waits until `f` returns, so keep it short and don't touch the map
+    /// from inside it or it deadlocks.
+    pub fn with_borrow_mut<T: Any + Send + Sync, R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let type_id = TypeId::of::<T>();
+        self.write()
+            .get_mut(&type_id)
+            .and_then(|b| b.downcast_mut::<T>())
+            .map(f)
+    }
+
+    /// The number of types with a value in the `SharedTypeMap`.
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    /// Whether the `SharedTypeMap` holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, TypeIdMap<dyn Any + Send + Sync>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, TypeIdMap<dyn Any + Send + Sync>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Config {
+        version: u32,
+    }
+
+    #[test]
+    fn insert_and_remove_by_type() {
+        let map = SharedTypeMap::new();
+        assert_eq!(None, map.insert(Config { version: 1 }));
+        assert_eq!(Some(Config { version: 1 }), map.insert(Config { version: 2 }));
+        assert!(map.has::<Config>());
+        assert_eq!(1, map.len());
+
+        assert_eq!(Some(Config { version: 2 }), map.remove::<Config>());
+        assert_eq!(None, map.remove::<Config>());
+        assert_eq!(None, map.get_cloned::<Config>());
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn run_closures_only_when_present() {
+        let map = SharedTypeMap::new();
+        assert_eq!(None, map.with_borrow(|config: &Config| config.version));
+        assert_eq!(None, map.with_borrow_mut(|config: &mut Config| config.version += 1));
+
+        map.insert(Config { version: 1 });
+        assert_eq!(Some(2), map.with_borrow_mut(|config: &mut Config| {
+            config.version += 1;
+            config.version
+        }));
+        assert_eq!(Some(2), map.with_borrow(|config: &Config| config.version));
+    }
+
+    #[test]
+    fn serve_concurrent_readers_with_a_writer() {
+        let map = Arc::new(SharedTypeMap::new());
+        map.insert(Config { version: 0 });
+        map.insert(0u64);
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    let mut last = 0;
+                    for _ in 0..1_000 {
+                        let version = map.get_cloned::<Config>().unwrap().version;
+                        // The writer only counts up, readers never see it go back
+                        assert!(version >= last);
+                        last = version;
+                        map.with_borrow(|reads: &u64| *reads).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let writer = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    map.with_borrow_mut(|config: &mut Config| config.version += 1);
+                    thread::yield_now();
+                }
+            })
+        };
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(Some(Config { version: 100 }), map.get_cloned::<Config>());
+    }
+
+    #[test]
+    fn keep_serving_after_a_panic() {
+        let map = SharedTypeMap::new();
+        map.insert(Config { version: 1 });
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            map.with_borrow_mut(|config: &mut Config| {
+                config.version = 2;
+                panic!("failed half way");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(Some(Config { version: 2 }), map.get_cloned::<Config>());
+    }
+}
@@ -0,0 +1,45 @@
+use std::any::Any;
+
+/// Marks a type as meant to be stored in a [`TypeMap`](crate::TypeMap), so storing something
+/// by accident (a `&'static str` where a `String` was meant) fails to compile rather than
+/// failing to be found later.
+///
+/// Without the `strict` feature every `'static` type is `StateData` and the marker changes
+/// nothing. With it, types opt in with `#[derive(StateData)]` (the `derive` feature) or an
+/// empty `impl StateData for MyType {}`.
+pub trait StateData: Any {}
+
+#[cfg(not(feature = "strict"))]
+impl<T: Any> StateData for T {}
+
+/// Expansion of `#[derive(StateData)]`, which can't see this crate's features itself. The
+/// blanket impl already covers every type unless `strict` is enabled.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(feature = "strict")]
+macro_rules! __impl_state_data {
+    ({ $($generics:tt)* } { $($ty:tt)* } { $($where:tt)* }) => {
+        impl $($generics)* $crate::StateData for $($ty)* $($where)* {}
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(feature = "strict"))]
+macro_rules! __impl_state_data {
+    ($($tokens:tt)*) => {};
+}
+
+// The unit tests store plain values, which strict mode otherwise rejects like any other
+// type that hasn't opted in
+#[cfg(all(test, feature = "strict"))]
+mod test_types {
+    use super::StateData;
+
+    impl StateData for u8 {}
+    impl StateData for u16 {}
+    impl StateData for u32 {}
+    impl StateData for u64 {}
+    impl StateData for String {}
+    impl StateData for Vec<u32> {}
+}
@@ -0,0 +1,154 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::{StateData, TypeMap};
+
+/// A value taken out of a [`TypeMap`] which goes back in when the guard drops, whether the
+/// scope ends normally, returns early or unwinds from a panic. See [`TypeMap::take_guard`].
+///
+/// The guard holds the map's mutable borrow for its whole life and puts the value back through
+/// it in `Drop`, no raw pointer or `unsafe` is involved. The flip side is that the map can only
+/// be reached through [`TakeGuard::map`] while the guard lives.
+#[derive(Debug)]
+pub struct TakeGuard<'a, T: StateData> {
+    map: &'a mut TypeMap,
+    /// Only `None` once `into_inner` has moved the value out
+    value: Option<T>,
+}
+
+impl TypeMap {
+    /// Takes the value of type `T` out of the `TypeMap` until the returned guard drops, so an
+    /// early return or panic while changing it can't leave the entry missing. `None` if there is
+    /// no value of type `T`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use typemap::TypeMap;
+    /// #
+    /// # struct Retries(u32);
+    /// # typemap::__impl_state_data!({} {Retries} {});
+    /// #
+    /// fn bump(map: &mut TypeMap) -> Result<(), &'static str> {
+    ///     let mut retries = map.take_guard::<Retries>().ok_or("no retries")?;
+    ///     retries.0 += 1;
+    ///     if retries.0 > 3 {
+    ///         return Err("too many retries");
+    ///     }
+    ///     Ok(())
+    /// }
+    ///
+    /// let mut map = TypeMap::new();
+    /// map.put(Retries(3));
+    ///
+    /// assert!(bump(&mut map).is_err());
+    /// assert_eq!(map.get::<Retries>().0, 4);
+    /// ```
+    pub fn take_guard<T: StateData>(&mut self) -> Option<TakeGuard<'_, T>> {
+        let value = self.try_take::<T>()?;
+        Some(TakeGuard {
+            map: self,
+            value: Some(value),
+        })
+    }
+}
+
+impl<T: StateData> TakeGuard<'_, T> {
+    /// The rest of the map, which doesn't hold the taken value until the guard drops.
+    pub fn map(&mut self) -> &mut TypeMap {
+        self.map
+    }
+
+    /// Keeps the value out of the map for good and returns it.
+    pub fn into_inner(mut self) -> T {
+        self.value.take().expect("TakeGuard holds its value until dropped")
+    }
+}
+
+impl<T: StateData> Deref for TakeGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value.as_ref().expect("TakeGuard holds its value until dropped")
+    }
+}
+
+impl<T: StateData> DerefMut for TakeGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value.as_mut().expect("TakeGuard holds its value until dropped")
+    }
+}
+
+impl<T: StateData> Drop for TakeGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            self.map.put(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod should {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+    crate::__impl_state_data!({} {Counter} {});
+
+    fn map() -> TypeMap {
+        let mut map = TypeMap::new();
+        map.put(Counter(1));
+        map
+    }
+
+    #[test]
+    fn restore_on_drop() {
+        let mut map = map();
+        {
+            let mut counter = map.take_guard::<Counter>().unwrap();
+            counter.0 += 1;
+            assert!(!counter.map().has::<Counter>());
+            counter.map().put(7u8);
+        }
+        assert_eq!(&Counter(2), map.get::<Counter>());
+        assert_eq!(&7, map.get::<u8>());
+        assert!(map.take_guard::<u16>().is_none());
+    }
+
+    #[test]
+    fn restore_on_early_return() {
+        fn bump_unless_odd(map: &mut TypeMap) -> Option<()> {
+            let mut counter = map.take_guard::<Counter>()?;
+            if counter.0 % 2 == 1 {
+                return None;
+            }
+            counter.0 += 1;
+            Some(())
+        }
+
+        let mut map = map();
+        assert_eq!(None, bump_unless_odd(&mut map));
+        assert_eq!(&Counter(1), map.get::<Counter>());
+    }
+
+    #[test]
+    fn leave_value_out_after_into_inner() {
+        let mut map = map();
+        let counter = map.take_guard::<Counter>().unwrap().into_inner();
+        assert_eq!(Counter(1), counter);
+        assert!(!map.has::<Counter>());
+    }
+
+    #[test]
+    fn restore_on_panic() {
+        let mut map = map();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut counter = map.take_guard::<Counter>().unwrap();
+            counter.0 += 1;
+            panic!("handler failed");
+        }));
+
+        assert!(result.is_err());
+        assert_eq!(&Counter(2), map.get::<Counter>());
+    }
+}
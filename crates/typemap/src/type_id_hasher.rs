@@ -1,9 +1,10 @@
 use std::hash::{Hasher};
 
 // https://docs.rs/http/0.2.5/src/http/extensions.rs.html#8-28
-// With TypeIds as keys, there's no need to hash them. They are already hashes
-// themselves, coming from the compiler. The IdHasher just holds the u64 of
-// the TypeId, and then returns it, instead of doing any bit fiddling.
+/// A `Hasher` for maps keyed by `TypeId` only. TypeIds are already hashes themselves, coming
+/// from the compiler, so it just holds the u64 the TypeId writes and returns it instead of
+/// doing any bit fiddling. Use it through [`TypeIdMap`](crate::TypeIdMap) or
+/// `BuildHasherDefault<TypeIdHasher>`, any other key type panics.
 #[derive(Default)]
 pub struct TypeIdHasher(u64);
 
@@ -23,29 +24,90 @@ impl Hasher for TypeIdHasher {
     }
 }
 
+/// Hashes a TypeId paired with more data (i.e. a key name): the TypeId passes through as in
+/// `TypeIdHasher` and anything else is folded in FxHash style, which is plenty for short keys.
+#[derive(Default)]
+pub struct KeyedHasher(u64);
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl Hasher for KeyedHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0.rotate_left(5) ^ u64::from(b)).wrapping_mul(SEED);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, id: u64) {
+        self.0 ^= id;
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[cfg(test)]
 mod should {
     use super::*;
-    use std::mem;
     use std::any::TypeId;
     use std::hash::{Hash, Hasher};
 
+    /// Records how a key feeds a hasher
+    #[derive(Default)]
+    struct Probe {
+        u64s: Vec<u64>,
+        other_writes: usize,
+    }
+
+    impl Hasher for Probe {
+        fn write(&mut self, _: &[u8]) {
+            self.other_writes += 1;
+        }
+
+        fn write_u64(&mut self, id: u64) {
+            self.u64s.push(id);
+        }
+
+        fn finish(&self) -> u64 {
+            0
+        }
+    }
+
+    fn verify_hashing_with(type_id: TypeId) -> u64 {
+        let mut probe = Probe::default();
+        type_id.hash(&mut probe);
+        assert!(
+            probe.u64s.len() == 1 && probe.other_writes == 0,
+            "TypeId no longer hashes through a single write_u64 on this toolchain \
+             ({} write_u64 and {} other writes). TypeIdHasher keeps only the last u64 it is given \
+             and panics on other writes, so switch TypeIdMap to KeyedHasher, which folds \
+             everything in, until TypeIdHasher is taught the new representation.",
+            probe.u64s.len(),
+            probe.other_writes,
+        );
+
+        let mut hasher = TypeIdHasher::default();
+        type_id.hash(&mut hasher);
+        assert_eq!(probe.u64s[0], hasher.finish());
+        hasher.finish()
+    }
+
     #[test]
     fn hash_various_types_correctly() {
-        
-        fn verify_hashing_with(type_id: TypeId) {
-            let mut hasher = TypeIdHasher::default();
-            type_id.hash(&mut hasher);
-            assert_eq!(hasher.finish(), unsafe {
-                mem::transmute::<TypeId, u64>(type_id)
-            });
-        }
         // Pick a variety of types, just to demonstrate it’s all sane. Normal,
         // zero-sized, unsized, &c.
-        verify_hashing_with(TypeId::of::<usize>());
-        verify_hashing_with(TypeId::of::<()>());
-        verify_hashing_with(TypeId::of::<str>());
-        verify_hashing_with(TypeId::of::<&str>());
-        verify_hashing_with(TypeId::of::<Vec<u8>>());
+        let hashes = [
+            verify_hashing_with(TypeId::of::<usize>()),
+            verify_hashing_with(TypeId::of::<()>()),
+            verify_hashing_with(TypeId::of::<str>()),
+            verify_hashing_with(TypeId::of::<&str>()),
+            verify_hashing_with(TypeId::of::<Vec<u8>>()),
+        ];
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(hash), "{hashes:?}");
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,8 @@
+// Compile checks of the strict feature, run with `cargo test -p typemap --features strict,derive`
+#[cfg(all(feature = "strict", feature = "derive"))]
+#[test]
+fn require_state_data_in_strict_mode() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/derived.rs");
+    cases.compile_fail("tests/ui/not_derived.rs");
+}
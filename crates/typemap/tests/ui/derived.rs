@@ -0,0 +1,18 @@
+use typemap::{StateData, TypeMap};
+
+#[derive(StateData)]
+struct RequestId(String);
+
+#[derive(StateData)]
+struct Tagged<T: Clone + 'static> {
+    value: T,
+}
+
+fn main() {
+    let mut map = TypeMap::new();
+    map.put(RequestId(String::from("r-1")));
+    map.put(Tagged { value: 7u8 });
+
+    assert_eq!(map.get::<RequestId>().0, "r-1");
+    assert_eq!(map.get::<Tagged<u8>>().value, 7);
+}
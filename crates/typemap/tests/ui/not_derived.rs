@@ -0,0 +1,8 @@
+use typemap::TypeMap;
+
+struct RequestId(String);
+
+fn main() {
+    let mut map = TypeMap::new();
+    map.put(RequestId(String::from("r-1")));
+}